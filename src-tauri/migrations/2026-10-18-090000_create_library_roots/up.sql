@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS library_roots
+(
+    id      INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    path    TEXT    NOT NULL UNIQUE,
+    enabled BOOLEAN NOT NULL DEFAULT 1
+);
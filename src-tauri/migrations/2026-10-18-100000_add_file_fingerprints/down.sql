@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS files_fingerprint_idx;
+
+ALTER TABLE files DROP COLUMN fingerprint;
@@ -0,0 +1,3 @@
+ALTER TABLE files ADD COLUMN fingerprint TEXT;
+
+CREATE INDEX IF NOT EXISTS files_fingerprint_idx ON files (fingerprint);
@@ -0,0 +1,7 @@
+ALTER TABLE files DROP COLUMN subtitle_languages;
+ALTER TABLE files DROP COLUMN audio_languages;
+ALTER TABLE files DROP COLUMN bitrate;
+ALTER TABLE files DROP COLUMN height;
+ALTER TABLE files DROP COLUMN width;
+ALTER TABLE files DROP COLUMN video_codec;
+ALTER TABLE files DROP COLUMN duration_ms;
@@ -0,0 +1,7 @@
+ALTER TABLE files ADD COLUMN duration_ms BIGINT;
+ALTER TABLE files ADD COLUMN video_codec TEXT;
+ALTER TABLE files ADD COLUMN width INTEGER;
+ALTER TABLE files ADD COLUMN height INTEGER;
+ALTER TABLE files ADD COLUMN bitrate BIGINT;
+ALTER TABLE files ADD COLUMN audio_languages TEXT;
+ALTER TABLE files ADD COLUMN subtitle_languages TEXT;
@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS subtitles_language_idx;
+DROP TABLE subtitles;
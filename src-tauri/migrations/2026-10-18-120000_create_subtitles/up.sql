@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS subtitles
+(
+    id       INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    file_id  INTEGER NOT NULL,
+    path     TEXT    NOT NULL,
+    language TEXT,
+    FOREIGN KEY (file_id) REFERENCES files (id) ON DELETE CASCADE,
+    UNIQUE (file_id, path)
+);
+
+CREATE INDEX IF NOT EXISTS subtitles_language_idx ON subtitles (language);
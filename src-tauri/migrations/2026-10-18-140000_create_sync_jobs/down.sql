@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS sync_jobs_status_idx;
+DROP TABLE sync_jobs;
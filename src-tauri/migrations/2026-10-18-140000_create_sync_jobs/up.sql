@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS sync_jobs
+(
+    id       INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    roots    TEXT    NOT NULL,
+    status   TEXT    NOT NULL,
+    total    INTEGER NOT NULL DEFAULT 0,
+    inserted INTEGER NOT NULL DEFAULT 0,
+    pending  TEXT    NOT NULL,
+    error    TEXT
+);
+
+CREATE INDEX IF NOT EXISTS sync_jobs_status_idx ON sync_jobs (status);
@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS metadata_cache
+(
+    kind       TEXT   NOT NULL,
+    key        TEXT   NOT NULL,
+    response   TEXT   NOT NULL,
+    fetched_at BIGINT NOT NULL,
+    PRIMARY KEY (kind, key)
+);
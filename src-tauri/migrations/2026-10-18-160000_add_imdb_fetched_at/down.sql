@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS imdbs_fetched_at_idx;
+
+ALTER TABLE imdbs DROP COLUMN fetched_at;
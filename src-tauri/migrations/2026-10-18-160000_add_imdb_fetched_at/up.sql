@@ -0,0 +1,4 @@
+-- Rows saved before this column existed are stale until they are refreshed.
+ALTER TABLE imdbs ADD COLUMN fetched_at BIGINT NOT NULL DEFAULT 0;
+
+CREATE INDEX IF NOT EXISTS imdbs_fetched_at_idx ON imdbs (fetched_at);
@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS match_suggestions
+(
+    media_id   INTEGER NOT NULL PRIMARY KEY REFERENCES medias (id),
+    imdb_id    TEXT    NOT NULL,
+    title      TEXT    NOT NULL,
+    year       INTEGER,
+    confidence INTEGER NOT NULL
+);
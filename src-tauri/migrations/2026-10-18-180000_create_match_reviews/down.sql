@@ -0,0 +1,2 @@
+DROP TABLE skipped_reviews;
+DROP TABLE rejected_matches;
@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS rejected_matches
+(
+    media_id INTEGER NOT NULL REFERENCES medias (id),
+    imdb_id  TEXT    NOT NULL,
+    PRIMARY KEY (media_id, imdb_id)
+);
+
+CREATE TABLE IF NOT EXISTS skipped_reviews
+(
+    media_id   INTEGER NOT NULL PRIMARY KEY REFERENCES medias (id),
+    skipped_at BIGINT  NOT NULL
+);
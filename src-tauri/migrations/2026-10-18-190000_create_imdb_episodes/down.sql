@@ -0,0 +1 @@
+DROP TABLE imdb_episodes;
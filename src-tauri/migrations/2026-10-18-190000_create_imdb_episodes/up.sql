@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS imdb_episodes
+(
+    series_imdb_id TEXT    NOT NULL,
+    season_number  INTEGER NOT NULL,
+    episode_number INTEGER NOT NULL,
+    imdb_id        TEXT    NOT NULL,
+    title          TEXT    NOT NULL,
+    air_date       TEXT,
+    runtime        INTEGER,
+    plot           TEXT,
+    rating         TEXT,
+    PRIMARY KEY (series_imdb_id, season_number, episode_number),
+    FOREIGN KEY (series_imdb_id) REFERENCES imdbs (imdb_id) ON DELETE CASCADE
+);
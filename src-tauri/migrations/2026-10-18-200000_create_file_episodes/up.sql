@@ -0,0 +1,17 @@
+-- Every episode a file holds; more than one for multi-episode files.
+-- files.episode_id stays the first of them, the one the file is listed under.
+CREATE TABLE IF NOT EXISTS file_episodes
+(
+    file_id    INTEGER NOT NULL,
+    episode_id INTEGER NOT NULL,
+    PRIMARY KEY (file_id, episode_id),
+    FOREIGN KEY (file_id) REFERENCES files (id) ON DELETE CASCADE,
+    FOREIGN KEY (episode_id) REFERENCES episodes (id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS file_episodes_episode_id_idx ON file_episodes (episode_id);
+
+INSERT INTO file_episodes (file_id, episode_id)
+SELECT id, episode_id
+FROM files
+WHERE episode_id IS NOT NULL;
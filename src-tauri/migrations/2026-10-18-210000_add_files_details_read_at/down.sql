@@ -0,0 +1 @@
+ALTER TABLE files DROP COLUMN details_read_at;
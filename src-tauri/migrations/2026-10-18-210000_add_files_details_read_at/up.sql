@@ -0,0 +1,2 @@
+-- Rows saved before this column existed are read once more by the next sync.
+ALTER TABLE files ADD COLUMN details_read_at BIGINT;
@@ -0,0 +1 @@
+ALTER TABLE imdbs DROP COLUMN episodes_fetched_at;
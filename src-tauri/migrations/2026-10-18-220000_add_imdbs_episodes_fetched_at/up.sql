@@ -0,0 +1,4 @@
+-- Series whose episode list is already stored count as fetched with their details.
+ALTER TABLE imdbs ADD COLUMN episodes_fetched_at BIGINT;
+UPDATE imdbs SET episodes_fetched_at = fetched_at
+WHERE imdb_id IN (SELECT series_imdb_id FROM imdb_episodes);
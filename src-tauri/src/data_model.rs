@@ -1,15 +1,28 @@
 mod episode;
+mod episode_parser;
 mod imdb;
+mod imdb_episode;
+mod library_root;
+mod match_suggestion;
 mod media;
 mod media_file;
+mod missing_episodes;
 mod season;
+mod subtitle;
+mod sync_job;
 mod tag;
 
 pub type IdType = i32;
 
 pub use episode::Episode;
 pub use imdb::{Imdb, Person};
+pub use imdb_episode::ImdbEpisode;
+pub use library_root::LibraryRoot;
+pub use match_suggestion::{MatchCandidate, MatchSuggestion, RejectedMatches};
 pub use media::Media;
 pub use media_file::{LanguageFormat, MediaFile};
+pub use missing_episodes::MissingEpisodes;
 pub use season::Season;
+pub use subtitle::{SidecarFolder, Subtitle};
+pub use sync_job::{SyncJob, SyncJobStatus};
 pub use tag::Tag;
@@ -1,4 +1,4 @@
-use super::{IdType, media_file::MediaFile};
+use super::{IdType, imdb_episode::ImdbEpisode, media_file::MediaFile};
 use itertools::Itertools;
 use std::path::PathBuf;
 
@@ -8,6 +8,8 @@ pub struct Episode {
     pub number: i32,
     pub watched: bool,
     pub files: Vec<MediaFile>,
+    /// Details from the provider, once they were fetched for the series.
+    pub imdb: Option<ImdbEpisode>,
 }
 impl Ord for Episode {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
@@ -41,6 +43,7 @@ impl Episode {
             number,
             watched: false,
             files: vec![MediaFile::from(path)],
+            imdb: None,
         }
     }
 
@@ -83,6 +86,7 @@ mod tests_episode {
             number: 0,
             watched: false,
             files: vec![file1.clone(), file2.clone()],
+            imdb: None,
         };
 
         let episode2 = Episode {
@@ -90,6 +94,7 @@ mod tests_episode {
             number: 0,
             watched: false,
             files: vec![file3.clone(), file4.clone()],
+            imdb: None,
         };
 
         episode1.merge(&episode2);
@@ -110,6 +115,7 @@ mod tests_episode {
             number: 0,
             watched: false,
             files: vec![file1.clone()],
+            imdb: None,
         };
 
         let empty_other = Episode {
@@ -117,6 +123,7 @@ mod tests_episode {
             number: 0,
             watched: false,
             files: vec![],
+            imdb: None,
         };
 
         episode.merge(&empty_other);
@@ -133,6 +140,7 @@ mod tests_episode {
             number: 0,
             watched: false,
             files: vec![],
+            imdb: None,
         };
 
         let other = Episode {
@@ -140,6 +148,7 @@ mod tests_episode {
             number: 0,
             watched: false,
             files: vec![file1.clone()],
+            imdb: None,
         };
 
         empty_episode.merge(&other);
@@ -0,0 +1,410 @@
+use regex::{Captures, Regex};
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+/// Where a file sits in a series, as read from its name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EpisodeNumber {
+    /// Missing for absolute numbering outside of a season folder.
+    pub season: Option<i32>,
+    /// The episodes the file covers, one unless it is a multi-episode file.
+    pub episodes: RangeInclusive<i32>,
+    /// The number of the episode counted from the start of the series.
+    pub absolute: Option<i32>,
+    /// Where the episode marker starts in the stem; the series name is before.
+    pub name_end: usize,
+}
+
+/// What the folder holding a file tells about it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Folder {
+    /// The number of the folder, when it is named like `Season 02`.
+    pub season: Option<i32>,
+    /// Whether other files in the folder are named like episodes.
+    pub has_episodes: bool,
+}
+
+/// A naming scheme of episode files.
+pub trait EpisodeParser: Sync {
+    /// Reads the episode from the lowercase file stem.
+    fn parse(&self, stem: &str, folder: &Folder) -> Option<EpisodeNumber>;
+}
+
+/// Tried in order, the first parser recognising a name wins. Stricter schemes
+/// come first so a looser one never reads a number that a stricter one owns.
+const PARSERS: &[&dyn EpisodeParser] = &[&SeasonEpisode, &Crossed, &Verbose, &Absolute, &Compact];
+
+/// Files rarely hold more episodes than this; a larger range is a year or a
+/// resolution following the episode.
+const MAX_EPISODES_PER_FILE: i32 = 10;
+
+/// The episode of a video file, or `None` when it is not named like one.
+pub fn parse(path: &Path) -> Option<EpisodeNumber> {
+    parse_among(path, false)
+}
+
+/// Like [`parse`], for a file whose folder holds other files named like
+/// episodes, which makes looser names such as `Show - 102` episodes too.
+pub fn parse_among(path: &Path, has_episodes: bool) -> Option<EpisodeNumber> {
+    let stem = path.file_stem()?.to_str()?.to_lowercase();
+    let folder = Folder {
+        season: folder_name(path, 1).and_then(|name| folder_season(&name)),
+        has_episodes,
+    };
+
+    PARSERS
+        .iter()
+        .find_map(|parser| parser.parse(&stem, &folder))
+}
+
+/// The lowercase name of the folder holding the series, skipping a season
+/// folder, for files whose names hold nothing but the episode.
+pub fn series_folder(path: &Path) -> Option<String> {
+    let parent = folder_name(path, 1)?;
+    if folder_season(&parent).is_some() {
+        folder_name(path, 2)
+    } else {
+        Some(parent)
+    }
+}
+
+fn folder_name(path: &Path, depth: usize) -> Option<String> {
+    let folder = path.ancestors().nth(depth)?;
+    Some(folder.file_name()?.to_str()?.to_lowercase())
+}
+
+fn folder_season(name: &str) -> Option<i32> {
+    let re = Regex::new(r"^(?:season|series|s)[\s._-]*(\d{1,3})$").unwrap();
+    number(&re.captures(name)?, 1)
+}
+
+fn number(caps: &Captures, group: usize) -> Option<i32> {
+    caps.get(group)?.as_str().parse().ok()
+}
+
+/// `first..=last`, or only `first` when `last` does not follow it closely.
+fn episodes(first: i32, last: Option<i32>) -> RangeInclusive<i32> {
+    match last {
+        Some(last) if last > first && last - first < MAX_EPISODES_PER_FILE => first..=last,
+        _ => first..=first,
+    }
+}
+
+/// `S01E02`, `s01.e02`, and the multi-episode `S01E01E02`, `S01E01-E02` and
+/// `S01E01-02`.
+struct SeasonEpisode;
+
+impl EpisodeParser for SeasonEpisode {
+    fn parse(&self, stem: &str, _: &Folder) -> Option<EpisodeNumber> {
+        let re = Regex::new(r"s(\d+)[\s._-]?e(\d+)((?:-?e\d+|-\d{1,3}\b)*)").unwrap();
+        let caps = re.captures(stem)?;
+
+        let first = number(&caps, 2)?;
+        let last = Regex::new(r"\d+")
+            .unwrap()
+            .find_iter(caps.get(3)?.as_str())
+            .last()
+            .and_then(|m| m.as_str().parse().ok());
+
+        Some(EpisodeNumber {
+            season: number(&caps, 1),
+            episodes: episodes(first, last),
+            absolute: None,
+            name_end: caps.get(0)?.start(),
+        })
+    }
+}
+
+/// `1x02` and `1x02-03`.
+struct Crossed;
+
+impl EpisodeParser for Crossed {
+    fn parse(&self, stem: &str, _: &Folder) -> Option<EpisodeNumber> {
+        let re = Regex::new(
+            r"(?:^|[^0-9a-z])(\d{1,2})x(\d{2,3})(?:-(?:\d{1,2}x)?(\d{2,3}))?(?:[^0-9a-z]|$)",
+        )
+        .unwrap();
+        let caps = re.captures(stem)?;
+
+        Some(EpisodeNumber {
+            season: number(&caps, 1),
+            episodes: episodes(number(&caps, 2)?, number(&caps, 3)),
+            absolute: None,
+            name_end: caps.get(1)?.start(),
+        })
+    }
+}
+
+/// `Season 1 Episode 02`, or `Episode 02` inside a season folder.
+struct Verbose;
+
+impl EpisodeParser for Verbose {
+    fn parse(&self, stem: &str, folder: &Folder) -> Option<EpisodeNumber> {
+        let re =
+            Regex::new(r"season[\s._-]*(\d{1,3})[\s._-]*(?:episode|ep)[\s._-]*(\d{1,4})").unwrap();
+        if let Some(caps) = re.captures(stem) {
+            return Some(EpisodeNumber {
+                season: number(&caps, 1),
+                episodes: episodes(number(&caps, 2)?, None),
+                absolute: None,
+                name_end: caps.get(0)?.start(),
+            });
+        }
+
+        let re = Regex::new(r"(?:^|[\s._-])((?:episode|ep)[\s._-]*(\d{1,4}))(?:[^0-9]|$)").unwrap();
+        let caps = re.captures(stem)?;
+        Some(EpisodeNumber {
+            season: Some(folder.season?),
+            episodes: episodes(number(&caps, 2)?, None),
+            absolute: None,
+            name_end: caps.get(1)?.start(),
+        })
+    }
+}
+
+/// `[Group] Show - 124`, numbered from the start of the series as anime
+/// releases are, and `[Group] Show - 01-02`. Inside a season folder the
+/// number is read as the episode of that season.
+struct Absolute;
+
+impl EpisodeParser for Absolute {
+    fn parse(&self, stem: &str, folder: &Folder) -> Option<EpisodeNumber> {
+        let re = Regex::new(
+            r"^(?:\[[^\]]*\][\s._]*)+.+?([\s._]+-[\s._]+)(\d{1,4})(?:v\d)?(?:-(\d{1,4})(?:v\d)?)?(?:[\s._\[(]|$)",
+        )
+        .unwrap();
+        let caps = re.captures(stem)?;
+        let episodes = episodes(number(&caps, 2)?, number(&caps, 3));
+
+        Some(EpisodeNumber {
+            season: folder.season,
+            absolute: folder.season.is_none().then(|| *episodes.start()),
+            episodes,
+            name_end: caps.get(1)?.start(),
+        })
+    }
+}
+
+/// `Show - 102` for season 1 episode 2, or `Show - 02` inside a season folder.
+/// Movies are named like this too, as `Fahrenheit - 451` is, so the numbers
+/// are only read inside a season folder or next to other episodes.
+struct Compact;
+
+impl EpisodeParser for Compact {
+    fn parse(&self, stem: &str, folder: &Folder) -> Option<EpisodeNumber> {
+        let re = Regex::new(r"[\s._]-[\s._]+(\d{1,4})(?:[^0-9p]|$)").unwrap();
+        let caps = re.captures(stem)?;
+        let value = number(&caps, 1)?;
+
+        if folder.season.is_none() && !folder.has_episodes {
+            return None;
+        }
+
+        let (season, episode) = match value {
+            // A year, not a season and episode.
+            1900..=2099 => return None,
+            100.. if value % 100 != 0 => (value / 100, value % 100),
+            1..100 => (folder.season?, value),
+            _ => return None,
+        };
+
+        Some(EpisodeNumber {
+            season: Some(season),
+            episodes: episode..=episode,
+            absolute: None,
+            name_end: caps.get(0)?.start(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests_episode_parser {
+    use super::*;
+
+    fn parsed(path: &str) -> Option<(Option<i32>, RangeInclusive<i32>, Option<i32>)> {
+        parse(Path::new(path)).map(|number| (number.season, number.episodes, number.absolute))
+    }
+
+    fn name(path: &str) -> String {
+        let stem = Path::new(path)
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap()
+            .to_lowercase();
+        stem[..parse(Path::new(path)).unwrap().name_end].to_string()
+    }
+
+    #[test]
+    fn reads_season_and_episode() {
+        let cases = [
+            ("/tv/show.s01e02.mkv", Some(1), 2..=2),
+            ("/tv/Show.S10E123.720p.mkv", Some(10), 123..=123),
+            ("/tv/show.s01.e02.mkv", Some(1), 2..=2),
+            ("/tv/show.s01e01e02.mkv", Some(1), 1..=2),
+            ("/tv/show.s01e01-e03.mkv", Some(1), 1..=3),
+            ("/tv/show.s01e01-02.720p.mkv", Some(1), 1..=2),
+            ("/tv/show.s01e01-1080p.mkv", Some(1), 1..=1),
+            ("/tv/show.s01e01-2019.mkv", Some(1), 1..=1),
+        ];
+
+        for (path, season, episodes) in cases {
+            assert_eq!(parsed(path), Some((season, episodes, None)), "{path}");
+        }
+        assert_eq!(name("/tv/show.s01e02.mkv"), "show.");
+    }
+
+    #[test]
+    fn reads_crossed_numbers() {
+        let cases = [
+            ("/tv/show.1x02.mkv", Some(1), 2..=2),
+            ("/tv/Show 12x105 Title.avi", Some(12), 105..=105),
+            ("/tv/show.1x02-03.mkv", Some(1), 2..=3),
+            ("/tv/show.1x02-1x03.mkv", Some(1), 2..=3),
+        ];
+
+        for (path, season, episodes) in cases {
+            assert_eq!(parsed(path), Some((season, episodes, None)), "{path}");
+        }
+        assert_eq!(name("/tv/show.1x02.mkv"), "show.");
+        assert_eq!(parsed("/movies/movie.1920x1080.mkv"), None);
+        assert_eq!(parsed("/movies/movie.x264.mkv"), None);
+    }
+
+    #[test]
+    fn reads_spelled_out_season_and_episode() {
+        assert_eq!(
+            parsed("/tv/Show Season 1 Episode 02.mkv"),
+            Some((Some(1), 2..=2, None))
+        );
+        assert_eq!(
+            parsed("/tv/show.season.2.ep.10.mkv"),
+            Some((Some(2), 10..=10, None))
+        );
+        assert_eq!(
+            parsed("/tv/Show/Season 1/Episode 02.mkv"),
+            Some((Some(1), 2..=2, None))
+        );
+        assert_eq!(name("/tv/Show Season 1 Episode 02.mkv"), "show ");
+        assert_eq!(name("/tv/Show/Season 1/Episode 02.mkv"), "");
+
+        // Without a season the episode could belong anywhere.
+        assert_eq!(parsed("/tv/Show/Episode 02.mkv"), None);
+    }
+
+    #[test]
+    fn reads_absolute_numbers() {
+        let cases = [
+            ("/anime/[Group] Show - 124.mkv", None, 124..=124, Some(124)),
+            (
+                "/anime/[Group] Show Name - 07v2 [1080p][ABCD1234].mkv",
+                None,
+                7..=7,
+                Some(7),
+            ),
+            (
+                "/anime/[Group] Show - 01-02 [720p].mkv",
+                None,
+                1..=2,
+                Some(1),
+            ),
+            (
+                "/anime/Show/Season 02/[Group] Show - 05.mkv",
+                Some(2),
+                5..=5,
+                None,
+            ),
+        ];
+
+        for (path, season, episodes, absolute) in cases {
+            assert_eq!(parsed(path), Some((season, episodes, absolute)), "{path}");
+        }
+        assert_eq!(name("/anime/[Group] Show - 124.mkv"), "[group] show");
+    }
+
+    #[test]
+    fn reads_compact_numbers() {
+        let among_episodes = |path: &str| {
+            parse_among(Path::new(path), true)
+                .map(|number| (number.season, number.episodes, number.absolute))
+        };
+        let cases = [
+            ("/tv/Show - 102.mkv", Some(1), 2..=2),
+            ("/tv/Show - 1012 - Title.mkv", Some(10), 12..=12),
+        ];
+
+        for (path, season, episodes) in cases {
+            assert_eq!(
+                among_episodes(path),
+                Some((season, episodes, None)),
+                "{path}"
+            );
+        }
+        assert_eq!(
+            parsed("/tv/Show/Season 02/Show - 05.mkv"),
+            Some((Some(2), 5..=5, None))
+        );
+        assert_eq!(
+            parsed("/tv/Show/Season 02/Show - 205.mkv"),
+            Some((Some(2), 5..=5, None))
+        );
+        assert_eq!(name("/tv/Show/Season 01/Show - 102.mkv"), "show");
+
+        // Alone in a folder, the numbers belong to the name of a movie.
+        assert_eq!(parsed("/tv/Show - 102.mkv"), None);
+        assert_eq!(parsed("/movies/Fahrenheit - 451.mkv"), None);
+        assert_eq!(parsed("/movies/Movie - 101 Dalmatians.mkv"), None);
+        assert_eq!(among_episodes("/movies/Movie - 2019.mkv"), None);
+        assert_eq!(among_episodes("/movies/Movie - 1080p.mkv"), None);
+        assert_eq!(among_episodes("/movies/Movie - 300.mkv"), None);
+        assert_eq!(among_episodes("/movies/Movie - 05.mkv"), None);
+    }
+
+    #[test]
+    fn season_folders_give_context() {
+        let cases = [
+            ("/tv/Show/Season 02/Episode 3.mkv", Some(2)),
+            ("/tv/Show/season.2/ep03.mkv", Some(2)),
+            ("/tv/Show/S02/Episode 3.mkv", Some(2)),
+            ("/tv/Show/Extras/Episode 3.mkv", None),
+        ];
+
+        for (path, season) in cases {
+            assert_eq!(
+                parsed(path).map(|(season, ..)| season),
+                season.map(Some),
+                "{path}"
+            );
+        }
+
+        // The name of the file wins over its folder.
+        assert_eq!(
+            parsed("/tv/Show/Season 02/show.s03e04.mkv"),
+            Some((Some(3), 4..=4, None))
+        );
+    }
+
+    #[test]
+    fn finds_the_series_folder() {
+        assert_eq!(
+            series_folder(Path::new("/tv/Show/Season 1/Episode 02.mkv")).as_deref(),
+            Some("show")
+        );
+        assert_eq!(
+            series_folder(Path::new("/tv/Show/Episode 02.mkv")).as_deref(),
+            Some("show")
+        );
+        assert_eq!(series_folder(Path::new("Episode 02.mkv")), None);
+    }
+
+    #[test]
+    fn ignores_other_names() {
+        for path in [
+            "/movies/Inception.2010.1080p.mkv",
+            "/movies/some_show_S05E.avi",
+            "/movies/Room 101.mkv",
+            "/movies/[Group] Movie.mkv",
+        ] {
+            assert_eq!(parsed(path), None, "{path}");
+        }
+    }
+}
@@ -1,4 +1,4 @@
-#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub struct Imdb {
     pub imdb_id: String,
     pub r#type: String,
@@ -10,12 +10,25 @@ pub struct Imdb {
     pub poster: String,
     pub imdb_rating: String,
     pub imdb_votes: i32,
+    /// Certification, such as `PG-13`.
+    #[serde(default)]
+    pub rated: String,
+    /// Length in minutes, 0 when unknown.
+    #[serde(default)]
+    pub runtime: i32,
+    #[serde(default)]
+    pub awards: String,
+    #[serde(default)]
+    pub box_office: String,
+    /// Number of seasons of a series, 0 for other titles.
+    #[serde(default)]
+    pub total_seasons: i32,
     pub actors: Vec<Person>,
     pub writers: Vec<Person>,
     pub directors: Vec<Person>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub struct Person {
     pub id: String,
     pub name: String,
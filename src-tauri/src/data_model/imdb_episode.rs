@@ -0,0 +1,16 @@
+/// Provider details of one episode of a series, known whether or not the
+/// library has a file of it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ImdbEpisode {
+    /// Id of the episode itself, not of its series.
+    pub imdb_id: String,
+    pub season: i32,
+    pub episode: i32,
+    pub title: String,
+    /// First air date as `YYYY-MM-DD`, or as much of it as is known.
+    pub air_date: String,
+    /// Length in minutes, 0 when unknown.
+    pub runtime: i32,
+    pub plot: String,
+    pub rating: String,
+}
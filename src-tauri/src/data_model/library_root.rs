@@ -0,0 +1,9 @@
+use super::IdType;
+
+/// A directory the scanner walks to find media files.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct LibraryRoot {
+    pub id: IdType,
+    pub path: String,
+    pub enabled: bool,
+}
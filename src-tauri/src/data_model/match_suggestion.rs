@@ -0,0 +1,44 @@
+use std::collections::{HashMap, HashSet};
+
+use super::media::Media;
+
+/// The best title found for a media when the matcher was not confident
+/// enough to attach it. The media keeps no imdb until the match is reviewed.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct MatchSuggestion {
+    pub imdb_id: String,
+    pub title: String,
+    pub year: Option<i32>,
+    /// How well the title fits the media, from 0 to 100.
+    pub confidence: u8,
+}
+
+/// A title found by a search, scored against what was searched for.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct MatchCandidate {
+    pub imdb_id: String,
+    pub title: String,
+    pub year: Option<i32>,
+    pub r#type: Option<String>,
+    pub poster: Option<String>,
+    pub rating: Option<String>,
+    /// How well the title fits the media, from 0 to 100.
+    pub confidence: u8,
+}
+
+/// Titles rejected during review, by the name and year of their media, so
+/// syncs never propose them again.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RejectedMatches(HashMap<(String, Option<i32>), HashSet<String>>);
+
+impl RejectedMatches {
+    pub fn insert(&mut self, name: String, year: Option<i32>, imdb_id: String) {
+        self.0.entry((name, year)).or_default().insert(imdb_id);
+    }
+
+    pub fn contains(&self, media: &Media, imdb_id: &str) -> bool {
+        self.0
+            .get(&(media.name.clone(), media.year))
+            .is_some_and(|ids| ids.contains(imdb_id))
+    }
+}
@@ -1,7 +1,19 @@
-use super::{IdType, imdb::Imdb, media_file::MediaFile, season::Season, tag::Tag};
+use super::{
+    IdType,
+    episode_parser::{self, EpisodeNumber},
+    imdb::Imdb,
+    match_suggestion::MatchSuggestion,
+    media_file::MediaFile,
+    season::Season,
+    tag::Tag,
+};
+use crate::settings::VideoExtensions;
 use itertools::Itertools;
 use regex::Regex;
-use std::path::PathBuf;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 
 #[derive(Debug, Clone, Default, Eq, serde::Serialize)]
 pub struct Media {
@@ -15,6 +27,8 @@ pub struct Media {
     pub files: Vec<MediaFile>,
     pub imdb: Option<Imdb>,
     pub tags: Vec<Tag>,
+    /// A low-confidence match waiting for review.
+    pub suggestion: Option<MatchSuggestion>,
 }
 
 impl PartialEq for Media {
@@ -32,43 +46,122 @@ impl PartialEq for Media {
             && self.tags.iter().sorted().eq(other.tags.iter().sorted())
             && self.imdb == other.imdb
             && self.watch_list == other.watch_list
+            && self.suggestion == other.suggestion
     }
 }
 
 impl From<PathBuf> for Media {
     fn from(path: PathBuf) -> Self {
-        let video_stem = path
-            .file_stem()
-            .and_then(|s| s.to_str())
-            .unwrap_or("")
-            .to_lowercase();
+        Self::from_path(path, false)
+    }
+}
 
-        let (season, files) = match Season::try_from(path.clone()) {
-            Ok(x) => (vec![x], vec![]),
-            Err(_) => (vec![], vec![MediaFile::from(path)]),
+impl From<&PathBuf> for Media {
+    fn from(path: &PathBuf) -> Self {
+        Self::from(path.clone())
+    }
+}
+
+impl Media {
+    /// Like [`Media::from`], for a file whose folder holds other files named
+    /// like episodes, see [`episode_parser::parse_among`].
+    pub fn from_path(path: PathBuf, has_episodes: bool) -> Self {
+        let video_stem = Self::video_stem(&path);
+        let number = episode_parser::parse_among(&path, has_episodes);
+        let name = Self::name_of(&path, &video_stem, number.as_ref());
+
+        let (seasons, files) = match number {
+            Some(number) => (vec![Season::new(path, &number)], vec![]),
+            None => (vec![], vec![MediaFile::from(path)]),
         };
 
         Self {
-            name: Self::detect_name(&video_stem),
+            name,
             year: Self::detect_year(&video_stem),
             files,
-            seasons: season,
+            seasons,
             ..Self::default()
         }
     }
-}
 
-impl From<&PathBuf> for Media {
-    fn from(path: &PathBuf) -> Self {
-        Self::from(path.clone())
+    /// Whether `path` is named like an episode on its own.
+    pub fn is_episode(path: &Path) -> bool {
+        episode_parser::parse(path).is_some()
+    }
+
+    /// The name and year [`Media::from`] gives the media of `path`, without
+    /// building its files.
+    pub fn name_and_year(path: &Path) -> (String, Option<i32>) {
+        let video_stem = Self::video_stem(path);
+        let number = episode_parser::parse(path);
+        (
+            Self::name_of(path, &video_stem, number.as_ref()),
+            Self::detect_year(&video_stem),
+        )
+    }
+
+    fn video_stem(path: &Path) -> String {
+        path.file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("")
+            .to_lowercase()
+    }
+
+    /// Episodes are named by the text before their number, or else by their
+    /// series folder.
+    fn name_of(path: &Path, video_stem: &str, number: Option<&EpisodeNumber>) -> String {
+        match number {
+            Some(number) => Some(Self::detect_name(&video_stem[..number.name_end]))
+                .filter(|name| !name.is_empty())
+                .or_else(|| episode_parser::series_folder(path).map(|f| Self::detect_name(&f)))
+                .unwrap_or_default(),
+            None => Self::detect_name(video_stem),
+        }
     }
-}
 
-impl Media {
     pub fn is_series(&self) -> bool {
         !self.seasons.is_empty()
     }
 
+    /// Id of the title the media is matched to.
+    pub fn imdb_id(&self) -> Option<&str> {
+        self.imdb.as_ref().map(|imdb| imdb.imdb_id.as_str())
+    }
+
+    /// Paths of every file of the media, episode files included.
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        self.files
+            .iter()
+            .chain(
+                self.seasons
+                    .iter()
+                    .flat_map(|season| &season.episodes)
+                    .flat_map(|episode| &episode.files),
+            )
+            .map(|file| file.path.as_str())
+    }
+
+    /// Reads what is on disk of every file, see [`MediaFile::read_details`].
+    /// A multi-episode file listed under several episodes is read once.
+    pub fn read_details(&mut self, videos: &VideoExtensions) {
+        let mut read: HashMap<String, MediaFile> = HashMap::new();
+        let episode_files = self
+            .seasons
+            .iter_mut()
+            .flat_map(|season| &mut season.episodes)
+            .flat_map(|episode| &mut episode.files);
+
+        for file in self.files.iter_mut().chain(episode_files) {
+            match read.get(&file.path) {
+                Some(done) => *file = done.clone(),
+                None => {
+                    file.read_details(videos);
+                    read.insert(file.path.clone(), file.clone());
+                }
+            }
+        }
+    }
+
     pub fn merge(&mut self, other: &Self) {
         if self.year.is_none() {
             self.year = other.year;
@@ -134,8 +227,12 @@ impl Media {
             "salamdl",
         ];
 
-        // Step 1: Normalize separators by replacing '.', '(', ')' with spaces.
-        let mut cleaned = input.replace(['.', '_', '-', '(', ')'], " ");
+        // Step 1: Drop release group and checksum tags such as "[group]", then
+        // normalize separators by replacing '.', '(', ')' with spaces.
+        let untagged = Regex::new(r"\[[^\]]*\]")
+            .expect("Regex compilation failed")
+            .replace_all(input, " ");
+        let mut cleaned = untagged.replace(['.', '_', '-', '(', ')'], " ");
 
         // Step 2: Remove all junk tags to reduce noise.
         for tag in junk_tags.iter() {
@@ -256,6 +353,7 @@ mod detect_name_tests {
                 "emperor_of_the_sea_2004_s01e04_farsi_dubbed_(mer30download.com)",
                 "emperor of the sea",
             ),
+            ("[group] show name [1080p]", "show name"),
             (
                 "197863_harry_potter_and_the_halfblood_prince_2009_dubbed_1080p_brrip_anoxmous_salamdl",
                 "197863 harry potter and the halfblood prince",
@@ -304,6 +402,53 @@ mod tests_media_from {
         assert_eq!(media.imdb, None);
     }
 
+    #[test]
+    fn names_series_of_each_naming_scheme() {
+        let cases = [
+            ("/tv/Black.Mirror.1x02.mkv", "black mirror", 1, 2),
+            (
+                "/tv/Black Mirror/Season 01/Black Mirror - 102.mkv",
+                "black mirror",
+                1,
+                2,
+            ),
+            (
+                "/tv/Black Mirror Season 1 Episode 02.mkv",
+                "black mirror",
+                1,
+                2,
+            ),
+            (
+                "/tv/Black Mirror/Season 01/Episode 02.mkv",
+                "black mirror",
+                1,
+                2,
+            ),
+            (
+                "/anime/[Group] One Piece - 124 [1080p].mkv",
+                "one piece",
+                1,
+                124,
+            ),
+            (
+                "/anime/One Piece/Season 02/[Group] One Piece - 05.mkv",
+                "one piece",
+                2,
+                5,
+            ),
+        ];
+
+        for (path, name, season, episode) in cases {
+            let media = Media::from(PathBuf::from(path));
+
+            assert_eq!(media.name, name, "{path}");
+            assert_eq!(media.seasons.len(), 1, "{path}");
+            assert_eq!(media.seasons[0].number, season, "{path}");
+            assert_eq!(media.seasons[0].episodes[0].number, episode, "{path}");
+            assert!(media.files.is_empty(), "{path}");
+        }
+    }
+
     #[test]
     fn lowercase_conversion() {
         let path = PathBuf::from("/path/to/MOVIE.2021.MP4");
@@ -328,6 +473,22 @@ mod tests_media_from {
         assert_eq!(media_from_ref.files.len(), media_from_owned.files.len());
         assert_eq!(media_from_ref.imdb, media_from_owned.imdb);
     }
+
+    #[test]
+    fn name_and_year_match_the_parsed_media() {
+        for path in [
+            "/path/to/Who.Am.I.2014.1080p.mkv",
+            "/series/Dark/Season 1/Dark.S01E02.2017.mkv",
+            "/anime/One Piece/Season 02/[Group] One Piece - 05.mkv",
+        ] {
+            let media = Media::from(PathBuf::from(path));
+            assert_eq!(
+                Media::name_and_year(Path::new(path)),
+                (media.name, media.year),
+                "{path}"
+            );
+        }
+    }
 }
 
 #[cfg(test)]
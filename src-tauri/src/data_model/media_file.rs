@@ -1,4 +1,6 @@
-use super::IdType;
+use super::{IdType, Subtitle};
+use crate::media_probe::{self, MediaProbe};
+use crate::settings::VideoExtensions;
 use diesel::{
     backend::Backend,
     deserialize::FromSql,
@@ -8,10 +10,19 @@ use diesel::{
     sqlite::Sqlite,
 };
 use regex::Regex;
-use std::path::PathBuf;
+use sha2::{Digest, Sha256};
+use std::{
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+/// Bytes hashed from each end of a file when fingerprinting it.
+const FINGERPRINT_BLOCK: u64 = 64 * 1024;
 
 #[derive(
     Debug,
+    Default,
     PartialEq,
     Eq,
     Clone,
@@ -27,6 +38,7 @@ pub enum LanguageFormat {
     SoftSub,
     HardSub,
     Dubbed,
+    #[default]
     Unknown,
 }
 
@@ -71,12 +83,6 @@ impl FromSql<Text, Sqlite> for LanguageFormat {
     }
 }
 
-impl Default for LanguageFormat {
-    fn default() -> Self {
-        Self::Unknown
-    }
-}
-
 impl LanguageFormat {
     pub fn as_str(&self) -> &str {
         match &self {
@@ -114,6 +120,11 @@ pub struct MediaFile {
     pub path: String,
     pub quality: Option<String>,
     pub language_format: LanguageFormat,
+    pub fingerprint: Option<String>,
+    /// Container details; `None` when the file has not been or cannot be probed.
+    pub probe: Option<MediaProbe>,
+    /// Sidecar subtitle files found next to the video.
+    pub subtitles: Vec<Subtitle>,
 }
 
 impl Ord for MediaFile {
@@ -143,6 +154,8 @@ impl PartialEq for MediaFile {
 
 impl Eq for MediaFile {}
 
+/// Reads only the name of the file; what is on disk is read by
+/// [`MediaFile::read_details`].
 impl From<PathBuf> for MediaFile {
     fn from(path: PathBuf) -> Self {
         let video_stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
@@ -155,11 +168,67 @@ impl From<PathBuf> for MediaFile {
             path: path.to_str().unwrap().to_string(),
             quality: Self::detect_quality(&normalized),
             language_format: LanguageFormat::from(&normalized),
+            fingerprint: None,
+            probe: None,
+            subtitles: vec![],
         }
     }
 }
 
 impl MediaFile {
+    /// Reads the fingerprint, the container details and the sidecar subtitles
+    /// of the file. This blocks on the disk, so async code runs it through
+    /// `spawn_blocking`.
+    pub fn read_details(&mut self, videos: &VideoExtensions) {
+        let path = PathBuf::from(&self.path);
+        self.fingerprint = Self::fingerprint(&path);
+        self.probe = media_probe::probe(&path);
+        self.subtitles = Subtitle::find_sidecars(&path, videos);
+    }
+
+    /// Identifies a file by its size and a hash of its first and last blocks,
+    /// so it can be recognised after a move or rename without reading it whole.
+    /// Returns `None` when the file cannot be read.
+    pub fn fingerprint(path: &Path) -> Option<String> {
+        Self::read_fingerprint(path).ok()
+    }
+
+    fn read_fingerprint(path: &Path) -> io::Result<String> {
+        let mut file = File::open(path)?;
+        let metadata = file.metadata()?;
+        if !metadata.is_file() {
+            return Err(io::Error::other("not a file"));
+        }
+        let size = metadata.len();
+
+        let mut hasher = Sha256::new();
+        let mut block = Vec::with_capacity(FINGERPRINT_BLOCK as usize);
+
+        (&mut file)
+            .take(FINGERPRINT_BLOCK)
+            .read_to_end(&mut block)?;
+        hasher.update(&block);
+
+        if size > FINGERPRINT_BLOCK * 2 {
+            block.clear();
+            file.seek(SeekFrom::End(-(FINGERPRINT_BLOCK as i64)))?;
+            file.read_to_end(&mut block)?;
+            hasher.update(&block);
+        } else if size > FINGERPRINT_BLOCK {
+            block.clear();
+            file.read_to_end(&mut block)?;
+            hasher.update(&block);
+        }
+
+        let digest: String = hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+
+        Ok(format!("{size}-{digest}"))
+    }
+
     fn detect_quality(input: &str) -> Option<String> {
         // Case-insensitive regex for common quality tags
         let re = Regex::new(r"(?i)\b(4k|2160p|1080p|720p|480p|hd|hq)\b").unwrap();
@@ -458,3 +527,86 @@ mod tests_file_from_path_buf {
         assert_eq!(file.language_format, LanguageFormat::Unknown);
     }
 }
+
+#[cfg(test)]
+mod tests_fingerprint {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn missing_file_has_no_fingerprint() {
+        assert_eq!(
+            MediaFile::fingerprint(Path::new("/path/to/movie.mkv")),
+            None
+        );
+        let mut file = MediaFile::from(PathBuf::from("/path/to/movie.mkv"));
+        file.read_details(&VideoExtensions::default());
+        assert_eq!(file.fingerprint, None);
+    }
+
+    #[test]
+    fn name_parse_does_not_read_the_file() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let path = temp_dir.path().join("movie.mkv");
+        fs::write(&path, b"movie").expect("Failed to write");
+        fs::write(temp_dir.path().join("movie.en.srt"), b"1").expect("Failed to write");
+
+        let mut file = MediaFile::from(path.clone());
+        assert_eq!(file.fingerprint, None);
+        assert!(file.subtitles.is_empty());
+
+        file.read_details(&VideoExtensions::default());
+        assert_eq!(file.fingerprint, MediaFile::fingerprint(&path));
+        assert_eq!(file.subtitles.len(), 1);
+    }
+
+    #[test]
+    fn directory_has_no_fingerprint() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        assert_eq!(MediaFile::fingerprint(temp_dir.path()), None);
+    }
+
+    #[test]
+    fn fingerprint_survives_rename() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let old = temp_dir.path().join("movie.mkv");
+        let new = temp_dir.path().join("Movie.2020.1080p.mkv");
+        fs::write(&old, vec![7u8; 300 * 1024]).expect("Failed to write");
+
+        let before = MediaFile::fingerprint(&old).unwrap();
+        fs::rename(&old, &new).expect("Failed to rename");
+
+        assert_eq!(MediaFile::fingerprint(&new), Some(before));
+    }
+
+    #[test]
+    fn fingerprint_depends_on_size_and_content() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let sizes = [
+            0,
+            10,
+            FINGERPRINT_BLOCK as usize + 1,
+            3 * FINGERPRINT_BLOCK as usize,
+        ];
+
+        let mut fingerprints = vec![];
+        for (i, size) in sizes.into_iter().enumerate() {
+            let path = temp_dir.path().join(format!("{i}.mkv"));
+            fs::write(&path, vec![1u8; size]).expect("Failed to write");
+            fingerprints.push(MediaFile::fingerprint(&path).unwrap());
+        }
+
+        let tail_changed = temp_dir.path().join("tail.mkv");
+        let mut content = vec![1u8; 3 * FINGERPRINT_BLOCK as usize];
+        *content.last_mut().unwrap() = 2;
+        fs::write(&tail_changed, content).expect("Failed to write");
+        fingerprints.push(MediaFile::fingerprint(&tail_changed).unwrap());
+
+        let mut unique = fingerprints.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), fingerprints.len());
+        assert!(fingerprints[0].starts_with("0-"));
+    }
+}
@@ -0,0 +1,149 @@
+use std::collections::{BTreeMap, HashSet};
+
+use super::{IdType, imdb_episode::ImdbEpisode};
+
+/// The aired episodes of a season the library has no file of.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SeasonGap {
+    pub season: i32,
+    /// Episodes of the season aired so far.
+    pub aired: usize,
+    pub missing: Vec<ImdbEpisode>,
+}
+
+/// How a series on disk compares to the provider's episode list.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MissingEpisodes {
+    pub media_id: IdType,
+    pub name: String,
+    /// Whether the episode list of the series was fetched. Without one
+    /// nothing is known to be missing.
+    pub checked: bool,
+    /// Seasons with missing episodes, in order.
+    pub seasons: Vec<SeasonGap>,
+}
+
+impl MissingEpisodes {
+    /// Compares the `(season, episode)` numbers found on disk with the listed
+    /// episodes. Specials of season 0 and episodes not aired by `today`, a
+    /// `YYYY-MM-DD` date, are never missing.
+    pub fn new(
+        media_id: IdType,
+        name: String,
+        listed: &[ImdbEpisode],
+        on_disk: &HashSet<(i32, i32)>,
+        today: &str,
+    ) -> Self {
+        let mut seasons: BTreeMap<i32, SeasonGap> = BTreeMap::new();
+        for episode in listed.iter().filter(|episode| episode.season > 0) {
+            // Partial dates such as `2011-12` sort before any day of their month.
+            if episode.air_date.is_empty() || episode.air_date.as_str() > today {
+                continue;
+            }
+
+            let gap = seasons.entry(episode.season).or_insert_with(|| SeasonGap {
+                season: episode.season,
+                aired: 0,
+                missing: vec![],
+            });
+            gap.aired += 1;
+            if !on_disk.contains(&(episode.season, episode.episode)) {
+                gap.missing.push(episode.clone());
+            }
+        }
+
+        Self {
+            media_id,
+            name,
+            checked: !listed.is_empty(),
+            seasons: seasons
+                .into_values()
+                .filter(|gap| !gap.missing.is_empty())
+                .collect(),
+        }
+    }
+
+    /// Every aired episode is on disk.
+    pub fn is_complete(&self) -> bool {
+        self.checked && self.seasons.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests_missing_episodes {
+    use super::*;
+
+    fn listed(season: i32, episode: i32, air_date: &str) -> ImdbEpisode {
+        ImdbEpisode {
+            imdb_id: format!("tt{season}{episode}"),
+            season,
+            episode,
+            air_date: air_date.into(),
+            ..ImdbEpisode::default()
+        }
+    }
+
+    fn numbers(gap: &SeasonGap) -> Vec<i32> {
+        gap.missing.iter().map(|episode| episode.episode).collect()
+    }
+
+    #[test]
+    fn reports_gaps_for_each_season() {
+        let listed = vec![
+            listed(1, 1, "2011-12-04"),
+            listed(1, 2, "2011-12-11"),
+            listed(1, 3, "2011-12-18"),
+            listed(2, 1, "2013-02-11"),
+            listed(2, 2, "2013-02-18"),
+            listed(3, 1, "2016-10-21"),
+        ];
+        let on_disk = HashSet::from([(1, 1), (1, 3), (2, 1), (2, 2)]);
+
+        let missing =
+            MissingEpisodes::new(1, "black mirror".into(), &listed, &on_disk, "2026-10-18");
+
+        assert!(missing.checked);
+        assert!(!missing.is_complete());
+        assert_eq!(missing.seasons.len(), 2);
+        assert_eq!(missing.seasons[0].season, 1);
+        assert_eq!(missing.seasons[0].aired, 3);
+        assert_eq!(numbers(&missing.seasons[0]), vec![2]);
+        // A season with no file at all is missing as a whole.
+        assert_eq!(missing.seasons[1].season, 3);
+        assert_eq!(numbers(&missing.seasons[1]), vec![1]);
+    }
+
+    #[test]
+    fn unaired_episodes_and_specials_are_not_missing() {
+        let listed = vec![
+            listed(1, 1, "2011-12-04"),
+            listed(0, 1, "2011-12-24"),
+            listed(1, 2, "2026-10-19"),
+            listed(1, 3, ""),
+            listed(1, 4, "2026-10"),
+            listed(1, 5, "2027"),
+        ];
+        let on_disk = HashSet::from([(1, 1), (1, 4)]);
+
+        let missing = MissingEpisodes::new(1, "show".into(), &listed, &on_disk, "2026-10-18");
+
+        assert!(missing.is_complete());
+    }
+
+    #[test]
+    fn unchecked_series_are_not_complete() {
+        let missing = MissingEpisodes::new(
+            1,
+            "show".into(),
+            &[],
+            &HashSet::from([(1, 1)]),
+            "2026-10-18",
+        );
+
+        assert!(!missing.checked);
+        assert!(missing.seasons.is_empty());
+        assert!(!missing.is_complete());
+    }
+}
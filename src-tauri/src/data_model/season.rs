@@ -1,6 +1,6 @@
+use super::episode_parser::{self, EpisodeNumber};
 use super::{IdType, episode::Episode};
 use itertools::Itertools;
-use regex::Regex;
 use std::path::PathBuf;
 
 #[derive(Debug, Clone, serde::Serialize)]
@@ -44,20 +44,32 @@ impl TryFrom<PathBuf> for Season {
     type Error = Box<dyn std::error::Error>;
 
     fn try_from(path: PathBuf) -> Result<Self, Self::Error> {
-        let video_stem = path
-            .file_stem()
-            .and_then(|s| s.to_str())
-            .unwrap_or("")
-            .to_lowercase();
+        let number = episode_parser::parse(&path).ok_or("it's not series")?;
 
-        let series = Self::detect_series(&video_stem)?;
+        Ok(Self::new(path, &number))
+    }
+}
+
+impl Season {
+    /// The season holding the episode file at `path`, listed under every
+    /// episode it holds. Absolute numbers outside of a season folder are kept
+    /// in the first season.
+    pub fn new(path: PathBuf, number: &EpisodeNumber) -> Self {
+        let first = Episode::new(path, *number.episodes.start());
 
-        Ok(Self {
+        Self {
             id: 0,
-            number: series.0,
+            number: number.season.unwrap_or(1),
             watched: false,
-            episodes: vec![Episode::new(path, series.1)],
-        })
+            episodes: number
+                .episodes
+                .clone()
+                .map(|n| Episode {
+                    number: n,
+                    ..first.clone()
+                })
+                .collect(),
+        }
     }
 }
 
@@ -79,69 +91,61 @@ impl Season {
     }
 }
 
-impl Season {
-    fn detect_series(input: &str) -> Result<(i32, i32), Box<dyn std::error::Error>> {
-        let re = Regex::new(r"(?i)s(\d+)[\s._-]?e(\d+)")?;
-
-        re.captures(input)
-            .and_then(|caps| {
-                let season = caps.get(1)?.as_str().parse().ok()?;
-                let episode = caps.get(2)?.as_str().parse().ok()?;
-                Some(Ok((season, episode)))
-            })
-            .unwrap_or(Err("it's not series".into()))
-    }
-}
-
 #[cfg(test)]
 mod detect_series_tests {
     use super::*;
+    use std::path::Path;
+
+    fn detect(input: &str) -> Option<(i32, i32)> {
+        episode_parser::parse(Path::new(input))
+            .map(|number| (number.season.unwrap_or(1), *number.episodes.start()))
+    }
 
     #[test]
     fn detects_standard_sxxexx_format() {
         let input = "Breaking.Bad.S02E05.720p.mkv";
         let expected = (2, 5);
-        assert_eq!(Season::detect_series(input).unwrap(), expected);
+        assert_eq!(detect(input), Some(expected));
     }
 
     #[test]
     fn detects_underscored_format() {
         let input = "_S03_E10_";
         let expected = (3, 10);
-        assert_eq!(Season::detect_series(input).unwrap(), expected);
+        assert_eq!(detect(input), Some(expected));
     }
 
     #[test]
     fn detects_mixed_case_and_separator() {
         let input = "s04-e11.avi";
         let expected = (4, 11);
-        assert_eq!(Season::detect_series(input).unwrap(), expected);
+        assert_eq!(detect(input), Some(expected));
     }
 
     #[test]
     fn handles_lowercase_with_dot_separator() {
         let input = "showname.s01.e09.mkv";
         let expected = (1, 9);
-        assert_eq!(Season::detect_series(input).unwrap(), expected);
+        assert_eq!(detect(input), Some(expected));
     }
 
     #[test]
     fn returns_none_if_no_match() {
         let input = "Inception.2010.1080p.mkv";
-        assert!(Season::detect_series(input).is_err());
+        assert!(detect(input).is_none());
     }
 
     #[test]
     fn handles_partial_match_but_incorrect_format() {
         let input = "some_show_S05E.avi";
-        assert!(Season::detect_series(input).is_err());
+        assert!(detect(input).is_none());
     }
 
     #[test]
     fn accepts_input_with_leading_or_trailing_underscores() {
         let input = "_S2_E8_.Something.Else.mp4";
         let expected = (2, 8);
-        assert_eq!(Season::detect_series(input).unwrap(), expected);
+        assert_eq!(detect(input), Some(expected));
     }
 }
 
@@ -161,6 +165,36 @@ mod test_try_from_season {
         assert_eq!(season.episodes.len(), 1);
     }
 
+    #[test]
+    fn multi_episode_file() {
+        let path = PathBuf::from("/tv/show.s01e01-e03.mkv");
+        let season = Season::try_from(path).unwrap();
+
+        let numbers: Vec<i32> = season.episodes.iter().map(|e| e.number).collect();
+        assert_eq!(numbers, vec![1, 2, 3]);
+        assert!(
+            season
+                .episodes
+                .iter()
+                .all(|e| e.files.len() == 1 && e.files[0].path == "/tv/show.s01e01-e03.mkv")
+        );
+    }
+
+    #[test]
+    fn absolute_number_outside_season_folder() {
+        let path = PathBuf::from("/anime/[Group] Show - 124.mkv");
+        let season = Season::try_from(path).unwrap();
+
+        assert_eq!(season.number, 1);
+        assert_eq!(season.episodes[0].number, 124);
+
+        let path = PathBuf::from("/anime/Show/Season 3/[Group] Show - 04.mkv");
+        let season = Season::try_from(path).unwrap();
+
+        assert_eq!(season.number, 3);
+        assert_eq!(season.episodes[0].number, 4);
+    }
+
     #[test]
     fn invalid_filename() {
         let path = PathBuf::from("invalid_file.mkv");
@@ -210,12 +244,14 @@ mod tests_merge {
                     number: 1,
                     watched: false,
                     files: vec![],
+                    imdb: None,
                 },
                 Episode {
                     id: 2,
                     number: 3,
                     watched: false,
                     files: vec![],
+                    imdb: None,
                 },
             ],
         };
@@ -230,12 +266,14 @@ mod tests_merge {
                     number: 2,
                     watched: false,
                     files: vec![],
+                    imdb: None,
                 },
                 Episode {
                     id: 4,
                     number: 4,
                     watched: false,
                     files: vec![],
+                    imdb: None,
                 },
             ],
         };
@@ -261,12 +299,14 @@ mod tests_merge {
                     number: 1,
                     watched: false,
                     files: vec![],
+                    imdb: None,
                 },
                 Episode {
                     id: 2,
                     number: 2,
                     watched: false,
                     files: vec![],
+                    imdb: None,
                 },
             ],
         };
@@ -281,12 +321,14 @@ mod tests_merge {
                     number: 2,
                     watched: false,
                     files: vec![],
+                    imdb: None,
                 },
                 Episode {
                     id: 4,
                     number: 3,
                     watched: false,
                     files: vec![],
+                    imdb: None,
                 },
             ],
         };
@@ -0,0 +1,388 @@
+use super::IdType;
+use crate::settings::VideoExtensions;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Supported sidecar subtitle file extensions.
+const SUBTITLE_EXTENSIONS: &[&str] = &["srt", "ass", "sub", "vtt"];
+
+/// Folder names release groups put subtitles in, next to the video.
+const SUBTITLE_FOLDERS: &[&str] = &["subs", "subtitles"];
+
+/// ISO 639-1 codes with the ISO 639-2 codes and English names that map to them.
+const LANGUAGES: &[(&str, &[&str])] = &[
+    ("ar", &["ara", "arabic"]),
+    ("cs", &["cze", "ces", "czech"]),
+    ("da", &["dan", "danish"]),
+    ("de", &["ger", "deu", "german"]),
+    ("el", &["gre", "ell", "greek"]),
+    ("en", &["eng", "english"]),
+    ("es", &["spa", "spanish"]),
+    ("fa", &["per", "fas", "persian", "farsi"]),
+    ("fi", &["fin", "finnish"]),
+    ("fr", &["fre", "fra", "french"]),
+    ("he", &["heb", "hebrew"]),
+    ("hi", &["hin", "hindi"]),
+    ("hu", &["hun", "hungarian"]),
+    ("id", &["ind", "indonesian"]),
+    ("it", &["ita", "italian"]),
+    ("ja", &["jpn", "japanese"]),
+    ("ko", &["kor", "korean"]),
+    ("nl", &["dut", "nld", "dutch"]),
+    ("no", &["nor", "norwegian"]),
+    ("pl", &["pol", "polish"]),
+    ("pt", &["por", "portuguese"]),
+    ("ro", &["rum", "ron", "romanian"]),
+    ("ru", &["rus", "russian"]),
+    ("sv", &["swe", "swedish"]),
+    ("th", &["tha", "thai"]),
+    ("tr", &["tur", "turkish"]),
+    ("uk", &["ukr", "ukrainian"]),
+    ("vi", &["vie", "vietnamese"]),
+    ("zh", &["chi", "zho", "chinese"]),
+];
+
+/// A subtitle file stored next to a video file.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Subtitle {
+    pub id: IdType,
+    pub path: String,
+    /// ISO 639-1 code detected from the file name.
+    pub language: Option<String>,
+}
+
+impl Ord for Subtitle {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.path
+            .cmp(&other.path)
+            .then_with(|| self.language.cmp(&other.language))
+    }
+}
+
+impl PartialOrd for Subtitle {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Subtitle {
+    fn eq(&self, other: &Self) -> bool {
+        self.path == other.path && self.language == other.language
+    }
+}
+
+impl Eq for Subtitle {}
+
+/// What [`Subtitle::find_sidecars`] reads of a folder: its entries and the
+/// entries of its `Subs/` folders. Read once, it serves every video in the
+/// folder.
+pub struct SidecarFolder {
+    entries: Vec<PathBuf>,
+    subtitle_folders: Vec<Vec<PathBuf>>,
+}
+
+impl SidecarFolder {
+    pub fn read(dir: &Path) -> Self {
+        let entries = list_dir(dir);
+        let subtitle_folders = entries
+            .iter()
+            .filter(|path| is_subtitle_folder(path))
+            .map(|folder| list_dir(folder))
+            .collect();
+
+        Self {
+            entries,
+            subtitle_folders,
+        }
+    }
+}
+
+impl Subtitle {
+    fn new(path: &Path, language: Option<String>) -> Self {
+        Self {
+            id: 0,
+            path: path.to_string_lossy().to_string(),
+            language,
+        }
+    }
+
+    /// Whether `path` has one of the supported subtitle extensions.
+    pub fn is_subtitle(path: &Path) -> bool {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| SUBTITLE_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+            .unwrap_or(false)
+    }
+
+    /// Finds the subtitles belonging to `video`: files next to it that share
+    /// its stem, such as `movie.fa.srt`, and files in a `Subs/` folder beside
+    /// it. A `Subs/` folder may hold the subtitles directly, named after the
+    /// video or not, or in a folder named after the video as season packs do.
+    /// Unnamed subtitles are only taken when the video is alone in its folder.
+    /// `videos` tells the other videos in the folder apart.
+    pub fn find_sidecars(video: &Path, videos: &VideoExtensions) -> Vec<Self> {
+        match video.parent() {
+            Some(dir) => Self::sidecars_in(&SidecarFolder::read(dir), video, videos),
+            None => vec![],
+        }
+    }
+
+    /// Like [`Subtitle::find_sidecars`], with the folder of `video` already
+    /// read.
+    pub fn sidecars_in(
+        folder: &SidecarFolder,
+        video: &Path,
+        videos: &VideoExtensions,
+    ) -> Vec<Self> {
+        let Some(stem) = video.file_stem().and_then(|s| s.to_str()) else {
+            return vec![];
+        };
+        let stem = stem.to_lowercase();
+        let entries = &folder.entries;
+
+        // Longer stems of other videos, so `movie.extended.srt` goes to
+        // `movie.extended.mkv` rather than `movie.mkv`.
+        let other_stems: Vec<String> = entries
+            .iter()
+            .filter(|path| videos.is_video(path) && path.as_path() != video)
+            .filter_map(|path| Some(path.file_stem()?.to_str()?.to_lowercase()))
+            .filter(|other| other.len() > stem.len())
+            .collect();
+        let mut found: Vec<Self> = entries
+            .iter()
+            .filter(|path| Self::is_subtitle(path))
+            .filter_map(|path| {
+                Some(Self::new(
+                    path,
+                    Self::detect_language(own_suffix(path, &stem, &other_stems)?),
+                ))
+            })
+            .collect();
+
+        let alone = entries.iter().filter(|path| videos.is_video(path)).count() <= 1;
+
+        for path in folder.subtitle_folders.iter().flatten() {
+            if path.is_dir() {
+                if file_name(path).is_some_and(|name| name.to_lowercase() == stem) {
+                    found.extend(
+                        list_dir(path)
+                            .iter()
+                            .filter(|path| Self::is_subtitle(path))
+                            .map(|path| Self::new(path, language_from_stem(path))),
+                    );
+                }
+            } else if Self::is_subtitle(path) {
+                if let Some(suffix) = own_suffix(path, &stem, &other_stems) {
+                    found.push(Self::new(path, Self::detect_language(suffix)));
+                } else if alone {
+                    found.push(Self::new(path, language_from_stem(path)));
+                }
+            }
+        }
+
+        found.sort();
+        found
+    }
+
+    /// Detects the language named in a file name, such as `fa` in `movie.fa`
+    /// or `English` in `2_English`. The last recognised token wins, since
+    /// tags like `forced` or `sdh` usually follow the language.
+    pub fn detect_language(name: &str) -> Option<String> {
+        name.to_lowercase()
+            .split(|c: char| !c.is_ascii_alphabetic())
+            .rev()
+            .find_map(|token| {
+                LANGUAGES
+                    .iter()
+                    .find(|(code, aliases)| *code == token || aliases.contains(&token))
+                    .map(|(code, _)| code.to_string())
+            })
+    }
+}
+
+fn list_dir(dir: &Path) -> Vec<PathBuf> {
+    let mut entries: Vec<_> = fs::read_dir(dir)
+        .map(|entries| entries.filter_map(Result::ok).map(|e| e.path()).collect())
+        .unwrap_or_default();
+    entries.sort();
+    entries
+}
+
+fn file_name(path: &Path) -> Option<&str> {
+    path.file_name().and_then(|name| name.to_str())
+}
+
+fn is_subtitle_folder(path: &Path) -> bool {
+    path.is_dir()
+        && file_name(path)
+            .is_some_and(|name| SUBTITLE_FOLDERS.contains(&name.to_lowercase().as_str()))
+}
+
+/// The part of a subtitle's stem after the video stem, e.g. `.fa` for
+/// `movie.fa.srt` and `movie`, or `None` when the names do not match.
+fn stem_suffix<'a>(path: &'a Path, video_stem: &str) -> Option<&'a str> {
+    let stem = path.file_stem()?.to_str()?;
+    let prefix = stem.get(..video_stem.len())?;
+    let suffix = &stem[video_stem.len()..];
+
+    (prefix.to_lowercase() == video_stem && (suffix.is_empty() || suffix.starts_with('.')))
+        .then_some(suffix)
+}
+
+/// Like [`stem_suffix`], but `None` when the subtitle also matches one of the
+/// longer `other_stems` and so belongs to that video instead.
+fn own_suffix<'a>(path: &'a Path, video_stem: &str, other_stems: &[String]) -> Option<&'a str> {
+    stem_suffix(path, video_stem).filter(|_| {
+        other_stems
+            .iter()
+            .all(|other| stem_suffix(path, other).is_none())
+    })
+}
+
+fn language_from_stem(path: &Path) -> Option<String> {
+    Subtitle::detect_language(path.file_stem()?.to_str()?)
+}
+
+#[cfg(test)]
+mod tests_subtitles {
+    use super::*;
+    use std::fs::File;
+    use tempfile::TempDir;
+
+    fn touch(path: &Path) {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).expect("Failed to create dir");
+        }
+        File::create(path).expect("Failed to create file");
+    }
+
+    fn found(video: &Path) -> Vec<(String, Option<String>)> {
+        let dir = video.parent().unwrap();
+        Subtitle::find_sidecars(video, &VideoExtensions::default())
+            .into_iter()
+            .map(|s| {
+                let relative = Path::new(&s.path).strip_prefix(dir).unwrap();
+                (relative.to_string_lossy().replace('\\', "/"), s.language)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn detects_languages() {
+        let cases = [
+            ("movie.fa", Some("fa")),
+            ("movie.en.forced", Some("en")),
+            ("movie.eng.sdh", Some("en")),
+            ("2_English", Some("en")),
+            ("movie.Persian", Some("fa")),
+            ("movie.pt-BR", Some("pt")),
+            ("movie", None),
+            ("movie.xx", None),
+        ];
+
+        for (name, expected) in cases {
+            assert_eq!(
+                Subtitle::detect_language(name).as_deref(),
+                expected,
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn finds_subtitles_sharing_the_stem() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let video = temp_dir.path().join("Movie.2020.mkv");
+        touch(&video);
+        touch(&temp_dir.path().join("Movie.2020.srt"));
+        touch(&temp_dir.path().join("movie.2020.fa.SRT"));
+        touch(&temp_dir.path().join("Movie.2020.en.forced.ass"));
+        touch(&temp_dir.path().join("Movie.2020.nfo"));
+        touch(&temp_dir.path().join("Movie.2020.Extended.mkv"));
+        touch(&temp_dir.path().join("Movie.2020.Extended.srt"));
+        touch(&temp_dir.path().join("Movie.20.srt"));
+
+        assert_eq!(
+            found(&video),
+            vec![
+                ("Movie.2020.en.forced.ass".into(), Some("en".into())),
+                ("Movie.2020.srt".into(), None),
+                ("movie.2020.fa.SRT".into(), Some("fa".into())),
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_subtitles_in_subs_folder() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let video = temp_dir.path().join("Movie.2020.mkv");
+        touch(&video);
+        touch(&temp_dir.path().join("Subs").join("2_English.srt"));
+        touch(&temp_dir.path().join("Subs").join("3_Farsi.vtt"));
+        touch(&temp_dir.path().join("Subs").join("readme.txt"));
+
+        assert_eq!(
+            found(&video),
+            vec![
+                ("Subs/2_English.srt".into(), Some("en".into())),
+                ("Subs/3_Farsi.vtt".into(), Some("fa".into())),
+            ]
+        );
+    }
+
+    #[test]
+    fn shared_subs_folder_is_matched_by_name() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let e1 = temp_dir.path().join("Show.S01E01.mkv");
+        let e2 = temp_dir.path().join("Show.S01E02.mkv");
+        touch(&e1);
+        touch(&e2);
+        touch(&temp_dir.path().join("Subs").join("Show.S01E01.fa.srt"));
+        touch(
+            &temp_dir
+                .path()
+                .join("Subs")
+                .join("Show.S01E02")
+                .join("2_English.srt"),
+        );
+        touch(&temp_dir.path().join("Subs").join("English.srt"));
+
+        assert_eq!(
+            found(&e1),
+            vec![("Subs/Show.S01E01.fa.srt".into(), Some("fa".into()))]
+        );
+        assert_eq!(
+            found(&e2),
+            vec![("Subs/Show.S01E02/2_English.srt".into(), Some("en".into()))]
+        );
+    }
+
+    #[test]
+    fn missing_folder_finds_nothing() {
+        assert!(
+            Subtitle::find_sidecars(
+                Path::new("/does/not/exist/movie.mkv"),
+                &VideoExtensions::default()
+            )
+            .is_empty()
+        );
+    }
+
+    #[test]
+    fn configured_extensions_tell_videos_apart() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let video = temp_dir.path().join("Movie.mkv");
+        touch(&video);
+        touch(&temp_dir.path().join("Other.m2ts"));
+        touch(&temp_dir.path().join("Subs").join("English.srt"));
+
+        // Without m2ts the video looks alone, so the unnamed subtitle is its.
+        let videos = VideoExtensions::new(["mkv"]);
+        assert_eq!(Subtitle::find_sidecars(&video, &videos).len(), 1);
+
+        let videos = VideoExtensions::new(["mkv", "m2ts"]);
+        assert!(Subtitle::find_sidecars(&video, &videos).is_empty());
+    }
+}
@@ -0,0 +1,66 @@
+use super::IdType;
+use std::{fmt, str::FromStr};
+
+/// Where a sync job stands. Running and paused jobs are unfinished and are
+/// picked up again after a restart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SyncJobStatus {
+    Running,
+    Paused,
+    Cancelled,
+    Completed,
+    Failed,
+}
+
+impl SyncJobStatus {
+    pub fn is_finished(&self) -> bool {
+        !matches!(self, SyncJobStatus::Running | SyncJobStatus::Paused)
+    }
+}
+
+impl fmt::Display for SyncJobStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = match self {
+            SyncJobStatus::Running => "running",
+            SyncJobStatus::Paused => "paused",
+            SyncJobStatus::Cancelled => "cancelled",
+            SyncJobStatus::Completed => "completed",
+            SyncJobStatus::Failed => "failed",
+        };
+        write!(f, "{status}")
+    }
+}
+
+impl FromStr for SyncJobStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "running" => Ok(SyncJobStatus::Running),
+            "paused" => Ok(SyncJobStatus::Paused),
+            "cancelled" => Ok(SyncJobStatus::Cancelled),
+            "completed" => Ok(SyncJobStatus::Completed),
+            "failed" => Ok(SyncJobStatus::Failed),
+            other => Err(anyhow::anyhow!("Unknown sync job status: {other}")),
+        }
+    }
+}
+
+/// A sync of one or more library roots, inserted chunk by chunk so it can be
+/// paused, cancelled and resumed after a restart.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncJob {
+    pub id: IdType,
+    pub roots: Vec<String>,
+    pub status: SyncJobStatus,
+    /// Medias found when the job started.
+    pub total: usize,
+    /// Medias inserted by the committed chunks.
+    pub inserted: usize,
+    /// Video files not inserted yet.
+    #[serde(skip)]
+    pub pending: Vec<String>,
+    pub error: Option<String>,
+}
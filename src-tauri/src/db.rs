@@ -1,9 +1,18 @@
-use std::{fmt, path::PathBuf};
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
 
-use crate::data_model::{IdType, Imdb, Media, MediaFile, Tag};
+use crate::data_model::{
+    IdType, Imdb, ImdbEpisode, LibraryRoot, Media, MediaFile, MissingEpisodes, RejectedMatches,
+    Subtitle, SyncJob, SyncJobStatus, Tag,
+};
+use crate::media_probe::MediaProbe;
 
 mod sqlite;
 pub use sqlite::Sqlite;
+#[cfg(test)]
+pub(crate) use sqlite::temp_db;
 
 #[cfg(test)]
 mod moke;
@@ -38,6 +47,7 @@ pub enum SortByType {
     Name,
     Year,
     Imdb,
+    Runtime,
 }
 
 #[derive(Debug, Clone, serde::Deserialize, PartialEq)]
@@ -73,10 +83,64 @@ pub struct FilterValues {
     pub sort_direction: SortDirectionType,
     pub watch_list: Option<bool>,
     pub tags: Vec<i32>,
+    /// Whether any file of the media has a sidecar subtitle.
+    #[serde(default)]
+    pub has_subtitles: Option<bool>,
+    /// Languages the media must have sidecar subtitles in.
+    #[serde(default)]
+    pub subtitle_language: Vec<String>,
+    /// Shortest runtime in minutes.
+    #[serde(default)]
+    pub min_runtime: Option<u32>,
+    /// Longest runtime in minutes.
+    #[serde(default)]
+    pub max_runtime: Option<u32>,
+    /// Certifications the title must have one of, such as `PG-13`.
+    #[serde(default)]
+    pub rated: Vec<String>,
+    /// Whether every aired episode of the series is on disk. Series whose
+    /// episode list was not fetched match neither value.
+    #[serde(default)]
+    pub complete: Option<bool>,
 }
 
 pub type Result<T> = std::result::Result<T, anyhow::Error>;
 
+/// Seconds since the unix epoch, as stored in the timestamp columns.
+pub fn unix_time() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+/// The local date as `YYYY-MM-DD`, the format of episode air dates.
+pub fn today() -> String {
+    chrono::Local::now().format("%Y-%m-%d").to_string()
+}
+
+#[derive(Debug, Clone, Default, serde::Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct OrphanCleanup {
+    /// Only report what would be removed.
+    pub dry_run: bool,
+    /// Also remove orphaned medias that are watched, ranked, on the watch list or tagged.
+    pub include_user_data: bool,
+    /// Limit the cleanup to these medias; `None` covers the whole library.
+    pub media_ids: Option<Vec<IdType>>,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct OrphanReport {
+    pub medias: Vec<NumericalString>,
+    pub seasons: Vec<IdType>,
+    pub episodes: Vec<IdType>,
+    pub imdbs: Vec<String>,
+    /// Orphaned medias left in place because they carry user data.
+    pub kept_medias: Vec<NumericalString>,
+}
+
 pub trait DB {
     fn insert_medias(&self, medias: &[Media]) -> Result<()>;
     fn delete_media(&self, media_id: IdType) -> Result<()>;
@@ -85,16 +149,33 @@ pub trait DB {
     fn update_media_watched(&self, media_id: IdType, watched: bool) -> Result<()>;
     fn update_season_watched(&self, season_id: IdType, watched: bool) -> Result<()>;
     fn update_episode_watched(&self, episode_id: IdType, watched: bool) -> Result<()>;
+    /// Marks every episode the file holds, or the movie it belongs to.
+    fn update_file_watched(&self, file_id: IdType, watched: bool) -> Result<()>;
+    /// Fails like [`DB::update_media_imdb`] would, without writing anything.
+    fn check_media_imdb(&self, media_id: IdType, imdb_id: &str) -> Result<()>;
+    /// Attaches a stored title to the media in place and closes its review.
     fn update_media_imdb(&self, media_id: IdType, imdb_id: &str) -> Result<IdType>;
     fn insert_media(&self, media: &Media) -> Result<IdType>;
     fn insert_imdb(&self, imdb: &Imdb) -> Result<()>;
-    #[allow(dead_code)]
-    fn clear_empty_data(&self) -> Result<()>;
+    fn remove_orphans(&self, cleanup: &OrphanCleanup) -> Result<OrphanReport>;
     fn get_genres(&self) -> Result<Vec<NumericalString>>;
     fn get_countries(&self) -> Result<Vec<NumericalString>>;
     fn get_people(&self) -> Result<Vec<(String, String)>>;
     fn remove_file_by_path(&self, paths: &[PathBuf]) -> Result<()>;
     fn get_all_files(&self) -> Result<Vec<MediaFile>>;
+    /// Files whose content was never read, see [`DB::mark_file_details_read`].
+    fn get_files_without_details(&self) -> Result<Vec<MediaFile>>;
+    /// Records that the fingerprint and container of the file were read, so
+    /// files the probe cannot make sense of are not read on every sync.
+    fn mark_file_details_read(&self, file_id: IdType, read_at: i64) -> Result<()>;
+    fn get_media_ids_by_paths(&self, paths: &[PathBuf]) -> Result<Vec<IdType>>;
+    fn update_file_path(&self, file_id: IdType, path: &Path) -> Result<()>;
+    fn update_file_fingerprint(&self, file_id: IdType, fingerprint: &str) -> Result<()>;
+    fn update_file_subtitles(&self, file_id: IdType, subtitles: &[Subtitle]) -> Result<()>;
+    fn get_subtitle_languages(&self) -> Result<Vec<String>>;
+    /// Distinct certifications of the stored titles.
+    fn get_certifications(&self) -> Result<Vec<String>>;
+    fn update_file_probe(&self, file_id: IdType, probe: &MediaProbe) -> Result<()>;
     fn filter_medias(&self, filters: &FilterValues, page: u32) -> Result<Vec<Media>>;
     fn get_media_by_id(&self, media_id: IdType) -> Result<Option<Media>>;
     fn get_tags(&self) -> Result<Vec<Tag>>;
@@ -104,4 +185,77 @@ pub trait DB {
     fn insert_tag(&self, tag: &Tag) -> Result<()>;
     fn insert_media_tag(&self, media_id: IdType, tag_id: IdType) -> Result<()>;
     fn remove_media_tag(&self, media_id: IdType, tag_id: IdType) -> Result<()>;
+    fn insert_library_root(&self, path: &str) -> Result<LibraryRoot>;
+    fn remove_library_root(&self, root_id: IdType) -> Result<()>;
+    fn get_library_roots(&self) -> Result<Vec<LibraryRoot>>;
+    fn update_library_root_enabled(&self, root_id: IdType, enabled: bool) -> Result<()>;
+    fn get_setting(&self, key: &str) -> Result<Option<String>>;
+    fn set_setting(&self, key: &str, value: &str) -> Result<()>;
+    fn insert_sync_job(
+        &self,
+        roots: &[String],
+        pending: &[String],
+        total: usize,
+    ) -> Result<SyncJob>;
+    /// Inserts a chunk of a sync job's medias and records the files still
+    /// pending in the same transaction.
+    fn commit_sync_job_chunk(
+        &self,
+        job_id: IdType,
+        medias: &[Media],
+        pending: &[String],
+    ) -> Result<()>;
+    fn update_sync_job_status(
+        &self,
+        job_id: IdType,
+        status: SyncJobStatus,
+        error: Option<&str>,
+    ) -> Result<()>;
+    fn get_sync_job(&self, job_id: IdType) -> Result<Option<SyncJob>>;
+    fn get_sync_jobs(&self) -> Result<Vec<SyncJob>>;
+    /// Replaces the stored details and links of the titles.
+    fn upsert_imdbs(&self, imdbs: &[Imdb], fetched_at: i64) -> Result<()>;
+    /// Ids of the titles fetched before the unix time, oldest first.
+    fn get_stale_imdb_ids(&self, fetched_before: i64) -> Result<Vec<String>>;
+    fn get_imdb(&self, imdb_id: &str) -> Result<Option<Imdb>>;
+    /// Replaces the stored episode list of a series.
+    fn set_imdb_episodes(&self, series_imdb_id: &str, episodes: &[ImdbEpisode]) -> Result<()>;
+    /// Records that the episode list of a series was fetched at the unix
+    /// time, even when the fetch failed or came back empty.
+    fn mark_imdb_episodes_fetched(&self, series_imdb_id: &str, fetched_at: i64) -> Result<()>;
+    /// Titles of the matched series among `imdb_ids` whose episode list was
+    /// never fetched.
+    fn get_unlisted_series_imdb_ids(&self, imdb_ids: &[String]) -> Result<Vec<String>>;
+    /// Titles of the matched series whose episode list was last fetched
+    /// before the unix time, or never.
+    fn get_stale_episode_list_imdb_ids(&self, fetched_before: i64) -> Result<Vec<String>>;
+    /// A cached provider response and the unix time it was fetched at.
+    fn get_cached_response(&self, kind: &str, key: &str) -> Result<Option<(String, i64)>>;
+    fn set_cached_response(
+        &self,
+        kind: &str,
+        key: &str,
+        response: &str,
+        fetched_at: i64,
+    ) -> Result<()>;
+    fn clear_cached_responses(&self) -> Result<usize>;
+    /// Unmatched medias to review, the best suggestions first and the skipped
+    /// ones last.
+    fn get_review_queue(&self) -> Result<Vec<Media>>;
+    fn get_rejected_matches(&self) -> Result<RejectedMatches>;
+    /// Remembers the title as wrong for the media, detaching it if attached.
+    fn reject_match(&self, media_id: IdType, imdb_id: &str) -> Result<()>;
+    /// Moves the files, seasons, tags and user data of the sources into the
+    /// target, then deletes the sources.
+    fn merge_medias(&self, target_id: IdType, source_ids: &[IdType]) -> Result<()>;
+    /// Moves the files into a new media, which starts with the tags and user
+    /// data of the original, and returns its id.
+    fn split_media(&self, media_id: IdType, file_ids: &[IdType]) -> Result<IdType>;
+    /// The aired episodes of a series that are not on disk, by season.
+    fn get_missing_episodes(&self, media_id: IdType) -> Result<MissingEpisodes>;
+    /// Every series known to have missing episodes, by name. Series whose
+    /// episode list was not fetched are left out.
+    fn get_missing_episodes_report(&self) -> Result<Vec<MissingEpisodes>>;
+    /// Moves the media to the end of the review queue.
+    fn skip_match_review(&self, media_id: IdType) -> Result<()>;
 }
@@ -1,12 +1,21 @@
-use std::path::PathBuf;
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 
-use crate::data_model::{IdType, Imdb, Media, MediaFile, Tag};
+use crate::data_model::{
+    IdType, Imdb, ImdbEpisode, LibraryRoot, Media, MediaFile, MissingEpisodes, RejectedMatches,
+    Subtitle, SyncJob, SyncJobStatus, Tag,
+};
+use crate::media_probe::MediaProbe;
 
-use super::{DB, NumericalString, Result};
+use super::{DB, NumericalString, OrphanCleanup, OrphanReport, Result};
 
 #[derive(Default, Clone)]
 pub struct MokeDB {
     inserted_file: Vec<MediaFile>,
+    settings: RefCell<HashMap<String, String>>,
 }
 
 impl MokeDB {
@@ -44,6 +53,14 @@ impl DB for MokeDB {
         todo!()
     }
 
+    fn update_file_watched(&self, _file_id: IdType, _watched: bool) -> Result<()> {
+        todo!()
+    }
+
+    fn check_media_imdb(&self, _media_id: IdType, _imdb_id: &str) -> Result<()> {
+        todo!()
+    }
+
     fn update_media_imdb(&self, _media_id: IdType, _imdb_id: &str) -> Result<IdType> {
         todo!()
     }
@@ -56,7 +73,7 @@ impl DB for MokeDB {
         todo!()
     }
 
-    fn clear_empty_data(&self) -> Result<()> {
+    fn remove_orphans(&self, _cleanup: &OrphanCleanup) -> Result<OrphanReport> {
         todo!()
     }
 
@@ -80,6 +97,42 @@ impl DB for MokeDB {
         Ok(self.inserted_file.clone())
     }
 
+    fn get_media_ids_by_paths(&self, _paths: &[PathBuf]) -> Result<Vec<IdType>> {
+        todo!()
+    }
+
+    fn get_files_without_details(&self) -> Result<Vec<MediaFile>> {
+        todo!()
+    }
+
+    fn mark_file_details_read(&self, _file_id: IdType, _read_at: i64) -> Result<()> {
+        todo!()
+    }
+
+    fn update_file_path(&self, _file_id: IdType, _path: &Path) -> Result<()> {
+        todo!()
+    }
+
+    fn update_file_fingerprint(&self, _file_id: IdType, _fingerprint: &str) -> Result<()> {
+        todo!()
+    }
+
+    fn update_file_subtitles(&self, _file_id: IdType, _subtitles: &[Subtitle]) -> Result<()> {
+        todo!()
+    }
+
+    fn get_subtitle_languages(&self) -> Result<Vec<String>> {
+        todo!()
+    }
+
+    fn get_certifications(&self) -> Result<Vec<String>> {
+        todo!()
+    }
+
+    fn update_file_probe(&self, _file_id: IdType, _probe: &MediaProbe) -> Result<()> {
+        todo!()
+    }
+
     fn filter_medias(&self, _filters: &super::FilterValues, _page: u32) -> Result<Vec<Media>> {
         todo!()
     }
@@ -115,4 +168,144 @@ impl DB for MokeDB {
     fn remove_media_tag(&self, _media_id: IdType, _tag_id: IdType) -> Result<()> {
         todo!()
     }
+
+    fn insert_library_root(&self, _path: &str) -> Result<LibraryRoot> {
+        todo!()
+    }
+
+    fn remove_library_root(&self, _root_id: IdType) -> Result<()> {
+        todo!()
+    }
+
+    fn get_library_roots(&self) -> Result<Vec<LibraryRoot>> {
+        Ok(vec![])
+    }
+
+    fn update_library_root_enabled(&self, _root_id: IdType, _enabled: bool) -> Result<()> {
+        todo!()
+    }
+
+    fn get_setting(&self, key: &str) -> Result<Option<String>> {
+        Ok(self.settings.borrow().get(key).cloned())
+    }
+
+    fn set_setting(&self, key: &str, value: &str) -> Result<()> {
+        self.settings
+            .borrow_mut()
+            .insert(key.to_string(), value.to_string());
+        Ok(())
+    }
+
+    fn insert_sync_job(
+        &self,
+        _roots: &[String],
+        _pending: &[String],
+        _total: usize,
+    ) -> Result<SyncJob> {
+        todo!()
+    }
+
+    fn commit_sync_job_chunk(
+        &self,
+        _job_id: IdType,
+        _medias: &[Media],
+        _pending: &[String],
+    ) -> Result<()> {
+        todo!()
+    }
+
+    fn update_sync_job_status(
+        &self,
+        _job_id: IdType,
+        _status: SyncJobStatus,
+        _error: Option<&str>,
+    ) -> Result<()> {
+        todo!()
+    }
+
+    fn get_sync_job(&self, _job_id: IdType) -> Result<Option<SyncJob>> {
+        todo!()
+    }
+
+    fn get_sync_jobs(&self) -> Result<Vec<SyncJob>> {
+        todo!()
+    }
+
+    fn upsert_imdbs(&self, _imdbs: &[Imdb], _fetched_at: i64) -> Result<()> {
+        todo!()
+    }
+
+    fn get_stale_imdb_ids(&self, _fetched_before: i64) -> Result<Vec<String>> {
+        todo!()
+    }
+
+    fn get_imdb(&self, _imdb_id: &str) -> Result<Option<Imdb>> {
+        todo!()
+    }
+
+    fn set_imdb_episodes(&self, _series_imdb_id: &str, _episodes: &[ImdbEpisode]) -> Result<()> {
+        todo!()
+    }
+
+    fn mark_imdb_episodes_fetched(&self, _series_imdb_id: &str, _fetched_at: i64) -> Result<()> {
+        todo!()
+    }
+
+    fn get_unlisted_series_imdb_ids(&self, _imdb_ids: &[String]) -> Result<Vec<String>> {
+        todo!()
+    }
+
+    fn get_stale_episode_list_imdb_ids(&self, _fetched_before: i64) -> Result<Vec<String>> {
+        todo!()
+    }
+
+    fn get_cached_response(&self, _kind: &str, _key: &str) -> Result<Option<(String, i64)>> {
+        todo!()
+    }
+
+    fn set_cached_response(
+        &self,
+        _kind: &str,
+        _key: &str,
+        _response: &str,
+        _fetched_at: i64,
+    ) -> Result<()> {
+        todo!()
+    }
+
+    fn clear_cached_responses(&self) -> Result<usize> {
+        todo!()
+    }
+
+    fn get_review_queue(&self) -> Result<Vec<Media>> {
+        todo!()
+    }
+
+    fn get_rejected_matches(&self) -> Result<RejectedMatches> {
+        todo!()
+    }
+
+    fn reject_match(&self, _media_id: IdType, _imdb_id: &str) -> Result<()> {
+        todo!()
+    }
+
+    fn merge_medias(&self, _target_id: IdType, _source_ids: &[IdType]) -> Result<()> {
+        todo!()
+    }
+
+    fn split_media(&self, _media_id: IdType, _file_ids: &[IdType]) -> Result<IdType> {
+        todo!()
+    }
+
+    fn skip_match_review(&self, _media_id: IdType) -> Result<()> {
+        todo!()
+    }
+
+    fn get_missing_episodes(&self, _media_id: IdType) -> Result<MissingEpisodes> {
+        todo!()
+    }
+
+    fn get_missing_episodes_report(&self) -> Result<Vec<MissingEpisodes>> {
+        todo!()
+    }
 }
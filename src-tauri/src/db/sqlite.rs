@@ -2,14 +2,22 @@ mod data_models;
 pub mod schema;
 
 use super::{
-    ContentType, DB, FilterValues, NumericalString, Result, SortByType, SortDirectionType,
+    ContentType, DB, FilterValues, NumericalString, OrphanCleanup, OrphanReport, Result,
+    SortByType, SortDirectionType, today, unix_time,
 };
-use crate::data_model::{Episode, IdType, Imdb, Media, MediaFile, Person, Season, Tag};
+use crate::data_model::{
+    Episode, IdType, Imdb, ImdbEpisode, LibraryRoot, MatchSuggestion, Media, MediaFile,
+    MissingEpisodes, Person, RejectedMatches, Season, Subtitle, SyncJob, SyncJobStatus, Tag,
+};
+use crate::error::AppError;
+use crate::media_probe::MediaProbe;
 use anyhow::Ok;
 use data_models::{
-    DbEpisode, DbFile, DbImdb, DbMedia, DbPerson, DbSeason, NewCountry, NewEpisode, NewFile,
-    NewGenre, NewImdb, NewImdbCountry, NewImdbGenre, NewImdbPerson, NewMedia, NewMediaTag,
-    NewPerson, NewSeason, NewTag,
+    DbEpisode, DbFile, DbImdb, DbImdbEpisode, DbLibraryRoot, DbMatchSuggestion, DbMedia, DbPerson,
+    DbSeason, DbSubtitle, DbSyncJob, NewCachedResponse, NewCountry, NewEpisode, NewFile,
+    NewFileEpisode, NewGenre, NewImdb, NewImdbCountry, NewImdbEpisode, NewImdbGenre, NewImdbPerson,
+    NewLibraryRoot, NewMedia, NewMediaTag, NewPerson, NewRejectedMatch, NewSeason, NewSetting,
+    NewSkippedReview, NewSubtitle, NewSyncJob, NewTag, join_languages,
 };
 use diesel::{
     BoolExpressionMethods, Connection, ExpressionMethods, NullableExpressionMethods, QueryDsl,
@@ -23,15 +31,25 @@ use diesel::{
 use diesel_migrations::{EmbeddedMigrations, MigrationHarness, embed_migrations};
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 pub use schema::{
-    countries, episodes, files, genres, imdb_countries, imdb_genres, imdb_people, imdbs,
-    media_tags, medias, people, seasons, tags,
+    countries, episodes, file_episodes, files, genres, imdb_countries, imdb_episodes, imdb_genres,
+    imdb_people, imdbs, library_roots, match_suggestions, media_tags, medias, metadata_cache,
+    people, rejected_matches, seasons, settings, skipped_reviews, subtitles, sync_jobs, tags,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    path::{Path, PathBuf},
 };
-use std::{fmt, path::PathBuf};
 use tauri::Manager;
 
 type DbPool = Pool<ConnectionManager<SqliteConnection>>;
 
 pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!();
+
+/// How many sync jobs are kept, finished or not, when a new one starts.
+const KEPT_SYNC_JOBS: i64 = 20;
+
+const UNFINISHED_SYNC_JOB_STATUSES: [&str; 2] = ["running", "paused"];
 enum PersonType {
     Actor,
     Writer,
@@ -48,6 +66,7 @@ impl fmt::Display for PersonType {
     }
 }
 
+#[derive(Clone)]
 pub struct Sqlite {
     pool: DbPool,
 }
@@ -57,7 +76,7 @@ impl Sqlite {
         self.pool.get().map_err(Into::into)
     }
 
-    fn new_with_path(db_path: PathBuf) -> Result<Self> {
+    pub(crate) fn new_with_path(db_path: PathBuf) -> Result<Self> {
         if let Some(p) = db_path.parent() {
             std::fs::create_dir_all(p)?;
         }
@@ -173,8 +192,49 @@ impl Sqlite {
         Ok(())
     }
 
-    fn insert_imdb(conn: &mut SqliteConnection, imdb: &Imdb) -> Result<()> {
-        let new = NewImdb {
+    fn new_imdb_episode<'a>(
+        series_imdb_id: &'a str,
+        episode: &'a ImdbEpisode,
+    ) -> NewImdbEpisode<'a> {
+        let text = |value: &'a String| Some(value.as_str()).filter(|value| !value.is_empty());
+        NewImdbEpisode {
+            series_imdb_id,
+            season_number: episode.season,
+            episode_number: episode.episode,
+            imdb_id: episode.imdb_id.as_str(),
+            title: episode.title.as_str(),
+            air_date: text(&episode.air_date),
+            runtime: Some(episode.runtime).filter(|runtime| *runtime > 0),
+            plot: text(&episode.plot),
+            rating: text(&episode.rating),
+        }
+    }
+
+    /// Replaces the episode list of a series with the one from the provider.
+    fn set_imdb_episodes(
+        conn: &mut SqliteConnection,
+        series_imdb_id: &str,
+        episodes: &[ImdbEpisode],
+    ) -> Result<()> {
+        diesel::delete(
+            imdb_episodes::table.filter(imdb_episodes::series_imdb_id.eq(series_imdb_id)),
+        )
+        .execute(conn)?;
+
+        let rows: Vec<_> = episodes
+            .iter()
+            .map(|episode| Self::new_imdb_episode(series_imdb_id, episode))
+            .collect();
+        // A provider listing an episode twice keeps the first entry.
+        diesel::insert_or_ignore_into(imdb_episodes::table)
+            .values(&rows)
+            .execute(conn)?;
+
+        Ok(())
+    }
+
+    fn new_imdb(imdb: &Imdb, fetched_at: i64) -> NewImdb<'_> {
+        NewImdb {
             imdb_id: imdb.imdb_id.as_str(),
             title: imdb.title.as_str(),
             year: imdb.year,
@@ -182,13 +242,63 @@ impl Sqlite {
             poster: Some(imdb.poster.as_str()),
             imdb_rating: Some(imdb.imdb_rating.as_str()),
             imdb_votes: imdb.imdb_votes,
+            rated: Some(imdb.rated.as_str()).filter(|rated| !rated.is_empty()),
+            runtime: Some(imdb.runtime)
+                .filter(|runtime| *runtime > 0)
+                .map(|runtime| runtime.to_string()),
+            awards: Some(imdb.awards.as_str()).filter(|awards| !awards.is_empty()),
+            box_office: Some(imdb.box_office.as_str()).filter(|box_office| !box_office.is_empty()),
+            total_seasons: Some(imdb.total_seasons)
+                .filter(|seasons| *seasons > 0)
+                .map(|seasons| seasons.to_string()),
             type_: imdb.r#type.as_str(),
-        };
+            fetched_at,
+        }
+    }
+
+    fn insert_imdb(conn: &mut SqliteConnection, imdb: &Imdb) -> Result<()> {
+        let inserted = diesel::insert_or_ignore_into(imdbs::table)
+            .values(&Self::new_imdb(imdb, unix_time()))
+            .execute(conn)?;
+
+        // A title that is already stored keeps its data until it is refreshed.
+        if inserted > 0 {
+            Self::insert_imdb_links(conn, imdb)?;
+        }
+
+        Ok(())
+    }
 
-        diesel::insert_or_ignore_into(imdbs::table)
+    /// Saves fresh details of a title, replacing what was stored along with
+    /// its genre, country and people links.
+    fn upsert_imdb(conn: &mut SqliteConnection, imdb: &Imdb, fetched_at: i64) -> Result<()> {
+        let new = Self::new_imdb(imdb, fetched_at);
+        diesel::insert_into(imdbs::table)
             .values(&new)
+            .on_conflict(imdbs::imdb_id)
+            .do_update()
+            .set((
+                imdbs::title.eq(new.title),
+                imdbs::year.eq(new.year),
+                imdbs::plot.eq(new.plot),
+                imdbs::poster.eq(new.poster),
+                imdbs::imdb_rating.eq(new.imdb_rating),
+                imdbs::imdb_votes.eq(new.imdb_votes),
+                imdbs::rated.eq(new.rated),
+                imdbs::runtime.eq(&new.runtime),
+                imdbs::awards.eq(new.awards),
+                imdbs::box_office.eq(new.box_office),
+                imdbs::total_seasons.eq(&new.total_seasons),
+                imdbs::type_.eq(new.type_),
+                imdbs::fetched_at.eq(fetched_at),
+            ))
             .execute(conn)?;
 
+        Self::delete_imdb_links(conn, &imdb.imdb_id)?;
+        Self::insert_imdb_links(conn, imdb)
+    }
+
+    fn insert_imdb_links(conn: &mut SqliteConnection, imdb: &Imdb) -> Result<()> {
         for g in &imdb.genres {
             Self::insert_imdb_genre_by_name(conn, &imdb.imdb_id, g)?;
         }
@@ -267,9 +377,34 @@ impl Sqlite {
 
         Self::insert_files(conn, &media.files, Some(id), None)?;
 
+        // A matched media has nothing left to review.
+        if media.imdb.is_some() {
+            Self::set_match_suggestion(conn, id, None)?;
+        } else if let Some(suggestion) = &media.suggestion {
+            Self::set_match_suggestion(conn, id, Some(suggestion))?;
+        }
+
         Ok(id)
     }
 
+    fn set_match_suggestion(
+        conn: &mut SqliteConnection,
+        media_id: IdType,
+        suggestion: Option<&MatchSuggestion>,
+    ) -> Result<()> {
+        match suggestion {
+            Some(suggestion) => {
+                diesel::replace_into(match_suggestions::table)
+                    .values(&DbMatchSuggestion::new(media_id, suggestion))
+                    .execute(conn)?;
+            }
+            None => {
+                diesel::delete(match_suggestions::table.find(media_id)).execute(conn)?;
+            }
+        }
+        Ok(())
+    }
+
     fn insert_season(conn: &mut SqliteConnection, media_id: IdType, season: &Season) -> Result<()> {
         let seasons_id = seasons::table
             .filter(
@@ -334,7 +469,43 @@ impl Sqlite {
             diesel::select(sql::<BigInt>("last_insert_rowid()")).get_result::<i64>(conn)? as i32
         };
 
-        Self::insert_files(conn, &episode.files, None, Some(id))?;
+        // A multi-episode file is listed under each episode it holds; it is
+        // stored with the first and linked to the others.
+        let paths: Vec<&str> = episode.files.iter().map(|f| f.path.as_str()).collect();
+        let stored: HashSet<String> = files::table
+            .filter(files::path.eq_any(&paths))
+            .filter(
+                files::episode_id.eq_any(
+                    episodes::table
+                        .filter(episodes::season_id.eq(season_id))
+                        .select(episodes::id.nullable()),
+                ),
+            )
+            .select(files::path)
+            .load::<String>(conn)?
+            .into_iter()
+            .collect();
+        let new_files: Vec<MediaFile> = episode
+            .files
+            .iter()
+            .filter(|f| !stored.contains(&f.path))
+            .cloned()
+            .collect();
+        Self::insert_files(conn, &new_files, None, Some(id))?;
+
+        let links: Vec<NewFileEpisode> = files::table
+            .filter(files::path.eq_any(&paths))
+            .select(files::id)
+            .load::<IdType>(conn)?
+            .into_iter()
+            .map(|file_id| NewFileEpisode {
+                file_id,
+                episode_id: id,
+            })
+            .collect();
+        diesel::insert_or_ignore_into(file_episodes::table)
+            .values(&links)
+            .execute(conn)?;
 
         Ok(())
     }
@@ -347,13 +518,25 @@ impl Sqlite {
     ) -> Result<()> {
         let new_files: Vec<NewFile> = files_in
             .iter()
-            .map(|f| NewFile {
-                media_id: media_id_val,
-                episode_id: episode_id_val,
-                file_name: &f.file_name,
-                path: &f.path,
-                quality: f.quality.as_deref(),
-                language_format: f.language_format.clone(),
+            .map(|f| {
+                let probe = f.probe.as_ref();
+                NewFile {
+                    media_id: media_id_val,
+                    episode_id: episode_id_val,
+                    file_name: &f.file_name,
+                    path: &f.path,
+                    quality: f.quality.as_deref(),
+                    language_format: f.language_format.clone(),
+                    fingerprint: f.fingerprint.as_deref(),
+                    duration_ms: probe.and_then(|p| p.duration_ms),
+                    video_codec: probe.and_then(|p| p.video_codec.as_deref()),
+                    width: probe.and_then(|p| p.width),
+                    height: probe.and_then(|p| p.height),
+                    bitrate: probe.and_then(|p| p.bitrate),
+                    audio_languages: probe.and_then(|p| join_languages(&p.audio_languages)),
+                    subtitle_languages: probe.and_then(|p| join_languages(&p.subtitle_languages)),
+                    details_read_at: f.fingerprint.is_some().then(unix_time),
+                }
             })
             .collect();
 
@@ -361,6 +544,35 @@ impl Sqlite {
             .values(&new_files)
             .execute(conn)?;
 
+        for f in files_in.iter().filter(|f| !f.subtitles.is_empty()) {
+            let file_id = files::table
+                .filter(files::path.eq(&f.path))
+                .select(files::id)
+                .first::<IdType>(conn)?;
+            Self::insert_subtitles(conn, file_id, &f.subtitles)?;
+        }
+
+        Ok(())
+    }
+
+    fn insert_subtitles(
+        conn: &mut SqliteConnection,
+        file_id: IdType,
+        subtitles_in: &[Subtitle],
+    ) -> Result<()> {
+        let new_subtitles: Vec<NewSubtitle> = subtitles_in
+            .iter()
+            .map(|s| NewSubtitle {
+                file_id,
+                path: &s.path,
+                language: s.language.as_deref(),
+            })
+            .collect();
+
+        diesel::insert_or_ignore_into(subtitles::table)
+            .values(&new_subtitles)
+            .execute(conn)?;
+
         Ok(())
     }
 }
@@ -430,39 +642,108 @@ impl Sqlite {
         episode_id_val: i32,
         watched_val: bool,
     ) -> Result<()> {
+        let media_id_val: i32 = episodes::table
+            .inner_join(seasons::table)
+            .filter(episodes::id.eq(episode_id_val))
+            .select(seasons::media_id)
+            .first(conn)?;
+
         diesel::update(episodes::table.filter(episodes::id.eq(episode_id_val)))
             .set(episodes::watched.eq(watched_val))
             .execute(conn)?;
 
-        let season_ids = episodes::table
-            .select(episodes::season_id)
-            .filter(episodes::id.eq(episode_id_val))
-            .first::<i32>(conn)?;
+        Self::roll_up_watched(conn, media_id_val)
+    }
 
-        let watched_count: i64 = episodes::table
-            .filter(episodes::season_id.eq(season_ids))
-            .filter(episodes::watched.eq(false))
-            .count()
-            .get_result(conn)?;
+    /// Marks every episode a file holds, or the movie it belongs to.
+    fn update_file_watched(
+        conn: &mut SqliteConnection,
+        file_id_val: i32,
+        watched_val: bool,
+    ) -> Result<()> {
+        let media_id_val: Option<i32> = files::table
+            .find(file_id_val)
+            .select(files::media_id)
+            .first(conn)?;
+        if let Some(media_id_val) = media_id_val {
+            return Self::update_media_watched(conn, media_id_val, watched_val);
+        }
+
+        let episode_ids = file_episodes::table
+            .filter(file_episodes::file_id.eq(file_id_val))
+            .select(file_episodes::episode_id);
+        let media_ids: Vec<i32> = episodes::table
+            .inner_join(seasons::table)
+            .filter(episodes::id.eq_any(episode_ids))
+            .select(seasons::media_id)
+            .distinct()
+            .load(conn)?;
 
-        diesel::update(seasons::table.filter(seasons::id.eq(season_ids)))
-            .set(seasons::watched.eq(watched_count == 0))
+        diesel::update(episodes::table.filter(episodes::id.eq_any(episode_ids)))
+            .set(episodes::watched.eq(watched_val))
             .execute(conn)?;
 
-        let media_id_val: i32 = seasons::table
-            .select(seasons::media_id)
-            .filter(seasons::id.eq(season_ids))
-            .first::<i32>(conn)?;
+        for media_id_val in media_ids {
+            Self::roll_up_watched(conn, media_id_val)?;
+        }
 
-        let watched_count: i64 = seasons::table
-            .filter(seasons::media_id.eq(media_id_val))
-            .filter(seasons::watched.eq(false))
-            .count()
-            .get_result(conn)?;
+        Ok(())
+    }
 
-        diesel::update(medias::table.filter(medias::id.eq(media_id_val)))
-            .set(medias::watched.eq(watched_count == 0))
+    /// Fails when the media does not exist or the title is matched to another
+    /// media, which is refused with the id of that media to offer merging both.
+    fn check_media_imdb(
+        conn: &mut SqliteConnection,
+        media_id: IdType,
+        imdb_id: &str,
+    ) -> Result<()> {
+        Self::find_media_id(conn, media_id)?;
+
+        let owner: Option<(IdType, String)> = medias::table
+            .filter(medias::imdb_id.eq(imdb_id))
+            .filter(medias::id.ne(media_id))
+            .select((medias::id, medias::name))
+            .first(conn)
+            .optional()?;
+        if let Some((owner_id, name)) = owner {
+            return Err(AppError::AlreadyMatched {
+                imdb_id: imdb_id.into(),
+                media_id: owner_id,
+                name,
+            }
+            .into());
+        }
+
+        Ok(())
+    }
+
+    /// Re-matches the media in place, so its id, files, tags and user data
+    /// stay. The previous title is dropped once no media is matched to it.
+    fn update_media_imdb(
+        conn: &mut SqliteConnection,
+        media_id: IdType,
+        imdb_id: &str,
+    ) -> Result<()> {
+        Self::check_media_imdb(conn, media_id, imdb_id)?;
+        let previous: Option<String> = medias::table
+            .find(media_id)
+            .select(medias::imdb_id)
+            .first(conn)?;
+
+        diesel::update(medias::table.find(media_id))
+            .set(medias::imdb_id.eq(imdb_id))
             .execute(conn)?;
+        if let Some(previous) = previous.filter(|previous| previous != imdb_id) {
+            let used: bool =
+                diesel::select(exists(medias::table.filter(medias::imdb_id.eq(&previous))))
+                    .get_result(conn)?;
+            if !used {
+                Self::delete_imdb(conn, &previous)?;
+            }
+        }
+        Self::set_match_suggestion(conn, media_id, None)?;
+        diesel::delete(skipped_reviews::table.find(media_id)).execute(conn)?;
+        diesel::delete(rejected_matches::table.find((media_id, imdb_id))).execute(conn)?;
 
         Ok(())
     }
@@ -543,6 +824,18 @@ impl Sqlite {
         Ok(Some(imdb))
     }
 
+    fn get_match_suggestion(
+        conn: &mut SqliteConnection,
+        media_id: IdType,
+    ) -> Result<Option<MatchSuggestion>> {
+        let suggestion = match_suggestions::table
+            .find(media_id)
+            .first::<DbMatchSuggestion>(conn)
+            .optional()?;
+
+        Ok(suggestion.map(MatchSuggestion::from))
+    }
+
     fn get_media_and_imdb_by_media_id(
         conn: &mut SqliteConnection,
         media_id: IdType,
@@ -570,6 +863,7 @@ impl Sqlite {
             tags: media_tags,
             seasons: vec![],
             files: vec![],
+            suggestion: Self::get_match_suggestion(conn, media_id)?,
         })
     }
 
@@ -578,10 +872,12 @@ impl Sqlite {
         episode_id: IdType,
     ) -> Result<Vec<MediaFile>> {
         let media_files = files::table
-            .filter(files::episode_id.eq(episode_id))
+            .inner_join(file_episodes::table)
+            .filter(file_episodes::episode_id.eq(episode_id))
+            .select(files::all_columns)
             .load::<DbFile>(conn)?;
 
-        Ok(media_files.into_iter().map(MediaFile::from).collect())
+        Self::with_subtitles(conn, media_files)
     }
 
     fn get_files_for_media(
@@ -592,7 +888,83 @@ impl Sqlite {
             .filter(files::media_id.eq(media_id))
             .load::<DbFile>(conn)?;
 
-        Ok(media_files.into_iter().map(MediaFile::from).collect())
+        Self::with_subtitles(conn, media_files)
+    }
+
+    /// Converts file rows and attaches their sidecar subtitles.
+    fn with_subtitles(
+        conn: &mut SqliteConnection,
+        db_files: Vec<DbFile>,
+    ) -> Result<Vec<MediaFile>> {
+        let file_ids: Vec<IdType> = db_files.iter().map(|f| f.id).collect();
+        let mut by_file: HashMap<IdType, Vec<Subtitle>> = HashMap::new();
+        for subtitle in subtitles::table
+            .filter(subtitles::file_id.eq_any(&file_ids))
+            .order(subtitles::path.asc())
+            .load::<DbSubtitle>(conn)?
+        {
+            by_file
+                .entry(subtitle.file_id)
+                .or_default()
+                .push(subtitle.into());
+        }
+
+        Ok(db_files
+            .into_iter()
+            .map(|db_file| {
+                let subtitles = by_file.remove(&db_file.id).unwrap_or_default();
+                MediaFile {
+                    subtitles,
+                    ..MediaFile::from(db_file)
+                }
+            })
+            .collect())
+    }
+
+    /// Medias with a sidecar subtitle on any of their files or episode files,
+    /// optionally only subtitles in `language`.
+    fn get_media_ids_with_subtitles(
+        conn: &mut SqliteConnection,
+        language: Option<&str>,
+    ) -> Result<Vec<IdType>> {
+        let mut movie_query = subtitles::table
+            .inner_join(files::table)
+            .filter(files::media_id.is_not_null())
+            .select(files::media_id.assume_not_null())
+            .into_boxed();
+        let mut series_query = subtitles::table
+            .inner_join(files::table.inner_join(episodes::table.inner_join(seasons::table)))
+            .select(seasons::media_id)
+            .into_boxed();
+        if let Some(language) = language {
+            movie_query = movie_query.filter(subtitles::language.eq(language));
+            series_query = series_query.filter(subtitles::language.eq(language));
+        }
+
+        let mut media_ids: Vec<IdType> = movie_query.load(conn)?;
+        media_ids.extend(series_query.load::<IdType>(conn)?);
+        media_ids.sort();
+        media_ids.dedup();
+
+        Ok(media_ids)
+    }
+
+    fn get_imdb_episodes(
+        conn: &mut SqliteConnection,
+        series_imdb_id: &str,
+    ) -> Result<Vec<ImdbEpisode>> {
+        let episodes = imdb_episodes::table
+            .filter(imdb_episodes::series_imdb_id.eq(series_imdb_id))
+            .order((
+                imdb_episodes::season_number.asc(),
+                imdb_episodes::episode_number.asc(),
+            ))
+            .load::<DbImdbEpisode>(conn)?
+            .into_iter()
+            .map(Into::into)
+            .collect();
+
+        Ok(episodes)
     }
 
     fn get_episodes_by_season_id(
@@ -612,6 +984,7 @@ impl Sqlite {
                     number: episode.episode_number,
                     watched: episode.watched,
                     files: Self::get_files_for_episode(conn, episode.id)?,
+                    imdb: None,
                 })
             })
             .collect::<Result<_>>()?;
@@ -627,11 +1000,32 @@ impl Sqlite {
             .order(seasons::season_number.asc())
             .load::<DbSeason>(conn)?;
 
+        // Episodes are matched to the provider's by number, through the title
+        // of the series.
+        let series_imdb_id: Option<String> = medias::table
+            .find(media_id)
+            .select(medias::imdb_id)
+            .first(conn)
+            .optional()?
+            .flatten();
+        let mut details: HashMap<(i32, i32), ImdbEpisode> = match series_imdb_id {
+            Some(series_imdb_id) => Self::get_imdb_episodes(conn, &series_imdb_id)?
+                .into_iter()
+                .map(|episode| ((episode.season, episode.episode), episode))
+                .collect(),
+            None => HashMap::new(),
+        };
+
         let seasons_list = seasons_list
             .into_iter()
             .map(|season| {
+                let mut episodes = Self::get_episodes_by_season_id(conn, season.id)?;
+                for episode in &mut episodes {
+                    episode.imdb = details.remove(&(season.season_number, episode.number));
+                }
+
                 Ok(Season {
-                    episodes: Self::get_episodes_by_season_id(conn, season.id)?,
+                    episodes,
                     id: season.id,
                     number: season.season_number,
                     watched: season.watched,
@@ -679,54 +1073,164 @@ impl Sqlite {
             tags: tags_list,
             seasons: seasons_list,
             files: files_list,
+            suggestion: Self::get_match_suggestion(conn, media_id)?,
         }))
     }
 }
 
 // remove
-#[allow(dead_code)]
 impl Sqlite {
-    fn remove_empty_imdb(conn: &mut SqliteConnection) -> Result<()> {
-        diesel::delete(imdbs::table.filter(diesel::dsl::not(diesel::dsl::exists(
-            medias::table.filter(medias::imdb_id.eq(imdbs::imdb_id.nullable())),
-        ))))
-        .execute(conn)?;
+    /// Finds episodes without files, seasons without such episodes, medias with
+    /// neither files nor seasons left, and imdbs no remaining media points to.
+    fn find_orphans(conn: &mut SqliteConnection, cleanup: &OrphanCleanup) -> Result<OrphanReport> {
+        let episode_ids_with_files: HashSet<IdType> = file_episodes::table
+            .select(file_episodes::episode_id)
+            .load(conn)?
+            .into_iter()
+            .collect();
+        let media_ids_with_files: HashSet<IdType> = files::table
+            .filter(files::media_id.is_not_null())
+            .select(files::media_id.assume_not_null())
+            .load(conn)?
+            .into_iter()
+            .collect();
+        let tagged_media_ids: HashSet<IdType> = media_tags::table
+            .select(media_tags::media_id)
+            .load(conn)?
+            .into_iter()
+            .collect();
 
-        Ok(())
-    }
+        let all_episodes: Vec<(IdType, IdType)> = episodes::table
+            .select((episodes::id, episodes::season_id))
+            .load(conn)?;
+        let all_seasons: Vec<(IdType, IdType)> = seasons::table
+            .select((seasons::id, seasons::media_id))
+            .load(conn)?;
+        let all_medias: Vec<DbMedia> = medias::table.load(conn)?;
 
-    fn remove_empty_media(conn: &mut SqliteConnection) -> Result<()> {
-        diesel::delete(
-            medias::table.filter(
-                diesel::dsl::not(diesel::dsl::exists(
-                    files::table.filter(files::media_id.eq(medias::id.nullable())),
-                ))
-                .and(diesel::dsl::not(diesel::dsl::exists(
-                    seasons::table.filter(seasons::media_id.eq(medias::id)),
-                ))),
-            ),
-        )
-        .execute(conn)?;
+        let in_scope = |media_id: IdType| {
+            cleanup
+                .media_ids
+                .as_ref()
+                .is_none_or(|ids| ids.contains(&media_id))
+        };
+        let season_media: HashMap<IdType, IdType> = all_seasons.iter().copied().collect();
 
-        Ok(())
-    }
+        let orphan_episodes: Vec<(IdType, IdType)> = all_episodes
+            .iter()
+            .copied()
+            .filter(|(id, _)| !episode_ids_with_files.contains(id))
+            .collect();
 
-    fn remove_empty_seasons(conn: &mut SqliteConnection) -> Result<()> {
-        diesel::delete(seasons::table.filter(diesel::dsl::not(diesel::dsl::exists(
-            episodes::table.filter(episodes::season_id.eq(seasons::id)),
-        ))))
-        .execute(conn)?;
+        let orphan_seasons: Vec<(IdType, IdType)> = all_seasons
+            .iter()
+            .copied()
+            .filter(|(season_id, _)| {
+                all_episodes
+                    .iter()
+                    .filter(|(_, s)| s == season_id)
+                    .all(|(id, _)| !episode_ids_with_files.contains(id))
+            })
+            .collect();
 
-        Ok(())
+        let mut report = OrphanReport::default();
+        let mut kept_media_ids = HashSet::new();
+
+        for media in &all_medias {
+            if !in_scope(media.id) || media_ids_with_files.contains(&media.id) {
+                continue;
+            }
+            let has_live_season = all_seasons
+                .iter()
+                .any(|(id, m)| *m == media.id && !orphan_seasons.iter().any(|(o, _)| o == id));
+            if has_live_season {
+                continue;
+            }
+
+            let has_user_data = media.watched
+                || media.my_ranking > 0
+                || media.watch_list
+                || tagged_media_ids.contains(&media.id);
+
+            if has_user_data && !cleanup.include_user_data {
+                kept_media_ids.insert(media.id);
+                report.kept_medias.push((media.id, media.name.clone()));
+            } else {
+                report.medias.push((media.id, media.name.clone()));
+            }
+        }
+
+        // A kept media keeps its whole tree, watched episodes included.
+        let keeps = |media_id: &IdType| kept_media_ids.contains(media_id) || !in_scope(*media_id);
+
+        report.seasons = orphan_seasons
+            .iter()
+            .filter(|(_, media_id)| !keeps(media_id))
+            .map(|(id, _)| *id)
+            .collect();
+        report.episodes = orphan_episodes
+            .iter()
+            .filter(|(_, season_id)| {
+                season_media
+                    .get(season_id)
+                    .is_none_or(|media_id| !keeps(media_id))
+            })
+            .map(|(id, _)| *id)
+            .collect();
+
+        let removed_media_ids: HashSet<IdType> = report.medias.iter().map(|(id, _)| *id).collect();
+        let still_used: HashSet<&str> = all_medias
+            .iter()
+            .filter(|media| !removed_media_ids.contains(&media.id))
+            .filter_map(|media| media.imdb_id.as_deref())
+            .collect();
+
+        report.imdbs = if cleanup.media_ids.is_some() {
+            // A scoped cleanup only drops imdbs of the medias it removes.
+            all_medias
+                .iter()
+                .filter(|media| removed_media_ids.contains(&media.id))
+                .filter_map(|media| media.imdb_id.clone())
+                .filter(|imdb_id| !still_used.contains(imdb_id.as_str()))
+                .collect()
+        } else {
+            imdbs::table
+                .select(imdbs::imdb_id)
+                .load::<String>(conn)?
+                .into_iter()
+                .filter(|imdb_id| !still_used.contains(imdb_id.as_str()))
+                .collect()
+        };
+        report.imdbs.sort();
+        report.imdbs.dedup();
+
+        Ok(report)
     }
 
-    fn remove_empty_episodes(conn: &mut SqliteConnection) -> Result<()> {
-        diesel::delete(episodes::table.filter(diesel::dsl::not(diesel::dsl::exists(
-            files::table.filter(files::episode_id.eq(episodes::id.nullable())),
-        ))))
-        .execute(conn)?;
+    fn remove_orphans(
+        conn: &mut SqliteConnection,
+        cleanup: &OrphanCleanup,
+    ) -> Result<OrphanReport> {
+        let report = Self::find_orphans(conn, cleanup)?;
+        if cleanup.dry_run {
+            return Ok(report);
+        }
 
-        Ok(())
+        let media_ids: Vec<IdType> = report.medias.iter().map(|(id, _)| *id).collect();
+
+        diesel::delete(episodes::table.filter(episodes::id.eq_any(&report.episodes)))
+            .execute(conn)?;
+        diesel::delete(seasons::table.filter(seasons::id.eq_any(&report.seasons))).execute(conn)?;
+        diesel::delete(media_tags::table.filter(media_tags::media_id.eq_any(&media_ids)))
+            .execute(conn)?;
+        Self::delete_match_reviews(conn, &media_ids)?;
+        diesel::delete(medias::table.filter(medias::id.eq_any(&media_ids))).execute(conn)?;
+
+        for imdb_id in &report.imdbs {
+            Self::delete_imdb(conn, imdb_id)?;
+        }
+
+        Ok(report)
     }
 
     fn delete_media(conn: &mut SqliteConnection, media_id: IdType) -> Result<()> {
@@ -768,16 +1272,14 @@ impl Sqlite {
         // Check all paths
         for path in media_file_paths.iter().chain(&all_episode_file_paths) {
             if std::path::Path::new(path).exists() {
-                return Err(anyhow::anyhow!(
-                    "Cannot delete media: file {} exists on disk",
-                    path
-                ));
+                return Err(AppError::FileStillOnDisk(path.clone()).into());
             }
         }
 
         // Delete media_tags
         diesel::delete(media_tags::table.filter(media_tags::media_id.eq(media_id)))
             .execute(conn)?;
+        Self::delete_match_reviews(conn, &[media_id])?;
 
         // Delete files associated with media
         diesel::delete(files::table.filter(files::media_id.eq(media_id))).execute(conn)?;
@@ -815,8 +1317,7 @@ impl Sqlite {
         Ok(())
     }
 
-    fn delete_imdb(conn: &mut SqliteConnection, imdb_id: &str) -> Result<()> {
-        // Delete related tables
+    fn delete_imdb_links(conn: &mut SqliteConnection, imdb_id: &str) -> Result<()> {
         diesel::delete(imdb_genres::table.filter(imdb_genres::imdb_id.eq(imdb_id)))
             .execute(conn)?;
         diesel::delete(imdb_countries::table.filter(imdb_countries::imdb_id.eq(imdb_id)))
@@ -824,6 +1325,14 @@ impl Sqlite {
         diesel::delete(imdb_people::table.filter(imdb_people::imdb_id.eq(imdb_id)))
             .execute(conn)?;
 
+        Ok(())
+    }
+
+    fn delete_imdb(conn: &mut SqliteConnection, imdb_id: &str) -> Result<()> {
+        Self::delete_imdb_links(conn, imdb_id)?;
+        diesel::delete(imdb_episodes::table.filter(imdb_episodes::series_imdb_id.eq(imdb_id)))
+            .execute(conn)?;
+
         // Delete the imdb entry
         diesel::delete(imdbs::table.filter(imdbs::imdb_id.eq(imdb_id))).execute(conn)?;
 
@@ -831,28 +1340,622 @@ impl Sqlite {
     }
 }
 
-impl DB for Sqlite {
-    fn insert_medias(&self, media_list: &[Media]) -> Result<()> {
-        self.get_conn()?.transaction(|conn| {
-            for media in media_list {
-                Self::insert_media(conn, media)?;
-            }
-            Ok(())
-        })
-    }
+// library roots
+impl Sqlite {
+    fn insert_library_root(conn: &mut SqliteConnection, path: &str) -> Result<LibraryRoot> {
+        // Drop trailing separators so `/movies/` and `/movies` are the same root.
+        let root: PathBuf = Path::new(path).components().collect();
+        if !root.is_dir() {
+            return Err(AppError::NotFound(format!("Directory does not exist: {path}")).into());
+        }
+        let root_str = root.to_string_lossy().to_string();
 
-    fn delete_media(&self, media_id: IdType) -> Result<()> {
-        self.get_conn()?
-            .transaction(|conn| Self::delete_media(conn, media_id))
-    }
+        let existing = library_roots::table.load::<DbLibraryRoot>(conn)?;
 
-    fn update_media_my_ranking(&self, media_id: IdType, my_ranking: u8) -> Result<usize> {
-        let conn = &mut self.get_conn()?;
-        diesel::update(medias::table.filter(medias::id.eq(media_id)))
-            .set(medias::my_ranking.eq(my_ranking as i32))
-            .execute(conn)
-            .map_err(Into::into)
-    }
+        if let Some(parent) = existing
+            .iter()
+            .find(|r| root.starts_with(Path::new(&r.path)))
+        {
+            return Err(AppError::Conflict(format!(
+                "Directory {} is already covered by {}",
+                root_str, parent.path
+            ))
+            .into());
+        }
+
+        // A new parent root replaces the roots nested under it.
+        let children: Vec<IdType> = existing
+            .iter()
+            .filter(|r| Path::new(&r.path).starts_with(&root))
+            .map(|r| r.id)
+            .collect();
+        diesel::delete(library_roots::table.filter(library_roots::id.eq_any(children)))
+            .execute(conn)?;
+
+        diesel::insert_into(library_roots::table)
+            .values(&NewLibraryRoot {
+                path: &root_str,
+                enabled: true,
+            })
+            .execute(conn)?;
+
+        let root = library_roots::table
+            .filter(library_roots::path.eq(&root_str))
+            .first::<DbLibraryRoot>(conn)?;
+
+        Ok(root.into())
+    }
+}
+
+// match review
+impl Sqlite {
+    fn find_media_id(conn: &mut SqliteConnection, media_id: IdType) -> Result<IdType> {
+        medias::table
+            .find(media_id)
+            .select(medias::id)
+            .first(conn)
+            .optional()?
+            .ok_or_else(|| AppError::NotFound(format!("Media {media_id} not found")).into())
+    }
+
+    fn delete_match_reviews(conn: &mut SqliteConnection, media_ids: &[IdType]) -> Result<()> {
+        diesel::delete(
+            match_suggestions::table.filter(match_suggestions::media_id.eq_any(media_ids)),
+        )
+        .execute(conn)?;
+        diesel::delete(
+            rejected_matches::table.filter(rejected_matches::media_id.eq_any(media_ids)),
+        )
+        .execute(conn)?;
+        diesel::delete(skipped_reviews::table.filter(skipped_reviews::media_id.eq_any(media_ids)))
+            .execute(conn)?;
+
+        Ok(())
+    }
+
+    fn reject_match(conn: &mut SqliteConnection, media_id: IdType, imdb_id: &str) -> Result<()> {
+        Self::find_media_id(conn, media_id)?;
+
+        diesel::insert_or_ignore_into(rejected_matches::table)
+            .values(&NewRejectedMatch { media_id, imdb_id })
+            .execute(conn)?;
+        diesel::delete(
+            match_suggestions::table
+                .find(media_id)
+                .filter(match_suggestions::imdb_id.eq(imdb_id)),
+        )
+        .execute(conn)?;
+        // A wrong match goes back to the queue.
+        diesel::update(
+            medias::table
+                .find(media_id)
+                .filter(medias::imdb_id.eq(imdb_id)),
+        )
+        .set(medias::imdb_id.eq(None::<String>))
+        .execute(conn)?;
+
+        Ok(())
+    }
+}
+
+// merge and split
+impl Sqlite {
+    /// The episode of the media with these numbers, created with its season
+    /// when missing. A watched episode stays watched.
+    fn episode_in(
+        conn: &mut SqliteConnection,
+        media_id: IdType,
+        season_number: i32,
+        episode_number: i32,
+        watched: bool,
+    ) -> Result<IdType> {
+        let season_id = match seasons::table
+            .filter(seasons::media_id.eq(media_id))
+            .filter(seasons::season_number.eq(season_number))
+            .select(seasons::id)
+            .first::<IdType>(conn)
+            .optional()?
+        {
+            Some(id) => id,
+            None => {
+                diesel::insert_into(seasons::table)
+                    .values(&NewSeason {
+                        media_id,
+                        season_number,
+                        watched: false,
+                    })
+                    .execute(conn)?;
+                diesel::select(sql::<BigInt>("last_insert_rowid()")).get_result::<i64>(conn)? as i32
+            }
+        };
+
+        let episode_id = match episodes::table
+            .filter(episodes::season_id.eq(season_id))
+            .filter(episodes::episode_number.eq(episode_number))
+            .select(episodes::id)
+            .first::<IdType>(conn)
+            .optional()?
+        {
+            Some(id) => id,
+            None => {
+                diesel::insert_into(episodes::table)
+                    .values(&NewEpisode {
+                        season_id,
+                        episode_number,
+                        watched,
+                    })
+                    .execute(conn)?;
+                diesel::select(sql::<BigInt>("last_insert_rowid()")).get_result::<i64>(conn)? as i32
+            }
+        };
+        if watched {
+            diesel::update(episodes::table.find(episode_id))
+                .set(episodes::watched.eq(true))
+                .execute(conn)?;
+        }
+
+        Ok(episode_id)
+    }
+
+    /// Marks the seasons of a series watched when all their episodes are, and
+    /// the series when all its seasons are.
+    fn roll_up_watched(conn: &mut SqliteConnection, media_id: IdType) -> Result<()> {
+        let season_ids: Vec<IdType> = seasons::table
+            .filter(seasons::media_id.eq(media_id))
+            .select(seasons::id)
+            .load(conn)?;
+        if season_ids.is_empty() {
+            return Ok(());
+        }
+
+        for season_id in &season_ids {
+            let unwatched: i64 = episodes::table
+                .filter(episodes::season_id.eq(season_id))
+                .filter(episodes::watched.eq(false))
+                .count()
+                .get_result(conn)?;
+            diesel::update(seasons::table.find(season_id))
+                .set(seasons::watched.eq(unwatched == 0))
+                .execute(conn)?;
+        }
+
+        let unwatched: i64 = seasons::table
+            .filter(seasons::media_id.eq(media_id))
+            .filter(seasons::watched.eq(false))
+            .count()
+            .get_result(conn)?;
+        diesel::update(medias::table.find(media_id))
+            .set(medias::watched.eq(unwatched == 0))
+            .execute(conn)?;
+
+        Ok(())
+    }
+
+    /// Removes the episodes left without files, and the seasons left without
+    /// episodes.
+    fn delete_emptied_episodes(conn: &mut SqliteConnection, episode_ids: &[IdType]) -> Result<()> {
+        let emptied: Vec<IdType> = episodes::table
+            .filter(episodes::id.eq_any(episode_ids))
+            .filter(diesel::dsl::not(exists(
+                file_episodes::table.filter(file_episodes::episode_id.eq(episodes::id)),
+            )))
+            .select(episodes::id)
+            .load(conn)?;
+        let season_ids: Vec<IdType> = episodes::table
+            .filter(episodes::id.eq_any(&emptied))
+            .select(episodes::season_id)
+            .distinct()
+            .load(conn)?;
+
+        diesel::delete(episodes::table.filter(episodes::id.eq_any(&emptied))).execute(conn)?;
+        diesel::delete(
+            seasons::table
+                .filter(seasons::id.eq_any(season_ids))
+                .filter(diesel::dsl::not(exists(
+                    episodes::table.filter(episodes::season_id.eq(seasons::id)),
+                ))),
+        )
+        .execute(conn)?;
+
+        Ok(())
+    }
+
+    fn merge_medias(
+        conn: &mut SqliteConnection,
+        target_id: IdType,
+        source_ids: &[IdType],
+    ) -> Result<()> {
+        if source_ids.is_empty() || source_ids.contains(&target_id) {
+            return Err(
+                AppError::InvalidInput("Merge needs other medias than the target".into()).into(),
+            );
+        }
+        let target: DbMedia = medias::table
+            .find(Self::find_media_id(conn, target_id)?)
+            .first(conn)?;
+        let mut sources: Vec<DbMedia> = vec![];
+        for source_id in source_ids {
+            sources.push(
+                medias::table
+                    .find(Self::find_media_id(conn, *source_id)?)
+                    .first(conn)?,
+            );
+        }
+
+        // Files of movies, then episodes with their files.
+        diesel::update(files::table.filter(files::media_id.eq_any(source_ids)))
+            .set(files::media_id.eq(target_id))
+            .execute(conn)?;
+
+        let episodes: Vec<(IdType, i32, i32, bool)> = episodes::table
+            .inner_join(seasons::table)
+            .filter(seasons::media_id.eq_any(source_ids))
+            .select((
+                episodes::id,
+                seasons::season_number,
+                episodes::episode_number,
+                episodes::watched,
+            ))
+            .load(conn)?;
+        for (episode_id, season_number, episode_number, watched) in &episodes {
+            let target_episode =
+                Self::episode_in(conn, target_id, *season_number, *episode_number, *watched)?;
+            diesel::update(files::table.filter(files::episode_id.eq(episode_id)))
+                .set(files::episode_id.eq(target_episode))
+                .execute(conn)?;
+            diesel::update(file_episodes::table.filter(file_episodes::episode_id.eq(episode_id)))
+                .set(file_episodes::episode_id.eq(target_episode))
+                .execute(conn)?;
+        }
+        let episode_ids: Vec<IdType> = episodes.iter().map(|episode| episode.0).collect();
+        diesel::delete(episodes::table.filter(episodes::id.eq_any(&episode_ids))).execute(conn)?;
+        diesel::delete(seasons::table.filter(seasons::media_id.eq_any(source_ids)))
+            .execute(conn)?;
+
+        let tag_ids: Vec<IdType> = media_tags::table
+            .filter(media_tags::media_id.eq_any(source_ids))
+            .select(media_tags::tag_id)
+            .load(conn)?;
+        for tag_id in tag_ids {
+            diesel::insert_or_ignore_into(media_tags::table)
+                .values(&NewMediaTag {
+                    media_id: target_id,
+                    tag_id,
+                })
+                .execute(conn)?;
+        }
+        diesel::delete(media_tags::table.filter(media_tags::media_id.eq_any(source_ids)))
+            .execute(conn)?;
+
+        // The target keeps its own ranking and title, and takes those of the
+        // sources it has none of.
+        let my_ranking = match target.my_ranking {
+            0 => sources.iter().map(|s| s.my_ranking).max().unwrap_or(0),
+            ranking => ranking,
+        };
+        let imdb_id = target
+            .imdb_id
+            .clone()
+            .or_else(|| sources.iter().find_map(|source| source.imdb_id.clone()));
+
+        Self::delete_match_reviews(conn, source_ids)?;
+        diesel::delete(medias::table.filter(medias::id.eq_any(source_ids))).execute(conn)?;
+        diesel::update(medias::table.find(target_id))
+            .set((
+                medias::watched.eq(target.watched || sources.iter().any(|s| s.watched)),
+                medias::my_ranking.eq(my_ranking),
+                medias::watch_list.eq(target.watch_list || sources.iter().any(|s| s.watch_list)),
+                medias::imdb_id.eq(&imdb_id),
+            ))
+            .execute(conn)?;
+        if imdb_id.is_some() {
+            Self::set_match_suggestion(conn, target_id, None)?;
+        }
+        Self::roll_up_watched(conn, target_id)?;
+
+        Ok(())
+    }
+
+    fn split_media(
+        conn: &mut SqliteConnection,
+        media_id: IdType,
+        file_ids: &[IdType],
+    ) -> Result<IdType> {
+        let media: DbMedia = medias::table
+            .find(Self::find_media_id(conn, media_id)?)
+            .first(conn)?;
+
+        // Every file of the media, with its episode for series.
+        let movie_files: Vec<(IdType, String)> = files::table
+            .filter(files::media_id.eq(media_id))
+            .select((files::id, files::path))
+            .load(conn)?;
+        let episode_files: Vec<(IdType, String, IdType, i32, i32, bool)> = files::table
+            .inner_join(episodes::table.inner_join(seasons::table))
+            .filter(seasons::media_id.eq(media_id))
+            .select((
+                files::id,
+                files::path,
+                episodes::id,
+                seasons::season_number,
+                episodes::episode_number,
+                episodes::watched,
+            ))
+            .load(conn)?;
+
+        let all: HashSet<IdType> = movie_files
+            .iter()
+            .map(|file| file.0)
+            .chain(episode_files.iter().map(|file| file.0))
+            .collect();
+        let moved: HashSet<IdType> = file_ids.iter().copied().collect();
+        if moved.is_empty() || moved.len() == all.len() {
+            return Err(AppError::InvalidInput(
+                "Split needs some, but not all, of the media's files".into(),
+            )
+            .into());
+        }
+        if let Some(file_id) = moved.iter().find(|id| !all.contains(id)) {
+            return Err(AppError::InvalidInput(format!(
+                "File {file_id} does not belong to media {media_id}"
+            ))
+            .into());
+        }
+
+        // The new media is named after its files, apart from existing ones.
+        let first_path = movie_files
+            .iter()
+            .map(|(id, path)| (id, path))
+            .chain(episode_files.iter().map(|(id, path, ..)| (id, path)))
+            .find(|(id, _)| moved.contains(id))
+            .map(|(_, path)| path.clone())
+            .unwrap_or_default();
+        let (detected_name, year) = Media::name_and_year(Path::new(&first_path));
+        let mut name = detected_name.clone();
+        for n in 2.. {
+            let taken = medias::table
+                .filter(medias::name.eq(&name))
+                .filter(medias::year.is(year))
+                .count()
+                .get_result::<i64>(conn)?
+                > 0;
+            if !taken {
+                break;
+            }
+            name = format!("{detected_name} ({n})");
+        }
+
+        diesel::insert_into(medias::table)
+            .values(&NewMedia {
+                name: &name,
+                year,
+                watched: media.watched,
+                my_ranking: media.my_ranking,
+                watch_list: media.watch_list,
+                imdb_id: None,
+            })
+            .execute(conn)?;
+        let new_id =
+            diesel::select(sql::<BigInt>("last_insert_rowid()")).get_result::<i64>(conn)? as i32;
+
+        let tag_ids: Vec<IdType> = media_tags::table
+            .filter(media_tags::media_id.eq(media_id))
+            .select(media_tags::tag_id)
+            .load(conn)?;
+        for tag_id in tag_ids {
+            diesel::insert_into(media_tags::table)
+                .values(&NewMediaTag {
+                    media_id: new_id,
+                    tag_id,
+                })
+                .execute(conn)?;
+        }
+
+        diesel::update(
+            files::table
+                .filter(files::media_id.eq(media_id))
+                .filter(files::id.eq_any(&moved)),
+        )
+        .set(files::media_id.eq(new_id))
+        .execute(conn)?;
+
+        let mut emptied = vec![];
+        for (file_id, _, episode_id, season_number, episode_number, watched) in &episode_files {
+            if !moved.contains(file_id) {
+                continue;
+            }
+            let new_episode =
+                Self::episode_in(conn, new_id, *season_number, *episode_number, *watched)?;
+            diesel::update(files::table.find(file_id))
+                .set(files::episode_id.eq(new_episode))
+                .execute(conn)?;
+            emptied.push(*episode_id);
+
+            // Every episode a multi-episode file holds moves with it.
+            let covered: Vec<(IdType, i32, i32, bool)> = file_episodes::table
+                .inner_join(episodes::table.inner_join(seasons::table))
+                .filter(file_episodes::file_id.eq(file_id))
+                .select((
+                    episodes::id,
+                    seasons::season_number,
+                    episodes::episode_number,
+                    episodes::watched,
+                ))
+                .load(conn)?;
+            for (episode_id, season_number, episode_number, watched) in covered {
+                let new_episode =
+                    Self::episode_in(conn, new_id, season_number, episode_number, watched)?;
+                diesel::update(
+                    file_episodes::table
+                        .filter(file_episodes::file_id.eq(file_id))
+                        .filter(file_episodes::episode_id.eq(episode_id)),
+                )
+                .set(file_episodes::episode_id.eq(new_episode))
+                .execute(conn)?;
+                emptied.push(episode_id);
+            }
+        }
+        Self::delete_emptied_episodes(conn, &emptied)?;
+        Self::roll_up_watched(conn, media_id)?;
+        Self::roll_up_watched(conn, new_id)?;
+
+        Ok(new_id)
+    }
+}
+
+// missing episodes
+impl Sqlite {
+    /// Compares the series among `media_ids`, or every series, with their
+    /// stored episode lists. Series are ordered by name.
+    fn find_missing_episodes(
+        conn: &mut SqliteConnection,
+        media_ids: Option<&[IdType]>,
+        today: &str,
+    ) -> Result<Vec<MissingEpisodes>> {
+        let mut series_query = medias::table
+            .filter(exists(
+                seasons::table.filter(seasons::media_id.eq(medias::id)),
+            ))
+            .select((medias::id, medias::name, medias::imdb_id))
+            .order((medias::name.asc(), medias::id.asc()))
+            .into_boxed();
+        let mut numbers_query = episodes::table
+            .inner_join(seasons::table)
+            .filter(exists(
+                file_episodes::table.filter(file_episodes::episode_id.eq(episodes::id)),
+            ))
+            .select((
+                seasons::media_id,
+                seasons::season_number,
+                episodes::episode_number,
+            ))
+            .into_boxed();
+        if let Some(media_ids) = media_ids {
+            series_query = series_query.filter(medias::id.eq_any(media_ids));
+            numbers_query = numbers_query.filter(seasons::media_id.eq_any(media_ids));
+        }
+
+        let series: Vec<(IdType, String, Option<String>)> = series_query.load(conn)?;
+        let mut on_disk: HashMap<IdType, HashSet<(i32, i32)>> = HashMap::new();
+        for (media_id, season, episode) in numbers_query.load::<(IdType, i32, i32)>(conn)? {
+            on_disk
+                .entry(media_id)
+                .or_default()
+                .insert((season, episode));
+        }
+
+        series
+            .into_iter()
+            .map(|(media_id, name, imdb_id)| {
+                let listed = match imdb_id {
+                    Some(imdb_id) => Self::get_imdb_episodes(conn, &imdb_id)?,
+                    None => vec![],
+                };
+                let on_disk = on_disk.remove(&media_id).unwrap_or_default();
+                Ok(MissingEpisodes::new(
+                    media_id, name, &listed, &on_disk, today,
+                ))
+            })
+            .collect()
+    }
+
+    /// Titles of the matched series, among `imdb_ids` when given, whose
+    /// episode list was not fetched since `fetched_before`.
+    fn get_series_imdb_ids_listed_before(
+        conn: &mut SqliteConnection,
+        fetched_before: i64,
+        imdb_ids: Option<&[String]>,
+    ) -> Result<Vec<String>> {
+        let mut query = medias::table
+            .filter(medias::imdb_id.is_not_null())
+            .filter(exists(
+                seasons::table.filter(seasons::media_id.eq(medias::id)),
+            ))
+            .filter(diesel::dsl::not(exists(
+                imdbs::table
+                    .filter(imdbs::imdb_id.nullable().eq(medias::imdb_id))
+                    .filter(imdbs::episodes_fetched_at.ge(fetched_before)),
+            )))
+            .select(medias::imdb_id.assume_not_null())
+            .distinct()
+            .order(medias::imdb_id.asc())
+            .into_boxed();
+        if let Some(imdb_ids) = imdb_ids {
+            query = query.filter(medias::imdb_id.eq_any(imdb_ids));
+        }
+
+        Ok(query.load(conn)?)
+    }
+}
+
+// sync jobs
+impl Sqlite {
+    fn insert_sync_job(
+        conn: &mut SqliteConnection,
+        roots: &[String],
+        pending: &[String],
+        total: usize,
+    ) -> Result<SyncJob> {
+        // Finished jobs are only kept around for the most recent history.
+        let kept: Vec<IdType> = sync_jobs::table
+            .order(sync_jobs::id.desc())
+            .select(sync_jobs::id)
+            .limit(KEPT_SYNC_JOBS - 1)
+            .load(conn)?;
+        diesel::delete(
+            sync_jobs::table
+                .filter(sync_jobs::id.ne_all(kept))
+                .filter(sync_jobs::status.ne_all(UNFINISHED_SYNC_JOB_STATUSES)),
+        )
+        .execute(conn)?;
+
+        diesel::insert_into(sync_jobs::table)
+            .values(&NewSyncJob {
+                roots: &serde_json::to_string(roots)?,
+                status: &SyncJobStatus::Running.to_string(),
+                total: total as i32,
+                pending: &serde_json::to_string(pending)?,
+            })
+            .execute(conn)?;
+
+        let job_id =
+            diesel::select(sql::<BigInt>("last_insert_rowid()")).get_result::<i64>(conn)? as i32;
+
+        Self::get_sync_job(conn, job_id)?.ok_or_else(|| anyhow::anyhow!("Sync job not saved"))
+    }
+
+    fn get_sync_job(conn: &mut SqliteConnection, job_id: IdType) -> Result<Option<SyncJob>> {
+        sync_jobs::table
+            .find(job_id)
+            .first::<DbSyncJob>(conn)
+            .optional()?
+            .map(SyncJob::try_from)
+            .transpose()
+    }
+}
+
+impl DB for Sqlite {
+    fn insert_medias(&self, media_list: &[Media]) -> Result<()> {
+        self.get_conn()?.transaction(|conn| {
+            for media in media_list {
+                Self::insert_media(conn, media)?;
+            }
+            Ok(())
+        })
+    }
+
+    fn delete_media(&self, media_id: IdType) -> Result<()> {
+        self.get_conn()?
+            .transaction(|conn| Self::delete_media(conn, media_id))
+    }
+
+    fn update_media_my_ranking(&self, media_id: IdType, my_ranking: u8) -> Result<usize> {
+        let conn = &mut self.get_conn()?;
+        diesel::update(medias::table.filter(medias::id.eq(media_id)))
+            .set(medias::my_ranking.eq(my_ranking as i32))
+            .execute(conn)
+            .map_err(Into::into)
+    }
 
     fn update_watch_list(&self, media_id: IdType, watch_list: bool) -> Result<()> {
         let conn = &mut self.get_conn()?;
@@ -877,14 +1980,20 @@ impl DB for Sqlite {
             .transaction(|conn| Self::update_episode_watched(conn, episode_id, watched))
     }
 
+    fn update_file_watched(&self, file_id: IdType, watched: bool) -> Result<()> {
+        self.get_conn()?
+            .transaction(|conn| Self::update_file_watched(conn, file_id, watched))
+    }
+
+    fn check_media_imdb(&self, media_id: IdType, imdb_id: &str) -> Result<()> {
+        let conn = &mut self.get_conn()?;
+        Self::check_media_imdb(conn, media_id, imdb_id)
+    }
+
     fn update_media_imdb(&self, media_id: IdType, imdb_id: &str) -> Result<IdType> {
         self.get_conn()?.transaction(|conn| {
-            let mut media = Self::get_media_by_id(conn, media_id)?.unwrap();
-            diesel::delete(medias::table.filter(medias::id.eq(media.id))).execute(conn)?;
-            let imdb = Self::get_imdb(conn, Some(imdb_id.into()))?;
-            media.imdb = imdb;
-
-            Self::insert_media(conn, &media)
+            Self::update_media_imdb(conn, media_id, imdb_id)?;
+            Ok(media_id)
         })
     }
 
@@ -900,15 +2009,9 @@ impl DB for Sqlite {
         })
     }
 
-    fn clear_empty_data(&self) -> Result<()> {
-        self.get_conn()?.transaction(|conn| {
-            Self::remove_empty_episodes(conn)?;
-            Self::remove_empty_seasons(conn)?;
-            Self::remove_empty_media(conn)?;
-            Self::remove_empty_imdb(conn)?;
-
-            Ok(())
-        })
+    fn remove_orphans(&self, cleanup: &OrphanCleanup) -> Result<OrphanReport> {
+        self.get_conn()?
+            .transaction(|conn| Self::remove_orphans(conn, cleanup))
     }
 
     fn get_genres(&self) -> Result<Vec<NumericalString>> {
@@ -946,6 +2049,7 @@ impl DB for Sqlite {
 
         let path_strings = paths.iter().map(|p| p.to_string_lossy().to_string());
 
+        // Their subtitles go with them, through the foreign key.
         diesel::delete(files::table.filter(files::path.eq_any(path_strings))).execute(conn)?;
 
         Ok(())
@@ -957,20 +2061,142 @@ impl DB for Sqlite {
             .select(files::all_columns)
             .load::<DbFile>(conn)?;
 
-        Ok(db_files.into_iter().map(MediaFile::from).collect())
+        Self::with_subtitles(conn, db_files)
     }
 
-    fn filter_medias(&self, filters: &FilterValues, page: u32) -> Result<Vec<Media>> {
+    fn get_files_without_details(&self) -> Result<Vec<MediaFile>> {
         let conn = &mut self.get_conn()?;
+        let db_files = files::table
+            .filter(files::details_read_at.is_null())
+            .select(files::all_columns)
+            .load::<DbFile>(conn)?;
 
-        let mut query = medias::table
-            .left_join(imdbs::table.on(medias::imdb_id.eq(imdbs::imdb_id.nullable())))
-            .into_boxed();
+        Self::with_subtitles(conn, db_files)
+    }
 
-        // -- Name Filter --
-        if !filters.name.is_empty() {
-            let search_pattern = format!("%{}%", filters.name);
-            query = query.filter(
+    fn mark_file_details_read(&self, file_id: IdType, read_at: i64) -> Result<()> {
+        let conn = &mut self.get_conn()?;
+        diesel::update(files::table.filter(files::id.eq(file_id)))
+            .set(files::details_read_at.eq(read_at))
+            .execute(conn)?;
+
+        Ok(())
+    }
+
+    fn get_media_ids_by_paths(&self, paths: &[PathBuf]) -> Result<Vec<IdType>> {
+        let conn = &mut self.get_conn()?;
+        let path_strings: Vec<_> = paths
+            .iter()
+            .map(|p| p.to_string_lossy().to_string())
+            .collect();
+
+        let mut media_ids: Vec<IdType> = files::table
+            .filter(files::path.eq_any(&path_strings))
+            .filter(files::media_id.is_not_null())
+            .select(files::media_id.assume_not_null())
+            .load(conn)?;
+
+        let series_ids: Vec<IdType> = files::table
+            .inner_join(episodes::table.on(files::episode_id.eq(episodes::id.nullable())))
+            .inner_join(seasons::table.on(episodes::season_id.eq(seasons::id)))
+            .filter(files::path.eq_any(&path_strings))
+            .select(seasons::media_id)
+            .load(conn)?;
+
+        media_ids.extend(series_ids);
+        media_ids.sort();
+        media_ids.dedup();
+
+        Ok(media_ids)
+    }
+
+    fn update_file_path(&self, file_id: IdType, path: &Path) -> Result<()> {
+        let conn = &mut self.get_conn()?;
+        // Only what the name tells changes: the file keeps its row, and with it
+        // the media or episode it belongs to, and what was read from its content.
+        let file = MediaFile::from(path.to_path_buf());
+
+        diesel::update(files::table.filter(files::id.eq(file_id)))
+            .set((
+                files::file_name.eq(&file.file_name),
+                files::path.eq(&file.path),
+                files::quality.eq(&file.quality),
+                files::language_format.eq(&file.language_format),
+            ))
+            .execute(conn)?;
+
+        Ok(())
+    }
+
+    fn update_file_probe(&self, file_id: IdType, probe: &MediaProbe) -> Result<()> {
+        let conn = &mut self.get_conn()?;
+        diesel::update(files::table.filter(files::id.eq(file_id)))
+            .set((
+                files::duration_ms.eq(probe.duration_ms),
+                files::video_codec.eq(&probe.video_codec),
+                files::width.eq(probe.width),
+                files::height.eq(probe.height),
+                files::bitrate.eq(probe.bitrate),
+                files::audio_languages.eq(join_languages(&probe.audio_languages)),
+                files::subtitle_languages.eq(join_languages(&probe.subtitle_languages)),
+            ))
+            .execute(conn)?;
+
+        Ok(())
+    }
+
+    fn update_file_subtitles(&self, file_id: IdType, subtitles_in: &[Subtitle]) -> Result<()> {
+        self.get_conn()?.transaction(|conn| {
+            diesel::delete(subtitles::table.filter(subtitles::file_id.eq(file_id)))
+                .execute(conn)?;
+            Self::insert_subtitles(conn, file_id, subtitles_in)
+        })
+    }
+
+    fn get_subtitle_languages(&self) -> Result<Vec<String>> {
+        let conn = &mut self.get_conn()?;
+        let languages = subtitles::table
+            .filter(subtitles::language.is_not_null())
+            .select(subtitles::language.assume_not_null())
+            .distinct()
+            .order(subtitles::language.asc())
+            .load(conn)?;
+
+        Ok(languages)
+    }
+
+    fn get_certifications(&self) -> Result<Vec<String>> {
+        let conn = &mut self.get_conn()?;
+        let certifications = imdbs::table
+            .filter(imdbs::rated.is_not_null())
+            .select(imdbs::rated.assume_not_null())
+            .distinct()
+            .order(imdbs::rated.asc())
+            .load(conn)?;
+
+        Ok(certifications)
+    }
+
+    fn update_file_fingerprint(&self, file_id: IdType, fingerprint: &str) -> Result<()> {
+        let conn = &mut self.get_conn()?;
+        diesel::update(files::table.filter(files::id.eq(file_id)))
+            .set(files::fingerprint.eq(fingerprint))
+            .execute(conn)?;
+
+        Ok(())
+    }
+
+    fn filter_medias(&self, filters: &FilterValues, page: u32) -> Result<Vec<Media>> {
+        let conn = &mut self.get_conn()?;
+
+        let mut query = medias::table
+            .left_join(imdbs::table.on(medias::imdb_id.eq(imdbs::imdb_id.nullable())))
+            .into_boxed();
+
+        // -- Name Filter --
+        if !filters.name.is_empty() {
+            let search_pattern = format!("%{}%", filters.name);
+            query = query.filter(
                 medias::name
                     .like(search_pattern.clone())
                     .or(imdbs::title.like(search_pattern)),
@@ -989,6 +2215,21 @@ impl DB for Sqlite {
             query = query.filter(rating_clause);
         }
 
+        // -- Runtime and Certification Filters --
+        if let Some(min_runtime) = filters.min_runtime {
+            let runtime_clause = sql::<Integer>("CAST(runtime AS INTEGER)").ge(min_runtime as i32);
+            query = query.filter(runtime_clause);
+        }
+
+        if let Some(max_runtime) = filters.max_runtime {
+            let runtime_clause = sql::<Integer>("CAST(runtime AS INTEGER)").le(max_runtime as i32);
+            query = query.filter(runtime_clause);
+        }
+
+        if !filters.rated.is_empty() {
+            query = query.filter(imdbs::rated.eq_any(&filters.rated));
+        }
+
         // -- Many-to-Many Filters (Country, Genre, Actor, Tags) --
         if !filters.country.is_empty() {
             for country_id in &filters.country {
@@ -1046,11 +2287,11 @@ impl DB for Sqlite {
                 .group_by(files::media_id)
                 .having(diesel::dsl::count_star().gt(1));
 
-            let episode_file_count = files::table
-                .left_join(episodes::table.on(files::episode_id.eq(episodes::id.nullable())))
-                .left_join(seasons::table.on(episodes::season_id.eq(seasons::id)))
+            let episode_file_count = file_episodes::table
+                .inner_join(episodes::table.inner_join(seasons::table))
                 .filter(seasons::media_id.eq(medias::id))
-                .group_by(files::episode_id)
+                .select(file_episodes::episode_id)
+                .group_by(file_episodes::episode_id)
                 .having(diesel::dsl::count_star().gt(1));
 
             let condition =
@@ -1063,6 +2304,31 @@ impl DB for Sqlite {
             };
         }
 
+        // -- Subtitle Filters --
+        if let Some(has_subtitles) = filters.has_subtitles {
+            let media_ids = Self::get_media_ids_with_subtitles(conn, None)?;
+            query = if has_subtitles {
+                query.filter(medias::id.eq_any(media_ids))
+            } else {
+                query.filter(medias::id.ne_all(media_ids))
+            };
+        }
+
+        for language in &filters.subtitle_language {
+            let media_ids = Self::get_media_ids_with_subtitles(conn, Some(language))?;
+            query = query.filter(medias::id.eq_any(media_ids));
+        }
+
+        // -- Episode Completeness Filter --
+        if let Some(complete) = filters.complete {
+            let media_ids: Vec<IdType> = Self::find_missing_episodes(conn, None, &today())?
+                .into_iter()
+                .filter(|series| series.checked && series.is_complete() == complete)
+                .map(|series| series.media_id)
+                .collect();
+            query = query.filter(medias::id.eq_any(media_ids));
+        }
+
         // -- Boolean Filters --
         if let Some(watched) = filters.watched {
             query = query.filter(medias::watched.eq(watched));
@@ -1099,6 +2365,14 @@ impl DB for Sqlite {
                     query.order(rating_sql.desc())
                 }
             }
+            SortByType::Runtime => {
+                let runtime_sql = sql::<Integer>("CAST(imdbs.runtime AS INTEGER)");
+                if is_asc {
+                    query.order((runtime_sql.asc(), medias::name.asc()))
+                } else {
+                    query.order((runtime_sql.desc(), medias::name.asc()))
+                }
+            }
         };
 
         // -- Pagination --
@@ -1189,4 +2463,2120 @@ impl DB for Sqlite {
         .execute(conn)?;
         Ok(())
     }
+
+    fn insert_library_root(&self, path: &str) -> Result<LibraryRoot> {
+        self.get_conn()?
+            .transaction(|conn| Self::insert_library_root(conn, path))
+    }
+
+    fn remove_library_root(&self, root_id: IdType) -> Result<()> {
+        let conn = &mut self.get_conn()?;
+        diesel::delete(library_roots::table.filter(library_roots::id.eq(root_id))).execute(conn)?;
+        Ok(())
+    }
+
+    fn get_library_roots(&self) -> Result<Vec<LibraryRoot>> {
+        let conn = &mut self.get_conn()?;
+        let roots = library_roots::table
+            .order(library_roots::path.asc())
+            .load::<DbLibraryRoot>(conn)?;
+
+        Ok(roots.into_iter().map(LibraryRoot::from).collect())
+    }
+
+    fn update_library_root_enabled(&self, root_id: IdType, enabled: bool) -> Result<()> {
+        let conn = &mut self.get_conn()?;
+        diesel::update(library_roots::table.filter(library_roots::id.eq(root_id)))
+            .set(library_roots::enabled.eq(enabled))
+            .execute(conn)?;
+        Ok(())
+    }
+
+    fn get_setting(&self, key: &str) -> Result<Option<String>> {
+        let conn = &mut self.get_conn()?;
+        let value = settings::table
+            .find(key)
+            .select(settings::value)
+            .first(conn)
+            .optional()?;
+
+        Ok(value)
+    }
+
+    fn set_setting(&self, key: &str, value: &str) -> Result<()> {
+        let conn = &mut self.get_conn()?;
+        diesel::insert_into(settings::table)
+            .values(&NewSetting { key, value })
+            .on_conflict(settings::key)
+            .do_update()
+            .set(settings::value.eq(value))
+            .execute(conn)?;
+
+        Ok(())
+    }
+
+    fn insert_sync_job(
+        &self,
+        roots: &[String],
+        pending: &[String],
+        total: usize,
+    ) -> Result<SyncJob> {
+        self.get_conn()?
+            .transaction(|conn| Self::insert_sync_job(conn, roots, pending, total))
+    }
+
+    fn commit_sync_job_chunk(
+        &self,
+        job_id: IdType,
+        medias: &[Media],
+        pending: &[String],
+    ) -> Result<()> {
+        self.get_conn()?.transaction(|conn| {
+            for media in medias {
+                Self::insert_media(conn, media)?;
+            }
+
+            diesel::update(sync_jobs::table.find(job_id))
+                .set((
+                    sync_jobs::inserted.eq(sync_jobs::inserted + medias.len() as i32),
+                    sync_jobs::pending.eq(serde_json::to_string(pending)?),
+                ))
+                .execute(conn)?;
+            Ok(())
+        })
+    }
+
+    fn update_sync_job_status(
+        &self,
+        job_id: IdType,
+        status: SyncJobStatus,
+        error: Option<&str>,
+    ) -> Result<()> {
+        let conn = &mut self.get_conn()?;
+        diesel::update(sync_jobs::table.find(job_id))
+            .set((
+                sync_jobs::status.eq(status.to_string()),
+                sync_jobs::error.eq(error),
+            ))
+            .execute(conn)?;
+        Ok(())
+    }
+
+    fn get_sync_job(&self, job_id: IdType) -> Result<Option<SyncJob>> {
+        let conn = &mut self.get_conn()?;
+        Self::get_sync_job(conn, job_id)
+    }
+
+    fn get_sync_jobs(&self) -> Result<Vec<SyncJob>> {
+        let conn = &mut self.get_conn()?;
+        sync_jobs::table
+            .order(sync_jobs::id.desc())
+            .load::<DbSyncJob>(conn)?
+            .into_iter()
+            .map(SyncJob::try_from)
+            .collect()
+    }
+
+    fn upsert_imdbs(&self, imdbs: &[Imdb], fetched_at: i64) -> Result<()> {
+        self.get_conn()?.transaction(|conn| {
+            for imdb in imdbs {
+                Self::upsert_imdb(conn, imdb, fetched_at)?;
+            }
+            Ok(())
+        })
+    }
+
+    fn get_stale_imdb_ids(&self, fetched_before: i64) -> Result<Vec<String>> {
+        let conn = &mut self.get_conn()?;
+        let ids = imdbs::table
+            .filter(imdbs::fetched_at.lt(fetched_before))
+            .order((imdbs::fetched_at.asc(), imdbs::imdb_id.asc()))
+            .select(imdbs::imdb_id)
+            .load(conn)?;
+
+        Ok(ids)
+    }
+
+    fn get_imdb(&self, imdb_id: &str) -> Result<Option<Imdb>> {
+        let conn = &mut self.get_conn()?;
+        Self::get_imdb(conn, Some(imdb_id.to_string()))
+    }
+
+    fn set_imdb_episodes(&self, series_imdb_id: &str, episodes: &[ImdbEpisode]) -> Result<()> {
+        self.get_conn()?
+            .transaction(|conn| Self::set_imdb_episodes(conn, series_imdb_id, episodes))
+    }
+
+    fn mark_imdb_episodes_fetched(&self, series_imdb_id: &str, fetched_at: i64) -> Result<()> {
+        let conn = &mut self.get_conn()?;
+        diesel::update(imdbs::table.find(series_imdb_id))
+            .set(imdbs::episodes_fetched_at.eq(fetched_at))
+            .execute(conn)?;
+        Ok(())
+    }
+
+    fn get_unlisted_series_imdb_ids(&self, imdb_ids: &[String]) -> Result<Vec<String>> {
+        let conn = &mut self.get_conn()?;
+        Self::get_series_imdb_ids_listed_before(conn, i64::MIN, Some(imdb_ids))
+    }
+
+    fn get_stale_episode_list_imdb_ids(&self, fetched_before: i64) -> Result<Vec<String>> {
+        let conn = &mut self.get_conn()?;
+        Self::get_series_imdb_ids_listed_before(conn, fetched_before, None)
+    }
+
+    fn get_cached_response(&self, kind: &str, key: &str) -> Result<Option<(String, i64)>> {
+        let conn = &mut self.get_conn()?;
+        let cached = metadata_cache::table
+            .find((kind, key))
+            .select((metadata_cache::response, metadata_cache::fetched_at))
+            .first(conn)
+            .optional()?;
+
+        Ok(cached)
+    }
+
+    fn set_cached_response(
+        &self,
+        kind: &str,
+        key: &str,
+        response: &str,
+        fetched_at: i64,
+    ) -> Result<()> {
+        let conn = &mut self.get_conn()?;
+        diesel::insert_into(metadata_cache::table)
+            .values(&NewCachedResponse {
+                kind,
+                key,
+                response,
+                fetched_at,
+            })
+            .on_conflict((metadata_cache::kind, metadata_cache::key))
+            .do_update()
+            .set((
+                metadata_cache::response.eq(response),
+                metadata_cache::fetched_at.eq(fetched_at),
+            ))
+            .execute(conn)?;
+
+        Ok(())
+    }
+
+    fn clear_cached_responses(&self) -> Result<usize> {
+        let conn = &mut self.get_conn()?;
+        Ok(diesel::delete(metadata_cache::table).execute(conn)?)
+    }
+
+    fn get_review_queue(&self) -> Result<Vec<Media>> {
+        self.get_conn()?.transaction(|conn| {
+            // SQLite sorts nulls first, so medias never skipped lead.
+            let media_ids: Vec<IdType> = medias::table
+                .left_join(match_suggestions::table)
+                .left_join(skipped_reviews::table)
+                .filter(medias::imdb_id.is_null())
+                .order((
+                    skipped_reviews::skipped_at.asc(),
+                    match_suggestions::confidence.desc(),
+                    medias::name.asc(),
+                ))
+                .select(medias::id)
+                .load(conn)?;
+
+            Ok(media_ids
+                .into_iter()
+                .map(|id| Self::get_media_by_id(conn, id))
+                .collect::<Result<Vec<_>>>()?
+                .into_iter()
+                .flatten()
+                .collect())
+        })
+    }
+
+    fn get_rejected_matches(&self) -> Result<RejectedMatches> {
+        let conn = &mut self.get_conn()?;
+        let rows: Vec<(String, Option<i32>, String)> = rejected_matches::table
+            .inner_join(medias::table)
+            .select((medias::name, medias::year, rejected_matches::imdb_id))
+            .load(conn)?;
+
+        let mut rejected = RejectedMatches::default();
+        for (name, year, imdb_id) in rows {
+            rejected.insert(name, year, imdb_id);
+        }
+        Ok(rejected)
+    }
+
+    fn reject_match(&self, media_id: IdType, imdb_id: &str) -> Result<()> {
+        self.get_conn()?
+            .transaction(|conn| Self::reject_match(conn, media_id, imdb_id))
+    }
+
+    fn merge_medias(&self, target_id: IdType, source_ids: &[IdType]) -> Result<()> {
+        self.get_conn()?
+            .transaction(|conn| Self::merge_medias(conn, target_id, source_ids))
+    }
+
+    fn split_media(&self, media_id: IdType, file_ids: &[IdType]) -> Result<IdType> {
+        self.get_conn()?
+            .transaction(|conn| Self::split_media(conn, media_id, file_ids))
+    }
+
+    fn get_missing_episodes(&self, media_id: IdType) -> Result<MissingEpisodes> {
+        let conn = &mut self.get_conn()?;
+        Self::find_media_id(conn, media_id)?;
+
+        Self::find_missing_episodes(conn, Some(&[media_id]), &today())?
+            .pop()
+            .ok_or_else(|| {
+                AppError::InvalidInput(format!("Media {media_id} is not a series")).into()
+            })
+    }
+
+    fn get_missing_episodes_report(&self) -> Result<Vec<MissingEpisodes>> {
+        let conn = &mut self.get_conn()?;
+        let report = Self::find_missing_episodes(conn, None, &today())?
+            .into_iter()
+            .filter(|series| series.checked && !series.seasons.is_empty())
+            .collect();
+
+        Ok(report)
+    }
+
+    fn skip_match_review(&self, media_id: IdType) -> Result<()> {
+        self.get_conn()?.transaction(|conn| {
+            Self::find_media_id(conn, media_id)?;
+            diesel::replace_into(skipped_reviews::table)
+                .values(&NewSkippedReview {
+                    media_id,
+                    skipped_at: unix_time(),
+                })
+                .execute(conn)?;
+            Ok(())
+        })
+    }
+}
+
+/// A database in a temporary folder, removed when the folder is dropped.
+#[cfg(test)]
+pub(crate) fn temp_db() -> (tempfile::TempDir, Sqlite) {
+    let temp_dir = tempfile::TempDir::new().expect("Failed to create temp dir");
+    let db = Sqlite::new_with_path(temp_dir.path().join("movies.db")).expect("Failed to open db");
+    (temp_dir, db)
+}
+
+/// Inserts the files at `paths` as one media, named after the first of them.
+#[cfg(test)]
+pub(crate) fn insert_paths(db: &Sqlite, paths: &[&str]) -> IdType {
+    let mut media = Media::from(PathBuf::from(paths[0]));
+    for path in &paths[1..] {
+        media.merge(&Media::from(PathBuf::from(path)));
+    }
+    db.insert_media(&media).unwrap()
+}
+
+#[cfg(test)]
+mod tests_library_roots {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn mkdir(temp_dir: &TempDir, name: &str) -> String {
+        let path = temp_dir.path().join(name);
+        std::fs::create_dir_all(&path).expect("Failed to create dir");
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn insert_and_list() {
+        let (temp_dir, db) = temp_db();
+        let movies = mkdir(&temp_dir, "movies");
+
+        let root = db.insert_library_root(&format!("{movies}/")).unwrap();
+
+        assert_eq!(root.path, movies);
+        assert!(root.enabled);
+        assert_eq!(db.get_library_roots().unwrap(), vec![root]);
+    }
+
+    #[test]
+    fn rejects_missing_directory() {
+        let (temp_dir, db) = temp_db();
+        let missing = temp_dir.path().join("missing");
+
+        let err = db
+            .insert_library_root(&missing.to_string_lossy())
+            .unwrap_err();
+
+        assert_eq!(AppError::from(err).code(), "notFound");
+        assert!(db.get_library_roots().unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_duplicate_and_nested_roots() {
+        let (temp_dir, db) = temp_db();
+        let movies = mkdir(&temp_dir, "movies");
+        let nested = mkdir(&temp_dir, "movies/action");
+
+        db.insert_library_root(&movies).unwrap();
+
+        assert!(db.insert_library_root(&movies).is_err());
+        assert!(db.insert_library_root(&nested).is_err());
+        assert_eq!(db.get_library_roots().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn parent_replaces_children() {
+        let (temp_dir, db) = temp_db();
+        let action = mkdir(&temp_dir, "movies/action");
+        let drama = mkdir(&temp_dir, "movies/drama");
+        let series = mkdir(&temp_dir, "series");
+        let movies = mkdir(&temp_dir, "movies");
+
+        db.insert_library_root(&action).unwrap();
+        db.insert_library_root(&drama).unwrap();
+        db.insert_library_root(&series).unwrap();
+        db.insert_library_root(&movies).unwrap();
+
+        let paths: Vec<_> = db
+            .get_library_roots()
+            .unwrap()
+            .into_iter()
+            .map(|r| r.path)
+            .collect();
+        assert_eq!(paths, vec![movies, series]);
+    }
+
+    #[test]
+    fn sibling_with_common_prefix_is_not_nested() {
+        let (temp_dir, db) = temp_db();
+        let movies = mkdir(&temp_dir, "movies");
+        let movies_old = mkdir(&temp_dir, "movies-old");
+
+        db.insert_library_root(&movies).unwrap();
+        db.insert_library_root(&movies_old).unwrap();
+
+        assert_eq!(db.get_library_roots().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn enable_disable_and_remove() {
+        let (temp_dir, db) = temp_db();
+        let movies = mkdir(&temp_dir, "movies");
+        let root = db.insert_library_root(&movies).unwrap();
+
+        db.update_library_root_enabled(root.id, false).unwrap();
+        assert!(!db.get_library_roots().unwrap()[0].enabled);
+
+        db.update_library_root_enabled(root.id, true).unwrap();
+        assert!(db.get_library_roots().unwrap()[0].enabled);
+
+        db.remove_library_root(root.id).unwrap();
+        assert!(db.get_library_roots().unwrap().is_empty());
+    }
+}
+
+#[cfg(test)]
+mod tests_settings {
+    use super::*;
+
+    #[test]
+    fn set_overwrites_and_get_reads_back() {
+        let (_temp_dir, db) = temp_db();
+
+        assert_eq!(db.get_setting("video_extensions").unwrap(), None);
+
+        db.set_setting("video_extensions", r#"["mkv"]"#).unwrap();
+        db.set_setting("video_extensions", r#"["mkv","mp4"]"#)
+            .unwrap();
+
+        assert_eq!(
+            db.get_setting("video_extensions").unwrap().as_deref(),
+            Some(r#"["mkv","mp4"]"#)
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests_sync_jobs {
+    use super::*;
+
+    fn movie(path: &str) -> Media {
+        Media {
+            name: "who am i".into(),
+            year: Some(2014),
+            files: vec![MediaFile::from(PathBuf::from(path))],
+            ..Media::default()
+        }
+    }
+
+    #[test]
+    fn chunks_commit_medias_with_progress() {
+        let (_temp_dir, db) = temp_db();
+        let pending = vec!["/m/a.mkv".to_string(), "/m/b.mkv".to_string()];
+
+        let job = db.insert_sync_job(&["/m".into()], &pending, 2).unwrap();
+        assert_eq!(job.status, SyncJobStatus::Running);
+        assert_eq!((job.total, job.inserted), (2, 0));
+        assert_eq!(job.pending, pending);
+
+        db.commit_sync_job_chunk(job.id, &[movie("/m/a.mkv")], &pending[1..])
+            .unwrap();
+        db.update_sync_job_status(job.id, SyncJobStatus::Paused, None)
+            .unwrap();
+
+        let job = db.get_sync_job(job.id).unwrap().unwrap();
+        assert_eq!(job.status, SyncJobStatus::Paused);
+        assert_eq!(job.inserted, 1);
+        assert_eq!(job.pending, vec!["/m/b.mkv"]);
+        assert_eq!(db.get_all_files().unwrap()[0].path, "/m/a.mkv");
+    }
+
+    #[test]
+    fn keeps_recent_and_unfinished_jobs() {
+        let (_temp_dir, db) = temp_db();
+
+        let paused = db.insert_sync_job(&[], &[], 0).unwrap();
+        db.update_sync_job_status(paused.id, SyncJobStatus::Paused, None)
+            .unwrap();
+        for _ in 0..KEPT_SYNC_JOBS + 5 {
+            let job = db.insert_sync_job(&[], &[], 0).unwrap();
+            db.update_sync_job_status(job.id, SyncJobStatus::Failed, Some("offline"))
+                .unwrap();
+        }
+
+        let jobs = db.get_sync_jobs().unwrap();
+        assert_eq!(jobs.len() as i64, KEPT_SYNC_JOBS + 1);
+        assert!(jobs.windows(2).all(|pair| pair[0].id > pair[1].id));
+        assert_eq!(jobs.last().unwrap().id, paused.id);
+        assert_eq!(jobs[0].error.as_deref(), Some("offline"));
+    }
+}
+
+#[cfg(test)]
+mod tests_media_errors {
+    use super::*;
+
+    #[test]
+    fn update_imdb_of_missing_media_is_not_found() {
+        let (_temp_dir, db) = temp_db();
+
+        let err = db.update_media_imdb(42, "tt0000042").unwrap_err();
+
+        assert_eq!(
+            AppError::from(err),
+            AppError::NotFound("Media 42 not found".into())
+        );
+    }
+
+    #[test]
+    fn delete_keeps_media_with_files_on_disk() {
+        let (temp_dir, db) = temp_db();
+        let path = temp_dir.path().join("Who.Am.I.2014.mkv");
+        std::fs::write(&path, b"who am i").expect("Failed to write");
+        let media_id = db
+            .insert_media(&Media {
+                name: "who am i".into(),
+                files: vec![MediaFile::from(path.clone())],
+                ..Media::default()
+            })
+            .unwrap();
+
+        let err = db.delete_media(media_id).unwrap_err();
+
+        assert_eq!(
+            AppError::from(err),
+            AppError::FileStillOnDisk(path.to_string_lossy().into())
+        );
+        assert!(db.get_media_by_id(media_id).unwrap().is_some());
+    }
+}
+
+#[cfg(test)]
+mod tests_file_moves {
+    use super::*;
+    use crate::media_scanner;
+    use crate::settings::VideoExtensions;
+    use std::fs;
+
+    fn insert_movie(db: &Sqlite, path: &Path) -> IdType {
+        let mut media = Media {
+            name: "who am i".into(),
+            year: Some(2014),
+            files: vec![MediaFile::from(path.to_path_buf())],
+            ..Media::default()
+        };
+        media.read_details(&VideoExtensions::default());
+        db.insert_media(&media).unwrap()
+    }
+
+    #[tokio::test]
+    async fn moved_file_keeps_media() {
+        let (temp_dir, db) = temp_db();
+        let old = temp_dir.path().join("Who.Am.I.2014.mkv");
+        fs::write(&old, b"who am i").expect("Failed to write");
+
+        let media_id = insert_movie(&db, &old);
+        db.update_media_watched(media_id, true).unwrap();
+        db.update_media_my_ranking(media_id, 4).unwrap();
+
+        let new = temp_dir.path().join("Who.Am.I.2014.1080p.mkv");
+        fs::rename(&old, &new).expect("Failed to rename");
+
+        let synced = media_scanner::sync_files(&db, &[temp_dir.path().into()], vec![new.clone()])
+            .await
+            .unwrap();
+        assert!(synced.unknown.is_empty());
+        assert_eq!(synced.orphans, OrphanReport::default());
+
+        let media = db.get_media_by_id(media_id).unwrap().unwrap();
+        assert!(media.watched);
+        assert_eq!(media.my_ranking, 4);
+        assert_eq!(media.files.len(), 1);
+        assert_eq!(media.files[0].path, new.to_string_lossy());
+        assert_eq!(media.files[0].quality, Some("1080p".into()));
+    }
+
+    #[tokio::test]
+    async fn deleted_file_is_removed_and_new_file_is_returned() {
+        let (temp_dir, db) = temp_db();
+        let old = temp_dir.path().join("Who.Am.I.2014.mkv");
+        fs::write(&old, b"who am i").expect("Failed to write");
+        insert_movie(&db, &old);
+
+        fs::remove_file(&old).expect("Failed to remove");
+        let new = temp_dir.path().join("Coco.2017.mkv");
+        fs::write(&new, b"coco").expect("Failed to write");
+
+        let synced = media_scanner::sync_files(&db, &[temp_dir.path().into()], vec![new.clone()])
+            .await
+            .unwrap();
+
+        assert_eq!(synced.unknown, vec![new]);
+        assert!(db.get_all_files().unwrap().is_empty());
+        assert_eq!(synced.orphans.medias.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn sync_keeps_files_of_other_and_unmounted_roots() {
+        let (temp_dir, db) = temp_db();
+        let (movies, unmounted) = (
+            temp_dir.path().join("movies"),
+            temp_dir.path().join("unmounted"),
+        );
+        fs::create_dir(&movies).expect("Failed to create dir");
+        let coco = movies.join("Coco.2017.mkv");
+        fs::write(&coco, b"coco").expect("Failed to write");
+        insert_movie(&db, &coco);
+        let media_id = insert_movie(&db, &unmounted.join("Who.Am.I.2014.mkv"));
+
+        for roots in [vec![movies.clone()], vec![movies, unmounted]] {
+            let synced = media_scanner::sync_files(&db, &roots, vec![])
+                .await
+                .unwrap();
+            assert_eq!(synced.orphans, OrphanReport::default());
+        }
+
+        assert_eq!(db.get_all_files().unwrap().len(), 2);
+        assert!(db.get_media_by_id(media_id).unwrap().is_some());
+    }
+
+    #[test]
+    fn probe_round_trips() {
+        let (_temp_dir, db) = temp_db();
+        let probe = MediaProbe {
+            duration_ms: Some(6_000_000),
+            video_codec: Some("hevc".into()),
+            width: Some(3840),
+            height: Some(1600),
+            bitrate: Some(12_000_000),
+            audio_languages: vec!["eng".into(), "fas".into()],
+            subtitle_languages: vec!["fas".into()],
+        };
+        let media = Media {
+            name: "who am i".into(),
+            files: vec![MediaFile {
+                probe: Some(probe.clone()),
+                ..MediaFile::from(PathBuf::from("/film/Who.Am.I.2014.mkv"))
+            }],
+            ..Media::default()
+        };
+        let media_id = db.insert_media(&media).unwrap();
+
+        let files = db.get_media_by_id(media_id).unwrap().unwrap().files;
+        assert_eq!(files[0].probe, Some(probe));
+
+        let file_id = files[0].id;
+        db.update_file_probe(file_id, &MediaProbe::default())
+            .unwrap();
+        assert_eq!(db.get_all_files().unwrap()[0].probe, None);
+    }
+
+    #[tokio::test]
+    async fn fingerprints_are_backfilled() {
+        let (temp_dir, db) = temp_db();
+        let path = temp_dir.path().join("Who.Am.I.2014.mkv");
+        fs::write(&path, b"who am i").expect("Failed to write");
+        insert_movie(&db, &path);
+
+        // A row saved before fingerprints and read times were recorded.
+        let conn = &mut db.get_conn().unwrap();
+        diesel::update(files::table)
+            .set((
+                files::fingerprint.eq(None::<String>),
+                files::details_read_at.eq(None::<i64>),
+            ))
+            .execute(conn)
+            .unwrap();
+
+        media_scanner::sync_files(&db, &[temp_dir.path().into()], vec![])
+            .await
+            .unwrap();
+
+        let files = db.get_all_files().unwrap();
+        assert_eq!(files[0].fingerprint, MediaFile::fingerprint(&path));
+        assert!(db.get_files_without_details().unwrap().is_empty());
+    }
+
+    #[test]
+    fn files_without_probe_are_read_once() {
+        let (temp_dir, db) = temp_db();
+        let path = temp_dir.path().join("Who.Am.I.2014.mkv");
+        fs::write(&path, b"who am i").expect("Failed to write");
+        insert_movie(&db, &path);
+
+        // Not a container the probe knows, but read already.
+        assert_eq!(db.get_all_files().unwrap()[0].probe, None);
+        assert!(db.get_files_without_details().unwrap().is_empty());
+    }
+
+    #[test]
+    fn missing_files_are_read_later() {
+        let (temp_dir, db) = temp_db();
+        insert_movie(&db, &temp_dir.path().join("Who.Am.I.2014.mkv"));
+
+        let files = db.get_files_without_details().unwrap();
+        assert_eq!(files.len(), 1);
+
+        db.mark_file_details_read(files[0].id, unix_time()).unwrap();
+        assert!(db.get_files_without_details().unwrap().is_empty());
+    }
+}
+
+#[cfg(test)]
+mod tests_subtitles {
+    use super::*;
+    use crate::media_scanner;
+    use crate::settings::VideoExtensions;
+    use std::fs;
+
+    fn insert_movie(db: &Sqlite, name: &str, path: &Path) -> IdType {
+        let mut media = Media {
+            name: name.into(),
+            files: vec![MediaFile::from(path.to_path_buf())],
+            ..Media::default()
+        };
+        media.read_details(&VideoExtensions::default());
+        db.insert_media(&media).unwrap()
+    }
+
+    fn filters(has_subtitles: Option<bool>, subtitle_language: &[&str]) -> FilterValues {
+        FilterValues {
+            name: String::new(),
+            r#type: ContentType::All,
+            min_rating: None,
+            country: vec![],
+            genre: vec![],
+            people: vec![],
+            exist_imdb: None,
+            exist_multi_file: None,
+            watched: None,
+            sort_by: SortByType::Name,
+            sort_direction: SortDirectionType::Asc,
+            watch_list: None,
+            tags: vec![],
+            has_subtitles,
+            subtitle_language: subtitle_language.iter().map(|l| l.to_string()).collect(),
+
+            min_runtime: None,
+            max_runtime: None,
+            rated: vec![],
+
+            complete: None,
+        }
+    }
+
+    fn names(medias: Vec<Media>) -> Vec<String> {
+        medias.into_iter().map(|m| m.name).collect()
+    }
+
+    #[test]
+    fn subtitles_are_stored_with_files() {
+        let (temp_dir, db) = temp_db();
+        let video = temp_dir.path().join("Coco.2017.mkv");
+        fs::write(&video, b"coco").expect("Failed to write");
+        fs::write(temp_dir.path().join("Coco.2017.fa.srt"), b"").expect("Failed to write");
+
+        let media_id = insert_movie(&db, "coco", &video);
+
+        let files = db.get_media_by_id(media_id).unwrap().unwrap().files;
+        assert_eq!(files[0].subtitles.len(), 1);
+        assert_eq!(files[0].subtitles[0].language.as_deref(), Some("fa"));
+        assert_eq!(db.get_subtitle_languages().unwrap(), vec!["fa"]);
+    }
+
+    #[test]
+    fn filters_by_subtitle_presence_and_language() {
+        let (temp_dir, db) = temp_db();
+        for dir in ["coco", "up", "soul"] {
+            fs::create_dir(temp_dir.path().join(dir)).expect("Failed to create dir");
+        }
+        let coco = temp_dir.path().join("coco").join("Coco.2017.mkv");
+        let up = temp_dir.path().join("up").join("Up.2009.mkv");
+        let soul = temp_dir.path().join("soul").join("Soul.2020.mkv");
+        for video in [&coco, &up, &soul] {
+            fs::write(video, b"video").expect("Failed to write");
+        }
+        fs::write(temp_dir.path().join("coco").join("Coco.2017.fa.srt"), b"")
+            .expect("Failed to write");
+        fs::write(temp_dir.path().join("coco").join("Coco.2017.en.srt"), b"")
+            .expect("Failed to write");
+        fs::create_dir(temp_dir.path().join("up").join("Subs")).expect("Failed to create dir");
+        fs::write(
+            temp_dir.path().join("up").join("Subs").join("English.srt"),
+            b"",
+        )
+        .expect("Failed to write");
+
+        insert_movie(&db, "coco", &coco);
+        insert_movie(&db, "up", &up);
+        insert_movie(&db, "soul", &soul);
+
+        let with = db.filter_medias(&filters(Some(true), &[]), 0).unwrap();
+        assert_eq!(names(with), vec!["coco", "up"]);
+
+        let without = db.filter_medias(&filters(Some(false), &[]), 0).unwrap();
+        assert_eq!(names(without), vec!["soul"]);
+
+        let english = db.filter_medias(&filters(None, &["en"]), 0).unwrap();
+        assert_eq!(names(english), vec!["coco", "up"]);
+
+        let both = db.filter_medias(&filters(None, &["en", "fa"]), 0).unwrap();
+        assert_eq!(names(both), vec!["coco"]);
+    }
+
+    #[tokio::test]
+    async fn sync_refreshes_subtitles() {
+        let (temp_dir, db) = temp_db();
+        let video = temp_dir.path().join("Coco.2017.mkv");
+        fs::write(&video, b"coco").expect("Failed to write");
+        let media_id = insert_movie(&db, "coco", &video);
+
+        let subtitle = temp_dir.path().join("Coco.2017.en.srt");
+        fs::write(&subtitle, b"").expect("Failed to write");
+        media_scanner::sync_files(&db, &[temp_dir.path().into()], vec![])
+            .await
+            .unwrap();
+
+        let files = db.get_media_by_id(media_id).unwrap().unwrap().files;
+        assert_eq!(files[0].subtitles[0].path, subtitle.to_string_lossy());
+
+        fs::remove_file(&subtitle).expect("Failed to remove");
+        media_scanner::sync_files(&db, &[temp_dir.path().into()], vec![])
+            .await
+            .unwrap();
+
+        let files = db.get_media_by_id(media_id).unwrap().unwrap().files;
+        assert!(files[0].subtitles.is_empty());
+    }
+
+    #[tokio::test]
+    async fn sync_leaves_subtitles_of_other_roots_alone() {
+        let (temp_dir, db) = temp_db();
+        let (movies, other) = (
+            temp_dir.path().join("movies"),
+            temp_dir.path().join("other"),
+        );
+        fs::create_dir(&movies).expect("Failed to create dir");
+        fs::create_dir(&other).expect("Failed to create dir");
+        let video = movies.join("Coco.2017.mkv");
+        fs::write(&video, b"coco").expect("Failed to write");
+        let media_id = insert_movie(&db, "coco", &video);
+
+        fs::write(movies.join("Coco.2017.en.srt"), b"").expect("Failed to write");
+        media_scanner::sync_files(&db, &[other], vec![])
+            .await
+            .unwrap();
+
+        let files = db.get_media_by_id(media_id).unwrap().unwrap().files;
+        assert!(files[0].subtitles.is_empty());
+    }
+
+    #[test]
+    fn removing_files_removes_their_subtitles() {
+        let (temp_dir, db) = temp_db();
+        let video = temp_dir.path().join("Coco.2017.mkv");
+        fs::write(&video, b"coco").expect("Failed to write");
+        fs::write(temp_dir.path().join("Coco.2017.fa.srt"), b"").expect("Failed to write");
+        insert_movie(&db, "coco", &video);
+
+        db.remove_file_by_path(std::slice::from_ref(&video))
+            .unwrap();
+
+        let conn = &mut db.get_conn().unwrap();
+        let count: i64 = subtitles::table.count().get_result(conn).unwrap();
+        assert_eq!(count, 0);
+    }
+}
+
+#[cfg(test)]
+mod tests_orphans {
+    use super::*;
+    use crate::{data_model::Imdb, media_scanner, metadata_extractor};
+
+    fn insert(db: &Sqlite, paths: &[&str]) -> Vec<IdType> {
+        let paths: Vec<PathBuf> = paths.iter().map(PathBuf::from).collect();
+        let mut medias = metadata_extractor::get_metadata(&paths);
+        medias.sort_by(|a, b| a.name.cmp(&b.name));
+
+        medias
+            .iter_mut()
+            .map(|media| {
+                media.imdb = Some(Imdb {
+                    imdb_id: format!("tt-{}", media.name),
+                    title: media.name.clone(),
+                    ..Imdb::default()
+                });
+                db.insert_media(media).unwrap()
+            })
+            .collect()
+    }
+
+    fn counts(db: &Sqlite) -> (i64, i64, i64, i64) {
+        let conn = &mut db.get_conn().unwrap();
+        (
+            medias::table.count().get_result(conn).unwrap(),
+            seasons::table.count().get_result(conn).unwrap(),
+            episodes::table.count().get_result(conn).unwrap(),
+            imdbs::table.count().get_result(conn).unwrap(),
+        )
+    }
+
+    const LOKI_E1: &str = "/marvel/loki/S1/Loki.S01E01.720p.mkv";
+    const LOKI_E2: &str = "/marvel/loki/S1/Loki.S01E02.720p.mkv";
+    const LOKI_S2_E1: &str = "/marvel/loki/S2/Loki.S02E01.720p.mkv";
+    const WHO_AM_I: &str = "/film/Who.Am.I.2014.720p.mp4";
+
+    #[test]
+    fn dry_run_reports_without_removing() {
+        let (_temp_dir, db) = temp_db();
+        let ids = insert(&db, &[LOKI_E1, LOKI_E2, LOKI_S2_E1, WHO_AM_I]);
+        db.remove_file_by_path(&[LOKI_S2_E1.into(), WHO_AM_I.into()])
+            .unwrap();
+
+        let report = db
+            .remove_orphans(&OrphanCleanup {
+                dry_run: true,
+                ..OrphanCleanup::default()
+            })
+            .unwrap();
+
+        assert_eq!(report.medias, vec![(ids[1], "who am i".to_string())]);
+        assert_eq!(report.seasons.len(), 1);
+        assert_eq!(report.episodes.len(), 1);
+        assert_eq!(report.imdbs, vec!["tt-who am i".to_string()]);
+        assert!(report.kept_medias.is_empty());
+        assert_eq!(counts(&db), (2, 2, 3, 2));
+    }
+
+    #[test]
+    fn removes_orphans() {
+        let (_temp_dir, db) = temp_db();
+        insert(&db, &[LOKI_E1, LOKI_E2, LOKI_S2_E1, WHO_AM_I]);
+        db.remove_file_by_path(&[LOKI_S2_E1.into(), WHO_AM_I.into()])
+            .unwrap();
+
+        let report = db.remove_orphans(&OrphanCleanup::default()).unwrap();
+
+        assert_eq!(report.medias.len(), 1);
+        assert_eq!(counts(&db), (1, 1, 2, 1));
+        assert_eq!(
+            db.remove_orphans(&OrphanCleanup::default()).unwrap(),
+            OrphanReport::default()
+        );
+    }
+
+    #[test]
+    fn keeps_medias_with_user_data_unless_confirmed() {
+        let (_temp_dir, db) = temp_db();
+        let ids = insert(&db, &[LOKI_E1, WHO_AM_I]);
+        db.update_media_watched(ids[0], true).unwrap();
+        db.update_media_my_ranking(ids[1], 3).unwrap();
+        db.remove_file_by_path(&[LOKI_E1.into(), WHO_AM_I.into()])
+            .unwrap();
+
+        let report = db.remove_orphans(&OrphanCleanup::default()).unwrap();
+
+        assert!(report.medias.is_empty());
+        assert!(report.seasons.is_empty());
+        assert!(report.episodes.is_empty());
+        assert!(report.imdbs.is_empty());
+        assert_eq!(report.kept_medias.len(), 2);
+        assert_eq!(counts(&db), (2, 1, 1, 2));
+
+        let report = db
+            .remove_orphans(&OrphanCleanup {
+                include_user_data: true,
+                ..OrphanCleanup::default()
+            })
+            .unwrap();
+
+        assert_eq!(report.medias.len(), 2);
+        assert_eq!(counts(&db), (0, 0, 0, 0));
+    }
+
+    #[test]
+    fn keeps_tagged_and_watch_listed_medias() {
+        let (_temp_dir, db) = temp_db();
+        let ids = insert(&db, &[LOKI_E1, WHO_AM_I]);
+        db.insert_tag(&Tag {
+            id: 0,
+            name: "favorite".into(),
+        })
+        .unwrap();
+        let tag_id = db.get_tags().unwrap()[0].id;
+        db.insert_media_tag(ids[0], tag_id).unwrap();
+        db.update_watch_list(ids[1], true).unwrap();
+        db.remove_file_by_path(&[LOKI_E1.into(), WHO_AM_I.into()])
+            .unwrap();
+
+        let report = db.remove_orphans(&OrphanCleanup::default()).unwrap();
+
+        assert!(report.medias.is_empty());
+        assert_eq!(report.kept_medias.len(), 2);
+    }
+
+    #[test]
+    fn missing_files_only_clean_up_their_own_medias() {
+        let (_temp_dir, db) = temp_db();
+        let ids = insert(&db, &[LOKI_E1, WHO_AM_I]);
+
+        // Added from IMDb by hand, so it never had files.
+        db.insert_media(&Media {
+            name: "coco".into(),
+            ..Media::default()
+        })
+        .unwrap();
+
+        let report = media_scanner::remove_missing_files(&db, &[LOKI_E1.into()]).unwrap();
+
+        assert_eq!(report.medias, vec![(ids[0], "loki".to_string())]);
+        assert_eq!(report.imdbs, vec!["tt-loki".to_string()]);
+        assert_eq!(counts(&db), (2, 0, 0, 1));
+    }
+}
+
+#[cfg(test)]
+mod tests_metadata_cache {
+    use super::*;
+
+    #[test]
+    fn replaces_and_clears_responses() {
+        let (_temp_dir, db) = temp_db();
+        assert_eq!(db.get_cached_response("search", "coco").unwrap(), None);
+
+        db.set_cached_response("search", "coco", "[]", 10).unwrap();
+        db.set_cached_response("search", "coco", "[{}]", 20)
+            .unwrap();
+        db.set_cached_response("title", "coco", "{}", 30).unwrap();
+
+        assert_eq!(
+            db.get_cached_response("search", "coco").unwrap(),
+            Some(("[{}]".to_string(), 20))
+        );
+        assert_eq!(db.clear_cached_responses().unwrap(), 2);
+        assert_eq!(db.get_cached_response("title", "coco").unwrap(), None);
+    }
+}
+
+#[cfg(test)]
+mod tests_imdb_refresh {
+    use super::*;
+
+    fn person(id: &str, name: &str) -> Person {
+        Person {
+            id: id.into(),
+            name: name.into(),
+            url: String::new(),
+        }
+    }
+
+    fn imdb(rating: &str, country: &str, actor: Person) -> Imdb {
+        Imdb {
+            imdb_id: "tt1".into(),
+            r#type: "movie".into(),
+            title: "Coco".into(),
+            year: 2017,
+            imdb_rating: rating.into(),
+            countries: vec![country.into()],
+            actors: vec![actor],
+            ..Imdb::default()
+        }
+    }
+
+    #[test]
+    fn insert_keeps_stored_details_and_upsert_replaces_them() {
+        let (_temp_dir, db) = temp_db();
+        db.insert_imdb(&imdb("8.0", "Mexico", person("nm1", "Anthony")))
+            .unwrap();
+        db.insert_imdb(&imdb("9.9", "Spain", person("nm2", "Gael")))
+            .unwrap();
+
+        let stored = db.get_imdb("tt1").unwrap().unwrap();
+        assert_eq!(stored.imdb_rating, "8.0");
+        assert_eq!(stored.countries, vec!["Mexico"]);
+
+        db.upsert_imdbs(&[imdb("8.4", "United States", person("nm2", "Gael"))], 5)
+            .unwrap();
+
+        let stored = db.get_imdb("tt1").unwrap().unwrap();
+        assert_eq!(stored.imdb_rating, "8.4");
+        assert_eq!(stored.countries, vec!["United States"]);
+        assert_eq!(stored.actors, vec![person("nm2", "Gael")]);
+    }
+
+    #[test]
+    fn lists_stale_titles_oldest_first() {
+        let (_temp_dir, db) = temp_db();
+        for (imdb_id, fetched_at) in [("tt1", 30), ("tt2", 10), ("tt3", 50)] {
+            let imdb = Imdb {
+                imdb_id: imdb_id.into(),
+                ..Imdb::default()
+            };
+            db.upsert_imdbs(&[imdb], fetched_at).unwrap();
+        }
+
+        assert_eq!(db.get_stale_imdb_ids(40).unwrap(), vec!["tt2", "tt1"]);
+        assert!(db.get_stale_imdb_ids(10).unwrap().is_empty());
+    }
+}
+
+#[cfg(test)]
+mod tests_imdb_episodes {
+    use super::*;
+    use tempfile::TempDir;
+
+    const SERIES: &str = "tt2085059";
+
+    fn setup() -> (TempDir, Sqlite, IdType) {
+        let (temp_dir, db) = temp_db();
+        db.insert_imdb(&Imdb {
+            imdb_id: SERIES.into(),
+            r#type: "tvSeries".into(),
+            title: "Black Mirror".into(),
+            ..Imdb::default()
+        })
+        .unwrap();
+
+        let media_id = insert_paths(
+            &db,
+            &["/s/black.mirror.s01e01.mkv", "/s/black.mirror.s02e01.mkv"],
+        );
+        db.update_media_imdb(media_id, SERIES).unwrap();
+        (temp_dir, db, media_id)
+    }
+
+    fn episode(season: i32, episode: i32, title: &str) -> ImdbEpisode {
+        ImdbEpisode {
+            imdb_id: format!("tt{season}{episode}"),
+            season,
+            episode,
+            title: title.into(),
+            air_date: "2011-12-04".into(),
+            runtime: 44,
+            ..ImdbEpisode::default()
+        }
+    }
+
+    fn titles(db: &Sqlite, media_id: IdType) -> Vec<Option<String>> {
+        db.get_media_by_id(media_id)
+            .unwrap()
+            .unwrap()
+            .seasons
+            .into_iter()
+            .flat_map(|season| season.episodes)
+            .map(|episode| episode.imdb.map(|imdb| imdb.title))
+            .collect()
+    }
+
+    #[test]
+    fn episodes_get_the_details_of_their_number() {
+        let (_temp_dir, db, media_id) = setup();
+        assert_eq!(titles(&db, media_id), vec![None, None]);
+
+        let listed = vec![
+            episode(1, 1, "The National Anthem"),
+            episode(1, 2, "Fifteen Million Merits"),
+            episode(2, 1, "Be Right Back"),
+        ];
+        db.set_imdb_episodes(SERIES, &listed).unwrap();
+
+        let conn = &mut db.get_conn().unwrap();
+        assert_eq!(Sqlite::get_imdb_episodes(conn, SERIES).unwrap(), listed);
+        assert_eq!(
+            titles(&db, media_id),
+            vec![
+                Some("The National Anthem".to_string()),
+                Some("Be Right Back".to_string())
+            ]
+        );
+
+        let media = db.get_media_by_id(media_id).unwrap().unwrap();
+        let details = media.seasons[0].episodes[0].imdb.clone().unwrap();
+        assert_eq!(details, listed[0]);
+    }
+
+    #[test]
+    fn refreshing_replaces_the_episode_list() {
+        let (_temp_dir, db, media_id) = setup();
+        db.set_imdb_episodes(SERIES, &[episode(1, 1, "Old"), episode(2, 1, "Old")])
+            .unwrap();
+        db.set_imdb_episodes(SERIES, &[episode(1, 1, "The National Anthem")])
+            .unwrap();
+
+        assert_eq!(
+            titles(&db, media_id),
+            vec![Some("The National Anthem".to_string()), None]
+        );
+    }
+
+    #[test]
+    fn episodes_go_with_their_series() {
+        let (_temp_dir, db, media_id) = setup();
+        db.set_imdb_episodes(SERIES, &[episode(1, 1, "The National Anthem")])
+            .unwrap();
+
+        db.delete_media(media_id).unwrap();
+        let conn = &mut db.get_conn().unwrap();
+        assert!(Sqlite::get_imdb_episodes(conn, SERIES).unwrap().is_empty());
+    }
+}
+
+#[cfg(test)]
+mod tests_missing_episodes {
+    use super::*;
+
+    /// A series with a file for each of `paths`, matched to `imdb_id`.
+    fn insert_series(db: &Sqlite, imdb_id: &str, paths: &[&str]) -> IdType {
+        let media_id = insert_paths(db, paths);
+        db.insert_imdb(&Imdb {
+            imdb_id: imdb_id.into(),
+            r#type: "tvSeries".into(),
+            ..Imdb::default()
+        })
+        .unwrap();
+        db.update_media_imdb(media_id, imdb_id).unwrap();
+        media_id
+    }
+
+    fn aired(season: i32, episode: i32) -> ImdbEpisode {
+        ImdbEpisode {
+            imdb_id: format!("tt{season}{episode}"),
+            season,
+            episode,
+            air_date: "2011-12-04".into(),
+            ..ImdbEpisode::default()
+        }
+    }
+
+    fn filters(complete: Option<bool>) -> FilterValues {
+        FilterValues {
+            name: String::new(),
+            r#type: ContentType::All,
+            min_rating: None,
+            country: vec![],
+            genre: vec![],
+            people: vec![],
+            exist_imdb: None,
+            exist_multi_file: None,
+            watched: None,
+            sort_by: SortByType::Name,
+            sort_direction: SortDirectionType::Asc,
+            watch_list: None,
+            tags: vec![],
+            has_subtitles: None,
+            subtitle_language: vec![],
+            min_runtime: None,
+            max_runtime: None,
+            rated: vec![],
+            complete,
+        }
+    }
+
+    fn names(medias: Vec<Media>) -> Vec<String> {
+        medias.into_iter().map(|m| m.name).collect()
+    }
+
+    /// Black Mirror misses S01E02, Fargo has every episode and Loki was never
+    /// checked.
+    fn library(db: &Sqlite) -> (IdType, IdType, IdType) {
+        let black_mirror = insert_series(
+            db,
+            "tt2085059",
+            &["/s/black.mirror.s01e01.mkv", "/s/black.mirror.s01e03.mkv"],
+        );
+        db.set_imdb_episodes("tt2085059", &[aired(1, 1), aired(1, 2), aired(1, 3)])
+            .unwrap();
+        let fargo = insert_series(db, "tt2802850", &["/s/fargo.s01e01.mkv"]);
+        db.set_imdb_episodes("tt2802850", &[aired(1, 1)]).unwrap();
+        let loki = insert_series(db, "tt9140554", &["/s/loki.s01e01.mkv"]);
+        db.insert_media(&Media::from(PathBuf::from("/m/coco.2017.mkv")))
+            .unwrap();
+
+        (black_mirror, fargo, loki)
+    }
+
+    #[test]
+    fn reports_the_gaps_of_a_series() {
+        let (_temp_dir, db) = temp_db();
+        let (black_mirror, fargo, loki) = library(&db);
+
+        let missing = db.get_missing_episodes(black_mirror).unwrap();
+        assert_eq!(missing.name, "black mirror");
+        assert_eq!(missing.seasons.len(), 1);
+        assert_eq!(missing.seasons[0].aired, 3);
+        assert_eq!(missing.seasons[0].missing, vec![aired(1, 2)]);
+
+        assert!(db.get_missing_episodes(fargo).unwrap().is_complete());
+        assert!(!db.get_missing_episodes(loki).unwrap().checked);
+    }
+
+    #[test]
+    fn rejects_movies_and_unknown_medias() {
+        let (_temp_dir, db) = temp_db();
+        let movie = db
+            .insert_media(&Media::from(PathBuf::from("/m/coco.2017.mkv")))
+            .unwrap();
+
+        let err = db.get_missing_episodes(movie).unwrap_err();
+        assert_eq!(AppError::from(err).code(), "invalidInput");
+
+        let err = db.get_missing_episodes(movie + 1).unwrap_err();
+        assert_eq!(AppError::from(err).code(), "notFound");
+    }
+
+    #[test]
+    fn report_lists_series_with_missing_episodes() {
+        let (_temp_dir, db) = temp_db();
+        let (black_mirror, _, _) = library(&db);
+
+        let report = db.get_missing_episodes_report().unwrap();
+        let ids: Vec<IdType> = report.iter().map(|series| series.media_id).collect();
+
+        // Loki has no episode list, so nothing is known to be missing.
+        assert_eq!(ids, vec![black_mirror]);
+    }
+
+    #[test]
+    fn filters_by_completeness() {
+        let (_temp_dir, db) = temp_db();
+        library(&db);
+
+        let complete = db.filter_medias(&filters(Some(true)), 0).unwrap();
+        assert_eq!(names(complete), vec!["fargo"]);
+
+        let incomplete = db.filter_medias(&filters(Some(false)), 0).unwrap();
+        assert_eq!(names(incomplete), vec!["black mirror"]);
+
+        assert_eq!(db.filter_medias(&filters(None), 0).unwrap().len(), 4);
+    }
+}
+
+#[cfg(test)]
+mod tests_imdb_details {
+    use super::*;
+
+    fn insert(db: &Sqlite, name: &str, imdb_id: &str, runtime: i32, rated: &str) {
+        db.insert_imdb(&Imdb {
+            imdb_id: imdb_id.into(),
+            r#type: "movie".into(),
+            title: name.into(),
+            runtime,
+            rated: rated.into(),
+            ..Imdb::default()
+        })
+        .unwrap();
+        let media_id = db
+            .insert_media(&Media::from(PathBuf::from(format!("/m/{name}.mkv"))))
+            .unwrap();
+        db.update_media_imdb(media_id, imdb_id).unwrap();
+    }
+
+    fn filters(min_runtime: Option<u32>, max_runtime: Option<u32>, rated: &[&str]) -> FilterValues {
+        FilterValues {
+            name: String::new(),
+            r#type: ContentType::All,
+            min_rating: None,
+            country: vec![],
+            genre: vec![],
+            people: vec![],
+            exist_imdb: None,
+            exist_multi_file: None,
+            watched: None,
+            sort_by: SortByType::Name,
+            sort_direction: SortDirectionType::Asc,
+            watch_list: None,
+            tags: vec![],
+            has_subtitles: None,
+            subtitle_language: vec![],
+            min_runtime,
+            max_runtime,
+            rated: rated.iter().map(|r| r.to_string()).collect(),
+
+            complete: None,
+        }
+    }
+
+    fn names(medias: Vec<Media>) -> Vec<String> {
+        medias.into_iter().map(|m| m.name).collect()
+    }
+
+    #[test]
+    fn details_round_trip() {
+        let (_temp_dir, db) = temp_db();
+        let imdb = Imdb {
+            imdb_id: "tt2085059".into(),
+            r#type: "tvSeries".into(),
+            title: "Black Mirror".into(),
+            rated: "TV-MA".into(),
+            runtime: 60,
+            awards: "1 win & 4 nominations".into(),
+            box_office: "$52,597,999".into(),
+            total_seasons: 7,
+            ..Imdb::default()
+        };
+        db.insert_imdb(&imdb).unwrap();
+        assert_eq!(db.get_imdb("tt2085059").unwrap().unwrap(), imdb);
+
+        let refreshed = Imdb {
+            rated: String::new(),
+            total_seasons: 8,
+            ..imdb
+        };
+        db.upsert_imdbs(std::slice::from_ref(&refreshed), 5)
+            .unwrap();
+        assert_eq!(db.get_imdb("tt2085059").unwrap().unwrap(), refreshed);
+
+        // Empty details are stored as NULL rather than blank text.
+        let conn = &mut db.get_conn().unwrap();
+        let rated: Option<String> = imdbs::table.select(imdbs::rated).first(conn).unwrap();
+        assert_eq!(rated, None);
+    }
+
+    #[test]
+    fn filters_by_runtime_and_certification() {
+        let (_temp_dir, db) = temp_db();
+        insert(&db, "coco", "tt1", 105, "PG");
+        insert(&db, "dune", "tt2", 155, "PG-13");
+        insert(&db, "heat", "tt3", 170, "R");
+        insert(&db, "unknown", "tt4", 0, "");
+
+        let long = db.filter_medias(&filters(Some(150), None, &[]), 0).unwrap();
+        assert_eq!(names(long), vec!["dune", "heat"]);
+
+        let short = db.filter_medias(&filters(None, Some(155), &[]), 0).unwrap();
+        assert_eq!(names(short), vec!["coco", "dune"]);
+
+        let rated = db
+            .filter_medias(&filters(None, None, &["PG", "R"]), 0)
+            .unwrap();
+        assert_eq!(names(rated), vec!["coco", "heat"]);
+
+        assert_eq!(db.get_certifications().unwrap(), vec!["PG", "PG-13", "R"]);
+    }
+
+    #[test]
+    fn sorts_by_runtime() {
+        let (_temp_dir, db) = temp_db();
+        insert(&db, "heat", "tt3", 170, "R");
+        insert(&db, "coco", "tt1", 105, "PG");
+        insert(&db, "dune", "tt2", 155, "PG-13");
+
+        let mut values = filters(None, None, &[]);
+        values.sort_by = SortByType::Runtime;
+        assert_eq!(
+            names(db.filter_medias(&values, 0).unwrap()),
+            vec!["coco", "dune", "heat"]
+        );
+
+        values.sort_direction = SortDirectionType::Desc;
+        assert_eq!(
+            names(db.filter_medias(&values, 0).unwrap()),
+            vec!["heat", "dune", "coco"]
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests_match_suggestions {
+    use super::*;
+
+    fn suggestion() -> MatchSuggestion {
+        MatchSuggestion {
+            imdb_id: "tt2172934".into(),
+            title: "3 Days to Kill".into(),
+            year: Some(2014),
+            confidence: 62,
+        }
+    }
+
+    #[test]
+    fn keeps_suggestion_until_matched() {
+        let (_temp_dir, db) = temp_db();
+        let media_id = db
+            .insert_media(&Media {
+                name: "3 days to kill".into(),
+                year: Some(1999),
+                suggestion: Some(suggestion()),
+                ..Media::default()
+            })
+            .unwrap();
+
+        let media = db.get_media_by_id(media_id).unwrap().unwrap();
+        assert_eq!(media.suggestion, Some(suggestion()));
+
+        db.insert_imdb(&Imdb {
+            imdb_id: "tt2172934".into(),
+            title: "3 Days to Kill".into(),
+            ..Imdb::default()
+        })
+        .unwrap();
+        let media_id = db.update_media_imdb(media_id, "tt2172934").unwrap();
+
+        let media = db.get_media_by_id(media_id).unwrap().unwrap();
+        assert!(media.imdb.is_some());
+        assert_eq!(media.suggestion, None);
+        let conn = &mut db.get_conn().unwrap();
+        assert_eq!(
+            match_suggestions::table
+                .count()
+                .get_result::<i64>(conn)
+                .unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn delete_removes_suggestion() {
+        let (_temp_dir, db) = temp_db();
+        let media_id = db
+            .insert_media(&Media {
+                name: "3 days to kill".into(),
+                suggestion: Some(suggestion()),
+                ..Media::default()
+            })
+            .unwrap();
+
+        db.delete_media(media_id).unwrap();
+
+        let conn = &mut db.get_conn().unwrap();
+        assert_eq!(
+            match_suggestions::table
+                .count()
+                .get_result::<i64>(conn)
+                .unwrap(),
+            0
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests_match_review {
+    use super::*;
+
+    fn insert(db: &Sqlite, name: &str, suggested: Option<(&str, u8)>) -> IdType {
+        db.insert_media(&Media {
+            name: name.into(),
+            suggestion: suggested.map(|(imdb_id, confidence)| MatchSuggestion {
+                imdb_id: imdb_id.into(),
+                title: name.into(),
+                year: None,
+                confidence,
+            }),
+            ..Media::default()
+        })
+        .unwrap()
+    }
+
+    fn imdb(imdb_id: &str) -> Imdb {
+        Imdb {
+            imdb_id: imdb_id.into(),
+            title: imdb_id.into(),
+            ..Imdb::default()
+        }
+    }
+
+    fn queue(db: &Sqlite) -> Vec<String> {
+        db.get_review_queue()
+            .unwrap()
+            .into_iter()
+            .map(|media| media.name)
+            .collect()
+    }
+
+    #[test]
+    fn lists_unmatched_medias_best_suggestion_first_and_skipped_last() {
+        let (_temp_dir, db) = temp_db();
+        let unsure = insert(&db, "unsure", Some(("tt1", 40)));
+        insert(&db, "likely", Some(("tt2", 75)));
+        insert(&db, "unknown", None);
+        db.insert_media(&Media {
+            name: "matched".into(),
+            imdb: Some(imdb("tt3")),
+            ..Media::default()
+        })
+        .unwrap();
+
+        assert_eq!(queue(&db), ["likely", "unsure", "unknown"]);
+
+        db.skip_match_review(unsure).unwrap();
+        assert_eq!(queue(&db), ["likely", "unknown", "unsure"]);
+    }
+
+    #[test]
+    fn accept_updates_media_in_place() {
+        let (_temp_dir, db) = temp_db();
+        let media_id = insert(&db, "coco", Some(("tt2380307", 60)));
+        db.insert_tag(&Tag {
+            id: 0,
+            name: "pixar".into(),
+        })
+        .unwrap();
+        let tag_id = db.get_tags().unwrap()[0].id;
+        db.insert_media_tag(media_id, tag_id).unwrap();
+        db.update_media_my_ranking(media_id, 4).unwrap();
+        db.insert_imdb(&imdb("tt2380307")).unwrap();
+
+        db.update_media_imdb(media_id, "tt2380307").unwrap();
+
+        let media = db.get_media_by_id(media_id).unwrap().unwrap();
+        assert_eq!(media.imdb.unwrap().imdb_id, "tt2380307");
+        assert_eq!(media.suggestion, None);
+        assert_eq!(media.tags.len(), 1);
+        assert_eq!(media.my_ranking, 4);
+        assert!(queue(&db).is_empty());
+    }
+
+    #[test]
+    fn accept_refuses_title_of_another_media() {
+        let (_temp_dir, db) = temp_db();
+        db.insert_media(&Media {
+            name: "coco".into(),
+            imdb: Some(imdb("tt2380307")),
+            ..Media::default()
+        })
+        .unwrap();
+        let media_id = insert(&db, "coco 2", None);
+
+        let err = db.update_media_imdb(media_id, "tt2380307").unwrap_err();
+        assert_eq!(AppError::from(err).code(), "alreadyMatched");
+
+        let err = db.update_media_imdb(999, "tt2380307").unwrap_err();
+        assert_eq!(AppError::from(err).code(), "notFound");
+    }
+
+    #[test]
+    fn reject_remembers_title_and_drops_its_suggestion() {
+        let (_temp_dir, db) = temp_db();
+        let media_id = insert(&db, "coco", Some(("tt1", 60)));
+
+        db.reject_match(media_id, "tt1").unwrap();
+        db.reject_match(media_id, "tt1").unwrap();
+
+        let media = db.get_media_by_id(media_id).unwrap().unwrap();
+        assert_eq!(media.suggestion, None);
+        let rejected = db.get_rejected_matches().unwrap();
+        assert!(rejected.contains(&media, "tt1"));
+        assert!(!rejected.contains(&media, "tt2"));
+        assert_eq!(queue(&db), ["coco"]);
+    }
+
+    #[test]
+    fn reject_detaches_a_wrong_match() {
+        let (_temp_dir, db) = temp_db();
+        let media_id = db
+            .insert_media(&Media {
+                name: "coco".into(),
+                imdb: Some(imdb("tt1")),
+                ..Media::default()
+            })
+            .unwrap();
+
+        db.reject_match(media_id, "tt1").unwrap();
+
+        let media = db.get_media_by_id(media_id).unwrap().unwrap();
+        assert!(media.imdb.is_none());
+        assert_eq!(queue(&db), ["coco"]);
+
+        // Deleting the media forgets its review.
+        db.delete_media(media_id).unwrap();
+        assert_eq!(
+            db.get_rejected_matches().unwrap(),
+            RejectedMatches::default()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests_merge_split {
+    use super::*;
+
+    fn media(db: &Sqlite, media_id: IdType) -> Media {
+        db.get_media_by_id(media_id).unwrap().unwrap()
+    }
+
+    fn file_ids(media: &Media) -> Vec<IdType> {
+        media
+            .files
+            .iter()
+            .chain(
+                media
+                    .seasons
+                    .iter()
+                    .flat_map(|season| &season.episodes)
+                    .flat_map(|episode| &episode.files),
+            )
+            .map(|file| file.id)
+            .collect()
+    }
+
+    fn tag(db: &Sqlite, media_id: IdType, name: &str) {
+        db.insert_tag(&Tag {
+            id: 0,
+            name: name.into(),
+        })
+        .unwrap();
+        let tag_id = db
+            .get_tags()
+            .unwrap()
+            .into_iter()
+            .find(|tag| tag.name == name)
+            .unwrap()
+            .id;
+        db.insert_media_tag(media_id, tag_id).unwrap();
+    }
+
+    #[test]
+    fn merge_moves_files_tags_and_user_data() {
+        let (_temp_dir, db) = temp_db();
+        let target = insert_paths(&db, &["/m/dune.mkv"]);
+        let source = insert_paths(&db, &["/m/dune.2021.mkv"]);
+        tag(&db, source, "sci-fi");
+        db.update_media_my_ranking(source, 5).unwrap();
+        db.update_media_watched(source, true).unwrap();
+        db.update_watch_list(source, true).unwrap();
+        db.insert_imdb(&Imdb {
+            imdb_id: "tt1160419".into(),
+            title: "Dune".into(),
+            ..Imdb::default()
+        })
+        .unwrap();
+        db.update_media_imdb(source, "tt1160419").unwrap();
+
+        db.merge_medias(target, &[source]).unwrap();
+
+        let merged = media(&db, target);
+        assert_eq!(merged.files.len(), 2);
+        assert_eq!(merged.tags.len(), 1);
+        assert_eq!(merged.my_ranking, 5);
+        assert!(merged.watched);
+        assert!(merged.watch_list);
+        assert_eq!(merged.imdb.unwrap().imdb_id, "tt1160419");
+        assert!(db.get_media_by_id(source).unwrap().is_none());
+    }
+
+    #[test]
+    fn merge_combines_episodes_and_rolls_up_watched() {
+        let (_temp_dir, db) = temp_db();
+        let target = insert_paths(&db, &["/s/black.mirror.s01e01.mkv"]);
+        let source = insert_paths(
+            &db,
+            &[
+                "/s/black.mirror.2011.s01e01.1080p.mkv",
+                "/s/black.mirror.2011.s01e02.mkv",
+            ],
+        );
+        let target_media = media(&db, target);
+        db.update_episode_watched(target_media.seasons[0].episodes[0].id, true)
+            .unwrap();
+        let source_media = media(&db, source);
+        let second = source_media.seasons[0]
+            .episodes
+            .iter()
+            .find(|episode| episode.number == 2)
+            .unwrap();
+        db.update_episode_watched(second.id, true).unwrap();
+
+        db.merge_medias(target, &[source]).unwrap();
+
+        let merged = media(&db, target);
+        assert_eq!(merged.seasons.len(), 1);
+        let mut episodes = merged.seasons[0].episodes.clone();
+        episodes.sort();
+        assert_eq!(episodes.len(), 2);
+        assert_eq!(episodes[0].files.len(), 2);
+        assert!(episodes.iter().all(|episode| episode.watched));
+        assert!(merged.seasons[0].watched);
+        assert!(merged.watched);
+    }
+
+    #[test]
+    fn split_moves_files_into_a_new_media() {
+        let (_temp_dir, db) = temp_db();
+        // Grouped as one film since the first file has no year.
+        let media_id = insert_paths(&db, &["/m/dune.mkv", "/m/dune.2021.mkv"]);
+        assert_eq!(media(&db, media_id).year, Some(2021));
+        tag(&db, media_id, "sci-fi");
+        db.update_media_my_ranking(media_id, 3).unwrap();
+        let original = media(&db, media_id);
+        let moved = original
+            .files
+            .iter()
+            .find(|file| file.path.ends_with("/dune.mkv"))
+            .unwrap()
+            .id;
+
+        let new_id = db.split_media(media_id, &[moved]).unwrap();
+
+        let split = media(&db, new_id);
+        assert_eq!(split.name, "dune");
+        assert_eq!(split.year, None);
+        assert_eq!(file_ids(&split), [moved]);
+        assert_eq!(split.tags.len(), 1);
+        assert_eq!(split.my_ranking, 3);
+        assert_eq!(media(&db, media_id).files.len(), 1);
+    }
+
+    #[test]
+    fn split_moves_episode_files_and_drops_emptied_episodes() {
+        let (_temp_dir, db) = temp_db();
+        let media_id = insert_paths(
+            &db,
+            &["/s/black.mirror.s01e01.mkv", "/s/black.mirror.s02e01.mkv"],
+        );
+        let original = media(&db, media_id);
+        let season_2 = original.seasons.iter().find(|s| s.number == 2).unwrap();
+        let moved = season_2.episodes[0].files[0].id;
+        db.update_season_watched(season_2.id, true).unwrap();
+
+        let new_id = db.split_media(media_id, &[moved]).unwrap();
+
+        let split = media(&db, new_id);
+        assert_eq!(split.name, "black mirror (2)");
+        assert_eq!(split.seasons.len(), 1);
+        assert_eq!(split.seasons[0].number, 2);
+        assert!(split.seasons[0].episodes[0].watched);
+        assert!(split.watched);
+
+        let rest = media(&db, media_id);
+        assert_eq!(rest.seasons.len(), 1);
+        assert_eq!(rest.seasons[0].number, 1);
+        assert!(!rest.watched);
+    }
+
+    #[test]
+    fn rejects_invalid_merges_and_splits() {
+        let (_temp_dir, db) = temp_db();
+        let dune = insert_paths(&db, &["/m/dune.mkv", "/m/dune.2021.mkv"]);
+        let coco = insert_paths(&db, &["/m/coco.mkv"]);
+        let code = |err: anyhow::Error| AppError::from(err).code();
+
+        assert_eq!(
+            code(db.merge_medias(dune, &[dune]).unwrap_err()),
+            "invalidInput"
+        );
+        assert_eq!(
+            code(db.merge_medias(dune, &[]).unwrap_err()),
+            "invalidInput"
+        );
+        assert_eq!(code(db.merge_medias(dune, &[999]).unwrap_err()), "notFound");
+
+        let dune_files = file_ids(&media(&db, dune));
+        let coco_files = file_ids(&media(&db, coco));
+        assert_eq!(
+            code(db.split_media(dune, &dune_files).unwrap_err()),
+            "invalidInput"
+        );
+        assert_eq!(
+            code(db.split_media(dune, &coco_files).unwrap_err()),
+            "invalidInput"
+        );
+        assert_eq!(
+            code(db.split_media(999, &coco_files).unwrap_err()),
+            "notFound"
+        );
+
+        // Nothing was changed by the failed attempts.
+        assert_eq!(media(&db, dune).files.len(), 2);
+        assert_eq!(media(&db, coco).files.len(), 1);
+    }
+}
+
+#[cfg(test)]
+mod tests_update_media_imdb {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn setup() -> (TempDir, Sqlite) {
+        let (temp_dir, db) = temp_db();
+        for imdb_id in ["tt0087182", "tt1160419"] {
+            db.insert_imdb(&Imdb {
+                imdb_id: imdb_id.into(),
+                title: "Dune".into(),
+                ..Imdb::default()
+            })
+            .unwrap();
+        }
+        (temp_dir, db)
+    }
+
+    fn media(db: &Sqlite, media_id: IdType) -> Media {
+        db.get_media_by_id(media_id).unwrap().unwrap()
+    }
+
+    #[test]
+    fn keeps_id_tags_and_user_data() {
+        let (_temp_dir, db) = setup();
+        let media_id = insert_paths(&db, &["/m/dune.mkv"]);
+        db.insert_tag(&Tag {
+            id: 0,
+            name: "sci-fi".into(),
+        })
+        .unwrap();
+        db.insert_media_tag(media_id, db.get_tags().unwrap()[0].id)
+            .unwrap();
+        db.update_media_my_ranking(media_id, 4).unwrap();
+        db.update_watch_list(media_id, true).unwrap();
+        db.update_media_watched(media_id, true).unwrap();
+
+        assert_eq!(
+            db.update_media_imdb(media_id, "tt0087182").unwrap(),
+            media_id
+        );
+        // Re-matching a matched media replaces its title too.
+        assert_eq!(
+            db.update_media_imdb(media_id, "tt1160419").unwrap(),
+            media_id
+        );
+
+        // The previous title is no longer matched to anything.
+        assert!(db.get_imdb("tt0087182").unwrap().is_none());
+
+        let updated = media(&db, media_id);
+        assert_eq!(updated.imdb.unwrap().imdb_id, "tt1160419");
+        assert_eq!(updated.tags.len(), 1);
+        assert_eq!(updated.my_ranking, 4);
+        assert!(updated.watch_list);
+        assert!(updated.watched);
+        assert_eq!(updated.files.len(), 1);
+    }
+
+    #[test]
+    fn title_of_another_media_is_refused_with_its_owner() {
+        let (_temp_dir, db) = setup();
+        let owner = insert_paths(&db, &["/m/dune.2021.mkv"]);
+        db.update_media_imdb(owner, "tt1160419").unwrap();
+        let media_id = insert_paths(&db, &["/m/dune.part.one.mkv"]);
+
+        let err = db.update_media_imdb(media_id, "tt1160419").unwrap_err();
+
+        assert_eq!(
+            AppError::from(err),
+            AppError::AlreadyMatched {
+                imdb_id: "tt1160419".into(),
+                media_id: owner,
+                name: "dune".into(),
+            }
+        );
+        // Nothing moved: both medias keep their own file.
+        assert!(media(&db, media_id).imdb.is_none());
+        assert_eq!(media(&db, media_id).files.len(), 1);
+        assert_eq!(media(&db, owner).files.len(), 1);
+
+        // The offered merge brings the files together.
+        db.merge_medias(owner, &[media_id]).unwrap();
+        assert_eq!(media(&db, owner).files.len(), 2);
+    }
+}
+
+#[cfg(test)]
+mod tests_multi_episode_files {
+    use super::*;
+
+    fn media(db: &Sqlite, media_id: IdType) -> Media {
+        db.get_media_by_id(media_id).unwrap().unwrap()
+    }
+
+    /// Each episode number with the paths of its files and whether it is watched.
+    fn episodes(media: &Media) -> Vec<(i32, Vec<&str>, bool)> {
+        media.seasons[0]
+            .episodes
+            .iter()
+            .map(|episode| {
+                let paths = episode.files.iter().map(|f| f.path.as_str()).collect();
+                (episode.number, paths, episode.watched)
+            })
+            .collect()
+    }
+
+    fn file_id(media: &Media, path: &str) -> IdType {
+        media.seasons[0]
+            .episodes
+            .iter()
+            .flat_map(|episode| &episode.files)
+            .find(|file| file.path == path)
+            .unwrap()
+            .id
+    }
+
+    #[test]
+    fn file_is_stored_once_and_listed_under_each_episode() {
+        let (_temp_dir, db) = temp_db();
+        let media_id = insert_paths(&db, &["/s/show.s01e01-e02.mkv", "/s/show.s01e03.mkv"]);
+
+        let media = media(&db, media_id);
+        assert_eq!(
+            episodes(&media),
+            vec![
+                (1, vec!["/s/show.s01e01-e02.mkv"], false),
+                (2, vec!["/s/show.s01e01-e02.mkv"], false),
+                (3, vec!["/s/show.s01e03.mkv"], false),
+            ]
+        );
+        assert_eq!(db.get_all_files().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn watching_a_file_watches_every_episode_it_holds() {
+        let (_temp_dir, db) = temp_db();
+        let media_id = insert_paths(&db, &["/s/show.s01e01-e02.mkv", "/s/show.s01e03.mkv"]);
+        let file = file_id(&media(&db, media_id), "/s/show.s01e01-e02.mkv");
+
+        db.update_file_watched(file, true).unwrap();
+        let watched = media(&db, media_id);
+        assert_eq!(
+            episodes(&watched)
+                .iter()
+                .map(|(number, _, watched)| (*number, *watched))
+                .collect::<Vec<_>>(),
+            vec![(1, true), (2, true), (3, false)]
+        );
+        assert!(!watched.seasons[0].watched);
+        assert!(!watched.watched);
+
+        // The last episode completes the season and the series.
+        db.update_episode_watched(watched.seasons[0].episodes[2].id, true)
+            .unwrap();
+        let watched = media(&db, media_id);
+        assert!(watched.seasons[0].watched);
+        assert!(watched.watched);
+
+        db.update_file_watched(file, false).unwrap();
+        let unwatched = media(&db, media_id);
+        assert!(!unwatched.seasons[0].episodes[1].watched);
+        assert!(!unwatched.seasons[0].watched);
+        assert!(!unwatched.watched);
+    }
+
+    #[test]
+    fn watching_a_movie_file_watches_the_movie() {
+        let (_temp_dir, db) = temp_db();
+        let media_id = insert_paths(&db, &["/m/coco.2017.mkv"]);
+        let file = media(&db, media_id).files[0].id;
+
+        db.update_file_watched(file, true).unwrap();
+        assert!(media(&db, media_id).watched);
+
+        let err = db.update_file_watched(9999, true).unwrap_err();
+        assert_eq!(AppError::from(err).code(), "notFound");
+    }
+
+    #[test]
+    fn covered_episodes_count_as_on_disk() {
+        let (_temp_dir, db) = temp_db();
+        let media_id = insert_paths(&db, &["/s/show.s01e01-e02.mkv"]);
+        db.insert_imdb(&Imdb {
+            imdb_id: "tt0000001".into(),
+            r#type: "tvSeries".into(),
+            ..Imdb::default()
+        })
+        .unwrap();
+        db.update_media_imdb(media_id, "tt0000001").unwrap();
+        let aired = |episode| ImdbEpisode {
+            imdb_id: format!("tt10{episode}"),
+            season: 1,
+            episode,
+            air_date: "2011-12-04".into(),
+            ..ImdbEpisode::default()
+        };
+        db.set_imdb_episodes("tt0000001", &[aired(1), aired(2), aired(3)])
+            .unwrap();
+
+        let report = db.get_missing_episodes(media_id).unwrap();
+        let missing: Vec<i32> = report
+            .seasons
+            .iter()
+            .flat_map(|season| &season.missing)
+            .map(|episode| episode.episode)
+            .collect();
+        assert_eq!(missing, vec![3]);
+    }
+
+    #[test]
+    fn removing_the_file_empties_every_episode() {
+        let (_temp_dir, db) = temp_db();
+        let media_id = insert_paths(&db, &["/s/show.s01e01-e02.mkv", "/s/show.s01e03.mkv"]);
+
+        db.remove_file_by_path(&[PathBuf::from("/s/show.s01e01-e02.mkv")])
+            .unwrap();
+        let report = db
+            .remove_orphans(&OrphanCleanup {
+                media_ids: Some(vec![media_id]),
+                ..OrphanCleanup::default()
+            })
+            .unwrap();
+
+        assert_eq!(report.episodes.len(), 2);
+        assert_eq!(
+            media(&db, media_id).seasons[0]
+                .episodes
+                .iter()
+                .map(|episode| episode.number)
+                .collect::<Vec<_>>(),
+            vec![3]
+        );
+    }
+
+    #[test]
+    fn split_moves_every_episode_of_the_file() {
+        let (_temp_dir, db) = temp_db();
+        let media_id = insert_paths(&db, &["/s/show.s01e01-e02.mkv", "/s/show.s01e03.mkv"]);
+        let file = file_id(&media(&db, media_id), "/s/show.s01e01-e02.mkv");
+
+        let new_id = db.split_media(media_id, &[file]).unwrap();
+
+        assert_eq!(
+            episodes(&media(&db, new_id)),
+            vec![
+                (1, vec!["/s/show.s01e01-e02.mkv"], false),
+                (2, vec!["/s/show.s01e01-e02.mkv"], false),
+            ]
+        );
+        assert_eq!(
+            episodes(&media(&db, media_id)),
+            vec![(3, vec!["/s/show.s01e03.mkv"], false)]
+        );
+
+        db.merge_medias(media_id, &[new_id]).unwrap();
+        assert_eq!(episodes(&media(&db, media_id)).len(), 3);
+        assert_eq!(db.get_all_files().unwrap().len(), 2);
+    }
 }
@@ -1,10 +1,13 @@
 use super::schema::{
-    countries, episodes, files, genres, imdb_countries, imdb_genres, imdb_people, imdbs,
-    media_tags, medias, people, seasons, tags,
+    countries, episodes, file_episodes, files, genres, imdb_countries, imdb_episodes, imdb_genres,
+    imdb_people, imdbs, library_roots, match_suggestions, media_tags, medias, metadata_cache,
+    people, rejected_matches, seasons, settings, skipped_reviews, subtitles, sync_jobs, tags,
 };
 use crate::data_model::{
-    Episode, IdType, Imdb, LanguageFormat, Media, MediaFile, Person, Season, Tag,
+    Episode, IdType, Imdb, ImdbEpisode, LanguageFormat, LibraryRoot, MatchSuggestion, Media,
+    MediaFile, Person, Season, Subtitle, SyncJob, Tag,
 };
+use crate::media_probe::MediaProbe;
 use diesel::{Identifiable, Insertable, Queryable};
 
 #[derive(Debug, Clone, Queryable, Identifiable, serde::Serialize)]
@@ -23,6 +26,7 @@ impl From<DbEpisode> for Episode {
             number: db.episode_number,
             watched: db.watched,
             files: vec![],
+            imdb: None,
         }
     }
 }
@@ -72,6 +76,7 @@ impl From<DbMedia> for Media {
             files: vec![],
             imdb: None,
             tags: vec![],
+            suggestion: None,
         }
     }
 }
@@ -94,6 +99,8 @@ pub struct DbImdb {
     pub total_seasons: Option<String>,
     #[diesel(column_name = "type")]
     pub type_: String,
+    pub fetched_at: i64,
+    pub episodes_fetched_at: Option<i64>,
 }
 
 impl From<DbImdb> for Imdb {
@@ -106,12 +113,51 @@ impl From<DbImdb> for Imdb {
             poster: db.poster.unwrap_or_default(),
             imdb_rating: db.imdb_rating.unwrap_or_default(),
             imdb_votes: db.imdb_votes,
+            rated: db.rated.unwrap_or_default(),
+            runtime: parse_count(db.runtime),
+            awards: db.awards.unwrap_or_default(),
+            box_office: db.box_office.unwrap_or_default(),
+            total_seasons: parse_count(db.total_seasons),
             r#type: db.type_,
             ..Self::default()
         }
     }
 }
 
+#[derive(Debug, Clone, Queryable, serde::Serialize)]
+#[diesel(table_name = imdb_episodes)]
+pub struct DbImdbEpisode {
+    pub series_imdb_id: String,
+    pub season_number: i32,
+    pub episode_number: i32,
+    pub imdb_id: String,
+    pub title: String,
+    pub air_date: Option<String>,
+    pub runtime: Option<i32>,
+    pub plot: Option<String>,
+    pub rating: Option<String>,
+}
+
+impl From<DbImdbEpisode> for ImdbEpisode {
+    fn from(db: DbImdbEpisode) -> Self {
+        Self {
+            imdb_id: db.imdb_id,
+            season: db.season_number,
+            episode: db.episode_number,
+            title: db.title,
+            air_date: db.air_date.unwrap_or_default(),
+            runtime: db.runtime.unwrap_or_default(),
+            plot: db.plot.unwrap_or_default(),
+            rating: db.rating.unwrap_or_default(),
+        }
+    }
+}
+
+/// Runtime and season counts are stored as text, and read as 0 when missing.
+fn parse_count(value: Option<String>) -> i32 {
+    value.and_then(|v| v.parse().ok()).unwrap_or_default()
+}
+
 #[derive(Debug, Clone, serde::Serialize, Queryable)]
 #[diesel(table_name = files)]
 pub struct DbFile {
@@ -122,16 +168,68 @@ pub struct DbFile {
     pub path: String,
     pub quality: Option<String>,
     pub language_format: LanguageFormat,
+    pub fingerprint: Option<String>,
+    pub duration_ms: Option<i64>,
+    pub video_codec: Option<String>,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub bitrate: Option<i64>,
+    pub audio_languages: Option<String>,
+    pub subtitle_languages: Option<String>,
+    pub details_read_at: Option<i64>,
+}
+
+/// Track languages are stored as a comma separated list.
+pub fn join_languages(languages: &[String]) -> Option<String> {
+    (!languages.is_empty()).then(|| languages.join(","))
+}
+
+fn split_languages(languages: Option<String>) -> Vec<String> {
+    languages
+        .map(|l| l.split(',').map(str::to_string).collect())
+        .unwrap_or_default()
 }
 
 impl From<DbFile> for MediaFile {
     fn from(db: DbFile) -> Self {
+        let probe = MediaProbe {
+            duration_ms: db.duration_ms,
+            video_codec: db.video_codec,
+            width: db.width,
+            height: db.height,
+            bitrate: db.bitrate,
+            audio_languages: split_languages(db.audio_languages),
+            subtitle_languages: split_languages(db.subtitle_languages),
+        };
+
         Self {
             id: db.id,
             file_name: db.file_name,
             path: db.path,
             quality: db.quality,
             language_format: db.language_format,
+            fingerprint: db.fingerprint,
+            probe: (probe != MediaProbe::default()).then_some(probe),
+            subtitles: vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Queryable, Identifiable, serde::Serialize)]
+#[diesel(table_name = subtitles)]
+pub struct DbSubtitle {
+    pub id: IdType,
+    pub file_id: IdType,
+    pub path: String,
+    pub language: Option<String>,
+}
+
+impl From<DbSubtitle> for Subtitle {
+    fn from(db: DbSubtitle) -> Self {
+        Self {
+            id: db.id,
+            path: db.path,
+            language: db.language,
         }
     }
 }
@@ -152,6 +250,52 @@ impl From<DbTag> for Tag {
     }
 }
 
+#[derive(Debug, Clone, Queryable, Identifiable, serde::Serialize)]
+#[diesel(table_name = library_roots)]
+pub struct DbLibraryRoot {
+    pub id: IdType,
+    pub path: String,
+    pub enabled: bool,
+}
+
+impl From<DbLibraryRoot> for LibraryRoot {
+    fn from(db: DbLibraryRoot) -> Self {
+        Self {
+            id: db.id,
+            path: db.path,
+            enabled: db.enabled,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Queryable, Identifiable)]
+#[diesel(table_name = sync_jobs)]
+pub struct DbSyncJob {
+    pub id: IdType,
+    pub roots: String,
+    pub status: String,
+    pub total: i32,
+    pub inserted: i32,
+    pub pending: String,
+    pub error: Option<String>,
+}
+
+impl TryFrom<DbSyncJob> for SyncJob {
+    type Error = anyhow::Error;
+
+    fn try_from(db: DbSyncJob) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: db.id,
+            roots: serde_json::from_str(&db.roots)?,
+            status: db.status.parse()?,
+            total: db.total as usize,
+            inserted: db.inserted as usize,
+            pending: serde_json::from_str(&db.pending)?,
+            error: db.error,
+        })
+    }
+}
+
 #[derive(Debug, Clone, serde::Serialize, Queryable)]
 #[diesel(table_name = files)]
 pub struct DbPerson {
@@ -212,6 +356,44 @@ pub struct NewFile<'a> {
     pub path: &'a str,
     pub quality: Option<&'a str>,
     pub language_format: LanguageFormat,
+    pub fingerprint: Option<&'a str>,
+    pub duration_ms: Option<i64>,
+    pub video_codec: Option<&'a str>,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub bitrate: Option<i64>,
+    pub audio_languages: Option<String>,
+    pub subtitle_languages: Option<String>,
+    pub details_read_at: Option<i64>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = file_episodes)]
+pub struct NewFileEpisode {
+    pub file_id: IdType,
+    pub episode_id: IdType,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = subtitles)]
+pub struct NewSubtitle<'a> {
+    pub file_id: IdType,
+    pub path: &'a str,
+    pub language: Option<&'a str>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = imdb_episodes)]
+pub struct NewImdbEpisode<'a> {
+    pub series_imdb_id: &'a str,
+    pub season_number: i32,
+    pub episode_number: i32,
+    pub imdb_id: &'a str,
+    pub title: &'a str,
+    pub air_date: Option<&'a str>,
+    pub runtime: Option<i32>,
+    pub plot: Option<&'a str>,
+    pub rating: Option<&'a str>,
 }
 
 #[derive(Insertable)]
@@ -224,8 +406,14 @@ pub struct NewImdb<'a> {
     pub poster: Option<&'a str>,
     pub imdb_rating: Option<&'a str>,
     pub imdb_votes: i32,
+    pub rated: Option<&'a str>,
+    pub runtime: Option<String>,
+    pub awards: Option<&'a str>,
+    pub box_office: Option<&'a str>,
+    pub total_seasons: Option<String>,
     // #[diesel(column_name = "type")]
     pub type_: &'a str,
+    pub fetched_at: i64,
 }
 
 #[derive(Insertable)]
@@ -276,9 +464,88 @@ pub struct NewTag<'a> {
     pub name: &'a str,
 }
 
+#[derive(Debug, Clone, Queryable, Insertable)]
+#[diesel(table_name = match_suggestions)]
+pub struct DbMatchSuggestion {
+    pub media_id: IdType,
+    pub imdb_id: String,
+    pub title: String,
+    pub year: Option<i32>,
+    pub confidence: i32,
+}
+
+impl DbMatchSuggestion {
+    pub fn new(media_id: IdType, suggestion: &MatchSuggestion) -> Self {
+        Self {
+            media_id,
+            imdb_id: suggestion.imdb_id.clone(),
+            title: suggestion.title.clone(),
+            year: suggestion.year,
+            confidence: i32::from(suggestion.confidence),
+        }
+    }
+}
+
+impl From<DbMatchSuggestion> for MatchSuggestion {
+    fn from(db: DbMatchSuggestion) -> Self {
+        Self {
+            imdb_id: db.imdb_id,
+            title: db.title,
+            year: db.year,
+            confidence: db.confidence.clamp(0, 100) as u8,
+        }
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = rejected_matches)]
+pub struct NewRejectedMatch<'a> {
+    pub media_id: IdType,
+    pub imdb_id: &'a str,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = skipped_reviews)]
+pub struct NewSkippedReview {
+    pub media_id: IdType,
+    pub skipped_at: i64,
+}
+
 #[derive(Insertable)]
 #[diesel(table_name = media_tags)]
 pub struct NewMediaTag {
     pub media_id: IdType,
     pub tag_id: IdType,
 }
+
+#[derive(Insertable)]
+#[diesel(table_name = library_roots)]
+pub struct NewLibraryRoot<'a> {
+    pub path: &'a str,
+    pub enabled: bool,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = settings)]
+pub struct NewSetting<'a> {
+    pub key: &'a str,
+    pub value: &'a str,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = metadata_cache)]
+pub struct NewCachedResponse<'a> {
+    pub kind: &'a str,
+    pub key: &'a str,
+    pub response: &'a str,
+    pub fetched_at: i64,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = sync_jobs)]
+pub struct NewSyncJob<'a> {
+    pub roots: &'a str,
+    pub status: &'a str,
+    pub total: i32,
+    pub pending: &'a str,
+}
@@ -16,6 +16,13 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    file_episodes (file_id, episode_id) {
+        file_id -> Integer,
+        episode_id -> Integer,
+    }
+}
+
 diesel::table! {
     files (id) {
         id -> Integer,
@@ -25,6 +32,15 @@ diesel::table! {
         path -> Text,
         quality -> Nullable<Text>,
         language_format -> Text,
+        fingerprint -> Nullable<Text>,
+        duration_ms -> Nullable<BigInt>,
+        video_codec -> Nullable<Text>,
+        width -> Nullable<Integer>,
+        height -> Nullable<Integer>,
+        bitrate -> Nullable<BigInt>,
+        audio_languages -> Nullable<Text>,
+        subtitle_languages -> Nullable<Text>,
+        details_read_at -> Nullable<BigInt>,
     }
 }
 
@@ -42,6 +58,20 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    imdb_episodes (series_imdb_id, season_number, episode_number) {
+        series_imdb_id -> Text,
+        season_number -> Integer,
+        episode_number -> Integer,
+        imdb_id -> Text,
+        title -> Text,
+        air_date -> Nullable<Text>,
+        runtime -> Nullable<Integer>,
+        plot -> Nullable<Text>,
+        rating -> Nullable<Text>,
+    }
+}
+
 diesel::table! {
     imdb_genres (imdb_id, genre_id) {
         imdb_id -> Text,
@@ -73,6 +103,26 @@ diesel::table! {
         total_seasons -> Nullable<Text>,
         #[sql_name = "type"]
         type_ -> Text,
+        fetched_at -> BigInt,
+        episodes_fetched_at -> Nullable<BigInt>,
+    }
+}
+
+diesel::table! {
+    library_roots (id) {
+        id -> Integer,
+        path -> Text,
+        enabled -> Bool,
+    }
+}
+
+diesel::table! {
+    match_suggestions (media_id) {
+        media_id -> Integer,
+        imdb_id -> Text,
+        title -> Text,
+        year -> Nullable<Integer>,
+        confidence -> Integer,
     }
 }
 
@@ -95,6 +145,15 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    metadata_cache (kind, key) {
+        kind -> Text,
+        key -> Text,
+        response -> Text,
+        fetched_at -> BigInt,
+    }
+}
+
 diesel::table! {
     people (id) {
         id -> Text,
@@ -103,6 +162,13 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    rejected_matches (media_id, imdb_id) {
+        media_id -> Integer,
+        imdb_id -> Text,
+    }
+}
+
 diesel::table! {
     seasons (id) {
         id -> Integer,
@@ -112,6 +178,41 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    settings (key) {
+        key -> Text,
+        value -> Text,
+    }
+}
+
+diesel::table! {
+    sync_jobs (id) {
+        id -> Integer,
+        roots -> Text,
+        status -> Text,
+        total -> Integer,
+        inserted -> Integer,
+        pending -> Text,
+        error -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    skipped_reviews (media_id) {
+        media_id -> Integer,
+        skipped_at -> BigInt,
+    }
+}
+
+diesel::table! {
+    subtitles (id) {
+        id -> Integer,
+        file_id -> Integer,
+        path -> Text,
+        language -> Nullable<Text>,
+    }
+}
+
 diesel::table! {
     tags (id) {
         id -> Integer,
@@ -120,31 +221,48 @@ diesel::table! {
 }
 
 diesel::joinable!(episodes -> seasons (season_id));
+diesel::joinable!(file_episodes -> episodes (episode_id));
+diesel::joinable!(file_episodes -> files (file_id));
 diesel::joinable!(files -> episodes (episode_id));
 diesel::joinable!(files -> medias (media_id));
 diesel::joinable!(imdb_countries -> countries (country_id));
 diesel::joinable!(imdb_countries -> imdbs (imdb_id));
+diesel::joinable!(imdb_episodes -> imdbs (series_imdb_id));
 diesel::joinable!(imdb_genres -> genres (genre_id));
 diesel::joinable!(imdb_genres -> imdbs (imdb_id));
 diesel::joinable!(imdb_people -> imdbs (imdb_id));
 diesel::joinable!(imdb_people -> people (person_id));
+diesel::joinable!(match_suggestions -> medias (media_id));
 diesel::joinable!(media_tags -> medias (media_id));
 diesel::joinable!(media_tags -> tags (tag_id));
 diesel::joinable!(medias -> imdbs (imdb_id));
+diesel::joinable!(rejected_matches -> medias (media_id));
 diesel::joinable!(seasons -> medias (media_id));
+diesel::joinable!(skipped_reviews -> medias (media_id));
+diesel::joinable!(subtitles -> files (file_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
     countries,
     episodes,
+    file_episodes,
     files,
     genres,
     imdb_countries,
+    imdb_episodes,
     imdb_genres,
     imdb_people,
     imdbs,
+    library_roots,
+    match_suggestions,
     media_tags,
     medias,
+    metadata_cache,
     people,
+    rejected_matches,
     seasons,
+    settings,
+    skipped_reviews,
+    subtitles,
+    sync_jobs,
     tags,
 );
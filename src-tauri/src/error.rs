@@ -0,0 +1,196 @@
+use serde::{Serialize, Serializer, ser::SerializeStruct};
+use std::fmt;
+use tauri_plugin_http::reqwest::{self, StatusCode};
+
+use crate::data_model::IdType;
+
+/// Error returned by the Tauri commands. It serialises as `{ code, message }`
+/// so the frontend can react to each case instead of parsing the message.
+///
+/// Lower layers return `anyhow` errors; they raise a variant of this enum
+/// where the case is known, and everything else is classified by its cause.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AppError {
+    NotFound(String),
+    Conflict(String),
+    InvalidInput(String),
+    NetworkUnavailable(String),
+    RateLimited(String),
+    /// The path of a file that still exists, so its media cannot be deleted.
+    FileStillOnDisk(String),
+    /// The title is already matched to another media, which the caller can
+    /// offer to merge with. Serialised with the `mediaId` of that media.
+    AlreadyMatched {
+        imdb_id: String,
+        media_id: IdType,
+        name: String,
+    },
+    Database(String),
+    Internal(String),
+}
+
+impl AppError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::NotFound(_) => "notFound",
+            AppError::Conflict(_) => "conflict",
+            AppError::InvalidInput(_) => "invalidInput",
+            AppError::NetworkUnavailable(_) => "networkUnavailable",
+            AppError::RateLimited(_) => "rateLimited",
+            AppError::FileStillOnDisk(_) => "fileStillOnDisk",
+            AppError::AlreadyMatched { .. } => "alreadyMatched",
+            AppError::Database(_) => "database",
+            AppError::Internal(_) => "internal",
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::FileStillOnDisk(path) => write!(f, "File {path} still exists on disk"),
+            AppError::AlreadyMatched { imdb_id, name, .. } => {
+                write!(f, "{imdb_id} is already matched to {name}")
+            }
+            AppError::NotFound(message)
+            | AppError::Conflict(message)
+            | AppError::InvalidInput(message)
+            | AppError::NetworkUnavailable(message)
+            | AppError::RateLimited(message)
+            | AppError::Database(message)
+            | AppError::Internal(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl Serialize for AppError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("AppError", 3)?;
+        state.serialize_field("code", self.code())?;
+        state.serialize_field("message", &self.to_string())?;
+        if let AppError::AlreadyMatched { media_id, .. } = self {
+            state.serialize_field("mediaId", media_id)?;
+        }
+        state.end()
+    }
+}
+
+impl From<anyhow::Error> for AppError {
+    fn from(err: anyhow::Error) -> Self {
+        let err = match err.downcast::<AppError>() {
+            Ok(app_error) => return app_error,
+            Err(err) => err,
+        };
+        let message = err.to_string();
+
+        for cause in err.chain() {
+            if let Some(app_error) = cause.downcast_ref::<AppError>() {
+                return app_error.clone();
+            }
+            if let Some(diesel_err) = cause.downcast_ref::<diesel::result::Error>() {
+                return match diesel_err {
+                    diesel::result::Error::NotFound => AppError::NotFound(message),
+                    _ => AppError::Database(message),
+                };
+            }
+            if cause.is::<diesel::r2d2::PoolError>() {
+                return AppError::Database(message);
+            }
+            if let Some(http_err) = cause.downcast_ref::<reqwest::Error>() {
+                if http_err.status() == Some(StatusCode::TOO_MANY_REQUESTS) {
+                    return AppError::RateLimited(message);
+                }
+                if http_err.is_connect() || http_err.is_timeout() || http_err.is_request() {
+                    return AppError::NetworkUnavailable(message);
+                }
+            }
+        }
+
+        AppError::Internal(message)
+    }
+}
+
+impl From<Box<dyn std::error::Error>> for AppError {
+    fn from(err: Box<dyn std::error::Error>) -> Self {
+        match err.downcast::<AppError>() {
+            Ok(app_error) => *app_error,
+            Err(err) => AppError::Internal(err.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests_app_error {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn serialises_code_and_message() {
+        let json = serde_json::to_value(AppError::NotFound("Media 3 not found".into())).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({ "code": "notFound", "message": "Media 3 not found" })
+        );
+
+        let json = serde_json::to_value(AppError::FileStillOnDisk("/m/a.mkv".into())).unwrap();
+        assert_eq!(json["code"], "fileStillOnDisk");
+        assert_eq!(json["message"], "File /m/a.mkv still exists on disk");
+
+        let json = serde_json::to_value(AppError::AlreadyMatched {
+            imdb_id: "tt1160419".into(),
+            media_id: 7,
+            name: "dune".into(),
+        })
+        .unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "code": "alreadyMatched",
+                "message": "tt1160419 is already matched to dune",
+                "mediaId": 7
+            })
+        );
+    }
+
+    #[test]
+    fn keeps_raised_variants_through_context() {
+        let err = anyhow::Error::from(AppError::Conflict("Already running".into()));
+        assert_eq!(
+            AppError::from(err),
+            AppError::Conflict("Already running".into())
+        );
+
+        let err = Err::<(), _>(AppError::RateLimited("429".into()))
+            .context("Failed to fetch")
+            .unwrap_err();
+        assert_eq!(AppError::from(err), AppError::RateLimited("429".into()));
+    }
+
+    #[test]
+    fn classifies_database_errors() {
+        let err = anyhow::Error::from(diesel::result::Error::NotFound);
+        assert_eq!(AppError::from(err).code(), "notFound");
+
+        let err = anyhow::Error::from(diesel::result::Error::RollbackTransaction);
+        assert_eq!(AppError::from(err).code(), "database");
+
+        assert_eq!(AppError::from(anyhow::anyhow!("boom")).code(), "internal");
+    }
+
+    #[tokio::test]
+    async fn classifies_network_errors() {
+        // Nothing listens on port 9 of the loopback address.
+        let err = reqwest::Client::new()
+            .get("http://127.0.0.1:9/")
+            .send()
+            .await
+            .unwrap_err();
+
+        assert_eq!(
+            AppError::from(anyhow::Error::from(err)).code(),
+            "networkUnavailable"
+        );
+    }
+}
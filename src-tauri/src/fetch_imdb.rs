@@ -1,14 +1,1178 @@
-use anyhow::Result;
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use futures::future::join_all;
+use itertools::Itertools;
+use std::{collections::HashMap, sync::Arc};
 
-use crate::data_model::{Imdb, Media};
+use crate::data_model::{
+    IdType, Imdb, ImdbEpisode, MatchCandidate, MatchSuggestion, Media, RejectedMatches,
+};
+use crate::db::{ContentType, DB, unix_time};
+use crate::error::AppError;
+use crate::settings::MetadataCacheSettings;
+use cache::{EPISODES, SEARCH, TITLE, search_key};
+use matcher::{LISTED_CANDIDATES, MAX_CANDIDATES, MIN_CONFIDENCE, Wanted};
 
+mod cache;
 mod freeimdb;
 mod imdbot;
+mod matcher;
 
-pub async fn get_imdb_data_by_id(id: &str) -> Result<Imdb> {
-    freeimdb::get_imdb_data_by_id(id).await
+#[cfg(test)]
+pub(crate) mod moke_server;
+
+pub use cache::MetadataCache;
+pub use freeimdb::FreeImdb;
+pub use imdbot::Imdbot;
+pub(crate) use matcher::is_series_type;
+
+/// A single hit returned by a provider's title search.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SearchResult {
+    pub imdb_id: String,
+    pub title: Option<String>,
+    pub year: Option<i32>,
+}
+
+/// A media none of the providers could match, and why.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LookupFailure {
+    pub name: String,
+    pub year: Option<i32>,
+    /// A file of the media, to tell apart titles with the same name.
+    pub path: Option<String>,
+    pub reason: String,
+}
+
+impl LookupFailure {
+    fn new(media: &Media, reason: String) -> Self {
+        Self {
+            name: media.name.clone(),
+            year: media.year,
+            path: media.paths().next().map(str::to_string),
+            reason,
+        }
+    }
+}
+
+/// A source of title metadata.
+///
+/// Providers only need to implement the operations their API supports; the
+/// default implementations report the operation as unsupported so the
+/// [`MetadataProviders`] chain moves on to the next provider.
+#[async_trait]
+pub trait MetadataProvider: Send + Sync {
+    fn name(&self) -> &str;
+
+    async fn search(&self, _query: &str) -> Result<Vec<SearchResult>> {
+        Err(anyhow!("{} does not support search", self.name()))
+    }
+
+    async fn get_by_id(&self, _id: &str) -> Result<Imdb> {
+        Err(anyhow!("{} does not support lookup by id", self.name()))
+    }
+
+    async fn get_by_ids(&self, _ids: &[String]) -> Result<Vec<Imdb>> {
+        Err(anyhow!("{} does not support batch lookup", self.name()))
+    }
+
+    /// Every episode of the series with the imdb id `id`.
+    async fn get_episodes(&self, _id: &str) -> Result<Vec<ImdbEpisode>> {
+        Err(anyhow!("{} does not support episode lists", self.name()))
+    }
+}
+
+/// An ordered chain of providers. Every operation is tried on each provider in
+/// turn until one of them succeeds.
+///
+/// With a [`MetadataCache`], searches and titles are answered from it while
+/// they are fresh, and from stale entries or the stored titles when the
+/// providers fail. In offline mode the providers are never asked.
+#[derive(Clone)]
+pub struct MetadataProviders {
+    providers: Vec<Arc<dyn MetadataProvider>>,
+    cache: Option<Arc<dyn MetadataCache>>,
+}
+
+impl Default for MetadataProviders {
+    fn default() -> Self {
+        Self::new(vec![
+            Arc::new(Imdbot::default()),
+            Arc::new(FreeImdb::default()),
+        ])
+    }
+}
+
+impl MetadataProviders {
+    pub fn new(providers: Vec<Arc<dyn MetadataProvider>>) -> Self {
+        Self {
+            providers,
+            cache: None,
+        }
+    }
+
+    pub fn with_cache(mut self, cache: Arc<dyn MetadataCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    pub async fn search(&self, query: &str) -> Result<Vec<SearchResult>> {
+        let Some((cache, settings)) = self.cache() else {
+            return self.search_providers(query).await;
+        };
+
+        let key = search_key(query);
+        let cached = match cache.lookup::<Vec<SearchResult>>(SEARCH, &key, settings.ttl_hours) {
+            Some(cached) if cached.fresh || settings.offline => return Ok(cached.value),
+            cached => cached.map(|cached| cached.value),
+        };
+        if settings.offline {
+            return Err(offline_error(&format!("Search for {query}")));
+        }
+
+        match self.search_providers(query).await {
+            Ok(results) => {
+                cache.store(SEARCH, &key, &results);
+                Ok(results)
+            }
+            Err(err) => cached.ok_or(err),
+        }
+    }
+
+    pub async fn get_imdb_data_by_id(&self, id: &str) -> Result<Imdb> {
+        let Some((cache, settings)) = self.cache() else {
+            return self.fetch_by_id(id).await;
+        };
+
+        let cached = match cache.lookup::<Imdb>(TITLE, id, settings.ttl_hours) {
+            Some(cached) if cached.fresh || settings.offline => return Ok(cached.value),
+            cached => cached
+                .map(|cached| cached.value)
+                .or_else(|| cache.stored(id)),
+        };
+        if settings.offline {
+            return cached.ok_or_else(|| offline_error(id));
+        }
+
+        match self.fetch_by_id(id).await {
+            Ok(imdb) => {
+                cache.store(TITLE, id, &imdb);
+                Ok(imdb)
+            }
+            Err(err) => cached.ok_or(err),
+        }
+    }
+
+    /// Details of the titles that could be found; cached ones are not fetched
+    /// again.
+    pub async fn get_imdb_data_by_ids(&self, ids: &[String]) -> Result<Vec<Imdb>> {
+        let Some((cache, settings)) = self.cache() else {
+            return self.fetch_by_ids(ids).await;
+        };
+
+        let mut imdbs = vec![];
+        let mut stale = vec![];
+        let mut missing = vec![];
+        for id in ids {
+            match cache.lookup::<Imdb>(TITLE, id, settings.ttl_hours) {
+                Some(cached) if cached.fresh || settings.offline => imdbs.push(cached.value),
+                cached => {
+                    missing.push(id.clone());
+                    stale.extend(
+                        cached
+                            .map(|cached| cached.value)
+                            .or_else(|| cache.stored(id)),
+                    );
+                }
+            }
+        }
+        if missing.is_empty() {
+            return Ok(imdbs);
+        }
+
+        let fetched = if settings.offline {
+            Err(offline_error(&missing.join(", ")))
+        } else {
+            self.fetch_by_ids(&missing).await
+        };
+        match fetched {
+            Ok(fetched) => {
+                for imdb in &fetched {
+                    cache.store(TITLE, &imdb.imdb_id, imdb);
+                }
+                imdbs.extend(
+                    fetched
+                        .into_iter()
+                        .filter(|imdb| missing.contains(&imdb.imdb_id)),
+                );
+            }
+            Err(err) => {
+                imdbs.extend(stale);
+                if imdbs.is_empty() {
+                    return Err(err);
+                }
+            }
+        }
+
+        Ok(imdbs)
+    }
+
+    /// The episodes of a series, answered from the cache like title details.
+    pub async fn get_episodes(&self, series_id: &str) -> Result<Vec<ImdbEpisode>> {
+        let Some((cache, settings)) = self.cache() else {
+            return self.fetch_episodes(series_id).await;
+        };
+
+        let cached = match cache.lookup::<Vec<ImdbEpisode>>(EPISODES, series_id, settings.ttl_hours)
+        {
+            Some(cached) if cached.fresh || settings.offline => return Ok(cached.value),
+            cached => cached.map(|cached| cached.value),
+        };
+        if settings.offline {
+            return cached.ok_or_else(|| offline_error(&format!("Episodes of {series_id}")));
+        }
+
+        match self.fetch_episodes(series_id).await {
+            Ok(episodes) => {
+                cache.store(EPISODES, series_id, &episodes);
+                Ok(episodes)
+            }
+            Err(err) => cached.ok_or(err),
+        }
+    }
+
+    /// Matches the media to the title, storing its details and, for a series,
+    /// its episode list. A missing media or a title matched to another media
+    /// is refused before anything is fetched or stored.
+    pub async fn match_media<T: DB>(
+        &self,
+        db: &T,
+        media_id: IdType,
+        imdb_id: &str,
+    ) -> Result<IdType> {
+        db.check_media_imdb(media_id, imdb_id)?;
+        let imdb = self.get_imdb_data_by_id(imdb_id).await?;
+
+        db.insert_imdb(&imdb)?;
+        let media_id = db.update_media_imdb(media_id, imdb_id)?;
+        self.store_new_episode_lists(db, &[imdb_id.to_string()])
+            .await?;
+        Ok(media_id)
+    }
+
+    /// Fetches and stores the episode lists of the matched series among
+    /// `imdb_ids` that were never fetched, so their missing episodes are known
+    /// without a manual refresh. A failed fetch is recorded too and left to
+    /// the periodic refresh.
+    pub async fn store_new_episode_lists<T: DB>(&self, db: &T, imdb_ids: &[String]) -> Result<()> {
+        for series_id in db.get_unlisted_series_imdb_ids(imdb_ids)? {
+            match self.get_episodes(&series_id).await {
+                Ok(episodes) => db.set_imdb_episodes(&series_id, &episodes)?,
+                Err(err) => eprintln!("Failed to fetch the episodes of {series_id}: {err}"),
+            }
+            db.mark_imdb_episodes_fetched(&series_id, unix_time())?;
+        }
+
+        Ok(())
+    }
+
+    fn cache(&self) -> Option<(&dyn MetadataCache, MetadataCacheSettings)> {
+        let cache = self.cache.as_deref()?;
+        let settings = cache
+            .settings()
+            .inspect_err(|err| eprintln!("Failed to read metadata cache settings: {err}"))
+            .unwrap_or_default();
+
+        Some((cache, settings))
+    }
+
+    async fn search_providers(&self, query: &str) -> Result<Vec<SearchResult>> {
+        let mut last_err = anyhow!("No metadata provider configured");
+
+        for provider in &self.providers {
+            match provider.search(query).await {
+                Ok(results) if !results.is_empty() => return Ok(results),
+                Ok(_) => last_err = anyhow!("No movies found"),
+                Err(err) => last_err = err,
+            }
+        }
+
+        Err(last_err)
+    }
+
+    async fn fetch_by_id(&self, id: &str) -> Result<Imdb> {
+        let mut last_err = anyhow!("No metadata provider configured");
+
+        for provider in &self.providers {
+            match provider.get_by_id(id).await {
+                Ok(imdb) => return Ok(imdb),
+                Err(err) => last_err = err,
+            }
+        }
+
+        Err(last_err)
+    }
+
+    async fn fetch_episodes(&self, series_id: &str) -> Result<Vec<ImdbEpisode>> {
+        let mut last_err = anyhow!("No metadata provider configured");
+
+        for provider in &self.providers {
+            match provider.get_episodes(series_id).await {
+                Ok(episodes) => return Ok(episodes),
+                Err(err) => last_err = err,
+            }
+        }
+
+        Err(last_err)
+    }
+
+    async fn fetch_by_ids(&self, ids: &[String]) -> Result<Vec<Imdb>> {
+        let mut last_err = anyhow!("No metadata provider configured");
+
+        for provider in &self.providers {
+            match provider.get_by_ids(ids).await {
+                Ok(imdbs) => return Ok(imdbs),
+                Err(err) => last_err = err,
+            }
+        }
+
+        Err(last_err)
+    }
+
+    /// Looks up and attaches the imdb data of every media, picking the best
+    /// scoring of the first search results. Matches below [`MIN_CONFIDENCE`]
+    /// are kept as a suggestion instead; they and the medias that could not be
+    /// matched are returned with the reason. Titles rejected for a media are
+    /// never picked for it.
+    pub async fn set_imdb_data(
+        &self,
+        medias: &mut [Media],
+        rejected: &RejectedMatches,
+    ) -> Vec<LookupFailure> {
+        let searches = join_all(medias.iter().map(|media| async move {
+            let results = self.search(&media.name).await?;
+            let results: Vec<SearchResult> = results
+                .into_iter()
+                .filter(|result| !rejected.contains(media, &result.imdb_id))
+                .collect();
+            if results.is_empty() {
+                return Err(anyhow!("No movies found"));
+            }
+            Ok(results)
+        }))
+        .await;
+
+        let ids: Vec<String> = searches
+            .iter()
+            .flatten()
+            .flat_map(|results| results.iter().take(MAX_CANDIDATES))
+            .map(|result| result.imdb_id.clone())
+            .unique()
+            .collect();
+        let details = if ids.is_empty() {
+            Ok(vec![])
+        } else {
+            self.get_imdb_data_by_ids(&ids).await
+        };
+        let by_id: HashMap<&str, &Imdb> = details
+            .iter()
+            .flatten()
+            .map(|imdb| (imdb.imdb_id.as_str(), imdb))
+            .collect();
+
+        let mut failures = vec![];
+        for (media, search) in medias.iter_mut().zip(searches) {
+            let reason = match (search, &details) {
+                (Err(err), _) => err.to_string(),
+                (Ok(_), Err(err)) => format!("Failed to fetch details: {err}"),
+                (Ok(results), Ok(_)) => {
+                    let candidates = matcher::candidates(&results, &by_id);
+                    match matcher::best_match(media, &candidates) {
+                        None => "No movies found".to_string(),
+                        Some((best, confidence)) if confidence >= MIN_CONFIDENCE => {
+                            match by_id.get(best.imdb_id.as_str()) {
+                                Some(imdb) => {
+                                    media.imdb = Some((*imdb).clone());
+                                    media.suggestion = None;
+                                    continue;
+                                }
+                                None => format!("No details returned for {}", best.imdb_id),
+                            }
+                        }
+                        Some((best, confidence)) => {
+                            media.suggestion = Some(MatchSuggestion {
+                                imdb_id: best.imdb_id.clone(),
+                                title: best.title.clone(),
+                                year: best.year,
+                                confidence,
+                            });
+                            format!(
+                                "Needs review: {} ({}) is a {confidence}% match",
+                                best.title, best.imdb_id
+                            )
+                        }
+                    }
+                }
+            };
+            failures.push(LookupFailure::new(media, reason));
+        }
+
+        failures
+    }
+
+    /// Titles matching a search typed by the user, best match first. With a
+    /// type, titles known to be of another type are left out.
+    pub async fn search_titles(
+        &self,
+        query: &str,
+        year: Option<i32>,
+        content_type: &ContentType,
+    ) -> Result<Vec<MatchCandidate>> {
+        let query = query.trim();
+        if query.is_empty() {
+            return Err(AppError::InvalidInput("Search is empty".into()).into());
+        }
+
+        let series = match content_type {
+            ContentType::All => None,
+            ContentType::Movie => Some(false),
+            ContentType::Series => Some(true),
+        };
+        let wanted = Wanted {
+            title: query,
+            year,
+            series,
+        };
+        let results = self.search(query).await?;
+        let candidates = self.rank(&wanted, results).await;
+
+        Ok(candidates
+            .into_iter()
+            .filter(|candidate| match (series, candidate.r#type.as_deref()) {
+                (Some(series), Some(r#type)) => matcher::is_series_type(r#type) == series,
+                _ => true,
+            })
+            .collect())
+    }
+
+    /// The titles found for the media in the review queue, best match first,
+    /// without the ones rejected for it.
+    pub async fn match_candidates(
+        &self,
+        media: &Media,
+        rejected: &RejectedMatches,
+    ) -> Result<Vec<MatchCandidate>> {
+        let results: Vec<SearchResult> = self
+            .search(&media.name)
+            .await?
+            .into_iter()
+            .filter(|result| !rejected.contains(media, &result.imdb_id))
+            .collect();
+
+        Ok(self.rank(&Wanted::from(media), results).await)
+    }
+
+    /// Scores the first search results with their details, best first.
+    async fn rank(&self, wanted: &Wanted<'_>, results: Vec<SearchResult>) -> Vec<MatchCandidate> {
+        let results: Vec<SearchResult> = results.into_iter().take(LISTED_CANDIDATES).collect();
+        if results.is_empty() {
+            return vec![];
+        }
+
+        // Candidates are still worth showing without their details.
+        let ids: Vec<String> = results.iter().map(|r| r.imdb_id.clone()).collect();
+        let details = self
+            .get_imdb_data_by_ids(&ids)
+            .await
+            .inspect_err(|err| eprintln!("Failed to fetch candidate details: {err}"))
+            .unwrap_or_default();
+        let by_id: HashMap<&str, &Imdb> = details
+            .iter()
+            .map(|imdb| (imdb.imdb_id.as_str(), imdb))
+            .collect();
+
+        let mut candidates: Vec<MatchCandidate> = results
+            .iter()
+            .map(|result| {
+                let imdb = by_id.get(result.imdb_id.as_str()).copied();
+                let candidate = matcher::Candidate::new(result, imdb);
+                let non_empty = |value: &String| Some(value.clone()).filter(|v| !v.is_empty());
+                MatchCandidate {
+                    confidence: matcher::score(wanted, &candidate),
+                    imdb_id: candidate.imdb_id,
+                    title: candidate.title,
+                    year: candidate.year,
+                    r#type: candidate.r#type,
+                    poster: imdb.and_then(|imdb| non_empty(&imdb.poster)),
+                    rating: imdb.and_then(|imdb| non_empty(&imdb.imdb_rating)),
+                }
+            })
+            .collect();
+        candidates.sort_by_key(|candidate| std::cmp::Reverse(candidate.confidence));
+
+        candidates
+    }
+}
+
+fn offline_error(what: &str) -> anyhow::Error {
+    AppError::NetworkUnavailable(format!("Offline mode: {what} is not cached")).into()
+}
+
+#[cfg(test)]
+mod real_api_test {
+    use super::*;
+    use crate::data_model::Media;
+    use std::path::PathBuf;
+
+    async fn matched_id(path: &str) -> Option<String> {
+        let mut medias = vec![Media::from(PathBuf::from(path))];
+        MetadataProviders::default()
+            .set_imdb_data(&mut medias, &RejectedMatches::default())
+            .await;
+        medias.remove(0).imdb.map(|imdb| imdb.imdb_id)
+    }
+
+    #[tokio::test]
+    #[ignore = "hits the real IMDb APIs"]
+    async fn fetch_movie_data() {
+        let imdb_id =
+            matched_id("3.days.to.kill.2014.extended.720p.farsi.dubbed.film2media.mkv").await;
+        assert_eq!(imdb_id.as_deref(), Some("tt2172934"));
+    }
+
+    #[tokio::test]
+    #[ignore = "hits the real IMDb APIs"]
+    async fn fetch_series_data() {
+        let imdb_id = matched_id("black.mirror.s01.e01.480p.web-dl.x264.mkv").await;
+        assert_eq!(imdb_id.as_deref(), Some("tt2085059"));
+    }
+
+    #[tokio::test]
+    #[ignore = "hits the real IMDb APIs"]
+    async fn set_imdb_data() {
+        let m1 = Media::from(PathBuf::from("black.mirror.s01.e01.480p.web-dl.x264.mkv"));
+        let m2 = Media::from(PathBuf::from(
+            "3.days.to.kill.2014.extended.720p.farsi.dubbed.film2media.mkv",
+        ));
+
+        let mut medias = vec![m1, m2];
+        MetadataProviders::default()
+            .set_imdb_data(&mut medias, &RejectedMatches::default())
+            .await;
+
+        let new_m1 = &medias[0];
+        assert_eq!(new_m1.name, "black mirror");
+
+        let imdb = new_m1.imdb.as_ref().unwrap();
+        assert_eq!(imdb.r#type, "tvSeries");
+        assert_eq!(imdb.year, 2011);
+
+        let new_m2 = &medias[1];
+        assert_eq!(new_m2.name, "3 days to kill");
+
+        let imdb = new_m2.imdb.as_ref().unwrap();
+        assert_eq!(imdb.r#type, "movie");
+        assert_eq!(imdb.year, 2014);
+    }
+}
+
+#[cfg(test)]
+mod moke_api_test {
+    use super::moke_server::MokeServer;
+    use super::*;
+    use crate::data_model::Media;
+    use std::{path::PathBuf, time::Duration};
+
+    fn providers(server: &MokeServer) -> MetadataProviders {
+        MetadataProviders::new(vec![
+            Arc::new(Imdbot::new(server.url())),
+            Arc::new(FreeImdb::new(server.url(), Duration::ZERO)),
+        ])
+    }
+
+    fn route_3_days_to_kill(server: &MokeServer) {
+        server.route(
+            "/search",
+            &[(
+                200,
+                include_str!("fetch_imdb/fixtures/imdbot_search_3_days_to_kill.json"),
+            )],
+        );
+        server.route(
+            "/titles:batchGet",
+            &[(
+                200,
+                include_str!("fetch_imdb/fixtures/freeimdb_batch_get.json"),
+            )],
+        );
+    }
+
+    #[tokio::test]
+    async fn picks_search_result_with_matching_year() {
+        let server = MokeServer::start().await;
+        route_3_days_to_kill(&server);
+
+        let mut medias = vec![Media::from(PathBuf::from(
+            "3.days.to.kill.2014.extended.720p.farsi.dubbed.film2media.mkv",
+        ))];
+        let failures = providers(&server)
+            .set_imdb_data(&mut medias, &RejectedMatches::default())
+            .await;
+
+        assert!(failures.is_empty());
+        assert_eq!(medias[0].imdb.as_ref().unwrap().imdb_id, "tt2172934");
+    }
+
+    #[tokio::test]
+    async fn prefers_matching_title_over_first_result_without_year() {
+        let server = MokeServer::start().await;
+        route_3_days_to_kill(&server);
+
+        let mut medias = vec![Media::from(PathBuf::from("3.days.to.kill.720p.mkv"))];
+        let failures = providers(&server)
+            .set_imdb_data(&mut medias, &RejectedMatches::default())
+            .await;
+
+        assert!(failures.is_empty());
+        assert_eq!(medias[0].imdb.as_ref().unwrap().imdb_id, "tt2172934");
+    }
+
+    #[tokio::test]
+    async fn keeps_low_confidence_match_for_review() {
+        let server = MokeServer::start().await;
+        route_3_days_to_kill(&server);
+
+        let mut medias = vec![Media::from(PathBuf::from("3.days.to.kill.1999.mkv"))];
+        let failures = providers(&server)
+            .set_imdb_data(&mut medias, &RejectedMatches::default())
+            .await;
+
+        assert!(medias[0].imdb.is_none());
+        let suggestion = medias[0].suggestion.as_ref().unwrap();
+        assert_eq!(suggestion.imdb_id, "tt2172934");
+        assert_eq!(suggestion.year, Some(2014));
+        assert!(suggestion.confidence < MIN_CONFIDENCE);
+        assert_eq!(failures.len(), 1);
+        assert!(failures[0].reason.starts_with("Needs review"));
+    }
+
+    #[tokio::test]
+    async fn never_picks_rejected_titles() {
+        let server = MokeServer::start().await;
+        route_3_days_to_kill(&server);
+
+        let mut medias = vec![Media::from(PathBuf::from("3.days.to.kill.2014.mkv"))];
+        let mut rejected = RejectedMatches::default();
+        rejected.insert("3 days to kill".into(), Some(2014), "tt2172934".into());
+        providers(&server)
+            .set_imdb_data(&mut medias, &rejected)
+            .await;
+
+        assert!(medias[0].imdb.is_none());
+        assert_eq!(medias[0].suggestion.as_ref().unwrap().imdb_id, "tt9000001");
+    }
+
+    #[tokio::test]
+    async fn lists_review_candidates_best_first() {
+        let server = MokeServer::start().await;
+        route_3_days_to_kill(&server);
+        let media = Media::from(PathBuf::from("3.days.to.kill.2014.mkv"));
+
+        let candidates = providers(&server)
+            .match_candidates(&media, &RejectedMatches::default())
+            .await
+            .unwrap();
+        let ids: Vec<&str> = candidates.iter().map(|c| c.imdb_id.as_str()).collect();
+        assert_eq!(ids, ["tt2172934", "tt9000001"]);
+        assert!(candidates[0].confidence >= MIN_CONFIDENCE);
+        assert_eq!(candidates[0].r#type.as_deref(), Some("movie"));
+        assert!(candidates[0].poster.is_some());
+
+        let mut rejected = RejectedMatches::default();
+        rejected.insert("3 days to kill".into(), Some(2014), "tt2172934".into());
+        let candidates = providers(&server)
+            .match_candidates(&media, &rejected)
+            .await
+            .unwrap();
+        let ids: Vec<&str> = candidates.iter().map(|c| c.imdb_id.as_str()).collect();
+        assert_eq!(ids, ["tt9000001"]);
+    }
+
+    #[tokio::test]
+    async fn search_titles_ranks_and_filters_by_type() {
+        let server = MokeServer::start().await;
+        route_3_days_to_kill(&server);
+        let providers = providers(&server);
+
+        let titles = providers
+            .search_titles(" 3 Days to Kill ", Some(2014), &ContentType::Movie)
+            .await
+            .unwrap();
+        assert_eq!(titles[0].imdb_id, "tt2172934");
+        assert_eq!(titles[0].year, Some(2014));
+        assert!(titles[0].rating.is_some());
+        assert!(titles[0].poster.is_some());
+
+        // Titles without details cannot be told apart and are kept.
+        let titles = providers
+            .search_titles("3 days to kill", None, &ContentType::Series)
+            .await
+            .unwrap();
+        let ids: Vec<&str> = titles.iter().map(|t| t.imdb_id.as_str()).collect();
+        assert_eq!(ids, ["tt9000001"]);
+
+        let err = providers
+            .search_titles("  ", None, &ContentType::All)
+            .await
+            .unwrap_err();
+        assert_eq!(AppError::from(err).code(), "invalidInput");
+    }
+
+    #[tokio::test]
+    async fn skips_providers_without_support() {
+        let server = MokeServer::start().await;
+        server.route(
+            "/titles/tt2172934",
+            &[(
+                200,
+                include_str!("fetch_imdb/fixtures/freeimdb_title_3_days_to_kill.json"),
+            )],
+        );
+
+        // imdbot has no lookup by id, so the chain must fall through to freeimdb.
+        let imdb = providers(&server)
+            .get_imdb_data_by_id("tt2172934")
+            .await
+            .unwrap();
+
+        assert_eq!(imdb.title, "3 Days to Kill");
+    }
+
+    #[tokio::test]
+    async fn empty_chain_is_an_error() {
+        let providers = MetadataProviders::new(vec![]);
+        assert!(providers.search("anything").await.is_err());
+        assert!(providers.get_imdb_data_by_id("tt2172934").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn set_imdb_data() {
+        let server = MokeServer::start().await;
+        server.route(
+            "/search?q=black+mirror",
+            &[(
+                200,
+                include_str!("fetch_imdb/fixtures/imdbot_search_black_mirror.json"),
+            )],
+        );
+        server.route(
+            "/search?q=3+days+to+kill",
+            &[(
+                200,
+                include_str!("fetch_imdb/fixtures/imdbot_search_3_days_to_kill.json"),
+            )],
+        );
+        server.route(
+            "/titles:batchGet",
+            &[(
+                200,
+                include_str!("fetch_imdb/fixtures/freeimdb_batch_get.json"),
+            )],
+        );
+
+        let mut medias = vec![
+            Media::from(PathBuf::from("black.mirror.s01.e01.480p.web-dl.x264.mkv")),
+            Media::from(PathBuf::from(
+                "3.days.to.kill.2014.extended.720p.farsi.dubbed.film2media.mkv",
+            )),
+        ];
+        let failures = providers(&server)
+            .set_imdb_data(&mut medias, &RejectedMatches::default())
+            .await;
+
+        assert!(failures.is_empty());
+        assert_eq!(server.hits("/search"), 2);
+        assert_eq!(server.hits("/titles:batchGet"), 1);
+        assert!(medias.iter().all(|media| media.imdb.is_some()));
+
+        let series = medias.iter().find(|m| m.name == "black mirror").unwrap();
+        let imdb = series.imdb.as_ref().unwrap();
+        assert_eq!(imdb.r#type, "tvSeries");
+        assert_eq!(imdb.year, 2011);
+
+        let movie = medias.iter().find(|m| m.name == "3 days to kill").unwrap();
+        let imdb = movie.imdb.as_ref().unwrap();
+        assert_eq!(imdb.r#type, "movie");
+        assert_eq!(imdb.year, 2014);
+    }
+
+    #[tokio::test]
+    async fn set_imdb_data_keeps_medias_on_failure() {
+        let server = MokeServer::start().await;
+        server.route(
+            "/search",
+            &[(
+                200,
+                include_str!("fetch_imdb/fixtures/imdbot_search_black_mirror.json"),
+            )],
+        );
+        server.route("/titles:batchGet", &[(500, "{}")]);
+
+        let mut medias = vec![Media::from(PathBuf::from(
+            "black.mirror.s01.e01.480p.web-dl.x264.mkv",
+        ))];
+        let failures = providers(&server)
+            .set_imdb_data(&mut medias, &RejectedMatches::default())
+            .await;
+
+        assert_eq!(medias[0].name, "black mirror");
+        assert!(medias[0].imdb.is_none());
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].name, "black mirror");
+        assert_eq!(
+            failures[0].path.as_deref(),
+            Some("black.mirror.s01.e01.480p.web-dl.x264.mkv")
+        );
+        assert!(failures[0].reason.starts_with("Failed to fetch details"));
+    }
 }
 
-pub async fn set_imdb_data(medias: &mut [Media]) {
-    imdbot::set_imdb_data(medias).await
+#[cfg(test)]
+mod tests_metadata_cache {
+    use super::moke_server::MokeServer;
+    use super::*;
+    use crate::db::{DB, Sqlite, temp_db};
+    use crate::settings::set_metadata_cache_settings;
+    use std::{path::PathBuf, time::Duration};
+    use tempfile::TempDir;
+
+    fn setup(server: &MokeServer) -> (TempDir, Sqlite, MetadataProviders) {
+        let (temp_dir, db) = temp_db();
+        let providers = MetadataProviders::new(vec![
+            Arc::new(Imdbot::new(server.url())),
+            Arc::new(FreeImdb::new(server.url(), Duration::ZERO)),
+        ])
+        .with_cache(Arc::new(db.clone()));
+        (temp_dir, db, providers)
+    }
+
+    fn set_cache(db: &Sqlite, ttl_hours: u32, offline: bool) {
+        set_metadata_cache_settings(db, MetadataCacheSettings { ttl_hours, offline }).unwrap();
+    }
+
+    fn route_fixtures(server: &MokeServer) {
+        server.route(
+            "/search?q=black+mirror",
+            &[(
+                200,
+                include_str!("fetch_imdb/fixtures/imdbot_search_black_mirror.json"),
+            )],
+        );
+        server.route(
+            "/search?q=3+days+to+kill",
+            &[(
+                200,
+                include_str!("fetch_imdb/fixtures/imdbot_search_3_days_to_kill.json"),
+            )],
+        );
+        server.route(
+            "/titles:batchGet",
+            &[(
+                200,
+                include_str!("fetch_imdb/fixtures/freeimdb_batch_get.json"),
+            )],
+        );
+        server.route(
+            "/titles/tt2172934",
+            &[(
+                200,
+                include_str!("fetch_imdb/fixtures/freeimdb_title_3_days_to_kill.json"),
+            )],
+        );
+    }
+
+    fn medias() -> Vec<Media> {
+        vec![
+            Media::from(PathBuf::from("black.mirror.s01.e01.480p.web-dl.x264.mkv")),
+            Media::from(PathBuf::from("3.days.to.kill.2014.720p.mkv")),
+        ]
+    }
+
+    #[tokio::test]
+    async fn fresh_responses_are_not_fetched_again() {
+        let server = MokeServer::start().await;
+        route_fixtures(&server);
+        let (_temp_dir, _db, providers) = setup(&server);
+
+        let first = providers.search("black mirror").await.unwrap();
+        let second = providers.search(" Black Mirror ").await.unwrap();
+        assert_eq!(first, second);
+        assert_eq!(server.hits("/search"), 1);
+
+        let first = providers.get_imdb_data_by_id("tt2172934").await.unwrap();
+        let second = providers.get_imdb_data_by_id("tt2172934").await.unwrap();
+        assert_eq!(first, second);
+        assert_eq!(server.hits("/titles/tt2172934"), 1);
+
+        // Only the title that is not cached yet is part of the batch.
+        let imdbs = providers
+            .get_imdb_data_by_ids(&["tt2172934".into(), "tt2085059".into()])
+            .await
+            .unwrap();
+        assert_eq!(imdbs.len(), 2);
+        assert_eq!(server.hits("/titles:batchGet"), 1);
+
+        // Both the search and the details of the series are cached by now.
+        let mut series = vec![medias().remove(0)];
+        let failures = providers
+            .set_imdb_data(&mut series, &RejectedMatches::default())
+            .await;
+        assert!(failures.is_empty());
+        assert_eq!(series[0].imdb.as_ref().unwrap().imdb_id, "tt2085059");
+        assert_eq!(server.hits("/search"), 1);
+        assert_eq!(server.hits("/titles:batchGet"), 1);
+    }
+
+    #[tokio::test]
+    async fn episode_lists_are_cached_and_served_offline() {
+        let server = MokeServer::start().await;
+        server.route(
+            "/titles/tt2085059/episodes",
+            &[(
+                200,
+                include_str!("fetch_imdb/fixtures/freeimdb_episodes_page_2.json"),
+            )],
+        );
+        let (_temp_dir, db, providers) = setup(&server);
+
+        let fetched = providers.get_episodes("tt2085059").await.unwrap();
+        assert_eq!(fetched.len(), 1);
+        assert_eq!(fetched[0].title, "Be Right Back");
+
+        set_cache(&db, 0, true);
+        assert_eq!(providers.get_episodes("tt2085059").await.unwrap(), fetched);
+        assert_eq!(server.hits("/titles/tt2085059/episodes"), 1);
+
+        let err = providers.get_episodes("tt0000001").await.unwrap_err();
+        assert_eq!(AppError::from(err).code(), "networkUnavailable");
+    }
+
+    #[tokio::test]
+    async fn matched_series_get_their_episode_list() {
+        let server = MokeServer::start().await;
+        server.route(
+            "/titles/tt2085059/episodes",
+            &[(
+                200,
+                include_str!("fetch_imdb/fixtures/freeimdb_episodes_page_2.json"),
+            )],
+        );
+        let (_temp_dir, db, providers) = setup(&server);
+
+        let series = Media {
+            imdb: Some(Imdb {
+                imdb_id: "tt2085059".into(),
+                r#type: "tvSeries".into(),
+                ..Imdb::default()
+            }),
+            ..Media::from(PathBuf::from("/s/black.mirror.s02e01.mkv"))
+        };
+        db.insert_media(&series).unwrap();
+        let imdb_ids = vec!["tt2085059".to_string()];
+        assert!(db.get_unlisted_series_imdb_ids(&[]).unwrap().is_empty());
+        assert_eq!(
+            db.get_unlisted_series_imdb_ids(&imdb_ids).unwrap(),
+            imdb_ids
+        );
+
+        providers
+            .store_new_episode_lists(&db, &imdb_ids)
+            .await
+            .unwrap();
+        providers
+            .store_new_episode_lists(&db, &imdb_ids)
+            .await
+            .unwrap();
+
+        assert!(
+            db.get_unlisted_series_imdb_ids(&imdb_ids)
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(server.hits("/titles/tt2085059/episodes"), 1);
+    }
+
+    #[tokio::test]
+    async fn refused_match_stores_no_title() {
+        let server = MokeServer::start().await;
+        let (_temp_dir, db, providers) = setup(&server);
+
+        let owner = Media {
+            imdb: Some(Imdb {
+                imdb_id: "tt2085059".into(),
+                title: "Black Mirror".into(),
+                ..Imdb::default()
+            }),
+            ..Media::from(PathBuf::from("/m/black.mirror.mkv"))
+        };
+        db.insert_media(&owner).unwrap();
+        let media_id = db
+            .insert_media(&Media::from(PathBuf::from("/m/dune.mkv")))
+            .unwrap();
+
+        let err = providers
+            .match_media(&db, media_id, "tt2085059")
+            .await
+            .unwrap_err();
+        assert_eq!(AppError::from(err).code(), "alreadyMatched");
+
+        let err = providers
+            .match_media(&db, media_id + 1, "tt1160419")
+            .await
+            .unwrap_err();
+        assert_eq!(AppError::from(err).code(), "notFound");
+
+        assert!(db.get_imdb("tt1160419").unwrap().is_none());
+        assert_eq!(
+            db.get_imdb("tt2085059").unwrap().unwrap().title,
+            "Black Mirror"
+        );
+        assert!(
+            db.get_media_by_id(media_id)
+                .unwrap()
+                .unwrap()
+                .imdb
+                .is_none()
+        );
+        assert_eq!(server.hits("/titles/tt2085059"), 0);
+        assert_eq!(server.hits("/titles/tt1160419"), 0);
+    }
+
+    #[tokio::test]
+    async fn failed_episode_lists_are_not_fetched_again() {
+        let server = MokeServer::start().await;
+        server.route("/titles/tt2085059/episodes", &[(500, "")]);
+        let (_temp_dir, db, providers) = setup(&server);
+
+        let series = Media {
+            imdb: Some(Imdb {
+                imdb_id: "tt2085059".into(),
+                r#type: "tvSeries".into(),
+                ..Imdb::default()
+            }),
+            ..Media::from(PathBuf::from("/s/black.mirror.s02e01.mkv"))
+        };
+        db.insert_media(&series).unwrap();
+        let imdb_ids = vec!["tt2085059".to_string()];
+
+        providers
+            .store_new_episode_lists(&db, &imdb_ids)
+            .await
+            .unwrap();
+        let hits = server.hits("/titles/tt2085059/episodes");
+        providers
+            .store_new_episode_lists(&db, &imdb_ids)
+            .await
+            .unwrap();
+
+        assert_eq!(server.hits("/titles/tt2085059/episodes"), hits);
+        assert!(
+            db.get_unlisted_series_imdb_ids(&imdb_ids)
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(
+            db.get_stale_episode_list_imdb_ids(unix_time() + 1).unwrap(),
+            imdb_ids
+        );
+    }
+
+    #[tokio::test]
+    async fn expired_responses_are_fetched_again() {
+        let server = MokeServer::start().await;
+        route_fixtures(&server);
+        let (_temp_dir, db, providers) = setup(&server);
+        set_cache(&db, 0, false);
+
+        providers.search("black mirror").await.unwrap();
+        providers.search("black mirror").await.unwrap();
+
+        assert_eq!(server.hits("/search"), 2);
+    }
+
+    #[tokio::test]
+    async fn expired_responses_cover_provider_failures() {
+        let server = MokeServer::start().await;
+        route_fixtures(&server);
+        server.route(
+            "/titles/tt2172934",
+            &[
+                (
+                    200,
+                    include_str!("fetch_imdb/fixtures/freeimdb_title_3_days_to_kill.json"),
+                ),
+                (500, "{}"),
+            ],
+        );
+        let (_temp_dir, db, providers) = setup(&server);
+        set_cache(&db, 0, false);
+
+        let fetched = providers.get_imdb_data_by_id("tt2172934").await.unwrap();
+        let stale = providers.get_imdb_data_by_id("tt2172934").await.unwrap();
+
+        assert_eq!(fetched, stale);
+        assert!(server.hits("/titles/tt2172934") > 1);
+    }
+
+    #[tokio::test]
+    async fn offline_mode_answers_from_cache_and_stored_titles() {
+        let server = MokeServer::start().await;
+        route_fixtures(&server);
+        let (_temp_dir, db, providers) = setup(&server);
+
+        providers.search("black mirror").await.unwrap();
+        providers.search("3 days to kill").await.unwrap();
+        providers
+            .get_imdb_data_by_ids(&["tt2085059".into()])
+            .await
+            .unwrap();
+        db.insert_imdb(&Imdb {
+            imdb_id: "tt2172934".into(),
+            r#type: "movie".into(),
+            title: "3 Days to Kill".into(),
+            year: 2014,
+            ..Imdb::default()
+        })
+        .unwrap();
+
+        set_cache(&db, 0, true);
+        let hits = server.hits("/search") + server.hits("/titles:batchGet");
+
+        let mut medias = medias();
+        let failures = providers
+            .set_imdb_data(&mut medias, &RejectedMatches::default())
+            .await;
+        assert!(failures.is_empty());
+        assert!(medias.iter().all(|media| media.imdb.is_some()));
+
+        let stored = providers.get_imdb_data_by_id("tt2172934").await.unwrap();
+        assert_eq!(stored.title, "3 Days to Kill");
+
+        let err = providers
+            .get_imdb_data_by_id("tt0000001")
+            .await
+            .unwrap_err();
+        assert_eq!(AppError::from(err).code(), "networkUnavailable");
+        let err = providers.search("coco").await.unwrap_err();
+        assert_eq!(AppError::from(err).code(), "networkUnavailable");
+
+        assert_eq!(
+            server.hits("/search")
+                + server.hits("/titles:batchGet")
+                + server.hits("/titles/tt2172934")
+                + server.hits("/titles/tt0000001"),
+            hits
+        );
+    }
 }
@@ -0,0 +1,98 @@
+use anyhow::Result;
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::data_model::Imdb;
+use crate::db::{DB, unix_time};
+use crate::settings::{self, MetadataCacheSettings};
+
+/// Cache kind of title searches, keyed by the normalised query.
+pub const SEARCH: &str = "search";
+
+/// Cache kind of title details, keyed by imdb id.
+pub const TITLE: &str = "title";
+
+/// Cache kind of episode lists, keyed by the imdb id of the series.
+pub const EPISODES: &str = "episodes";
+
+/// Where provider responses are kept between lookups. Any [`DB`] can serve as
+/// one; the settings are read on every lookup so changes apply right away.
+pub trait MetadataCache: Send + Sync {
+    fn settings(&self) -> Result<MetadataCacheSettings>;
+
+    /// The response stored under `kind` and `key`, and the unix time it was
+    /// fetched at.
+    fn get(&self, kind: &str, key: &str) -> Result<Option<(String, i64)>>;
+
+    fn set(&self, kind: &str, key: &str, response: &str, fetched_at: i64) -> Result<()>;
+
+    /// Details of a title already saved in the library.
+    fn stored_imdb(&self, imdb_id: &str) -> Result<Option<Imdb>>;
+}
+
+impl<T: DB + Send + Sync> MetadataCache for T {
+    fn settings(&self) -> Result<MetadataCacheSettings> {
+        settings::get_metadata_cache_settings(self)
+    }
+
+    fn get(&self, kind: &str, key: &str) -> Result<Option<(String, i64)>> {
+        self.get_cached_response(kind, key)
+    }
+
+    fn set(&self, kind: &str, key: &str, response: &str, fetched_at: i64) -> Result<()> {
+        self.set_cached_response(kind, key, response, fetched_at)
+    }
+
+    fn stored_imdb(&self, imdb_id: &str) -> Result<Option<Imdb>> {
+        self.get_imdb(imdb_id)
+    }
+}
+
+/// A cached response and whether it is younger than the TTL.
+pub struct Cached<T> {
+    pub value: T,
+    pub fresh: bool,
+}
+
+/// The cache only saves requests: when it cannot be read or written the
+/// lookup carries on as if the entry was missing.
+impl dyn MetadataCache + '_ {
+    pub fn lookup<T: DeserializeOwned>(
+        &self,
+        kind: &str,
+        key: &str,
+        ttl_hours: u32,
+    ) -> Option<Cached<T>> {
+        let (response, fetched_at) = self
+            .get(kind, key)
+            .inspect_err(|err| eprintln!("Failed to read cached {kind} {key}: {err}"))
+            .ok()??;
+        let value = serde_json::from_str(&response)
+            .inspect_err(|err| eprintln!("Invalid cached {kind} {key}: {err}"))
+            .ok()?;
+
+        Some(Cached {
+            value,
+            fresh: unix_time() - fetched_at < i64::from(ttl_hours) * 3600,
+        })
+    }
+
+    pub fn store<T: Serialize>(&self, kind: &str, key: &str, value: &T) {
+        let stored = serde_json::to_string(value)
+            .map_err(anyhow::Error::from)
+            .and_then(|response| self.set(kind, key, &response, unix_time()));
+        if let Err(err) = stored {
+            eprintln!("Failed to cache {kind} {key}: {err}");
+        }
+    }
+
+    pub fn stored(&self, imdb_id: &str) -> Option<Imdb> {
+        self.stored_imdb(imdb_id)
+            .inspect_err(|err| eprintln!("Failed to read stored title {imdb_id}: {err}"))
+            .ok()?
+    }
+}
+
+/// Cache key of a search, so the same title typed differently is one entry.
+pub fn search_key(query: &str) -> String {
+    query.trim().to_lowercase()
+}
@@ -1,11 +1,15 @@
-use anyhow::{Result, anyhow};
+use anyhow::Result;
+use async_trait::async_trait;
 use futures::stream::{self, StreamExt, TryStreamExt};
-use serde::Deserialize;
+use serde::{Deserialize, de::DeserializeOwned};
 use std::time::Duration;
 use tauri_plugin_http::reqwest::{Client, StatusCode};
 use tokio::time::sleep;
 
-use crate::data_model::{self, Imdb};
+use super::MetadataProvider;
+use super::matcher::is_series_type;
+use crate::data_model::{self, Imdb, ImdbEpisode};
+use crate::error::AppError;
 
 #[derive(Deserialize, Debug)]
 struct Response {
@@ -22,6 +26,7 @@ struct Title {
     #[serde(default)]
     primary_image: Option<Image>,
     start_year: Option<i32>,
+    runtime_seconds: Option<i32>,
     genres: Vec<String>,
     rating: Option<Rating>,
     plot: Option<String>,
@@ -44,6 +49,7 @@ struct Image {
 #[serde(rename_all = "camelCase")]
 struct Rating {
     aggregate_rating: f64,
+    #[serde(default)]
     vote_count: i32,
 }
 
@@ -58,9 +64,175 @@ struct Person {
 
 #[derive(Deserialize, Debug)]
 struct Country {
+    #[serde(default)]
+    code: String,
     name: String,
 }
 
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct EpisodesPage {
+    #[serde(default)]
+    episodes: Vec<EpisodeTitle>,
+    next_page_token: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct EpisodeTitle {
+    id: String,
+    title: Option<String>,
+    /// Seasons are sent as strings, and are not always numbers.
+    season: Option<String>,
+    episode_number: Option<i32>,
+    runtime_seconds: Option<i32>,
+    plot: Option<String>,
+    rating: Option<Rating>,
+    release_date: Option<ReleaseDate>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ReleaseDate {
+    year: i32,
+    month: Option<u32>,
+    day: Option<u32>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Certificates {
+    #[serde(default)]
+    certificates: Vec<Certificate>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Certificate {
+    rating: String,
+    country: Option<Country>,
+}
+
+#[derive(Deserialize, Debug)]
+struct AwardNominations {
+    stats: Option<AwardStats>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct AwardStats {
+    #[serde(default)]
+    nomination_count: i32,
+    #[serde(default)]
+    win_count: i32,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct BoxOffice {
+    worldwide_gross: Option<Money>,
+}
+
+/// The API sends 64-bit amounts as strings.
+#[derive(Deserialize, Debug)]
+struct Money {
+    amount: serde_json::Value,
+    #[serde(default)]
+    currency: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct Seasons {
+    #[serde(default)]
+    seasons: Vec<serde_json::Value>,
+}
+
+impl EpisodeTitle {
+    /// The episode, unless its season or number is unknown.
+    fn parse(self) -> Option<ImdbEpisode> {
+        Some(ImdbEpisode {
+            season: self.season?.trim().parse().ok()?,
+            episode: self.episode_number?,
+            imdb_id: self.id,
+            title: self.title.unwrap_or_default(),
+            air_date: self.release_date.map(|d| d.format()).unwrap_or_default(),
+            runtime: self.runtime_seconds.map(minutes).unwrap_or_default(),
+            plot: self.plot.unwrap_or_default(),
+            rating: self
+                .rating
+                .map(|r| r.aggregate_rating.to_string())
+                .unwrap_or_default(),
+        })
+    }
+}
+
+impl ReleaseDate {
+    /// `YYYY-MM-DD`, shortened to the parts that are known.
+    fn format(&self) -> String {
+        match (self.month, self.day) {
+            (Some(month), Some(day)) => format!("{}-{month:02}-{day:02}", self.year),
+            (Some(month), None) => format!("{}-{month:02}", self.year),
+            _ => self.year.to_string(),
+        }
+    }
+}
+
+/// Runtimes are sent in seconds and kept in minutes, rounded to the nearest.
+fn minutes(seconds: i32) -> i32 {
+    (seconds + 30) / 60
+}
+
+impl Certificates {
+    /// The US certification, or the first listed when there is none.
+    fn rated(self) -> Option<String> {
+        let is_us = |c: &Certificate| {
+            c.country
+                .as_ref()
+                .is_some_and(|country| country.code == "US")
+        };
+        let index = self.certificates.iter().position(is_us).unwrap_or(0);
+        self.certificates.into_iter().nth(index).map(|c| c.rating)
+    }
+}
+
+impl AwardNominations {
+    fn summary(&self) -> Option<String> {
+        let stats = self.stats.as_ref()?;
+        let plural = |n: i32| if n == 1 { "" } else { "s" };
+        match (stats.win_count, stats.nomination_count) {
+            (0, 0) => None,
+            (wins, 0) => Some(format!("{wins} win{}", plural(wins))),
+            (0, nominations) => Some(format!("{nominations} nomination{}", plural(nominations))),
+            (wins, nominations) => Some(format!(
+                "{wins} win{} & {nominations} nomination{}",
+                plural(wins),
+                plural(nominations)
+            )),
+        }
+    }
+}
+
+impl Money {
+    /// `$1,234,567` for dollars, `1,234,567 EUR` for other currencies.
+    fn format(&self) -> Option<String> {
+        let amount = match &self.amount {
+            serde_json::Value::String(amount) => amount.parse::<u64>().ok()?,
+            serde_json::Value::Number(amount) => amount.as_u64()?,
+            _ => return None,
+        };
+        let digits = amount.to_string();
+        let mut grouped = String::new();
+        for (i, digit) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i) % 3 == 0 {
+                grouped.push(',');
+            }
+            grouped.push(digit);
+        }
+
+        Some(match self.currency.as_str() {
+            "USD" | "" => format!("${grouped}"),
+            currency => format!("{grouped} {currency}"),
+        })
+    }
+}
+
 impl From<&Person> for data_model::Person {
     fn from(value: &Person) -> Self {
         Self {
@@ -80,6 +252,7 @@ impl From<Title> for Imdb {
         Self {
             title: value.primary_title.unwrap_or_default(),
             year: value.start_year.unwrap_or_default(),
+            runtime: value.runtime_seconds.map(minutes).unwrap_or_default(),
             genres: value.genres,
             actors: value.stars.iter().map(Into::into).collect(),
             writers: value.writers.iter().map(Into::into).collect(),
@@ -103,6 +276,7 @@ impl From<Title> for Imdb {
                 .unwrap_or_default(),
             imdb_id: value.id,
             r#type: value.title_type.unwrap_or_default(),
+            ..Self::default()
         }
     }
 }
@@ -111,102 +285,422 @@ const BATCH_SIZE: usize = 5;
 const MAX_RETRIES: u32 = 3;
 const DELAY_S: u64 = 10;
 const CONCURRENCY: usize = 4;
+const DEFAULT_BASE_URL: &str = "https://api.imdbapi.dev";
+const EPISODES_PAGE_SIZE: &str = "50";
+/// Stops following page tokens that never run out.
+const MAX_EPISODE_PAGES: usize = 50;
 
-async fn fetch_movies(client: &Client, ids: &[String]) -> Result<Response> {
-    let url = "https://api.imdbapi.dev/titles:batchGet";
-    let query: Vec<(&str, &str)> = ids.iter().map(|id| ("titleIds", id.as_str())).collect();
+/// Title details backed by api.imdbapi.dev.
+pub struct FreeImdb {
+    client: Client,
+    base_url: String,
+    retry_delay: Duration,
+}
 
-    for attempt in 1..=MAX_RETRIES {
-        match client.get(url).query(&query).send().await {
-            Ok(resp) if resp.status().is_success() => {
-                return Ok(resp.json::<Response>().await?);
-            }
-            Ok(resp) if resp.status() == StatusCode::TOO_MANY_REQUESTS => {
-                eprintln!("429 Too Many Requests — retrying after {}s", DELAY_S);
-                if attempt == MAX_RETRIES {
-                    return Err(anyhow!("429 Too Many Requests"));
+impl Default for FreeImdb {
+    fn default() -> Self {
+        Self::new(DEFAULT_BASE_URL, Duration::from_secs(DELAY_S))
+    }
+}
+
+impl FreeImdb {
+    /// `retry_delay` is how long to back off after a `429 Too Many Requests`.
+    pub fn new(base_url: impl Into<String>, retry_delay: Duration) -> Self {
+        Self {
+            client: Client::new(),
+            base_url: base_url.into(),
+            retry_delay,
+        }
+    }
+
+    async fn get_json<T: DeserializeOwned>(&self, url: &str, query: &[(&str, &str)]) -> Result<T> {
+        for attempt in 1..=MAX_RETRIES {
+            match self.client.get(url).query(query).send().await {
+                Ok(resp) if resp.status().is_success() => {
+                    return Ok(resp.json::<T>().await?);
                 }
-                sleep(Duration::from_secs(DELAY_S)).await;
-            }
-            Ok(resp) => {
-                eprintln!("Request failed: {}", resp.status());
-                if attempt == MAX_RETRIES {
-                    return Err(resp.error_for_status().unwrap_err().into());
+                Ok(resp) if resp.status() == StatusCode::TOO_MANY_REQUESTS => {
+                    eprintln!(
+                        "429 Too Many Requests — retrying after {}s",
+                        self.retry_delay.as_secs()
+                    );
+                    if attempt == MAX_RETRIES {
+                        return Err(AppError::RateLimited("429 Too Many Requests".into()).into());
+                    }
+                    sleep(self.retry_delay).await;
                 }
-            }
-            Err(err) => {
-                eprintln!("Network error: {}", err);
-                if attempt == MAX_RETRIES {
-                    return Err(err.into());
+                Ok(resp) => {
+                    eprintln!("Request failed: {}", resp.status());
+                    if attempt == MAX_RETRIES {
+                        return Err(resp.error_for_status().unwrap_err().into());
+                    }
+                }
+                Err(err) => {
+                    eprintln!("Network error: {}", err);
+                    if attempt == MAX_RETRIES {
+                        return Err(err.into());
+                    }
                 }
             }
+
+            eprintln!("Retry {}/{}", attempt, MAX_RETRIES);
         }
 
-        eprintln!("Retry {}/{}", attempt, MAX_RETRIES);
+        unreachable!("Loop must return or error out before reaching here")
     }
 
-    unreachable!("Loop must return or error out before reaching here")
-}
-
-pub async fn process_movies(movie_ids: Vec<String>) -> Result<Vec<Imdb>> {
-    let client = Client::builder().build()?;
+    async fn fetch_movies(&self, ids: &[String]) -> Result<Response> {
+        let url = format!("{}/titles:batchGet", self.base_url);
+        let query: Vec<(&str, &str)> = ids.iter().map(|id| ("titleIds", id.as_str())).collect();
+        self.get_json(&url, &query).await
+    }
 
-    let batches = movie_ids
-        .chunks(BATCH_SIZE)
-        .map(|chunk| chunk.to_vec())
-        .collect::<Vec<_>>();
+    /// A detail of a title that is missing from the title itself. These
+    /// lookups are best effort: a failure leaves the field empty.
+    async fn fetch_detail<T: DeserializeOwned>(&self, id: &str, detail: &str) -> Option<T> {
+        let url = format!("{}/titles/{id}/{detail}", self.base_url);
+        self.get_json(&url, &[])
+            .await
+            .inspect_err(|err| eprintln!("Failed to fetch {detail} of {id}: {err}"))
+            .ok()
+    }
 
-    let imdbs = stream::iter(batches)
-        .map(|ids| {
-            let client = client.clone();
-            async move {
-                let movies = fetch_movies(&client, &ids).await?;
-                Ok::<Vec<Imdb>, anyhow::Error>(movies.titles.into_iter().map(Imdb::from).collect())
+    /// Completes a title with its certification, awards, box office and
+    /// number of seasons.
+    async fn enrich(&self, mut imdb: Imdb) -> Imdb {
+        let id = imdb.imdb_id.as_str();
+        let seasons = async {
+            if is_series_type(&imdb.r#type) {
+                self.fetch_detail::<Seasons>(id, "seasons").await
+            } else {
+                None
             }
-        })
-        .buffer_unordered(CONCURRENCY)
-        .try_collect::<Vec<_>>()
-        .await?
-        .into_iter()
-        .flatten()
-        .collect();
+        };
+        let (certificates, awards, box_office, seasons) = futures::join!(
+            self.fetch_detail::<Certificates>(id, "certificates"),
+            self.fetch_detail::<AwardNominations>(id, "awardNominations"),
+            self.fetch_detail::<BoxOffice>(id, "boxOffice"),
+            seasons,
+        );
 
-    Ok(imdbs)
-}
+        imdb.rated = certificates
+            .and_then(Certificates::rated)
+            .unwrap_or_default();
+        imdb.awards = awards.and_then(|a| a.summary()).unwrap_or_default();
+        imdb.box_office = box_office
+            .and_then(|b| b.worldwide_gross)
+            .and_then(|gross| gross.format())
+            .unwrap_or_default();
+        imdb.total_seasons = seasons.map(|s| s.seasons.len() as i32).unwrap_or_default();
+        imdb
+    }
 
-pub async fn get_imdb_data_by_id(id: &str) -> Result<Imdb> {
-    let client = Client::new();
+    pub async fn process_movies(&self, movie_ids: &[String]) -> Result<Vec<Imdb>> {
+        let batches = movie_ids
+            .chunks(BATCH_SIZE)
+            .map(|chunk| chunk.to_vec())
+            .collect::<Vec<_>>();
 
-    let url = format!("https://api.imdbapi.dev/titles/{id}");
+        let imdbs = stream::iter(batches)
+            .map(|ids| async move {
+                let movies = self.fetch_movies(&ids).await?;
+                let imdbs = movies
+                    .titles
+                    .into_iter()
+                    .map(|title| self.enrich(title.into()));
+                Ok::<Vec<Imdb>, anyhow::Error>(futures::future::join_all(imdbs).await)
+            })
+            .buffer_unordered(CONCURRENCY)
+            .try_collect::<Vec<_>>()
+            .await?
+            .into_iter()
+            .flatten()
+            .collect();
 
-    for attempt in 1..=MAX_RETRIES {
-        match client.get(&url).send().await {
-            Ok(resp) if resp.status().is_success() => {
-                return Ok(resp.json::<Title>().await?.into());
-            }
-            Ok(resp) if resp.status() == StatusCode::TOO_MANY_REQUESTS => {
-                eprintln!("429 Too Many Requests — retrying after {}s", DELAY_S);
-                if attempt == MAX_RETRIES {
-                    return Err(anyhow!("429 Too Many Requests"));
-                }
-                sleep(Duration::from_secs(DELAY_S)).await;
+        Ok(imdbs)
+    }
+
+    /// Every episode of a series, following the pages of the list.
+    pub async fn get_episodes(&self, series_id: &str) -> Result<Vec<ImdbEpisode>> {
+        let url = format!("{}/titles/{series_id}/episodes", self.base_url);
+        let mut episodes = vec![];
+        let mut page_token: Option<String> = None;
+
+        for _ in 0..MAX_EPISODE_PAGES {
+            let mut query = vec![("pageSize", EPISODES_PAGE_SIZE)];
+            if let Some(token) = &page_token {
+                query.push(("pageToken", token.as_str()));
             }
-            Ok(resp) => {
-                eprintln!("Request failed: {}", resp.status());
-                if attempt == MAX_RETRIES {
-                    return Err(resp.error_for_status().unwrap_err().into());
-                }
+            let page = self.get_json::<EpisodesPage>(&url, &query).await?;
+
+            episodes.extend(page.episodes.into_iter().filter_map(EpisodeTitle::parse));
+            match page.next_page_token.filter(|token| !token.is_empty()) {
+                Some(token) => page_token = Some(token),
+                None => break,
             }
-            Err(err) => {
-                eprintln!("Network error: {}", err);
-                if attempt == MAX_RETRIES {
-                    return Err(err.into());
-                }
+        }
+
+        Ok(episodes)
+    }
+
+    pub async fn get_imdb_data_by_id(&self, id: &str) -> Result<Imdb> {
+        let url = format!("{}/titles/{id}", self.base_url);
+        let title = self.get_json::<Title>(&url, &[]).await?;
+        Ok(self.enrich(title.into()).await)
+    }
+}
+
+#[async_trait]
+impl MetadataProvider for FreeImdb {
+    fn name(&self) -> &str {
+        "freeimdb"
+    }
+
+    async fn get_by_id(&self, id: &str) -> Result<Imdb> {
+        self.get_imdb_data_by_id(id).await
+    }
+
+    async fn get_by_ids(&self, ids: &[String]) -> Result<Vec<Imdb>> {
+        self.process_movies(ids).await
+    }
+
+    async fn get_episodes(&self, id: &str) -> Result<Vec<ImdbEpisode>> {
+        FreeImdb::get_episodes(self, id).await
+    }
+}
+
+#[cfg(test)]
+mod moke_api_test {
+    use super::*;
+    use crate::fetch_imdb::moke_server::MokeServer;
+
+    const TITLE: &str = include_str!("fixtures/freeimdb_title_3_days_to_kill.json");
+    const BATCH: &str = include_str!("fixtures/freeimdb_batch_get.json");
+    const CERTIFICATES: &str = include_str!("fixtures/freeimdb_certificates.json");
+    const AWARDS: &str = include_str!("fixtures/freeimdb_award_nominations.json");
+    const BOX_OFFICE: &str = include_str!("fixtures/freeimdb_box_office.json");
+    const SEASONS: &str = include_str!("fixtures/freeimdb_seasons.json");
+
+    fn ids(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn get_imdb_data_by_id_maps_title() {
+        let server = MokeServer::start().await;
+        server.route("/titles/tt2172934", &[(200, TITLE)]);
+
+        let imdb = FreeImdb::new(server.url(), Duration::ZERO)
+            .get_imdb_data_by_id("tt2172934")
+            .await
+            .unwrap();
+
+        assert_eq!(imdb.imdb_id, "tt2172934");
+        assert_eq!(imdb.title, "3 Days to Kill");
+        assert_eq!(imdb.year, 2014);
+        assert_eq!(imdb.r#type, "movie");
+        assert_eq!(imdb.imdb_rating, "6.2");
+        assert_eq!(imdb.imdb_votes, 101234);
+        assert_eq!(imdb.genres, vec!["Action", "Drama", "Thriller"]);
+        assert_eq!(imdb.countries, vec!["United States"]);
+        assert_eq!(imdb.actors.len(), 2);
+        assert_eq!(imdb.directors[0].name, "McG");
+        assert_eq!(imdb.writers[0].id, "nm0000108");
+        assert_eq!(imdb.runtime, 117);
+    }
+
+    #[tokio::test]
+    async fn completes_titles_with_their_details() {
+        let server = MokeServer::start().await;
+        server.route("/titles/tt2172934", &[(200, TITLE)]);
+        server.route("/titles/tt2172934/certificates", &[(200, CERTIFICATES)]);
+        server.route("/titles/tt2172934/awardNominations", &[(200, AWARDS)]);
+        server.route("/titles/tt2172934/boxOffice", &[(200, BOX_OFFICE)]);
+        server.route("/titles:batchGet", &[(200, BATCH)]);
+        server.route("/titles/tt2085059/seasons", &[(200, SEASONS)]);
+
+        let provider = FreeImdb::new(server.url(), Duration::ZERO);
+        let movie = provider.get_imdb_data_by_id("tt2172934").await.unwrap();
+
+        assert_eq!(movie.rated, "PG-13");
+        assert_eq!(movie.awards, "1 win & 4 nominations");
+        assert_eq!(movie.box_office, "$52,597,999");
+        assert_eq!(movie.total_seasons, 0);
+        assert_eq!(server.hits("/titles/tt2172934/seasons"), 0);
+
+        let imdbs = provider
+            .process_movies(&ids(&["tt2172934", "tt2085059"]))
+            .await
+            .unwrap();
+        let series = imdbs.iter().find(|i| i.imdb_id == "tt2085059").unwrap();
+
+        assert_eq!(series.total_seasons, 3);
+        assert_eq!(series.rated, "");
+    }
+
+    #[tokio::test]
+    async fn missing_details_leave_fields_empty() {
+        let server = MokeServer::start().await;
+        server.route("/titles/tt2172934", &[(200, TITLE)]);
+        server.route("/titles/tt2172934/boxOffice", &[(200, "{}")]);
+
+        let imdb = FreeImdb::new(server.url(), Duration::ZERO)
+            .get_imdb_data_by_id("tt2172934")
+            .await
+            .unwrap();
+
+        assert_eq!(imdb.title, "3 Days to Kill");
+        assert_eq!(imdb.rated, "");
+        assert_eq!(imdb.awards, "");
+        assert_eq!(imdb.box_office, "");
+        assert_eq!(
+            server.hits("/titles/tt2172934/certificates"),
+            MAX_RETRIES as usize
+        );
+    }
+
+    #[tokio::test]
+    async fn lists_episodes_across_pages() {
+        let server = MokeServer::start().await;
+        server.route(
+            "/titles/tt2085059/episodes?pageSize=50",
+            &[(200, include_str!("fixtures/freeimdb_episodes_page_1.json"))],
+        );
+        server.route(
+            "/titles/tt2085059/episodes?pageSize=50&pageToken=page-2",
+            &[(200, include_str!("fixtures/freeimdb_episodes_page_2.json"))],
+        );
+
+        let episodes = FreeImdb::new(server.url(), Duration::ZERO)
+            .get_episodes("tt2085059")
+            .await
+            .unwrap();
+
+        assert_eq!(server.hits("/titles/tt2085059/episodes"), 2);
+        // The episode without a numbered season is left out.
+        assert_eq!(episodes.len(), 3);
+        assert_eq!(
+            episodes[0],
+            ImdbEpisode {
+                imdb_id: "tt2089051".into(),
+                season: 1,
+                episode: 1,
+                title: "The National Anthem".into(),
+                air_date: "2011-12-04".into(),
+                runtime: 44,
+                plot: "Prime Minister Michael Callow faces a shocking dilemma when a much-loved member of the royal family is kidnapped.".into(),
+                rating: "7.5".into(),
             }
+        );
+        assert_eq!(episodes[1].plot, "");
+        assert_eq!(episodes[2].season, 2);
+        assert_eq!(episodes[2].air_date, "2013-02");
+        assert_eq!(episodes[2].rating, "");
+    }
+
+    #[test]
+    fn formats_box_office_amounts() {
+        let money = |amount: serde_json::Value, currency: &str| Money {
+            amount,
+            currency: currency.into(),
+        };
+
+        let cases = [
+            (money("52597999".into(), "USD"), Some("$52,597,999")),
+            (money(950.into(), "USD"), Some("$950")),
+            (money("1000".into(), "EUR"), Some("1,000 EUR")),
+            (money("n/a".into(), "USD"), None),
+        ];
+        for (money, expected) in cases {
+            assert_eq!(money.format().as_deref(), expected, "{money:?}");
         }
+    }
+
+    #[tokio::test]
+    async fn retries_after_too_many_requests() {
+        let server = MokeServer::start().await;
+        server.route(
+            "/titles/tt2172934",
+            &[(429, "{}"), (429, "{}"), (200, TITLE)],
+        );
 
-        eprintln!("Retry {}/{}", attempt, MAX_RETRIES);
+        let imdb = FreeImdb::new(server.url(), Duration::ZERO)
+            .get_imdb_data_by_id("tt2172934")
+            .await
+            .unwrap();
+
+        assert_eq!(imdb.title, "3 Days to Kill");
+        assert_eq!(server.hits("/titles/tt2172934"), 3);
+    }
+
+    #[tokio::test]
+    async fn backs_off_after_too_many_requests() {
+        let server = MokeServer::start().await;
+        server.route("/titles:batchGet", &[(429, "{}"), (200, BATCH)]);
+
+        let delay = Duration::from_millis(200);
+        let started = tokio::time::Instant::now();
+        let imdbs = FreeImdb::new(server.url(), delay)
+            .process_movies(&ids(&["tt2172934", "tt2085059"]))
+            .await
+            .unwrap();
+
+        assert!(started.elapsed() >= delay);
+        assert_eq!(imdbs.len(), 2);
+        assert_eq!(server.hits("/titles:batchGet"), 2);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_retries_on_too_many_requests() {
+        let server = MokeServer::start().await;
+        server.route("/titles/tt2172934", &[(429, "{}")]);
+
+        let result = FreeImdb::new(server.url(), Duration::ZERO)
+            .get_imdb_data_by_id("tt2172934")
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(server.hits("/titles/tt2172934"), MAX_RETRIES as usize);
+    }
+
+    #[tokio::test]
+    async fn retries_on_server_error() {
+        let server = MokeServer::start().await;
+        server.route("/titles:batchGet", &[(503, "{}"), (200, BATCH)]);
+
+        let imdbs = FreeImdb::new(server.url(), Duration::ZERO)
+            .process_movies(&ids(&["tt2172934", "tt2085059"]))
+            .await
+            .unwrap();
+
+        assert_eq!(imdbs.len(), 2);
+        assert_eq!(server.hits("/titles:batchGet"), 2);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_retries_on_server_error() {
+        let server = MokeServer::start().await;
+        server.route("/titles:batchGet", &[(500, "{}")]);
+
+        let result = FreeImdb::new(server.url(), Duration::ZERO)
+            .process_movies(&ids(&["tt2172934"]))
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(server.hits("/titles:batchGet"), MAX_RETRIES as usize);
     }
 
-    unreachable!("Loop must return or error out before reaching here")
+    #[tokio::test]
+    async fn process_movies_splits_into_batches() {
+        let server = MokeServer::start().await;
+        server.route("/titles:batchGet", &[(200, BATCH)]);
+
+        let movie_ids: Vec<String> = (0..BATCH_SIZE * 2 + 1).map(|i| format!("tt{i}")).collect();
+        FreeImdb::new(server.url(), Duration::ZERO)
+            .process_movies(&movie_ids)
+            .await
+            .unwrap();
+
+        assert_eq!(server.hits("/titles:batchGet"), 3);
+    }
 }
@@ -1,10 +1,9 @@
 use anyhow::{Result, anyhow};
-use futures::future::join_all;
+use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use tauri_plugin_http::reqwest::Client;
 
-use super::freeimdb;
-use crate::data_model::Media;
+use super::{MetadataProvider, SearchResult};
 
 #[derive(Debug, Serialize, Deserialize)]
 struct MovieSearchResult {
@@ -15,118 +14,129 @@ struct MovieSearchResult {
 
 #[derive(Debug, Serialize, Deserialize)]
 struct SearchedMovie {
+    #[serde(rename = "#TITLE")]
+    title: Option<String>,
     #[serde(rename = "#YEAR")]
     year: Option<i32>,
     #[serde(rename = "#IMDB_ID")]
     imdb_id: String,
 }
 
-async fn get_imdb_id(client: &Client, media: &Media) -> Result<String> {
-    let result: MovieSearchResult = client
-        .get("https://imdb.iamidiotareyoutoo.com/search")
-        .query(&[("q", &media.name)])
-        .send()
-        .await?
-        .json()
-        .await?;
-
-    if !result.ok {
-        return Err(anyhow!("API error: {}", result.error_code));
+impl From<SearchedMovie> for SearchResult {
+    fn from(value: SearchedMovie) -> Self {
+        Self {
+            imdb_id: value.imdb_id,
+            title: value.title,
+            year: value.year,
+        }
     }
+}
 
-    let movies = result.description;
-    let matched = media
-        .year
-        .and_then(|year| movies.iter().find(|m| m.year == Some(year)))
-        .or_else(|| movies.first());
+const DEFAULT_BASE_URL: &str = "https://imdb.iamidiotareyoutoo.com";
 
-    matched
-        .map(|m| m.imdb_id.clone())
-        .ok_or_else(|| anyhow!("No movies found"))
+/// Title search backed by imdb.iamidiotareyoutoo.com.
+pub struct Imdbot {
+    client: Client,
+    base_url: String,
 }
 
-pub async fn set_imdb_data(medias: &mut [Media]) {
-    let client = Client::new();
+impl Default for Imdbot {
+    fn default() -> Self {
+        Self::new(DEFAULT_BASE_URL)
+    }
+}
 
-    let results = join_all(medias.iter_mut().map(|media| {
-        let client = client.clone();
-        async move {
-            get_imdb_id(&client, media)
-                .await
-                .map(|imdb_id| (imdb_id, media))
+impl Imdbot {
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            client: Client::new(),
+            base_url: base_url.into(),
         }
-    }))
-    .await;
-
-    let mut pairs: Vec<_> = results.into_iter().filter_map(Result::ok).collect();
+    }
+}
 
-    let ids: Vec<_> = pairs.iter().map(|(id, _)| id.clone()).collect();
+#[async_trait]
+impl MetadataProvider for Imdbot {
+    fn name(&self) -> &str {
+        "imdbot"
+    }
 
-    match freeimdb::process_movies(ids).await {
-        Ok(imdbs) => {
-            for imdb in imdbs {
-                if let Some((_, media)) = pairs.iter_mut().find(|(id, _)| id == &imdb.imdb_id) {
-                    media.imdb = Some(imdb);
-                }
-            }
-        }
-        Err(err) => {
-            eprintln!("Failed to fetch movies batch: {err}");
+    async fn search(&self, query: &str) -> Result<Vec<SearchResult>> {
+        let result: MovieSearchResult = self
+            .client
+            .get(format!("{}/search", self.base_url))
+            .query(&[("q", query)])
+            .send()
+            .await?
+            .json()
+            .await?;
+
+        if !result.ok {
+            return Err(anyhow!("API error: {}", result.error_code));
         }
+
+        Ok(result.description.into_iter().map(Into::into).collect())
     }
 }
 
 #[cfg(test)]
-mod real_api_test {
+mod moke_api_test {
     use super::*;
-    use crate::data_model::Media;
-    use std::path::PathBuf;
-    use tauri_plugin_http::reqwest::Client;
+    use crate::fetch_imdb::moke_server::MokeServer;
 
     #[tokio::test]
-    async fn fetch_movie_data() {
-        let media = Media::from(PathBuf::from(
-            "3.days.to.kill.2014.extended.720p.farsi.dubbed.film2media.mkv",
-        ));
-        let client = Client::new();
-
-        let result = get_imdb_id(&client, &media).await;
-        let imdb_id = result.unwrap();
-        assert_eq!(imdb_id, "tt2172934");
+    async fn search_maps_results() {
+        let server = MokeServer::start().await;
+        server.route(
+            "/search?q=3+days+to+kill",
+            &[(
+                200,
+                include_str!("fixtures/imdbot_search_3_days_to_kill.json"),
+            )],
+        );
+
+        let results = Imdbot::new(server.url())
+            .search("3 days to kill")
+            .await
+            .unwrap();
+
+        assert_eq!(
+            results,
+            vec![
+                SearchResult {
+                    imdb_id: "tt9000001".into(),
+                    title: Some("3 Days to Kill: Behind the Scenes".into()),
+                    year: Some(2015),
+                },
+                SearchResult {
+                    imdb_id: "tt2172934".into(),
+                    title: Some("3 Days to Kill".into()),
+                    year: Some(2014),
+                },
+            ]
+        );
     }
 
     #[tokio::test]
-    async fn fetch_series_data() {
-        let media = Media::from(PathBuf::from("black.mirror.s01.e01.480p.web-dl.x264.mkv"));
-        let client = Client::new();
+    async fn search_reports_api_error() {
+        let server = MokeServer::start().await;
+        server.route(
+            "/search",
+            &[(200, include_str!("fixtures/imdbot_search_error.json"))],
+        );
+
+        let result = Imdbot::new(server.url()).search("anything").await;
 
-        let result = get_imdb_id(&client, &media).await;
-        let imdb_id = result.unwrap();
-        assert_eq!(imdb_id, "tt2085059");
+        assert!(result.is_err());
     }
 
     #[tokio::test]
-    async fn set_imdb_data() {
-        let m1 = Media::from(PathBuf::from("black.mirror.s01.e01.480p.web-dl.x264.mkv"));
-        let m2 = Media::from(PathBuf::from(
-            "3.days.to.kill.2014.extended.720p.farsi.dubbed.film2media.mkv",
-        ));
-
-        let mut medias = vec![m1, m2];
-        super::set_imdb_data(&mut medias).await;
-
-        let new_m1 = &medias[0];
-        assert_eq!(new_m1.name, "black mirror");
-
-        let imdb = new_m1.imdb.as_ref().unwrap();
-        assert_eq!(imdb.r#type, "tvSeries");
-        assert_eq!(imdb.year, 2011);
+    async fn search_fails_on_unexpected_body() {
+        let server = MokeServer::start().await;
+        server.route("/search", &[(502, "bad gateway")]);
 
-        let new_m2 = &medias[1];
-        assert_eq!(new_m2.name, "3 days to kill");
+        let result = Imdbot::new(server.url()).search("anything").await;
 
-        let imdb = new_m2.imdb.as_ref().unwrap();
-        assert_eq!(imdb.r#type, "movie");
-        assert_eq!(imdb.year, 2014);
+        assert!(result.is_err());
     }
 }
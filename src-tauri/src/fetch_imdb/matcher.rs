@@ -0,0 +1,426 @@
+use std::collections::HashMap;
+
+use super::SearchResult;
+use crate::data_model::{Imdb, Media};
+
+/// Matches at least this confident are attached to the media; weaker ones are
+/// kept as suggestions for review.
+pub const MIN_CONFIDENCE: u8 = 80;
+
+/// Search results whose details are fetched and scored for each media.
+pub const MAX_CANDIDATES: usize = 3;
+
+/// Search results listed for the user to pick from.
+pub const LISTED_CANDIDATES: usize = 10;
+
+// A wrong year or type alone keeps an exact title below `MIN_CONFIDENCE`,
+// while a title missing only the year still passes.
+const TITLE_WEIGHT: f64 = 0.4;
+const YEAR_WEIGHT: f64 = 0.3;
+const TYPE_WEIGHT: f64 = 0.25;
+const VOTES_WEIGHT: f64 = 0.05;
+
+/// Score given to a criterion one side knows nothing about.
+const UNKNOWN: f64 = 0.5;
+
+/// Votes at which a title counts as fully established.
+const WELL_KNOWN_VOTES: f64 = 100_000.0;
+
+const SERIES_TYPES: &[&str] = &["tvSeries", "tvMiniSeries"];
+
+/// What a search is looking for: a media, or a title typed by the user.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Wanted<'a> {
+    pub title: &'a str,
+    pub year: Option<i32>,
+    /// Whether a series is wanted, when that is known.
+    pub series: Option<bool>,
+}
+
+impl<'a> From<&'a Media> for Wanted<'a> {
+    fn from(media: &'a Media) -> Self {
+        Self {
+            title: &media.name,
+            year: media.year,
+            series: Some(media.is_series()),
+        }
+    }
+}
+
+/// A search result, completed with its details when they could be fetched.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    pub imdb_id: String,
+    pub title: String,
+    pub year: Option<i32>,
+    pub r#type: Option<String>,
+    pub votes: Option<i32>,
+}
+
+impl Candidate {
+    pub fn new(result: &SearchResult, details: Option<&Imdb>) -> Self {
+        match details {
+            Some(imdb) => Self {
+                imdb_id: imdb.imdb_id.clone(),
+                title: imdb.title.clone(),
+                year: Some(imdb.year).filter(|year| *year > 0),
+                r#type: Some(imdb.r#type.clone()).filter(|t| !t.is_empty()),
+                votes: Some(imdb.imdb_votes),
+            },
+            None => Self {
+                imdb_id: result.imdb_id.clone(),
+                title: result.title.clone().unwrap_or_default(),
+                year: result.year,
+                r#type: None,
+                votes: None,
+            },
+        }
+    }
+
+    /// Whether the title is a series, when its type is known.
+    pub fn is_series(&self) -> Option<bool> {
+        self.r#type.as_deref().map(is_series_type)
+    }
+}
+
+/// The first search results of a media, completed with the fetched details.
+pub fn candidates(results: &[SearchResult], details: &HashMap<&str, &Imdb>) -> Vec<Candidate> {
+    results
+        .iter()
+        .take(MAX_CANDIDATES)
+        .map(|result| Candidate::new(result, details.get(result.imdb_id.as_str()).copied()))
+        .collect()
+}
+
+pub fn is_series_type(r#type: &str) -> bool {
+    SERIES_TYPES.contains(&r#type)
+}
+
+/// How well `candidate` fits what is wanted, from 0 to 100.
+pub fn score(wanted: &Wanted, candidate: &Candidate) -> u8 {
+    let title = title_similarity(wanted.title, &candidate.title);
+
+    let year = match (wanted.year, candidate.year) {
+        (Some(wanted), Some(found)) => match (wanted - found).abs() {
+            0 => 1.0,
+            // Festival and release years often differ by one.
+            1 => 0.7,
+            2 => 0.3,
+            _ => 0.0,
+        },
+        _ => UNKNOWN,
+    };
+
+    let r#type = match (wanted.series, candidate.is_series()) {
+        (Some(wanted), Some(found)) if wanted == found => 1.0,
+        (Some(_), Some(_)) => 0.0,
+        _ => UNKNOWN,
+    };
+
+    let votes = match candidate.votes {
+        Some(votes) => (f64::from(votes.max(0)) + 1.0).log10() / WELL_KNOWN_VOTES.log10(),
+        None => UNKNOWN,
+    }
+    .min(1.0);
+
+    let confidence =
+        TITLE_WEIGHT * title + YEAR_WEIGHT * year + TYPE_WEIGHT * r#type + VOTES_WEIGHT * votes;
+
+    (confidence * 100.0).round().clamp(0.0, 100.0) as u8
+}
+
+/// The best scoring candidate and its confidence; the earlier result wins a
+/// tie, as providers list the most relevant first.
+pub fn best_match<'a>(media: &Media, candidates: &'a [Candidate]) -> Option<(&'a Candidate, u8)> {
+    let wanted = Wanted::from(media);
+    candidates
+        .iter()
+        .map(|candidate| (candidate, score(&wanted, candidate)))
+        .rev()
+        .max_by_key(|(_, confidence)| *confidence)
+}
+
+/// Sørensen–Dice coefficient of the character pairs of both titles, ignoring
+/// case, punctuation and spacing.
+fn title_similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (normalize(a), normalize(b));
+    if a == b {
+        return 1.0;
+    }
+
+    let pairs = |s: &str| -> Vec<(char, char)> {
+        let chars: Vec<char> = s.chars().collect();
+        chars.windows(2).map(|w| (w[0], w[1])).collect()
+    };
+    let (a, mut b) = (pairs(&a), pairs(&b));
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+
+    let total = a.len() + b.len();
+    let mut shared = 0;
+    for pair in a {
+        if let Some(i) = b.iter().position(|other| *other == pair) {
+            b.swap_remove(i);
+            shared += 1;
+        }
+    }
+
+    (2 * shared) as f64 / total as f64
+}
+
+fn normalize(title: &str) -> String {
+    title
+        .to_lowercase()
+        .replace('&', "and")
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests_matcher {
+    use super::*;
+    use crate::data_model::Season;
+
+    fn media(name: &str, year: Option<i32>, series: bool) -> Media {
+        Media {
+            name: name.into(),
+            year,
+            seasons: if series {
+                vec![Season {
+                    id: 0,
+                    number: 1,
+                    watched: false,
+                    episodes: vec![],
+                }]
+            } else {
+                vec![]
+            },
+            ..Media::default()
+        }
+    }
+
+    fn candidate(
+        imdb_id: &str,
+        title: &str,
+        year: Option<i32>,
+        r#type: Option<&str>,
+        votes: Option<i32>,
+    ) -> Candidate {
+        Candidate {
+            imdb_id: imdb_id.into(),
+            title: title.into(),
+            year,
+            r#type: r#type.map(str::to_string),
+            votes,
+        }
+    }
+
+    #[test]
+    fn title_similarity_ignores_case_and_punctuation() {
+        let cases = [
+            ("3 days to kill", "3 Days to Kill", 1.0, 1.0),
+            ("spider man", "Spider-Man", 1.0, 1.0),
+            ("fast and furious", "Fast & Furious", 1.0, 1.0),
+            (
+                "3 days to kill",
+                "3 Days to Kill: Behind the Scenes",
+                0.5,
+                0.7,
+            ),
+            ("black mirror", "Mirror Mirror", 0.3, 0.6),
+            ("coco", "Zodiac", 0.0, 0.1),
+            ("", "Coco", 0.0, 0.0),
+        ];
+
+        for (a, b, min, max) in cases {
+            let similarity = title_similarity(a, b);
+            assert!((min..=max).contains(&similarity), "{a} / {b}: {similarity}");
+        }
+    }
+
+    #[test]
+    fn scores_each_criterion() {
+        let cases = [
+            // (case, media, candidate, min, max)
+            (
+                "exact movie with year and votes",
+                media("3 days to kill", Some(2014), false),
+                candidate(
+                    "tt1",
+                    "3 Days to Kill",
+                    Some(2014),
+                    Some("movie"),
+                    Some(101_234),
+                ),
+                95,
+                100,
+            ),
+            (
+                "series without year",
+                media("black mirror", None, true),
+                candidate(
+                    "tt1",
+                    "Black Mirror",
+                    Some(2011),
+                    Some("tvSeries"),
+                    Some(692_001),
+                ),
+                85,
+                90,
+            ),
+            (
+                "year off by one",
+                media("coco", Some(2018), false),
+                candidate("tt1", "Coco", Some(2017), Some("movie"), Some(100_000)),
+                MIN_CONFIDENCE,
+                95,
+            ),
+            (
+                "remake from another decade",
+                media("dune", Some(2021), false),
+                candidate("tt1", "Dune", Some(1984), Some("movie"), Some(100_000)),
+                0,
+                MIN_CONFIDENCE - 1,
+            ),
+            (
+                "series found for a movie file",
+                media("fargo", Some(1996), false),
+                candidate("tt1", "Fargo", Some(1996), Some("tvSeries"), Some(100_000)),
+                0,
+                MIN_CONFIDENCE - 1,
+            ),
+            (
+                "details missing",
+                media("coco", None, false),
+                candidate("tt1", "Coco", None, None, None),
+                0,
+                MIN_CONFIDENCE - 1,
+            ),
+            (
+                "unrelated title",
+                media("coco", Some(2017), false),
+                candidate("tt1", "Zodiac", Some(2017), Some("movie"), Some(100_000)),
+                0,
+                65,
+            ),
+            (
+                "obscure exact title",
+                media("coco", Some(2017), false),
+                candidate("tt1", "Coco", Some(2017), Some("short"), Some(12)),
+                MIN_CONFIDENCE,
+                100,
+            ),
+        ];
+
+        for (case, media, candidate, min, max) in cases {
+            let confidence = score(&Wanted::from(&media), &candidate);
+            assert!(
+                (min..=max).contains(&confidence),
+                "{case}: {confidence} not in {min}..={max}"
+            );
+        }
+    }
+
+    #[test]
+    fn picks_the_best_candidate() {
+        let cases = [
+            // (case, media, candidates, expected imdb id)
+            (
+                "year decides between remakes",
+                media("dune", Some(2021), false),
+                vec![
+                    candidate(
+                        "tt0087182",
+                        "Dune",
+                        Some(1984),
+                        Some("movie"),
+                        Some(170_000),
+                    ),
+                    candidate(
+                        "tt1160419",
+                        "Dune",
+                        Some(2021),
+                        Some("movie"),
+                        Some(900_000),
+                    ),
+                ],
+                "tt1160419",
+            ),
+            (
+                "type decides between a show and a film",
+                media("fargo", None, true),
+                vec![
+                    candidate(
+                        "tt0116282",
+                        "Fargo",
+                        Some(1996),
+                        Some("movie"),
+                        Some(700_000),
+                    ),
+                    candidate(
+                        "tt2802850",
+                        "Fargo",
+                        Some(2014),
+                        Some("tvSeries"),
+                        Some(450_000),
+                    ),
+                ],
+                "tt2802850",
+            ),
+            (
+                "title beats an earlier extra",
+                media("3 days to kill", None, false),
+                vec![
+                    candidate(
+                        "tt9000001",
+                        "3 Days to Kill: Behind the Scenes",
+                        Some(2015),
+                        Some("video"),
+                        Some(40),
+                    ),
+                    candidate(
+                        "tt2172934",
+                        "3 Days to Kill",
+                        Some(2014),
+                        Some("movie"),
+                        Some(101_234),
+                    ),
+                ],
+                "tt2172934",
+            ),
+            (
+                "first result wins a tie",
+                media("coco", None, false),
+                vec![
+                    candidate("tt1", "Coco", None, None, None),
+                    candidate("tt2", "Coco", None, None, None),
+                ],
+                "tt1",
+            ),
+        ];
+
+        for (case, media, candidates, expected) in cases {
+            let (best, _) = best_match(&media, &candidates).unwrap();
+            assert_eq!(best.imdb_id, expected, "{case}");
+        }
+
+        assert!(best_match(&media("coco", None, false), &[]).is_none());
+    }
+
+    #[test]
+    fn unknown_type_neither_helps_nor_hurts() {
+        let wanted = |series| Wanted {
+            title: "fargo",
+            year: None,
+            series,
+        };
+        let movie = candidate("tt1", "Fargo", Some(1996), Some("movie"), Some(100_000));
+        let show = candidate("tt2", "Fargo", Some(2014), Some("tvSeries"), Some(100_000));
+
+        assert_eq!(score(&wanted(None), &movie), score(&wanted(None), &show));
+        assert!(score(&wanted(Some(true)), &show) > score(&wanted(None), &show));
+        assert!(score(&wanted(Some(true)), &movie) < score(&wanted(None), &movie));
+    }
+}
@@ -0,0 +1,115 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::TcpListener,
+};
+
+/// A canned HTTP reply: status code and JSON body.
+pub type Reply = (u16, &'static str);
+
+#[derive(Default)]
+struct Routes {
+    replies: HashMap<String, VecDeque<Reply>>,
+    hits: HashMap<String, usize>,
+}
+
+/// A local HTTP stand-in that answers each path with recorded fixtures, so the
+/// fetchers can be tested without touching the network.
+///
+/// Routes are keyed either by the full request target (`/search?q=coco`) or by
+/// the bare path; the full target wins. Replies registered for a route are
+/// served in order and the last one keeps being served once the queue runs dry.
+/// Unknown routes get a `404`.
+pub struct MokeServer {
+    url: String,
+    routes: Arc<Mutex<Routes>>,
+}
+
+impl MokeServer {
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Failed to bind moke server");
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let routes = Arc::new(Mutex::new(Routes::default()));
+
+        let shared = routes.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let routes = shared.clone();
+                tokio::spawn(async move {
+                    let (read, mut write) = stream.into_split();
+                    let mut reader = BufReader::new(read);
+
+                    let mut request_line = String::new();
+                    if reader.read_line(&mut request_line).await.is_err() {
+                        return;
+                    }
+
+                    // Drain the headers; the fetchers only send GET requests.
+                    let mut line = String::new();
+                    while reader.read_line(&mut line).await.is_ok_and(|n| n > 2) {
+                        line.clear();
+                    }
+
+                    let target = request_line.split_whitespace().nth(1).unwrap_or("/");
+                    let (status, body) = Self::next_reply(&routes, target);
+
+                    let response = format!(
+                        "HTTP/1.1 {status} MOKE\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                        body.len()
+                    );
+                    let _ = write.write_all(response.as_bytes()).await;
+                    let _ = write.shutdown().await;
+                });
+            }
+        });
+
+        Self { url, routes }
+    }
+
+    fn next_reply(routes: &Mutex<Routes>, target: &str) -> Reply {
+        let path = target.split('?').next().unwrap_or(target);
+
+        let mut routes = routes.lock().unwrap();
+        *routes.hits.entry(path.to_string()).or_default() += 1;
+
+        let key = if routes.replies.contains_key(target) {
+            target
+        } else {
+            path
+        };
+
+        match routes.replies.get_mut(key) {
+            Some(queue) if queue.len() > 1 => queue.pop_front().unwrap(),
+            Some(queue) => queue.front().copied().unwrap_or((404, "{}")),
+            None => (404, "{}"),
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn route(&self, path: &str, replies: &[Reply]) {
+        self.routes
+            .lock()
+            .unwrap()
+            .replies
+            .insert(path.to_string(), replies.iter().copied().collect());
+    }
+
+    /// Number of requests received for `path`, whatever their query string.
+    pub fn hits(&self, path: &str) -> usize {
+        self.routes
+            .lock()
+            .unwrap()
+            .hits
+            .get(path)
+            .copied()
+            .unwrap_or_default()
+    }
+}
@@ -1,10 +1,19 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, sync::Arc};
 
 use serde::Serialize;
 use tauri::{Emitter, Manager};
 
-use crate::data_model::{IdType, Media};
-use crate::db::{NumericalString, Sqlite};
+use crate::data_model::{
+    IdType, LibraryRoot, MatchCandidate, Media, MissingEpisodes, SyncJob, SyncJobStatus,
+};
+use crate::db::{ContentType, NumericalString, OrphanCleanup, OrphanReport, Sqlite};
+use crate::error::AppError;
+use crate::fetch_imdb::{LookupFailure, MetadataProviders};
+use crate::library_watcher::LibraryWatcher;
+use crate::media_scanner::SkippedPath;
+use crate::metadata_refresh::{MetadataRefresher, REFRESH_INTERVAL, RefreshReport};
+use crate::settings::{IgnoreRules, MetadataCacheSettings, MetadataRefreshSettings};
+use crate::sync_jobs::{SyncJobs, SyncPhase, SyncProgress};
 use crate::{
     data_model::Tag,
     db::{DB, FilterValues},
@@ -12,19 +21,248 @@ use crate::{
 
 mod data_model;
 mod db;
+mod error;
 mod fetch_imdb;
+mod library_watcher;
+mod media_probe;
 mod media_scanner;
 mod metadata_extractor;
+mod metadata_refresh;
+mod settings;
+mod sync_jobs;
 
 struct AppState {
     db: Sqlite,
+    providers: MetadataProviders,
+    watcher: LibraryWatcher,
+    sync_jobs: SyncJobs,
+    refresher: MetadataRefresher,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LibraryChangedBare {
+    added: Vec<String>,
+    moved: Vec<String>,
+    removed: Vec<String>,
+    skipped: Vec<SkippedPath>,
+    failures: Vec<LookupFailure>,
+    orphans: OrphanReport,
 }
 
 #[derive(Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
-struct SyncFileProgressBare {
-    inserted: usize,
+struct SyncSummaryBare {
+    job_id: IdType,
+    status: SyncJobStatus,
+    /// Medias the job looks up and inserts in the background; its end is sent
+    /// as a `sync-finished` event.
     total: usize,
+    skipped: Vec<SkippedPath>,
+    /// Medias, seasons and titles removed with the files that are gone.
+    orphans: OrphanReport,
+}
+
+fn watch_library_roots(state: &AppState) -> Result<(), AppError> {
+    let roots = state
+        .db
+        .get_library_roots()?
+        .into_iter()
+        .filter(|root| root.enabled)
+        .map(|root| PathBuf::from(root.path))
+        .collect();
+
+    state.watcher.watch_roots(roots);
+    Ok(())
+}
+
+async fn apply_library_changes(
+    paths: Vec<PathBuf>,
+    app_handle: &tauri::AppHandle,
+) -> anyhow::Result<()> {
+    let state = app_handle.state::<AppState>();
+    let db = &state.db;
+
+    let changes = library_watcher::collect_changes(db, &paths)?;
+    if changes.is_empty() {
+        return Ok(());
+    }
+
+    let missing = db
+        .get_all_files()?
+        .into_iter()
+        .filter(|file| {
+            changes
+                .removed
+                .iter()
+                .any(|path| path.as_os_str() == file.path.as_str())
+        })
+        .collect();
+    let relinked = media_scanner::relink_moved_files(db, missing, changes.added)
+        .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+
+    let orphans = media_scanner::remove_missing_files(db, &relinked.missing)
+        .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+
+    let videos = settings::get_video_extensions(db)?;
+    let mut medias =
+        metadata_extractor::read_details(metadata_extractor::get_metadata(&relinked.found), videos)
+            .await?;
+    let rejected = db.get_rejected_matches()?;
+    let failures = state.providers.set_imdb_data(&mut medias, &rejected).await;
+    db.insert_medias(&medias)?;
+    let imdb_ids: Vec<String> = medias
+        .iter()
+        .filter_map(Media::imdb_id)
+        .map(str::to_string)
+        .collect();
+    state
+        .providers
+        .store_new_episode_lists(db, &imdb_ids)
+        .await?;
+
+    let to_strings = |paths: Vec<PathBuf>| {
+        paths
+            .into_iter()
+            .map(|path| path.to_string_lossy().into_owned())
+            .collect()
+    };
+    app_handle.emit(
+        "library-changed",
+        LibraryChangedBare {
+            added: to_strings(relinked.found),
+            moved: to_strings(relinked.moved),
+            removed: to_strings(relinked.missing),
+            skipped: changes.skipped,
+            failures,
+            orphans,
+        },
+    )?;
+
+    Ok(())
+}
+
+fn emit_sync_progress(app_handle: &tauri::AppHandle, progress: SyncProgress) {
+    if let Err(err) = app_handle.emit("sync-progress", progress) {
+        eprintln!("Failed to emit sync progress: {err}");
+    }
+}
+
+/// Sends the job as it ended, completed, cancelled or failed.
+fn emit_sync_finished(app_handle: &tauri::AppHandle, job_id: IdType) {
+    let state = app_handle.state::<AppState>();
+    match state.db.get_sync_job(job_id) {
+        Ok(Some(job)) => {
+            if let Err(err) = app_handle.emit("sync-finished", job) {
+                eprintln!("Failed to emit the end of sync job {job_id}: {err}");
+            }
+        }
+        Ok(None) => {}
+        Err(err) => eprintln!("Failed to read sync job {job_id}: {err}"),
+    }
+}
+
+/// Looks up and inserts the medias of a saved job in the background.
+async fn drive_sync_job(job: SyncJob, medias: Vec<Media>, app_handle: tauri::AppHandle) {
+    let state = app_handle.state::<AppState>();
+    let job_id = job.id;
+
+    if let Err(err) = state
+        .sync_jobs
+        .drive(&state.db, &state.providers, job, medias, |progress| {
+            emit_sync_progress(&app_handle, progress)
+        })
+        .await
+    {
+        eprintln!("Sync job {job_id} failed: {err}");
+    }
+    emit_sync_finished(&app_handle, job_id);
+}
+
+/// Runs an unfinished sync job in the background, from its last committed chunk.
+async fn run_sync_job(job_id: IdType, app_handle: tauri::AppHandle) {
+    let state = app_handle.state::<AppState>();
+
+    if let Err(err) = state
+        .sync_jobs
+        .run(&state.db, &state.providers, job_id, |progress| {
+            emit_sync_progress(&app_handle, progress)
+        })
+        .await
+    {
+        eprintln!("Sync job {job_id} failed: {err}");
+    }
+    emit_sync_finished(&app_handle, job_id);
+}
+
+/// Refreshes stale titles every [`REFRESH_INTERVAL`] while enabled in the
+/// settings.
+async fn refresh_metadata_periodically(app_handle: tauri::AppHandle) {
+    loop {
+        let state = app_handle.state::<AppState>();
+        let enabled = settings::get_metadata_refresh_settings(&state.db)
+            .map(|refresh| refresh.enabled)
+            .unwrap_or_default();
+
+        if enabled {
+            match state.refresher.refresh_stale(&state.db).await {
+                Ok(report) if report.refreshed > 0 => {
+                    let _ = app_handle.emit("metadata-refreshed", &report);
+                }
+                Ok(_) => {}
+                Err(err) => eprintln!("Metadata refresh failed: {err}"),
+            }
+        }
+
+        tokio::time::sleep(REFRESH_INTERVAL).await;
+    }
+}
+
+async fn sync_roots(
+    roots: Vec<PathBuf>,
+    state: &AppState,
+    app_handle: &tauri::AppHandle,
+) -> Result<SyncSummaryBare, AppError> {
+    let db = &state.db;
+
+    let mut found_files = vec![];
+    let mut skipped = vec![];
+    for (walked, root) in roots.iter().enumerate() {
+        emit_sync_progress(
+            app_handle,
+            SyncProgress::new(SyncPhase::Walking, walked, roots.len()).at(root),
+        );
+        let result = media_scanner::find_movies(db, root.clone()).await?;
+        found_files.extend(result.videos);
+        skipped.extend(result.skipped);
+    }
+
+    emit_sync_progress(
+        app_handle,
+        SyncProgress::new(SyncPhase::Walking, roots.len(), roots.len()),
+    );
+
+    emit_sync_progress(app_handle, SyncProgress::new(SyncPhase::Cleanup, 0, 1));
+    let synced = media_scanner::sync_files(db, &roots, found_files).await?;
+    emit_sync_progress(app_handle, SyncProgress::new(SyncPhase::Cleanup, 1, 1));
+
+    let (job, medias) = state
+        .sync_jobs
+        .prepare(db, &roots, &synced.unknown, |progress| {
+            emit_sync_progress(app_handle, progress)
+        })
+        .await?;
+
+    let summary = SyncSummaryBare {
+        job_id: job.id,
+        status: job.status,
+        total: job.total,
+        skipped,
+        orphans: synced.orphans,
+    };
+    // A paused job would otherwise keep the command pending until resumed.
+    tauri::async_runtime::spawn(drive_sync_job(job, medias, app_handle.clone()));
+    Ok(summary)
 }
 
 #[tauri::command]
@@ -32,58 +270,227 @@ async fn sync_files(
     root: String,
     state: tauri::State<'_, AppState>,
     app_handle: tauri::AppHandle,
-) -> Result<usize, String> {
+) -> Result<SyncSummaryBare, AppError> {
+    sync_roots(vec![PathBuf::from(root)], &state, &app_handle).await
+}
+
+#[tauri::command]
+async fn sync_all(
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<SyncSummaryBare, AppError> {
+    let roots = state
+        .db
+        .get_library_roots()?
+        .into_iter()
+        .filter(|root| root.enabled)
+        .map(|root| PathBuf::from(root.path))
+        .filter(|root| {
+            // An unmounted drive should not abort the sync of the other roots.
+            let exists = root.is_dir();
+            if !exists {
+                eprintln!("Skipping missing library root: {}", root.display());
+            }
+            exists
+        })
+        .collect();
+
+    sync_roots(roots, &state, &app_handle).await
+}
+
+#[tauri::command]
+fn get_sync_jobs(state: tauri::State<AppState>) -> Result<Vec<SyncJob>, AppError> {
     let db = &state.db;
+    db.get_sync_jobs().map_err(AppError::from)
+}
 
-    media_scanner::sync_files(db)
-        .await
-        .map_err(|e| e.to_string())?;
+#[tauri::command]
+fn pause_sync_job(job_id: IdType, state: tauri::State<AppState>) -> Result<(), AppError> {
+    let db = &state.db;
+    state.sync_jobs.pause(db, job_id).map_err(AppError::from)
+}
 
-    let found_files = media_scanner::find_movies(db, PathBuf::from(root))
-        .await
-        .map_err(|e| e.to_string())?;
+#[tauri::command]
+fn resume_sync_job(
+    job_id: IdType,
+    state: tauri::State<AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<(), AppError> {
+    let db = &state.db;
+    let woken = state.sync_jobs.resume(db, job_id)?;
+
+    if !woken {
+        tauri::async_runtime::spawn(run_sync_job(job_id, app_handle));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+fn cancel_sync_job(job_id: IdType, state: tauri::State<AppState>) -> Result<(), AppError> {
+    let db = &state.db;
+    state.sync_jobs.cancel(db, job_id).map_err(AppError::from)
+}
+
+#[tauri::command]
+fn remove_orphans(
+    cleanup: OrphanCleanup,
+    state: tauri::State<'_, AppState>,
+) -> Result<OrphanReport, AppError> {
+    let db = &state.db;
+    db.remove_orphans(&cleanup).map_err(AppError::from)
+}
+
+#[tauri::command]
+fn get_library_roots(state: tauri::State<'_, AppState>) -> Result<Vec<LibraryRoot>, AppError> {
+    let db = &state.db;
+    db.get_library_roots().map_err(AppError::from)
+}
 
-    let metadata = metadata_extractor::get_metadata(&found_files);
+#[tauri::command]
+fn add_library_root(
+    path: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<LibraryRoot, AppError> {
+    let db = &state.db;
+    let root = db.insert_library_root(&path)?;
+    watch_library_roots(&state)?;
+    Ok(root)
+}
+
+#[tauri::command]
+fn remove_library_root(root_id: IdType, state: tauri::State<'_, AppState>) -> Result<(), AppError> {
+    let db = &state.db;
+    db.remove_library_root(root_id)?;
+    watch_library_roots(&state)
+}
 
-    let chunk_size = 50;
-    let mut inserted = 0;
-    let total = metadata.len();
+#[tauri::command]
+fn update_library_root_enabled(
+    root_id: IdType,
+    enabled: bool,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), AppError> {
+    let db = &state.db;
+    db.update_library_root_enabled(root_id, enabled)?;
+    watch_library_roots(&state)
+}
 
-    for chunk in metadata.chunks(chunk_size) {
-        let mut chunk = chunk.to_vec();
+#[tauri::command]
+fn get_video_extensions(state: tauri::State<'_, AppState>) -> Result<Vec<String>, AppError> {
+    let db = &state.db;
+    settings::get_video_extensions(db)
+        .map(|extensions| extensions.to_vec())
+        .map_err(AppError::from)
+}
 
-        fetch_imdb::set_imdb_data(&mut chunk).await;
+#[tauri::command]
+fn set_video_extensions(
+    extensions: Vec<String>,
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<String>, AppError> {
+    let db = &state.db;
+    settings::set_video_extensions(db, &extensions)
+        .map(|extensions| extensions.to_vec())
+        .map_err(AppError::from)
+}
 
-        db.insert_medias(&chunk).map_err(|e| e.to_string())?;
+#[tauri::command]
+fn get_ignore_rules(state: tauri::State<'_, AppState>) -> Result<IgnoreRules, AppError> {
+    let db = &state.db;
+    settings::get_ignore_rules(db).map_err(AppError::from)
+}
 
-        inserted += chunk.len();
+#[tauri::command]
+fn set_ignore_rules(
+    rules: IgnoreRules,
+    state: tauri::State<'_, AppState>,
+) -> Result<IgnoreRules, AppError> {
+    let db = &state.db;
+    settings::set_ignore_rules(db, rules).map_err(AppError::from)
+}
 
-        app_handle
-            .emit("sync-progress", SyncFileProgressBare { inserted, total })
-            .unwrap();
-    }
+#[tauri::command]
+fn get_metadata_cache_settings(
+    state: tauri::State<'_, AppState>,
+) -> Result<MetadataCacheSettings, AppError> {
+    let db = &state.db;
+    settings::get_metadata_cache_settings(db).map_err(AppError::from)
+}
 
-    Ok(inserted)
+#[tauri::command]
+fn set_metadata_cache_settings(
+    cache: MetadataCacheSettings,
+    state: tauri::State<'_, AppState>,
+) -> Result<MetadataCacheSettings, AppError> {
+    let db = &state.db;
+    settings::set_metadata_cache_settings(db, cache).map_err(AppError::from)
 }
 
 #[tauri::command]
-fn get_countries(state: tauri::State<'_, AppState>) -> Result<Vec<NumericalString>, String> {
+fn clear_metadata_cache(state: tauri::State<'_, AppState>) -> Result<usize, AppError> {
     let db = &state.db;
-    db.get_countries().map_err(|e| e.to_string())
+    db.clear_cached_responses().map_err(AppError::from)
 }
 
 #[tauri::command]
-fn get_genres(state: tauri::State<'_, AppState>) -> Result<Vec<NumericalString>, String> {
+fn get_metadata_refresh_settings(
+    state: tauri::State<'_, AppState>,
+) -> Result<MetadataRefreshSettings, AppError> {
     let db = &state.db;
+    settings::get_metadata_refresh_settings(db).map_err(AppError::from)
+}
 
-    db.get_genres().map_err(|e| e.to_string())
+#[tauri::command]
+fn set_metadata_refresh_settings(
+    refresh: MetadataRefreshSettings,
+    state: tauri::State<'_, AppState>,
+) -> Result<MetadataRefreshSettings, AppError> {
+    let db = &state.db;
+    settings::set_metadata_refresh_settings(db, refresh).map_err(AppError::from)
 }
 
 #[tauri::command]
-fn get_people(state: tauri::State<'_, AppState>) -> Result<Vec<(String, String)>, String> {
+async fn refresh_metadata(state: tauri::State<'_, AppState>) -> Result<RefreshReport, AppError> {
     let db = &state.db;
+    state
+        .refresher
+        .refresh_stale(db)
+        .await
+        .map_err(AppError::from)
+}
 
-    db.get_people().map_err(|e| e.to_string())
+#[tauri::command]
+fn get_countries(state: tauri::State<'_, AppState>) -> Result<Vec<NumericalString>, AppError> {
+    let db = &state.db;
+    db.get_countries().map_err(AppError::from)
+}
+
+#[tauri::command]
+fn get_genres(state: tauri::State<'_, AppState>) -> Result<Vec<NumericalString>, AppError> {
+    let db = &state.db;
+
+    db.get_genres().map_err(AppError::from)
+}
+
+#[tauri::command]
+fn get_people(state: tauri::State<'_, AppState>) -> Result<Vec<(String, String)>, AppError> {
+    let db = &state.db;
+
+    db.get_people().map_err(AppError::from)
+}
+
+#[tauri::command]
+fn get_subtitle_languages(state: tauri::State<'_, AppState>) -> Result<Vec<String>, AppError> {
+    let db = &state.db;
+
+    db.get_subtitle_languages().map_err(AppError::from)
+}
+
+#[tauri::command]
+fn get_certifications(state: tauri::State<'_, AppState>) -> Result<Vec<String>, AppError> {
+    let db = &state.db;
+
+    db.get_certifications().map_err(AppError::from)
 }
 
 #[tauri::command]
@@ -91,20 +498,19 @@ fn filter_medias(
     filters: FilterValues,
     page: u32,
     state: tauri::State<'_, AppState>,
-) -> Result<Vec<data_model::Media>, String> {
+) -> Result<Vec<data_model::Media>, AppError> {
     let db = &state.db;
-    db.filter_medias(&filters, page).map_err(|e| e.to_string())
+    db.filter_medias(&filters, page).map_err(AppError::from)
 }
 
 #[tauri::command]
 fn get_media_by_id(
     media_id: IdType,
     state: tauri::State<'_, AppState>,
-) -> Result<data_model::Media, String> {
+) -> Result<data_model::Media, AppError> {
     let db = &state.db;
-    db.get_media_by_id(media_id)
-        .map_err(|e| e.to_string())?
-        .ok_or_else(|| "movie not found".to_string())
+    db.get_media_by_id(media_id)?
+        .ok_or_else(|| AppError::NotFound(format!("Media {media_id} not found")))
 }
 
 #[tauri::command]
@@ -112,26 +518,83 @@ async fn update_media_imdb(
     media_id: IdType,
     imdb_id: &str,
     state: tauri::State<'_, AppState>,
-) -> Result<IdType, String> {
+) -> Result<IdType, AppError> {
     let db = &state.db;
-    let imdb = fetch_imdb::get_imdb_data_by_id(imdb_id)
+    state
+        .providers
+        .match_media(db, media_id, imdb_id)
         .await
-        .map_err(|e| e.to_string())?;
+        .map_err(AppError::from)
+}
+
+/// Fetches the episode titles, air dates and plots of a matched series again.
+#[tauri::command]
+async fn refresh_episodes(
+    media_id: IdType,
+    state: tauri::State<'_, AppState>,
+) -> Result<data_model::Media, AppError> {
+    let db = &state.db;
+    let media = db
+        .get_media_by_id(media_id)?
+        .ok_or_else(|| AppError::NotFound(format!("Media {media_id} not found")))?;
+    if !media.is_series() {
+        return Err(AppError::InvalidInput(format!(
+            "{} is not a series",
+            media.name
+        )));
+    }
+    let Some(imdb) = &media.imdb else {
+        return Err(AppError::InvalidInput(format!(
+            "{} is not matched to a title",
+            media.name
+        )));
+    };
+
+    let episodes = state.providers.get_episodes(&imdb.imdb_id).await?;
+    db.set_imdb_episodes(&imdb.imdb_id, &episodes)?;
+
+    db.get_media_by_id(media_id)?
+        .ok_or_else(|| AppError::NotFound(format!("Media {media_id} not found")))
+}
+
+#[tauri::command]
+fn get_missing_episodes(
+    media_id: IdType,
+    state: tauri::State<'_, AppState>,
+) -> Result<MissingEpisodes, AppError> {
+    let db = &state.db;
+    db.get_missing_episodes(media_id).map_err(AppError::from)
+}
 
-    db.insert_imdb(&imdb).map_err(|e| e.to_string())?;
-    db.update_media_imdb(media_id, imdb_id)
-        .map_err(|e| e.to_string())
+#[tauri::command]
+fn get_missing_episodes_report(
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<MissingEpisodes>, AppError> {
+    let db = &state.db;
+    db.get_missing_episodes_report().map_err(AppError::from)
+}
+
+#[tauri::command]
+async fn search_titles(
+    query: &str,
+    year: Option<i32>,
+    content_type: ContentType,
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<MatchCandidate>, AppError> {
+    state
+        .providers
+        .search_titles(query, year, &content_type)
+        .await
+        .map_err(AppError::from)
 }
 
 #[tauri::command]
 async fn create_media_from_imdb(
     imdb_id: &str,
     state: tauri::State<'_, AppState>,
-) -> Result<IdType, String> {
+) -> Result<IdType, AppError> {
     let db = &state.db;
-    let imdb = fetch_imdb::get_imdb_data_by_id(imdb_id)
-        .await
-        .map_err(|e| e.to_string())?;
+    let imdb = state.providers.get_imdb_data_by_id(imdb_id).await?;
 
     let media = Media {
         name: imdb.title.clone(),
@@ -140,7 +603,84 @@ async fn create_media_from_imdb(
         ..Media::default()
     };
 
-    db.insert_media(&media).map_err(|e| e.to_string())
+    db.insert_media(&media).map_err(AppError::from)
+}
+
+#[tauri::command]
+fn merge_medias(
+    target_id: IdType,
+    source_ids: Vec<IdType>,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), AppError> {
+    let db = &state.db;
+    db.merge_medias(target_id, &source_ids)
+        .map_err(AppError::from)
+}
+
+#[tauri::command]
+fn split_media(
+    media_id: IdType,
+    file_ids: Vec<IdType>,
+    state: tauri::State<'_, AppState>,
+) -> Result<IdType, AppError> {
+    let db = &state.db;
+    db.split_media(media_id, &file_ids).map_err(AppError::from)
+}
+
+#[tauri::command]
+fn get_review_queue(state: tauri::State<'_, AppState>) -> Result<Vec<Media>, AppError> {
+    let db = &state.db;
+    db.get_review_queue().map_err(AppError::from)
+}
+
+#[tauri::command]
+async fn get_match_candidates(
+    media_id: IdType,
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<MatchCandidate>, AppError> {
+    let db = &state.db;
+    let media = reviewed_media(db, media_id)?;
+    let rejected = db.get_rejected_matches()?;
+
+    state
+        .providers
+        .match_candidates(&media, &rejected)
+        .await
+        .map_err(AppError::from)
+}
+
+#[tauri::command]
+async fn accept_match(
+    media_id: IdType,
+    imdb_id: &str,
+    state: tauri::State<'_, AppState>,
+) -> Result<Media, AppError> {
+    let db = &state.db;
+    state.providers.match_media(db, media_id, imdb_id).await?;
+    reviewed_media(db, media_id)
+}
+
+#[tauri::command]
+fn reject_match(
+    media_id: IdType,
+    imdb_id: &str,
+    state: tauri::State<'_, AppState>,
+) -> Result<Media, AppError> {
+    let db = &state.db;
+    db.reject_match(media_id, imdb_id)?;
+    reviewed_media(db, media_id)
+}
+
+#[tauri::command]
+fn skip_match(media_id: IdType, state: tauri::State<'_, AppState>) -> Result<Media, AppError> {
+    let db = &state.db;
+    db.skip_match_review(media_id)?;
+    reviewed_media(db, media_id)
+}
+
+fn reviewed_media(db: &Sqlite, media_id: IdType) -> Result<Media, AppError> {
+    db.get_media_by_id(media_id)?
+        .ok_or_else(|| AppError::NotFound(format!("Media {media_id} not found")))
 }
 
 #[tauri::command]
@@ -148,10 +688,9 @@ fn update_watch_list(
     media_id: IdType,
     watch_list: bool,
     state: tauri::State<'_, AppState>,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     let db = &state.db;
-    db.update_watch_list(media_id, watch_list)
-        .map_err(|e| e.to_string())?;
+    db.update_watch_list(media_id, watch_list)?;
     Ok(())
 }
 
@@ -160,10 +699,9 @@ fn update_media_watched(
     media_id: IdType,
     watched: bool,
     state: tauri::State<'_, AppState>,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     let db = &state.db;
-    db.update_media_watched(media_id, watched)
-        .map_err(|e| e.to_string())?;
+    db.update_media_watched(media_id, watched)?;
     Ok(())
 }
 
@@ -172,10 +710,9 @@ fn update_season_watched(
     season_id: IdType,
     watched: bool,
     state: tauri::State<'_, AppState>,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     let db = &state.db;
-    db.update_season_watched(season_id, watched)
-        .map_err(|e| e.to_string())?;
+    db.update_season_watched(season_id, watched)?;
     Ok(())
 }
 
@@ -184,10 +721,20 @@ fn update_episode_watched(
     episode_id: IdType,
     watched: bool,
     state: tauri::State<'_, AppState>,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
+    let db = &state.db;
+    db.update_episode_watched(episode_id, watched)?;
+    Ok(())
+}
+
+#[tauri::command]
+fn update_file_watched(
+    file_id: IdType,
+    watched: bool,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), AppError> {
     let db = &state.db;
-    db.update_episode_watched(episode_id, watched)
-        .map_err(|e| e.to_string())?;
+    db.update_file_watched(file_id, watched)?;
     Ok(())
 }
 
@@ -196,29 +743,28 @@ fn update_media_my_ranking(
     media_id: IdType,
     my_ranking: u8,
     state: tauri::State<'_, AppState>,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     let db = &state.db;
-    db.update_media_my_ranking(media_id, my_ranking)
-        .map_err(|e| e.to_string())?;
+    db.update_media_my_ranking(media_id, my_ranking)?;
     Ok(())
 }
 #[tauri::command]
-fn get_tags(state: tauri::State<'_, AppState>) -> Result<Vec<Tag>, String> {
+fn get_tags(state: tauri::State<'_, AppState>) -> Result<Vec<Tag>, AppError> {
     let db = &state.db;
-    db.get_tags().map_err(|e| e.to_string())
+    db.get_tags().map_err(AppError::from)
 }
 
 #[tauri::command]
-fn remove_tag(tag_id: IdType, state: tauri::State<'_, AppState>) -> Result<(), String> {
+fn remove_tag(tag_id: IdType, state: tauri::State<'_, AppState>) -> Result<(), AppError> {
     let db = &state.db;
-    db.remove_tag(tag_id).map_err(|e| e.to_string())?;
+    db.remove_tag(tag_id)?;
     Ok(())
 }
 
 #[tauri::command]
-fn update_tag(tag: Tag, state: tauri::State<'_, AppState>) -> Result<(), String> {
+fn update_tag(tag: Tag, state: tauri::State<'_, AppState>) -> Result<(), AppError> {
     let db = &state.db;
-    db.update_tag(&tag).map_err(|e| e.to_string())?;
+    db.update_tag(&tag)?;
     Ok(())
 }
 
@@ -226,15 +772,15 @@ fn update_tag(tag: Tag, state: tauri::State<'_, AppState>) -> Result<(), String>
 fn get_medias_by_tag(
     tag_id: IdType,
     state: tauri::State<'_, AppState>,
-) -> Result<Vec<data_model::Media>, String> {
+) -> Result<Vec<data_model::Media>, AppError> {
     let db = &state.db;
-    db.get_medias_by_tag(tag_id).map_err(|e| e.to_string())
+    db.get_medias_by_tag(tag_id).map_err(AppError::from)
 }
 
 #[tauri::command]
-fn insert_tag(tag: Tag, state: tauri::State<'_, AppState>) -> Result<(), String> {
+fn insert_tag(tag: Tag, state: tauri::State<'_, AppState>) -> Result<(), AppError> {
     let db = &state.db;
-    db.insert_tag(&tag).map_err(|e| e.to_string())
+    db.insert_tag(&tag).map_err(AppError::from)
 }
 
 #[tauri::command]
@@ -242,10 +788,10 @@ fn insert_media_tag(
     media_id: IdType,
     tag_id: IdType,
     state: tauri::State<'_, AppState>,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     let db = &state.db;
     db.insert_media_tag(media_id, tag_id)
-        .map_err(|e| e.to_string())
+        .map_err(AppError::from)
 }
 
 #[tauri::command]
@@ -253,16 +799,16 @@ fn remove_media_tag(
     media_id: IdType,
     tag_id: IdType,
     state: tauri::State<'_, AppState>,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     let db = &state.db;
     db.remove_media_tag(media_id, tag_id)
-        .map_err(|e| e.to_string())
+        .map_err(AppError::from)
 }
 
 #[tauri::command]
-fn delete_media(media_id: IdType, state: tauri::State<'_, AppState>) -> Result<(), String> {
+fn delete_media(media_id: IdType, state: tauri::State<'_, AppState>) -> Result<(), AppError> {
     let db = &state.db;
-    db.delete_media(media_id).map_err(|e| e.to_string())
+    db.delete_media(media_id).map_err(AppError::from)
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -276,16 +822,50 @@ pub fn run() {
         .plugin(tauri_plugin_opener::init())
         .invoke_handler(tauri::generate_handler![
             sync_files,
+            sync_all,
+            get_sync_jobs,
+            pause_sync_job,
+            resume_sync_job,
+            cancel_sync_job,
+            remove_orphans,
+            get_library_roots,
+            add_library_root,
+            remove_library_root,
+            update_library_root_enabled,
+            get_video_extensions,
+            set_video_extensions,
+            get_ignore_rules,
+            set_ignore_rules,
+            get_metadata_cache_settings,
+            set_metadata_cache_settings,
+            clear_metadata_cache,
+            get_metadata_refresh_settings,
+            set_metadata_refresh_settings,
+            refresh_metadata,
             get_countries,
             get_genres,
             filter_medias,
             get_media_by_id,
             get_people,
+            get_subtitle_languages,
+            get_certifications,
             update_media_imdb,
+            refresh_episodes,
+            get_missing_episodes,
+            get_missing_episodes_report,
             create_media_from_imdb,
+            search_titles,
+            merge_medias,
+            split_media,
+            get_review_queue,
+            get_match_candidates,
+            accept_match,
+            reject_match,
+            skip_match,
             update_media_watched,
             update_season_watched,
             update_episode_watched,
+            update_file_watched,
             update_media_my_ranking,
             update_watch_list,
             get_tags,
@@ -299,7 +879,43 @@ pub fn run() {
         ])
         .setup(|app| {
             let db = Sqlite::from_app_handle(app.app_handle())?;
-            app.manage(AppState { db });
+            let providers = MetadataProviders::default().with_cache(Arc::new(db.clone()));
+
+            let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+            let watcher = LibraryWatcher::new(sender)?;
+
+            app.manage(AppState {
+                db,
+                providers,
+                watcher,
+                sync_jobs: SyncJobs::default(),
+                refresher: MetadataRefresher::default(),
+            });
+            watch_library_roots(&app.state::<AppState>())?;
+
+            // Jobs interrupted by the last shutdown carry on; paused ones wait
+            // for the user to resume them.
+            let interrupted = app
+                .state::<AppState>()
+                .db
+                .get_sync_jobs()?
+                .into_iter()
+                .filter(|job| job.status == SyncJobStatus::Running);
+            for job in interrupted {
+                tauri::async_runtime::spawn(run_sync_job(job.id, app.app_handle().clone()));
+            }
+
+            tauri::async_runtime::spawn(refresh_metadata_periodically(app.app_handle().clone()));
+
+            let app_handle = app.app_handle().clone();
+            tauri::async_runtime::spawn(async move {
+                while let Some(paths) = receiver.recv().await {
+                    if let Err(err) = apply_library_changes(paths, &app_handle).await {
+                        eprintln!("Failed to apply library changes: {err}");
+                    }
+                }
+            });
+
             Ok(())
         })
         .run(tauri::generate_context!())
@@ -0,0 +1,272 @@
+use anyhow::Result;
+use notify_debouncer_full::{
+    DebounceEventResult, DebouncedEvent, Debouncer, RecommendedCache, new_debouncer,
+    notify::{EventKind, RecommendedWatcher, RecursiveMode, event::ModifyKind},
+};
+use std::{
+    collections::{BTreeSet, HashSet},
+    path::PathBuf,
+    sync::Mutex,
+    time::Duration,
+};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::{
+    db::DB,
+    media_scanner::{LibraryWalker, SkippedPath},
+};
+
+/// How long a burst of file system events has to settle before it is reported.
+const DEBOUNCE: Duration = Duration::from_secs(2);
+
+/// Video files that appeared in or disappeared from the library.
+#[derive(Debug, Default, PartialEq)]
+pub struct LibraryChanges {
+    pub added: Vec<PathBuf>,
+    pub removed: Vec<PathBuf>,
+    /// Appeared paths left out by the ignore rules.
+    pub skipped: Vec<SkippedPath>,
+}
+
+impl LibraryChanges {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.skipped.is_empty()
+    }
+}
+
+/// Watches the enabled library roots and sends the paths touched by every
+/// debounced burst of create, rename and delete events.
+pub struct LibraryWatcher {
+    debouncer: Mutex<Debouncer<RecommendedWatcher, RecommendedCache>>,
+    roots: Mutex<Vec<PathBuf>>,
+}
+
+impl LibraryWatcher {
+    pub fn new(sender: UnboundedSender<Vec<PathBuf>>) -> Result<Self> {
+        let debouncer =
+            new_debouncer(
+                DEBOUNCE,
+                None,
+                move |result: DebounceEventResult| match result {
+                    Ok(events) => {
+                        let paths = touched_paths(&events);
+                        if !paths.is_empty() {
+                            let _ = sender.send(paths);
+                        }
+                    }
+                    Err(errors) => {
+                        for err in errors {
+                            eprintln!("Watch error: {err}");
+                        }
+                    }
+                },
+            )?;
+
+        Ok(Self {
+            debouncer: Mutex::new(debouncer),
+            roots: Mutex::new(vec![]),
+        })
+    }
+
+    /// Replaces the watched roots. Roots that cannot be watched, such as an
+    /// unmounted drive, are skipped.
+    pub fn watch_roots(&self, roots: Vec<PathBuf>) {
+        let mut debouncer = self.debouncer.lock().unwrap();
+        let mut watched = self.roots.lock().unwrap();
+
+        for root in watched.drain(..) {
+            let _ = debouncer.unwatch(&root);
+        }
+
+        for root in roots {
+            match debouncer.watch(&root, RecursiveMode::Recursive) {
+                Ok(()) => watched.push(root),
+                Err(err) => eprintln!("Failed to watch {}: {err}", root.display()),
+            }
+        }
+    }
+}
+
+fn touched_paths(events: &[DebouncedEvent]) -> Vec<PathBuf> {
+    events
+        .iter()
+        .filter(|e| {
+            matches!(
+                e.kind,
+                EventKind::Create(_)
+                    | EventKind::Remove(_)
+                    | EventKind::Modify(ModifyKind::Name(_))
+            )
+        })
+        .flat_map(|e| e.paths.iter().cloned())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+/// Turns the paths touched by file system events into files to insert and
+/// files to drop. A path that no longer exists removes every known file at or
+/// below it, so deleting or moving away a whole folder is handled too. New
+/// paths go through the same ignore rules as a full scan of their root.
+pub fn collect_changes<T: DB>(db: &T, paths: &[PathBuf]) -> Result<LibraryChanges> {
+    let known: HashSet<PathBuf> = db
+        .get_all_files()?
+        .into_iter()
+        .map(|file| PathBuf::from(file.path))
+        .collect();
+    let roots: Vec<PathBuf> = db
+        .get_library_roots()?
+        .into_iter()
+        .filter(|root| root.enabled)
+        .map(|root| PathBuf::from(root.path))
+        .collect();
+    let walker = LibraryWalker::load(db)?;
+
+    let mut added = BTreeSet::new();
+    let mut removed = BTreeSet::new();
+    let mut skipped = vec![];
+
+    for path in paths {
+        if path.exists() {
+            let root = roots
+                .iter()
+                .find(|root| path.starts_with(root))
+                .map_or(path.as_path(), PathBuf::as_path);
+            let result = walker.walk_from(root, path);
+
+            added.extend(result.videos.into_iter().filter(|p| !known.contains(p)));
+            skipped.extend(result.skipped);
+        } else {
+            removed.extend(known.iter().filter(|p| p.starts_with(path)).cloned());
+        }
+    }
+
+    Ok(LibraryChanges {
+        added: added.into_iter().collect(),
+        removed: removed.into_iter().collect(),
+        skipped,
+    })
+}
+
+#[cfg(test)]
+mod collect_changes_tests {
+    use super::*;
+    use crate::db::MokeDB;
+    use std::fs::{self, File};
+    use tempfile::TempDir;
+
+    fn touch(path: &PathBuf) {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).expect("Failed to create dir");
+        }
+        File::create(path).expect("Failed to create file");
+    }
+
+    #[test]
+    fn created_video_is_added() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let video = temp_dir.path().join("movie.2020.mkv");
+        let text = temp_dir.path().join("notes.txt");
+        touch(&video);
+        touch(&text);
+
+        let changes = collect_changes(&MokeDB::default(), &[video.clone(), text]).unwrap();
+
+        assert_eq!(changes.added, vec![video]);
+        assert!(changes.removed.is_empty());
+    }
+
+    #[test]
+    fn known_video_is_not_added_again() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let video = temp_dir.path().join("movie.2020.mkv");
+        touch(&video);
+
+        let mut db = MokeDB::default();
+        db.insert_file(video.clone());
+
+        let changes = collect_changes(&db, &[video]).unwrap();
+
+        assert!(changes.is_empty());
+    }
+
+    #[test]
+    fn deleted_video_is_removed() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let video = temp_dir.path().join("movie.2020.mkv");
+
+        let mut db = MokeDB::default();
+        db.insert_file(video.clone());
+
+        let changes = collect_changes(&db, std::slice::from_ref(&video)).unwrap();
+
+        assert!(changes.added.is_empty());
+        assert_eq!(changes.removed, vec![video]);
+    }
+
+    #[test]
+    fn renamed_video_is_removed_and_added() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let old = temp_dir.path().join("movie.mkv");
+        let new = temp_dir.path().join("movie.2020.mkv");
+        touch(&new);
+
+        let mut db = MokeDB::default();
+        db.insert_file(old.clone());
+
+        let changes = collect_changes(&db, &[old.clone(), new.clone()]).unwrap();
+
+        assert_eq!(changes.added, vec![new]);
+        assert_eq!(changes.removed, vec![old]);
+    }
+
+    #[test]
+    fn deleted_folder_removes_nested_files() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let folder = temp_dir.path().join("loki");
+        let kept = temp_dir.path().join("loki-extras").join("loki.s01e01.mkv");
+        touch(&kept);
+
+        let mut db = MokeDB::default();
+        db.insert_file(folder.join("S1").join("loki.s01e01.mkv"));
+        db.insert_file(folder.join("S1").join("loki.s01e02.mkv"));
+        db.insert_file(kept);
+
+        let changes = collect_changes(&db, &[folder]).unwrap();
+
+        assert!(changes.added.is_empty());
+        assert_eq!(changes.removed.len(), 2);
+    }
+
+    #[test]
+    fn moved_in_folder_adds_nested_videos() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let folder = temp_dir.path().join("loki");
+        let e1 = folder.join("S1").join("loki.s01e01.mkv");
+        let e2 = folder.join("S1").join("loki.s01e02.mkv");
+        touch(&e1);
+        touch(&e2);
+        touch(&folder.join("S1").join("loki.s01e01.srt"));
+
+        let changes = collect_changes(&MokeDB::default(), &[folder]).unwrap();
+
+        assert_eq!(changes.added, vec![e1, e2]);
+        assert!(changes.removed.is_empty());
+    }
+
+    #[test]
+    fn ignored_paths_are_skipped() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let folder = temp_dir.path().join("coco");
+        let video = folder.join("Coco.2017.mkv");
+        let extras = folder.join("Extras");
+        touch(&video);
+        touch(&extras.join("Trailer.mkv"));
+
+        let changes = collect_changes(&MokeDB::default(), &[folder]).unwrap();
+
+        assert_eq!(changes.added, vec![video]);
+        assert_eq!(changes.skipped.len(), 1);
+        assert_eq!(changes.skipped[0].path, extras.to_string_lossy());
+    }
+}
@@ -0,0 +1,111 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+mod matroska;
+mod mp4;
+
+/// Technical details read from the headers of a video container.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct MediaProbe {
+    pub duration_ms: Option<i64>,
+    pub video_codec: Option<String>,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    /// Overall bitrate in bits per second.
+    pub bitrate: Option<i64>,
+    pub audio_languages: Vec<String>,
+    pub subtitle_languages: Vec<String>,
+}
+
+/// Reads the container headers of a Matroska/WebM or MP4 file. Returns `None`
+/// for other containers and for files that cannot be read.
+pub fn probe(path: &Path) -> Option<MediaProbe> {
+    let file = File::open(path).ok()?;
+    let size = file.metadata().ok()?.len();
+
+    probe_reader(&mut BufReader::new(file), size).ok().flatten()
+}
+
+fn probe_reader<R: Read + Seek>(reader: &mut R, size: u64) -> io::Result<Option<MediaProbe>> {
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic)?;
+    reader.seek(SeekFrom::Start(0))?;
+
+    let mut probe = if matroska::is_matroska(&magic) {
+        matroska::probe(reader, size)?
+    } else if mp4::is_mp4(&magic) {
+        mp4::probe(reader, size)?
+    } else {
+        return Ok(None);
+    };
+
+    if probe.bitrate.is_none() {
+        probe.bitrate = probe
+            .duration_ms
+            .filter(|duration| *duration > 0)
+            .map(|duration| (size as i64).saturating_mul(8_000) / duration);
+    }
+
+    Ok(Some(probe))
+}
+
+/// Adds a track language once, skipping the "undetermined" code.
+fn push_language(languages: &mut Vec<String>, language: &str) {
+    let language = language.trim().to_lowercase();
+    if !language.is_empty() && language != "und" && !languages.contains(&language) {
+        languages.push(language);
+    }
+}
+
+#[cfg(test)]
+mod probe_tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn unknown_container_is_not_probed() {
+        let mut data = Cursor::new(b"RIFF\0\0\0\0AVI LIST".to_vec());
+        assert_eq!(probe_reader(&mut data, 16).unwrap(), None);
+    }
+
+    #[test]
+    fn tiny_file_is_not_probed() {
+        let mut data = Cursor::new(vec![0x1A, 0x45]);
+        assert!(probe_reader(&mut data, 2).is_err());
+    }
+
+    #[test]
+    fn missing_file_is_not_probed() {
+        assert_eq!(probe(Path::new("/path/to/movie.mkv")), None);
+    }
+
+    #[test]
+    fn probes_files_and_derives_bitrate() {
+        let temp_dir = tempfile::TempDir::new().expect("Failed to create temp dir");
+
+        let mkv = temp_dir.path().join("movie.mkv");
+        std::fs::write(&mkv, matroska::fixtures::movie()).expect("Failed to write");
+        let probe = super::probe(&mkv).unwrap();
+        assert_eq!(probe.video_codec.as_deref(), Some("h264"));
+        let size = std::fs::metadata(&mkv).unwrap().len() as i64;
+        assert_eq!(probe.bitrate, Some(size * 8 / 3_600));
+
+        let mp4 = temp_dir.path().join("movie.mp4");
+        std::fs::write(&mp4, mp4::fixtures::movie()).expect("Failed to write");
+        let probe = super::probe(&mp4).unwrap();
+        assert_eq!(probe.video_codec.as_deref(), Some("hevc"));
+        assert_eq!(probe.subtitle_languages, vec!["eng"]);
+    }
+
+    #[test]
+    fn languages_are_deduplicated() {
+        let mut languages = vec![];
+        for language in ["eng", "ENG", "und", "", "fas"] {
+            push_language(&mut languages, language);
+        }
+        assert_eq!(languages, vec!["eng", "fas"]);
+    }
+}
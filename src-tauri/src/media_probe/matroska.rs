@@ -0,0 +1,460 @@
+use std::io::{self, Read, Seek, SeekFrom};
+
+use super::{MediaProbe, push_language};
+
+const EBML: u32 = 0x1A45_DFA3;
+const DOC_TYPE: u32 = 0x4282;
+const SEGMENT: u32 = 0x1853_8067;
+const INFO: u32 = 0x1549_A966;
+const TIMESTAMP_SCALE: u32 = 0x2A_D7B1;
+const DURATION: u32 = 0x4489;
+const TRACKS: u32 = 0x1654_AE6B;
+const TRACK_ENTRY: u32 = 0xAE;
+const TRACK_TYPE: u32 = 0x83;
+const CODEC_ID: u32 = 0x86;
+const LANGUAGE: u32 = 0x22_B59C;
+const LANGUAGE_BCP47: u32 = 0x22_B59D;
+const VIDEO: u32 = 0xE0;
+const PIXEL_WIDTH: u32 = 0xB0;
+const PIXEL_HEIGHT: u32 = 0xBA;
+const CLUSTER: u32 = 0x1F43_B675;
+
+const TRACK_TYPE_VIDEO: u64 = 1;
+const TRACK_TYPE_AUDIO: u64 = 2;
+const TRACK_TYPE_SUBTITLE: u64 = 0x11;
+
+/// Largest Info or Tracks element read into memory.
+const MAX_ELEMENT_SIZE: u64 = 4 * 1024 * 1024;
+
+pub fn is_matroska(magic: &[u8]) -> bool {
+    magic.starts_with(&EBML.to_be_bytes())
+}
+
+/// Reads a variable-length integer from the start of `data`. Returns the value
+/// with the length marker removed, its length in bytes, and whether all value
+/// bits were set (an "unknown" size).
+fn vint(data: &[u8]) -> Option<(u64, usize, bool)> {
+    let first = *data.first()?;
+    if first == 0 {
+        return None;
+    }
+
+    let len = first.leading_zeros() as usize + 1;
+    let bytes = data.get(..len)?;
+
+    let mask = 0xFFu8.checked_shr(len as u32).unwrap_or(0);
+    let mut value = u64::from(first & mask);
+    let mut all_ones = first & mask == mask;
+    for byte in &bytes[1..] {
+        value = (value << 8) | u64::from(*byte);
+        all_ones &= *byte == 0xFF;
+    }
+
+    Some((value, len, all_ones))
+}
+
+/// Reads an element id, which keeps its length marker.
+fn element_id(data: &[u8]) -> Option<(u32, usize)> {
+    let (_, len, _) = vint(data)?;
+    if len > 4 {
+        return None;
+    }
+    let id = data[..len]
+        .iter()
+        .fold(0u32, |id, byte| (id << 8) | u32::from(*byte));
+    Some((id, len))
+}
+
+/// Splits an in-memory element body into its children.
+fn children(mut data: &[u8]) -> Vec<(u32, &[u8])> {
+    let mut elements = vec![];
+
+    while let Some((id, id_len)) = element_id(data) {
+        let Some((size, size_len, _)) = vint(&data[id_len..]) else {
+            break;
+        };
+        let start = id_len + size_len;
+        let end = start.saturating_add(size as usize).min(data.len());
+
+        elements.push((id, &data[start..end]));
+        data = &data[end..];
+    }
+
+    elements
+}
+
+fn uint(data: &[u8]) -> u64 {
+    data.iter()
+        .take(8)
+        .fold(0u64, |value, byte| (value << 8) | u64::from(*byte))
+}
+
+fn float(data: &[u8]) -> Option<f64> {
+    match data.len() {
+        4 => Some(f64::from(f32::from_be_bytes(data.try_into().ok()?))),
+        8 => Some(f64::from_be_bytes(data.try_into().ok()?)),
+        _ => None,
+    }
+}
+
+fn string(data: &[u8]) -> String {
+    String::from_utf8_lossy(data)
+        .trim_end_matches('\0')
+        .to_string()
+}
+
+/// Reads an element header from the stream. A `None` size means the element
+/// runs to the end of its parent.
+fn read_header<R: Read>(reader: &mut R) -> io::Result<(u32, Option<u64>)> {
+    let mut buf = [0u8; 12];
+
+    reader.read_exact(&mut buf[..1])?;
+    let id_len = buf[0].leading_zeros() as usize + 1;
+    if id_len > 4 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "bad element id"));
+    }
+    reader.read_exact(&mut buf[1..id_len])?;
+
+    reader.read_exact(&mut buf[id_len..id_len + 1])?;
+    let size_len = buf[id_len].leading_zeros() as usize + 1;
+    if size_len > 8 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "bad element size",
+        ));
+    }
+    reader.read_exact(&mut buf[id_len + 1..id_len + size_len])?;
+
+    let (id, _) = element_id(&buf[..id_len])
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "bad element id"))?;
+    let (size, _, unknown) = vint(&buf[id_len..id_len + size_len])
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "bad element size"))?;
+
+    Ok((id, (!unknown).then_some(size)))
+}
+
+fn read_body<R: Read>(reader: &mut R, size: u64) -> io::Result<Vec<u8>> {
+    if size > MAX_ELEMENT_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "element too large",
+        ));
+    }
+    let mut body = vec![0u8; size as usize];
+    reader.read_exact(&mut body)?;
+    Ok(body)
+}
+
+fn codec_name(codec_id: &str) -> String {
+    match codec_id {
+        "V_MPEG4/ISO/AVC" => "h264".into(),
+        "V_MPEGH/ISO/HEVC" => "hevc".into(),
+        "V_AV1" => "av1".into(),
+        "V_VP9" => "vp9".into(),
+        "V_VP8" => "vp8".into(),
+        "V_MPEG4/ISO/SP" | "V_MPEG4/ISO/ASP" | "V_MPEG4/ISO/AP" | "V_MS/VFW/FOURCC" => {
+            "mpeg4".into()
+        }
+        "V_MPEG2" => "mpeg2".into(),
+        other => other.trim_start_matches("V_").to_lowercase(),
+    }
+}
+
+fn read_info(body: &[u8], probe: &mut MediaProbe) {
+    let mut scale = 1_000_000u64;
+    let mut duration = None;
+
+    for (id, data) in children(body) {
+        match id {
+            TIMESTAMP_SCALE => scale = uint(data),
+            DURATION => duration = float(data),
+            _ => {}
+        }
+    }
+
+    // Duration is counted in ticks of `scale` nanoseconds.
+    probe.duration_ms = duration
+        .filter(|d| d.is_finite() && *d > 0.0)
+        .map(|d| (d * scale as f64 / 1_000_000.0).round() as i64);
+}
+
+fn read_tracks(body: &[u8], probe: &mut MediaProbe) {
+    for (id, entry) in children(body) {
+        if id != TRACK_ENTRY {
+            continue;
+        }
+
+        let mut track_type = 0;
+        let mut codec = None;
+        // Matroska defaults a missing language to English.
+        let mut language = String::from("eng");
+        let mut bcp47 = None;
+        let mut size = (None, None);
+
+        for (id, data) in children(entry) {
+            match id {
+                TRACK_TYPE => track_type = uint(data),
+                CODEC_ID => codec = Some(string(data)),
+                LANGUAGE => language = string(data),
+                LANGUAGE_BCP47 => bcp47 = Some(string(data)),
+                VIDEO => {
+                    for (id, data) in children(data) {
+                        match id {
+                            PIXEL_WIDTH => size.0 = i32::try_from(uint(data)).ok(),
+                            PIXEL_HEIGHT => size.1 = i32::try_from(uint(data)).ok(),
+                            _ => {}
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let language = bcp47.unwrap_or(language);
+        match track_type {
+            TRACK_TYPE_VIDEO if probe.video_codec.is_none() => {
+                probe.video_codec = codec.as_deref().map(codec_name);
+                (probe.width, probe.height) = size;
+            }
+            TRACK_TYPE_AUDIO => push_language(&mut probe.audio_languages, &language),
+            TRACK_TYPE_SUBTITLE => push_language(&mut probe.subtitle_languages, &language),
+            _ => {}
+        }
+    }
+}
+
+/// Walks the EBML header and the top level of the Segment, reading Info and
+/// Tracks and skipping everything else up to the first Cluster, so only the
+/// start of the file is read.
+pub fn probe<R: Read + Seek>(reader: &mut R, file_size: u64) -> io::Result<MediaProbe> {
+    let (id, size) = read_header(reader)?;
+    let header = read_body(reader, size.unwrap_or_default())?;
+    let doc_type = children(&header)
+        .into_iter()
+        .find(|(id, _)| *id == DOC_TYPE)
+        .map(|(_, data)| string(data));
+    if id != EBML || !matches!(doc_type.as_deref(), Some("matroska" | "webm")) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "not a Matroska file",
+        ));
+    }
+
+    let (id, size) = read_header(reader)?;
+    if id != SEGMENT {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "missing Segment",
+        ));
+    }
+    let segment_start = reader.stream_position()?;
+    let segment_end = size
+        .map(|size| segment_start + size)
+        .unwrap_or(file_size)
+        .min(file_size);
+
+    let mut probe = MediaProbe::default();
+    let (mut has_info, mut has_tracks) = (false, false);
+
+    while reader.stream_position()? < segment_end {
+        let Ok((id, size)) = read_header(reader) else {
+            break;
+        };
+
+        match (id, size) {
+            (INFO, Some(size)) => {
+                read_info(&read_body(reader, size)?, &mut probe);
+                has_info = true;
+            }
+            (TRACKS, Some(size)) => {
+                read_tracks(&read_body(reader, size)?, &mut probe);
+                has_tracks = true;
+            }
+            // Muxers write Info and Tracks ahead of the media data.
+            (CLUSTER, _) => break,
+            (_, Some(size)) => {
+                reader.seek(SeekFrom::Current(size as i64))?;
+            }
+            // An element of unknown size can only be stepped into, not over.
+            (_, None) => break,
+        }
+
+        if has_info && has_tracks {
+            break;
+        }
+    }
+
+    Ok(probe)
+}
+
+#[cfg(test)]
+pub(crate) mod fixtures {
+    /// Encodes a Matroska element with a one-to-eight byte size.
+    pub fn element(id: u32, body: &[u8]) -> Vec<u8> {
+        let mut out: Vec<u8> = id
+            .to_be_bytes()
+            .into_iter()
+            .skip_while(|byte| *byte == 0)
+            .collect();
+
+        let size = body.len() as u64;
+        let len = (1..=8).find(|len| size < (1 << (7 * len)) - 1).unwrap();
+        let marked = size | (1 << (7 * len));
+        out.extend_from_slice(&marked.to_be_bytes()[8 - len..]);
+
+        out.extend_from_slice(body);
+        out
+    }
+
+    pub fn uint_element(id: u32, value: u64) -> Vec<u8> {
+        element(id, &value.to_be_bytes())
+    }
+
+    pub fn string_element(id: u32, value: &str) -> Vec<u8> {
+        element(id, value.as_bytes())
+    }
+
+    pub fn header(doc_type: &str) -> Vec<u8> {
+        element(super::EBML, &string_element(super::DOC_TYPE, doc_type))
+    }
+
+    pub fn track(track_type: u64, codec: &str, language: Option<&str>, extra: &[u8]) -> Vec<u8> {
+        let mut body = uint_element(super::TRACK_TYPE, track_type);
+        body.extend(string_element(super::CODEC_ID, codec));
+        if let Some(language) = language {
+            body.extend(string_element(super::LANGUAGE, language));
+        }
+        body.extend_from_slice(extra);
+        element(super::TRACK_ENTRY, &body)
+    }
+
+    /// A one-hour 1080p H.264 file with English and Persian audio and a
+    /// Persian subtitle.
+    pub fn movie() -> Vec<u8> {
+        let mut info = uint_element(super::TIMESTAMP_SCALE, 1_000_000);
+        info.extend(element(super::DURATION, &3_600_000f64.to_be_bytes()));
+
+        let mut video = uint_element(super::PIXEL_WIDTH, 1920);
+        video.extend(uint_element(super::PIXEL_HEIGHT, 1080));
+
+        let mut tracks = track(1, "V_MPEG4/ISO/AVC", None, &element(super::VIDEO, &video));
+        tracks.extend(track(2, "A_AAC", None, &[]));
+        tracks.extend(track(2, "A_AC3", Some("per"), &[]));
+        tracks.extend(track(0x11, "S_TEXT/UTF8", Some("per"), &[]));
+
+        let mut segment = element(0x114D_9B74, &[0u8; 16]);
+        segment.extend(element(super::INFO, &info));
+        segment.extend(element(super::TRACKS, &tracks));
+        segment.extend(element(super::CLUSTER, &[0u8; 64]));
+
+        let mut file = header("matroska");
+        file.extend(element(super::SEGMENT, &segment));
+        file
+    }
+}
+
+#[cfg(test)]
+mod matroska_tests {
+    use super::fixtures::*;
+    use super::*;
+    use std::io::Cursor;
+
+    fn probe_bytes(data: Vec<u8>) -> io::Result<MediaProbe> {
+        let size = data.len() as u64;
+        probe(&mut Cursor::new(data), size)
+    }
+
+    #[test]
+    fn reads_vints() {
+        assert_eq!(vint(&[0x81]), Some((1, 1, false)));
+        assert_eq!(vint(&[0x40, 0x02]), Some((2, 2, false)));
+        assert_eq!(vint(&[0xFF]), Some((0x7F, 1, true)));
+        assert_eq!(
+            vint(&[0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]).map(|v| v.2),
+            Some(true)
+        );
+        assert_eq!(vint(&[0x00]), None);
+        assert_eq!(vint(&[0x40]), None);
+    }
+
+    #[test]
+    fn reads_movie() {
+        let probe = probe_bytes(movie()).unwrap();
+
+        assert_eq!(
+            probe,
+            MediaProbe {
+                duration_ms: Some(3_600_000),
+                video_codec: Some("h264".into()),
+                width: Some(1920),
+                height: Some(1080),
+                bitrate: None,
+                audio_languages: vec!["eng".into(), "per".into()],
+                subtitle_languages: vec!["per".into()],
+            }
+        );
+    }
+
+    #[test]
+    fn reads_webm_with_custom_timestamp_scale() {
+        let mut info = uint_element(TIMESTAMP_SCALE, 1_000);
+        info.extend(element(DURATION, &1_500_000f32.to_be_bytes()));
+
+        let mut video = uint_element(PIXEL_WIDTH, 640);
+        video.extend(uint_element(PIXEL_HEIGHT, 360));
+        let mut tracks = track(1, "V_VP9", None, &element(VIDEO, &video));
+        tracks.extend(track(2, "A_OPUS", Some("und"), &[]));
+
+        // Tracks before Info, and a segment of unknown size as written by live encoders.
+        let mut segment = element(TRACKS, &tracks);
+        segment.extend(element(INFO, &info));
+        let mut file = header("webm");
+        file.extend(SEGMENT.to_be_bytes());
+        file.push(0xFF);
+        file.extend(segment);
+
+        let probe = probe_bytes(file).unwrap();
+
+        assert_eq!(probe.duration_ms, Some(1_500));
+        assert_eq!(probe.video_codec.as_deref(), Some("vp9"));
+        assert_eq!((probe.width, probe.height), (Some(640), Some(360)));
+        assert!(probe.audio_languages.is_empty());
+    }
+
+    #[test]
+    fn prefers_bcp47_language() {
+        let extra = string_element(LANGUAGE_BCP47, "fa-IR");
+        let tracks = track(2, "A_AAC", Some("per"), &extra);
+
+        let mut segment = element(INFO, &[]);
+        segment.extend(element(TRACKS, &tracks));
+        let mut file = header("matroska");
+        file.extend(element(SEGMENT, &segment));
+
+        let probe = probe_bytes(file).unwrap();
+
+        assert_eq!(probe.audio_languages, vec!["fa-ir"]);
+        assert_eq!(probe.duration_ms, None);
+    }
+
+    #[test]
+    fn rejects_other_ebml_documents() {
+        let mut file = header("something");
+        file.extend(element(SEGMENT, &[]));
+        assert!(probe_bytes(file).is_err());
+    }
+
+    #[test]
+    fn truncated_tracks_are_an_error() {
+        let mut file = movie();
+        let tracks_at = file
+            .windows(4)
+            .position(|w| w == TRACKS.to_be_bytes())
+            .unwrap();
+        file.truncate(tracks_at + 10);
+
+        let probe = probe_bytes(file);
+
+        assert!(probe.is_err());
+    }
+}
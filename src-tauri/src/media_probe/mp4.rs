@@ -0,0 +1,343 @@
+use std::io::{self, Read, Seek, SeekFrom};
+
+use super::{MediaProbe, push_language};
+
+/// Largest `moov` box read into memory. Its sample tables grow with the
+/// length of the movie but stay far below this.
+const MAX_MOOV_SIZE: u64 = 64 * 1024 * 1024;
+
+pub fn is_mp4(magic: &[u8]) -> bool {
+    matches!(
+        magic.get(4..8),
+        Some(b"ftyp" | b"moov" | b"mdat" | b"free" | b"skip" | b"wide")
+    )
+}
+
+fn u16_at(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn u32_at(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn u64_at(data: &[u8], at: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(data.get(at..at + 8)?.try_into().ok()?))
+}
+
+/// Splits an in-memory box body into its child boxes.
+fn boxes(mut data: &[u8]) -> Vec<([u8; 4], &[u8])> {
+    let mut found = vec![];
+
+    while data.len() >= 8 {
+        let size = u32_at(data, 0).unwrap_or_default() as u64;
+        let kind: [u8; 4] = data[4..8].try_into().unwrap();
+
+        let (header, size) = match size {
+            0 => (8, data.len() as u64),
+            1 => match u64_at(data, 8) {
+                Some(size) => (16, size),
+                None => break,
+            },
+            size => (8, size),
+        };
+        if size < header as u64 {
+            break;
+        }
+
+        let end = size.min(data.len() as u64) as usize;
+        found.push((kind, &data[header..end]));
+        data = &data[end..];
+    }
+
+    found
+}
+
+fn child<'a>(data: &'a [u8], kind: &[u8; 4]) -> Option<&'a [u8]> {
+    boxes(data)
+        .into_iter()
+        .find(|(k, _)| k == kind)
+        .map(|(_, body)| body)
+}
+
+/// Reads `(duration, timescale)` from an `mvhd` or `mdhd` box, both of which
+/// share this layout up to the duration.
+fn duration(body: &[u8]) -> Option<(u64, u32)> {
+    match body.first()? {
+        1 => Some((u64_at(body, 24)?, u32_at(body, 20)?)),
+        _ => Some((u64::from(u32_at(body, 16)?), u32_at(body, 12)?)),
+    }
+}
+
+/// Unpacks the ISO-639-2 code stored as three 5-bit letters in `mdhd`.
+fn language(mdhd: &[u8]) -> Option<String> {
+    let at = if *mdhd.first()? == 1 { 32 } else { 20 };
+    let packed = u16_at(mdhd, at)?;
+    if packed == 0 || packed == 0x7FFF {
+        return None;
+    }
+
+    (0..3)
+        .rev()
+        .map(|i| char::from_u32(u32::from((packed >> (i * 5)) & 0x1F) + 0x60))
+        .collect()
+}
+
+fn codec_name(fourcc: &[u8]) -> String {
+    match fourcc {
+        b"avc1" | b"avc3" => "h264".into(),
+        b"hvc1" | b"hev1" => "hevc".into(),
+        b"av01" => "av1".into(),
+        b"vp09" => "vp9".into(),
+        b"vp08" => "vp8".into(),
+        b"mp4v" => "mpeg4".into(),
+        other => String::from_utf8_lossy(other).trim().to_lowercase(),
+    }
+}
+
+fn read_track(trak: &[u8], probe: &mut MediaProbe) -> Option<()> {
+    let mdia = child(trak, b"mdia")?;
+    let handler = child(mdia, b"hdlr")?.get(8..12)?;
+    let language = child(mdia, b"mdhd").and_then(language);
+
+    match handler {
+        b"vide" if probe.video_codec.is_none() => {
+            let stsd = child(child(child(mdia, b"minf")?, b"stbl")?, b"stsd")?;
+            // Skip version, flags and entry count to reach the first sample entry.
+            let entry = boxes(stsd.get(8..)?).into_iter().next()?;
+
+            probe.video_codec = Some(codec_name(&entry.0));
+            probe.width = u16_at(entry.1, 24).map(i32::from);
+            probe.height = u16_at(entry.1, 26).map(i32::from);
+        }
+        b"soun" => push_language(&mut probe.audio_languages, language.as_deref()?),
+        b"sbtl" | b"subt" | b"text" | b"clcp" => {
+            push_language(&mut probe.subtitle_languages, language.as_deref()?)
+        }
+        _ => {}
+    }
+
+    Some(())
+}
+
+fn read_moov(moov: &[u8], probe: &mut MediaProbe) {
+    if let Some((duration, timescale)) = child(moov, b"mvhd").and_then(duration)
+        && timescale > 0
+    {
+        probe.duration_ms = Some((duration.saturating_mul(1000) / u64::from(timescale)) as i64);
+    }
+
+    for (kind, trak) in boxes(moov) {
+        if &kind == b"trak" {
+            read_track(trak, probe);
+        }
+    }
+}
+
+/// Walks the top-level boxes looking for `moov`, which may sit before or
+/// after the media data, and reads the movie header and track headers from it.
+pub fn probe<R: Read + Seek>(reader: &mut R, file_size: u64) -> io::Result<MediaProbe> {
+    let mut probe = MediaProbe::default();
+    let mut offset = 0;
+
+    while offset + 8 <= file_size {
+        reader.seek(SeekFrom::Start(offset))?;
+
+        let mut header = [0u8; 16];
+        reader.read_exact(&mut header[..8])?;
+        let kind: [u8; 4] = header[4..8].try_into().unwrap();
+
+        let (header_len, size) = match u32_at(&header, 0).unwrap_or_default() {
+            0 => (8, file_size - offset),
+            1 => {
+                reader.read_exact(&mut header[8..16])?;
+                (16, u64_at(&header, 8).unwrap_or_default())
+            }
+            size => (8, u64::from(size)),
+        };
+        if size < header_len {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "bad box size"));
+        }
+
+        if &kind == b"moov" {
+            let body_size = size - header_len;
+            if body_size > MAX_MOOV_SIZE {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "moov too large"));
+            }
+            let mut moov = vec![0u8; body_size as usize];
+            reader.read_exact(&mut moov)?;
+
+            read_moov(&moov, &mut probe);
+            return Ok(probe);
+        }
+
+        offset = offset
+            .checked_add(size)
+            .filter(|end| *end <= file_size)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "bad box size"))?;
+    }
+
+    Err(io::Error::new(io::ErrorKind::InvalidData, "missing moov"))
+}
+
+#[cfg(test)]
+pub(crate) mod fixtures {
+    pub fn mp4_box(kind: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut out = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+        out.extend_from_slice(kind);
+        out.extend_from_slice(body);
+        out
+    }
+
+    pub fn mvhd(timescale: u32, duration: u32) -> Vec<u8> {
+        let mut body = vec![0u8; 12];
+        body.extend(timescale.to_be_bytes());
+        body.extend(duration.to_be_bytes());
+        body.extend([0u8; 80]);
+        mp4_box(b"mvhd", &body)
+    }
+
+    pub fn mdhd(language: &str) -> Vec<u8> {
+        let packed = language
+            .bytes()
+            .fold(0u16, |packed, c| (packed << 5) | u16::from(c - 0x60));
+        let mut body = vec![0u8; 20];
+        body.extend(packed.to_be_bytes());
+        body.extend([0u8; 2]);
+        mp4_box(b"mdhd", &body)
+    }
+
+    pub fn hdlr(handler: &[u8; 4]) -> Vec<u8> {
+        let mut body = vec![0u8; 8];
+        body.extend_from_slice(handler);
+        body.extend([0u8; 13]);
+        mp4_box(b"hdlr", &body)
+    }
+
+    pub fn video_stsd(fourcc: &[u8; 4], width: u16, height: u16) -> Vec<u8> {
+        let mut entry = vec![0u8; 24];
+        entry.extend(width.to_be_bytes());
+        entry.extend(height.to_be_bytes());
+        entry.extend([0u8; 50]);
+
+        let mut body = vec![0, 0, 0, 0, 0, 0, 0, 1];
+        body.extend(mp4_box(fourcc, &entry));
+        mp4_box(b"stsd", &body)
+    }
+
+    pub fn trak(handler: &[u8; 4], language: &str, stsd: &[u8]) -> Vec<u8> {
+        let stbl = mp4_box(b"stbl", stsd);
+        let minf = mp4_box(b"minf", &stbl);
+
+        let mut mdia = mdhd(language);
+        mdia.extend(hdlr(handler));
+        mdia.extend(minf);
+
+        let mut trak = mp4_box(b"tkhd", &[0u8; 84]);
+        trak.extend(mp4_box(b"mdia", &mdia));
+        mp4_box(b"trak", &trak)
+    }
+
+    /// A 90 minute 720p HEVC file with English and Persian audio and an
+    /// English subtitle, with `moov` written after the media data.
+    pub fn movie() -> Vec<u8> {
+        let mut moov = mvhd(1000, 5_400_000);
+        moov.extend(trak(b"vide", "und", &video_stsd(b"hvc1", 1280, 720)));
+        moov.extend(trak(b"soun", "eng", &[]));
+        moov.extend(trak(b"soun", "per", &[]));
+        moov.extend(trak(b"sbtl", "eng", &[]));
+
+        let mut file = mp4_box(b"ftyp", b"isom\0\0\x02\0isomiso2");
+        file.extend(mp4_box(b"mdat", &[0u8; 256]));
+        file.extend(mp4_box(b"moov", &moov));
+        file
+    }
+}
+
+#[cfg(test)]
+mod mp4_tests {
+    use super::fixtures::*;
+    use super::*;
+    use std::io::Cursor;
+
+    fn probe_bytes(data: Vec<u8>) -> io::Result<MediaProbe> {
+        let size = data.len() as u64;
+        probe(&mut Cursor::new(data), size)
+    }
+
+    #[test]
+    fn reads_movie() {
+        let probe = probe_bytes(movie()).unwrap();
+
+        assert_eq!(
+            probe,
+            MediaProbe {
+                duration_ms: Some(5_400_000),
+                video_codec: Some("hevc".into()),
+                width: Some(1280),
+                height: Some(720),
+                bitrate: None,
+                audio_languages: vec!["eng".into(), "per".into()],
+                subtitle_languages: vec!["eng".into()],
+            }
+        );
+    }
+
+    #[test]
+    fn reads_moov_before_mdat_and_large_sizes() {
+        let mut moov = mvhd(600, 600 * 42);
+        moov.extend(trak(b"vide", "und", &video_stsd(b"avc1", 1920, 800)));
+
+        // A 64-bit box size, as used for files over 4 GiB.
+        let mut mdat = 1u32.to_be_bytes().to_vec();
+        mdat.extend(b"mdat");
+        mdat.extend(32u64.to_be_bytes());
+        mdat.extend([0u8; 16]);
+
+        let mut file = mp4_box(b"ftyp", b"mp42\0\0\0\0");
+        file.extend(mdat);
+        file.extend(mp4_box(b"moov", &moov));
+
+        let probe = probe_bytes(file).unwrap();
+
+        assert_eq!(probe.duration_ms, Some(42_000));
+        assert_eq!(probe.video_codec.as_deref(), Some("h264"));
+        assert_eq!((probe.width, probe.height), (Some(1920), Some(800)));
+        assert!(probe.audio_languages.is_empty());
+    }
+
+    #[test]
+    fn unpacks_languages() {
+        let mdhd = mdhd("fas");
+        assert_eq!(language(&mdhd[8..]), Some("fas".into()));
+    }
+
+    #[test]
+    fn missing_moov_is_an_error() {
+        let mut file = mp4_box(b"ftyp", b"isom\0\0\0\0");
+        file.extend(mp4_box(b"mdat", &[0u8; 32]));
+        assert!(probe_bytes(file).is_err());
+    }
+
+    #[test]
+    fn broken_box_size_is_an_error() {
+        let mut file = mp4_box(b"ftyp", b"isom\0\0\0\0");
+        file.extend(4u32.to_be_bytes());
+        file.extend(b"free");
+        assert!(probe_bytes(file).is_err());
+    }
+
+    #[test]
+    fn huge_box_size_is_an_error() {
+        let mut mdat = 1u32.to_be_bytes().to_vec();
+        mdat.extend(b"mdat");
+        mdat.extend((u64::MAX - 8).to_be_bytes());
+
+        let mut file = mp4_box(b"ftyp", b"isom\0\0\0\0");
+        file.extend(mdat);
+        file.extend(mp4_box(b"moov", &mvhd(600, 600)));
+
+        let err = probe_bytes(file).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -1,68 +1,280 @@
 use futures::future::join_all;
-use std::path::PathBuf;
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
 use tokio::{fs, task};
-use walkdir::WalkDir;
 
-use crate::db::DB;
+use crate::{
+    data_model::{IdType, MediaFile, SidecarFolder, Subtitle},
+    db::{DB, OrphanCleanup, OrphanReport, unix_time},
+    error::AppError,
+    media_probe, settings,
+};
 
-/// Supported video file extensions.
-const VIDEO_EXTENSIONS: &[&str] = &["mp4", "mkv", "avi"];
+mod walker;
 
-/// Recursively scan a directory to find video
+pub use walker::{LibraryWalker, SkippedPath, WalkResult};
+
+/// Recursively scan a directory to find videos with one of the configured
+/// video extensions that are not in the library yet. Paths left out by the
+/// ignore rules are reported in `skipped`.
 pub async fn find_movies<T: DB + 'static>(
     db: &T,
     root: PathBuf,
-) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+) -> Result<WalkResult, Box<dyn std::error::Error>> {
     if !root.exists() {
-        return Err(format!("Directory does not exist: {}", root.display()).into());
+        return Err(
+            AppError::NotFound(format!("Directory does not exist: {}", root.display())).into(),
+        );
     }
 
-    let files = db.get_all_files()?;
-    let videos = task::spawn_blocking(move || {
-        WalkDir::new(root)
+    let known: HashSet<PathBuf> = db
+        .get_all_files()?
+        .into_iter()
+        .map(|file| PathBuf::from(file.path))
+        .collect();
+    let walker = LibraryWalker::load(db)?;
+
+    let mut result = task::spawn_blocking(move || walker.walk(&root)).await?;
+    result.videos.retain(|path| !known.contains(path));
+
+    Ok(result)
+}
+
+/// Known files under `roots` that are no longer on disk. A root that is not
+/// an existing directory, such as an unmounted drive, is left out so its
+/// files are not taken for gone.
+async fn find_non_existent_files<T: DB>(
+    db: &T,
+    roots: &[PathBuf],
+) -> Result<Vec<MediaFile>, Box<dyn std::error::Error>> {
+    let mut mounted = vec![];
+    for root in roots {
+        if fs::metadata(root).await.is_ok_and(|meta| meta.is_dir()) {
+            mounted.push(root);
+        }
+    }
+
+    let files = db
+        .get_all_files()?
+        .into_iter()
+        .filter(|file| {
+            mounted
+                .iter()
+                .any(|root| Path::new(&file.path).starts_with(root))
+        })
+        .map(|video| async move {
+            let exists = fs::try_exists(&video.path).await.unwrap_or(false);
+            if !exists { Some(video) } else { None }
+        });
+
+    Ok(join_all(files).await.into_iter().flatten().collect())
+}
+
+/// Fills in the fingerprint and container details of files added before they
+/// were recorded, so those files can be followed when they move. Every file
+/// is read once; a file that cannot be read is tried again on the next sync.
+async fn backfill_file_details<T: DB>(db: &T) -> Result<(), Box<dyn std::error::Error>> {
+    let files = db.get_files_without_details()?;
+
+    let details = task::spawn_blocking(move || {
+        files
             .into_iter()
-            .filter_map(Result::ok)
-            .filter(|e| e.file_type().is_file())
-            .map(|e| e.into_path())
-            .filter(|path| {
-                path.extension()
-                    .and_then(|ext| ext.to_str())
-                    .map(|ext| VIDEO_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
-                    .unwrap_or(false)
+            .map(|file| {
+                let path = Path::new(&file.path);
+                let fingerprint = file
+                    .fingerprint
+                    .is_none()
+                    .then(|| MediaFile::fingerprint(path))
+                    .flatten();
+                let probe = file
+                    .probe
+                    .is_none()
+                    .then(|| media_probe::probe(path))
+                    .flatten();
+                let readable = file.fingerprint.is_some() || fingerprint.is_some();
+                (file.id, fingerprint, probe, readable)
             })
-            .filter(|path| files.iter().all(|x| x.path != path.to_string_lossy()))
             .collect::<Vec<_>>()
     })
     .await?;
 
-    Ok(videos)
+    for (file_id, fingerprint, probe, readable) in details {
+        if let Some(fingerprint) = fingerprint {
+            db.update_file_fingerprint(file_id, &fingerprint)?;
+        }
+        if let Some(probe) = probe {
+            db.update_file_probe(file_id, &probe)?;
+        }
+        if readable {
+            db.mark_file_details_read(file_id, unix_time())?;
+        }
+    }
+
+    Ok(())
 }
 
-async fn find_non_existent_paths<T: DB>(
+/// Looks for sidecar subtitles again next to the known files under `roots`,
+/// so subtitles added, renamed or removed, or left behind by a move, are
+/// picked up. Each folder is read once.
+async fn refresh_subtitles<T: DB>(
     db: &T,
-) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
-    let files = db.get_all_files()?.into_iter().map(|video| async move {
-        let exists = fs::try_exists(&video.path).await.unwrap_or(false);
-        if !exists { Some(video) } else { None }
-    });
-
-    let paths = join_all(files)
-        .await
-        .into_iter()
-        .flatten()
-        .map(|video| video.path.into())
-        .collect();
+    roots: &[PathBuf],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut by_folder: HashMap<PathBuf, Vec<MediaFile>> = HashMap::new();
+    for file in db.get_all_files()? {
+        let path = Path::new(&file.path);
+        if let Some(dir) = path.parent()
+            && roots.iter().any(|root| path.starts_with(root))
+        {
+            by_folder.entry(dir.to_path_buf()).or_default().push(file);
+        }
+    }
 
-    Ok(paths)
-}
+    let videos = settings::get_video_extensions(db)?;
+    let changed = task::spawn_blocking(move || {
+        let videos = &videos;
+        by_folder
+            .into_iter()
+            .flat_map(|(dir, files)| {
+                let folder = SidecarFolder::read(&dir);
+                files.into_iter().filter_map(move |file| {
+                    let subtitles = Subtitle::sidecars_in(&folder, Path::new(&file.path), videos);
+                    (subtitles != file.subtitles).then_some((file.id, subtitles))
+                })
+            })
+            .collect::<Vec<_>>()
+    })
+    .await?;
+
+    for (file_id, subtitles) in changed {
+        db.update_file_subtitles(file_id, &subtitles)?;
+    }
 
-pub async fn sync_files<T: DB>(db: &T) -> Result<(), Box<dyn std::error::Error>> {
-    let paths = find_non_existent_paths(db).await?;
-    db.remove_file_by_path(&paths)?;
-    // db.clear_empty_data()?;
     Ok(())
 }
 
+/// Result of matching files that disappeared against files that appeared.
+#[derive(Debug, Default, PartialEq)]
+pub struct Relinked {
+    /// New paths of known files that were moved or renamed.
+    pub moved: Vec<PathBuf>,
+    /// Known files that are gone.
+    pub missing: Vec<PathBuf>,
+    /// Files that are new to the library.
+    pub found: Vec<PathBuf>,
+}
+
+/// Pairs missing files with found files that carry the same fingerprint.
+/// A fingerprint shared by several missing files is ambiguous and never matched.
+fn match_moved_files(missing: &[MediaFile], found: &[PathBuf]) -> Vec<(IdType, PathBuf)> {
+    let mut candidates: HashMap<&str, Option<IdType>> = HashMap::new();
+    for file in missing {
+        if let Some(fingerprint) = file.fingerprint.as_deref() {
+            candidates
+                .entry(fingerprint)
+                .and_modify(|id| *id = None)
+                .or_insert(Some(file.id));
+        }
+    }
+
+    if candidates.is_empty() {
+        return vec![];
+    }
+
+    found
+        .iter()
+        .filter_map(|path| {
+            let fingerprint = MediaFile::fingerprint(path)?;
+            let file_id = candidates.get_mut(fingerprint.as_str())?.take()?;
+            Some((file_id, path.clone()))
+        })
+        .collect()
+}
+
+/// Points the rows of missing files at the found files they were moved to, so
+/// the media they belong to keeps its id, watched state, ranking and tags.
+pub fn relink_moved_files<T: DB>(
+    db: &T,
+    missing: Vec<MediaFile>,
+    found: Vec<PathBuf>,
+) -> Result<Relinked, Box<dyn std::error::Error>> {
+    let moves = match_moved_files(&missing, &found);
+
+    for (file_id, path) in &moves {
+        db.update_file_path(*file_id, path)?;
+    }
+
+    let moved_ids: HashSet<_> = moves.iter().map(|(id, _)| *id).collect();
+    let moved: Vec<_> = moves.into_iter().map(|(_, path)| path).collect();
+
+    Ok(Relinked {
+        missing: missing
+            .into_iter()
+            .filter(|file| !moved_ids.contains(&file.id))
+            .map(|file| file.path.into())
+            .collect(),
+        found: found
+            .into_iter()
+            .filter(|path| !moved.contains(path))
+            .collect(),
+        moved,
+    })
+}
+
+/// Drops the rows of files that are gone, then the episodes, seasons, medias
+/// and imdbs left without any file. Medias carrying user data are kept.
+pub fn remove_missing_files<T: DB>(
+    db: &T,
+    paths: &[PathBuf],
+) -> Result<OrphanReport, Box<dyn std::error::Error>> {
+    if paths.is_empty() {
+        return Ok(OrphanReport::default());
+    }
+
+    let media_ids = db.get_media_ids_by_paths(paths)?;
+    db.remove_file_by_path(paths)?;
+
+    let report = db.remove_orphans(&OrphanCleanup {
+        media_ids: Some(media_ids),
+        ..OrphanCleanup::default()
+    })?;
+
+    Ok(report)
+}
+
+/// Result of reconciling the database with the disk.
+#[derive(Debug, Default, PartialEq)]
+pub struct SyncedFiles {
+    /// Found files that are still unknown and need to be inserted.
+    pub unknown: Vec<PathBuf>,
+    /// What was removed along with the files that are gone.
+    pub orphans: OrphanReport,
+}
+
+/// Reconciles the files under the walked `roots` with the disk: files that
+/// moved are followed, files that are gone are removed and subtitles are
+/// looked up again.
+pub async fn sync_files<T: DB>(
+    db: &T,
+    roots: &[PathBuf],
+    found: Vec<PathBuf>,
+) -> Result<SyncedFiles, Box<dyn std::error::Error>> {
+    let missing = find_non_existent_files(db, roots).await?;
+    let relinked = relink_moved_files(db, missing, found)?;
+
+    let orphans = remove_missing_files(db, &relinked.missing)?;
+
+    backfill_file_details(db).await?;
+    refresh_subtitles(db, roots).await?;
+
+    Ok(SyncedFiles {
+        unknown: relinked.found,
+        orphans,
+    })
+}
+
 #[cfg(test)]
 mod find_movies_tests {
     use super::*;
@@ -106,7 +318,10 @@ mod find_movies_tests {
 
         let root = temp_dir.path().to_path_buf();
 
-        let videos = find_movies(&db, root).await.expect("Function failed");
+        let videos = find_movies(&db, root)
+            .await
+            .expect("Function failed")
+            .videos;
 
         let video_paths: Vec<String> = videos
             .iter()
@@ -130,7 +345,8 @@ mod find_movies_tests {
         let db = MokeDB::default();
         let videos = find_movies(&db, temp_dir.path().to_path_buf())
             .await
-            .expect("Function failed");
+            .expect("Function failed")
+            .videos;
 
         assert_eq!(videos.len(), 0, "Should find no valid video files");
     }
@@ -149,7 +365,8 @@ mod find_movies_tests {
         let temp_dir = TempDir::new().expect("Failed to create temp dir");
         let videos = find_movies(&db, temp_dir.path().to_path_buf())
             .await
-            .expect("Function failed");
+            .expect("Function failed")
+            .videos;
 
         assert_eq!(videos.len(), 0, "Should find no files in empty directory");
     }
@@ -167,8 +384,163 @@ mod find_movies_tests {
         db.insert_file(path_file);
         let videos = find_movies(&db, temp_dir.path().to_path_buf())
             .await
-            .expect("Function failed");
+            .expect("Function failed")
+            .videos;
 
         assert_eq!(videos.len(), 0, "Should exclude files existing in DB");
     }
+
+    #[tokio::test]
+    async fn mixed_case_extensions() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        for name in [
+            "Movie.MKV",
+            "clip.WebM",
+            "disc.M2ts",
+            "show.Ts",
+            "notes.TXT",
+        ] {
+            File::create(temp_dir.path().join(name)).expect("Failed to create file");
+        }
+
+        let db = MokeDB::default();
+        let mut videos = find_movies(&db, temp_dir.path().to_path_buf())
+            .await
+            .expect("Function failed")
+            .videos;
+        videos.sort();
+
+        let names: Vec<_> = videos
+            .iter()
+            .map(|p| p.file_name().unwrap().to_str().unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            vec!["Movie.MKV", "clip.WebM", "disc.M2ts", "show.Ts"]
+        );
+    }
+
+    #[tokio::test]
+    async fn ignored_files_are_reported() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        fs::create_dir(temp_dir.path().join("Extras")).expect("Failed to create dir");
+        for name in ["movie.mkv", "Extras/trailer.mkv", "movie-sample.mkv"] {
+            File::create(temp_dir.path().join(name)).expect("Failed to create file");
+        }
+
+        let db = MokeDB::default();
+        let result = find_movies(&db, temp_dir.path().to_path_buf())
+            .await
+            .expect("Function failed");
+
+        assert_eq!(result.videos, vec![temp_dir.path().join("movie.mkv")]);
+        assert_eq!(result.skipped.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn configured_extensions() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        for name in ["movie.mkv", "old.DivX"] {
+            File::create(temp_dir.path().join(name)).expect("Failed to create file");
+        }
+
+        let db = MokeDB::default();
+        crate::settings::set_video_extensions(&db, &[".divx".into()]).unwrap();
+        let videos = find_movies(&db, temp_dir.path().to_path_buf())
+            .await
+            .expect("Function failed")
+            .videos;
+
+        assert_eq!(videos, vec![temp_dir.path().join("old.DivX")]);
+    }
+}
+
+#[cfg(test)]
+mod match_moved_files_tests {
+    use super::*;
+    use crate::settings::VideoExtensions;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn known_file(id: IdType, path: &Path) -> MediaFile {
+        let mut file = MediaFile {
+            id,
+            ..MediaFile::from(path.to_path_buf())
+        };
+        file.read_details(&VideoExtensions::default());
+        file
+    }
+
+    #[test]
+    fn renamed_file_is_matched() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let old = temp_dir.path().join("movie.mkv");
+        let new = temp_dir.path().join("other").join("Movie.2020.mkv");
+        fs::write(&old, b"movie").expect("Failed to write");
+        let missing = vec![known_file(7, &old)];
+
+        fs::create_dir(temp_dir.path().join("other")).expect("Failed to create dir");
+        fs::rename(&old, &new).expect("Failed to rename");
+
+        assert_eq!(
+            match_moved_files(&missing, std::slice::from_ref(&new)),
+            vec![(7, new)]
+        );
+    }
+
+    #[test]
+    fn different_content_is_not_matched() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let old = temp_dir.path().join("movie.mkv");
+        let new = temp_dir.path().join("movie.2020.mkv");
+        fs::write(&old, b"movie").expect("Failed to write");
+        let missing = vec![known_file(7, &old)];
+
+        fs::remove_file(&old).expect("Failed to remove");
+        fs::write(&new, b"other").expect("Failed to write");
+
+        assert!(match_moved_files(&missing, &[new]).is_empty());
+    }
+
+    #[test]
+    fn file_without_fingerprint_is_not_matched() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let new = temp_dir.path().join("movie.2020.mkv");
+        fs::write(&new, b"movie").expect("Failed to write");
+        let missing = vec![known_file(7, &temp_dir.path().join("movie.mkv"))];
+
+        assert!(match_moved_files(&missing, &[new]).is_empty());
+    }
+
+    #[test]
+    fn ambiguous_fingerprint_is_not_matched() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let a = temp_dir.path().join("a.mkv");
+        let b = temp_dir.path().join("b.mkv");
+        fs::write(&a, b"same").expect("Failed to write");
+        fs::write(&b, b"same").expect("Failed to write");
+        let missing = vec![known_file(1, &a), known_file(2, &b)];
+
+        let new = temp_dir.path().join("c.mkv");
+        fs::rename(&a, &new).expect("Failed to rename");
+        fs::remove_file(&b).expect("Failed to remove");
+
+        assert!(match_moved_files(&missing, &[new]).is_empty());
+    }
+
+    #[test]
+    fn copy_is_matched_once() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let old = temp_dir.path().join("movie.mkv");
+        fs::write(&old, b"movie").expect("Failed to write");
+        let missing = vec![known_file(7, &old)];
+
+        let first = temp_dir.path().join("first.mkv");
+        let second = temp_dir.path().join("second.mkv");
+        fs::copy(&old, &first).expect("Failed to copy");
+        fs::rename(&old, &second).expect("Failed to rename");
+
+        let moves = match_moved_files(&missing, &[first.clone(), second]);
+        assert_eq!(moves, vec![(7, first)]);
+    }
 }
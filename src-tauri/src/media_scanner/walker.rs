@@ -0,0 +1,399 @@
+use ignore::{
+    Match,
+    gitignore::{Gitignore, GitignoreBuilder},
+};
+use serde::Serialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    db::DB,
+    settings::{self, IgnoreRules, VideoExtensions},
+};
+
+/// Name of the gitignore-style files that keep paths out of the library.
+pub const IGNORE_FILE_NAME: &str = ".movievaultignore";
+
+/// Why a path was left out of a scan.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "reason", rename_all = "camelCase")]
+pub enum SkipReason {
+    /// Matched a pattern of a `.movievaultignore` file.
+    #[serde(rename_all = "camelCase")]
+    IgnoreFile {
+        ignore_file: String,
+        pattern: String,
+    },
+    /// Matched one of the global exclude globs.
+    ExcludeGlob { pattern: String },
+    /// A video smaller than the minimum file size, such as a sample clip.
+    TooSmall { size: u64 },
+}
+
+/// A video, or a whole folder, left out of a scan.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SkippedPath {
+    pub path: String,
+    #[serde(flatten)]
+    pub reason: SkipReason,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct WalkResult {
+    pub videos: Vec<PathBuf>,
+    pub skipped: Vec<SkippedPath>,
+}
+
+/// Walks library folders for videos, honouring `.movievaultignore` files at
+/// any level, the global exclude globs and the minimum file size. Ignored
+/// folders are not descended into.
+pub struct LibraryWalker {
+    extensions: VideoExtensions,
+    rules: IgnoreRules,
+}
+
+/// The global excludes and the ignore files of the folders being walked,
+/// outermost first.
+struct Matchers {
+    global: Gitignore,
+    files: Vec<Gitignore>,
+}
+
+impl Matchers {
+    /// Adds the ignore file of `dir`, if it has one.
+    fn push_dir(&mut self, dir: &Path) -> bool {
+        let path = dir.join(IGNORE_FILE_NAME);
+        if !path.is_file() {
+            return false;
+        }
+
+        let mut builder = GitignoreBuilder::new(dir);
+        if let Some(err) = builder.add(&path) {
+            eprintln!("Invalid lines in {}: {err}", path.display());
+        }
+        match builder.build() {
+            Ok(gitignore) => {
+                self.files.push(gitignore);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Deeper ignore files win over shallower ones, and all of them over the
+    /// global excludes, so a `!pattern` can bring back what a parent excluded.
+    fn check(&self, path: &Path, is_dir: bool) -> Option<SkipReason> {
+        for gitignore in self.files.iter().rev() {
+            match gitignore.matched(path, is_dir) {
+                Match::Ignore(glob) => {
+                    return Some(SkipReason::IgnoreFile {
+                        ignore_file: glob
+                            .from()
+                            .map(|from| from.to_string_lossy().to_string())
+                            .unwrap_or_default(),
+                        pattern: glob.original().to_string(),
+                    });
+                }
+                Match::Whitelist(_) => return None,
+                Match::None => {}
+            }
+        }
+
+        match self.global.matched(path, is_dir) {
+            Match::Ignore(glob) => Some(SkipReason::ExcludeGlob {
+                pattern: glob.original().to_string(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl LibraryWalker {
+    pub fn new(extensions: VideoExtensions, rules: IgnoreRules) -> Self {
+        Self { extensions, rules }
+    }
+
+    pub fn load<T: DB>(db: &T) -> anyhow::Result<Self> {
+        Ok(Self::new(
+            settings::get_video_extensions(db)?,
+            settings::get_ignore_rules(db)?,
+        ))
+    }
+
+    pub fn is_video(&self, path: &Path) -> bool {
+        self.extensions.is_video(path)
+    }
+
+    fn matchers(&self, root: &Path) -> Matchers {
+        let mut builder = GitignoreBuilder::new(root);
+        let _ = builder.case_insensitive(true);
+        for glob in &self.rules.exclude_globs {
+            if let Err(err) = builder.add_line(None, glob) {
+                eprintln!("Invalid exclude glob {glob}: {err}");
+            }
+        }
+
+        Matchers {
+            global: builder.build().unwrap_or_else(|_| Gitignore::empty()),
+            files: vec![],
+        }
+    }
+
+    /// Finds the videos under `root`.
+    pub fn walk(&self, root: &Path) -> WalkResult {
+        self.walk_from(root, root)
+    }
+
+    /// Finds the videos at or under `start`, a file or folder inside `root`.
+    /// The ignore files of the folders between `root` and `start` apply too.
+    pub fn walk_from(&self, root: &Path, start: &Path) -> WalkResult {
+        let mut result = WalkResult::default();
+        let mut matchers = self.matchers(root);
+
+        if let Ok(relative) = start.strip_prefix(root) {
+            let mut path = root.to_path_buf();
+            for component in relative.components() {
+                matchers.push_dir(&path);
+                path.push(component);
+
+                let is_dir = path.is_dir();
+                if let Some(reason) = matchers.check(&path, is_dir) {
+                    self.skip(&mut result, &path, is_dir, reason);
+                    return result;
+                }
+            }
+        }
+
+        self.visit(start, &mut matchers, &mut result);
+        result
+    }
+
+    fn skip(&self, result: &mut WalkResult, path: &Path, is_dir: bool, reason: SkipReason) {
+        // Only report what could have been part of the library.
+        if is_dir || self.is_video(path) {
+            result.skipped.push(SkippedPath {
+                path: path.to_string_lossy().to_string(),
+                reason,
+            });
+        }
+    }
+
+    fn visit(&self, path: &Path, matchers: &mut Matchers, result: &mut WalkResult) {
+        // Symlinks are not followed.
+        let Ok(metadata) = fs::symlink_metadata(path) else {
+            return;
+        };
+
+        if metadata.is_file() {
+            if !self.is_video(path) {
+                return;
+            }
+            if metadata.len() < self.rules.min_file_size {
+                self.skip(
+                    result,
+                    path,
+                    false,
+                    SkipReason::TooSmall {
+                        size: metadata.len(),
+                    },
+                );
+            } else {
+                result.videos.push(path.to_path_buf());
+            }
+            return;
+        }
+
+        if !metadata.is_dir() {
+            return;
+        }
+
+        let pushed = matchers.push_dir(path);
+
+        let mut entries: Vec<_> = fs::read_dir(path)
+            .map(|entries| entries.filter_map(Result::ok).collect())
+            .unwrap_or_default();
+        entries.sort_by_key(|entry| entry.file_name());
+
+        for entry in entries {
+            let entry_path = entry.path();
+            let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+
+            match matchers.check(&entry_path, is_dir) {
+                Some(reason) => self.skip(result, &entry_path, is_dir, reason),
+                None => self.visit(&entry_path, matchers, result),
+            }
+        }
+
+        if pushed {
+            matchers.files.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests_walker {
+    use super::*;
+    use std::fs::File;
+    use std::io::Write;
+    use tempfile::TempDir;
+
+    fn write(path: &Path, content: &[u8]) {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).expect("Failed to create dir");
+        }
+        File::create(path)
+            .expect("Failed to create file")
+            .write_all(content)
+            .expect("Failed to write");
+    }
+
+    fn walker(exclude_globs: &[&str], min_file_size: u64) -> LibraryWalker {
+        LibraryWalker::new(
+            VideoExtensions::default(),
+            IgnoreRules {
+                exclude_globs: exclude_globs.iter().map(|g| g.to_string()).collect(),
+                min_file_size,
+            },
+        )
+    }
+
+    fn relative(root: &Path, paths: impl IntoIterator<Item = PathBuf>) -> Vec<String> {
+        paths
+            .into_iter()
+            .map(|p| {
+                p.strip_prefix(root)
+                    .unwrap()
+                    .to_string_lossy()
+                    .replace('\\', "/")
+            })
+            .collect()
+    }
+
+    fn skipped(root: &Path, result: &WalkResult) -> Vec<String> {
+        relative(root, result.skipped.iter().map(|s| PathBuf::from(&s.path)))
+    }
+
+    #[test]
+    fn default_rules_skip_extras_samples_and_trash() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let root = temp_dir.path();
+        write(&root.join("Coco (2017)/Coco.2017.mkv"), b"coco");
+        write(&root.join("Coco (2017)/Coco.2017-sample.mkv"), b"coco");
+        write(&root.join("Coco (2017)/extras/Making.Of.mkv"), b"coco");
+        write(&root.join("Coco (2017)/Featurettes/Short.mp4"), b"coco");
+        write(&root.join(".Trash-1000/files/Old.mkv"), b"old");
+
+        let result =
+            LibraryWalker::new(VideoExtensions::default(), IgnoreRules::default()).walk(root);
+
+        assert_eq!(
+            relative(root, result.videos.clone()),
+            vec!["Coco (2017)/Coco.2017.mkv"]
+        );
+        assert_eq!(
+            skipped(root, &result),
+            vec![
+                ".Trash-1000",
+                "Coco (2017)/Coco.2017-sample.mkv",
+                "Coco (2017)/Featurettes",
+                "Coco (2017)/extras",
+            ]
+        );
+    }
+
+    #[test]
+    fn ignore_files_apply_at_any_level() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let root = temp_dir.path();
+        write(&root.join(IGNORE_FILE_NAME), b"# top level\nKids/\n");
+        write(&root.join("Kids/Up.2009.mkv"), b"up");
+        write(&root.join("Shows/Loki/.movievaultignore"), b"*.s01e02.*\n");
+        write(&root.join("Shows/Loki/loki.s01e01.mkv"), b"loki");
+        write(&root.join("Shows/Loki/loki.s01e02.mkv"), b"loki");
+        write(&root.join("Shows/Other/other.s01e02.mkv"), b"other");
+
+        let result = walker(&[], 0).walk(root);
+
+        assert_eq!(
+            relative(root, result.videos.clone()),
+            vec!["Shows/Loki/loki.s01e01.mkv", "Shows/Other/other.s01e02.mkv"]
+        );
+        assert_eq!(
+            skipped(root, &result),
+            vec!["Kids", "Shows/Loki/loki.s01e02.mkv"]
+        );
+        assert_eq!(
+            result.skipped[1].reason,
+            SkipReason::IgnoreFile {
+                ignore_file: root
+                    .join("Shows/Loki/.movievaultignore")
+                    .to_string_lossy()
+                    .to_string(),
+                pattern: "*.s01e02.*".into(),
+            }
+        );
+    }
+
+    #[test]
+    fn deeper_ignore_file_can_bring_back_excluded_paths() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let root = temp_dir.path();
+        write(&root.join("Anime/.movievaultignore"), b"!Extras/\n");
+        write(&root.join("Anime/Extras/OVA.mkv"), b"ova");
+        write(&root.join("Movies/Extras/Trailer.mkv"), b"trailer");
+
+        let result = walker(&["extras/"], 0).walk(root);
+
+        assert_eq!(
+            relative(root, result.videos.clone()),
+            vec!["Anime/Extras/OVA.mkv"]
+        );
+        assert_eq!(skipped(root, &result), vec!["Movies/Extras"]);
+        assert_eq!(
+            result.skipped[0].reason,
+            SkipReason::ExcludeGlob {
+                pattern: "extras/".into()
+            }
+        );
+    }
+
+    #[test]
+    fn small_videos_are_skipped() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let root = temp_dir.path();
+        write(&root.join("Coco.2017.mkv"), &[0; 64]);
+        write(&root.join("sample.mkv"), &[0; 8]);
+        write(&root.join("notes.txt"), &[0; 8]);
+
+        let result = walker(&[], 32).walk(root);
+
+        assert_eq!(relative(root, result.videos.clone()), vec!["Coco.2017.mkv"]);
+        assert_eq!(skipped(root, &result), vec!["sample.mkv"]);
+        assert_eq!(result.skipped[0].reason, SkipReason::TooSmall { size: 8 });
+    }
+
+    #[test]
+    fn walk_from_honours_parent_ignore_files() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let root = temp_dir.path();
+        write(&root.join(IGNORE_FILE_NAME), b"Kids/\n*.part.mkv\n");
+        write(&root.join("Kids/Up/Up.2009.mkv"), b"up");
+        write(&root.join("Movies/Coco.2017.mkv"), b"coco");
+        write(&root.join("Movies/Soul.2020.part.mkv"), b"soul");
+
+        let walker = walker(&[], 0);
+
+        let kids = walker.walk_from(root, &root.join("Kids/Up"));
+        assert!(kids.videos.is_empty());
+        assert_eq!(skipped(root, &kids), vec!["Kids"]);
+
+        let movies = walker.walk_from(root, &root.join("Movies"));
+        assert_eq!(relative(root, movies.videos), vec!["Movies/Coco.2017.mkv"]);
+
+        let part = walker.walk_from(root, &root.join("Movies/Soul.2020.part.mkv"));
+        assert_eq!(skipped(root, &part), vec!["Movies/Soul.2020.part.mkv"]);
+    }
+}
@@ -1,16 +1,50 @@
 use rayon::prelude::*;
 use std::{
+    collections::HashSet,
     hash::{Hash, Hasher},
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
+use tokio::task::{self, JoinError};
 
 use crate::data_model::Media;
+use crate::settings::VideoExtensions;
 
+/// Groups the videos into medias by their names, without touching the disk.
+/// Files next to episodes are read as episodes more readily.
 pub fn get_metadata(videos: &[PathBuf]) -> Vec<Media> {
-    let meta_data: Vec<_> = videos.par_iter().map(Media::from).collect();
+    let episode_folders: HashSet<&Path> = videos
+        .par_iter()
+        .filter(|path| Media::is_episode(path))
+        .filter_map(|path| path.parent())
+        .collect();
+
+    let meta_data: Vec<Media> = videos
+        .par_iter()
+        .map(|path| {
+            let has_episodes = path
+                .parent()
+                .is_some_and(|folder| episode_folders.contains(folder));
+            Media::from_path(path.clone(), has_episodes)
+        })
+        .collect();
     merge_media(&meta_data)
 }
 
+/// Reads the fingerprints, container details and subtitles of the files of
+/// `medias` on a blocking thread, so a slow disk does not stall the runtime.
+pub async fn read_details(
+    mut medias: Vec<Media>,
+    videos: VideoExtensions,
+) -> Result<Vec<Media>, JoinError> {
+    task::spawn_blocking(move || {
+        medias
+            .par_iter_mut()
+            .for_each(|media| media.read_details(&videos));
+        medias
+    })
+    .await
+}
+
 use std::collections::HashMap;
 
 #[derive(Clone, Eq)]
@@ -102,6 +136,7 @@ mod tests_merge_media {
             number,
             watched: false,
             files,
+            imdb: None,
         }
     }
 
@@ -298,7 +333,11 @@ mod tests_get_metadata {
                         quality: Some("720p".into()),
                         id: 0,
                         language_format: LanguageFormat::Dubbed,
+                        fingerprint: None,
+                        probe: None,
+                        subtitles: vec![],
                     }],
+                    imdb: None,
                 }],
             }],
             ..Media::default()
@@ -320,6 +359,9 @@ mod tests_get_metadata {
                 path: "/film/Who.Am.I.2014.720p.BluRay.HardSub.DigiMoviez.mp4".into(),
                 quality: Some("720p".into()),
                 language_format: LanguageFormat::HardSub,
+                fingerprint: None,
+                probe: None,
+                subtitles: vec![],
             }],
             ..Media::default()
         }];
@@ -353,6 +395,9 @@ mod tests_get_metadata {
                                         file_name: "Loki.S01E02.720p.WEB.DL.Dubbed.ZarFilm".into(),
                                         quality: Some("720p".into()),
                                         language_format: LanguageFormat::Dubbed,
+                                        fingerprint: None,
+                                        probe: None,
+                                        subtitles: vec![],
                                     },
                                     MediaFile {
                                         id: 0,
@@ -360,8 +405,12 @@ mod tests_get_metadata {
                                         file_name: "Loki.S01E02.720p.WEB.DL.Dubbed".into(),
                                         quality: Some("720p".into()),
                                         language_format: LanguageFormat::Dubbed,
+                                        fingerprint: None,
+                                        probe: None,
+                                        subtitles: vec![],
                                     },
                                 ],
+                                imdb: None,
                             },
                             Episode {
                                 id: 0,
@@ -374,6 +423,9 @@ mod tests_get_metadata {
                                         file_name: "Loki.S01E03.720p.WEB.DL.Dubbed".into(),
                                         quality: Some("720p".into()),
                                         language_format: LanguageFormat::Dubbed,
+                                        fingerprint: None,
+                                        probe: None,
+                                        subtitles: vec![],
                                     },
                                     MediaFile {
                                         id: 0,
@@ -381,8 +433,12 @@ mod tests_get_metadata {
                                         file_name: "Loki.S01E03.720p.WEB.DL.Dubbed.ZarFilm".into(),
                                         quality: Some("720p".into()),
                                         language_format: LanguageFormat::Dubbed,
+                                        fingerprint: None,
+                                        probe: None,
+                                        subtitles: vec![],
                                     },
                                 ],
+                                imdb: None,
                             },
                         ],
                     },
@@ -402,6 +458,9 @@ mod tests_get_metadata {
                                         file_name: "Loki.S02E02.720p.WEB.DL.Dubbed.ZarFilm".into(),
                                         quality: Some("720p".into()),
                                         language_format: LanguageFormat::Dubbed,
+                                        fingerprint: None,
+                                        probe: None,
+                                        subtitles: vec![],
                                     },
                                     MediaFile {
                                         id: 0,
@@ -409,8 +468,12 @@ mod tests_get_metadata {
                                         file_name: "Loki.S02E02.720p.WEB.DL.Dubbed".into(),
                                         quality: Some("720p".into()),
                                         language_format: LanguageFormat::Dubbed,
+                                        fingerprint: None,
+                                        probe: None,
+                                        subtitles: vec![],
                                     },
                                 ],
+                                imdb: None,
                             },
                             Episode {
                                 id: 0,
@@ -423,6 +486,9 @@ mod tests_get_metadata {
                                         file_name: "Loki.S02E03.720p.WEB.DL.Dubbed.ZarFilm".into(),
                                         quality: Some("720p".into()),
                                         language_format: LanguageFormat::Dubbed,
+                                        fingerprint: None,
+                                        probe: None,
+                                        subtitles: vec![],
                                     },
                                     MediaFile {
                                         id: 0,
@@ -430,8 +496,12 @@ mod tests_get_metadata {
                                         file_name: "Loki.S02E03.720p.WEB.DL.Dubbed".into(),
                                         quality: Some("720p".into()),
                                         language_format: LanguageFormat::Dubbed,
+                                        fingerprint: None,
+                                        probe: None,
+                                        subtitles: vec![],
                                     },
                                 ],
+                                imdb: None,
                             },
                         ],
                     },
@@ -448,6 +518,9 @@ mod tests_get_metadata {
                         file_name: "Who.Am.I.2014.720p.BluRay.HardSub.DigiMoviez".into(),
                         quality: Some("720p".into()),
                         language_format: LanguageFormat::HardSub,
+                        fingerprint: None,
+                        probe: None,
+                        subtitles: vec![],
                     },
                     MediaFile {
                         id: 0,
@@ -455,6 +528,9 @@ mod tests_get_metadata {
                         file_name: "Who.Am.I.2014.720p.BluRay.HardSub.F2M".into(),
                         quality: Some("720p".into()),
                         language_format: LanguageFormat::HardSub,
+                        fingerprint: None,
+                        probe: None,
+                        subtitles: vec![],
                     },
                 ],
             ..Media::default()
@@ -476,4 +552,21 @@ mod tests_get_metadata {
 
         assert_eq!(result, ans);
     }
+
+    #[test]
+    fn compact_numbers_next_to_episodes() {
+        let result = get_metadata(&[
+            "/tv/fargo/Fargo.S01E01.mkv".into(),
+            "/tv/fargo/Fargo - 102.mkv".into(),
+            "/film/Fahrenheit - 451.mkv".into(),
+        ]);
+
+        let (series, movies): (Vec<_>, Vec<_>) = result.iter().partition(|media| media.is_series());
+        assert_eq!(series.len(), 1);
+        assert_eq!(series[0].name, "fargo");
+        assert_eq!(series[0].seasons[0].episodes.len(), 2);
+        // Alone in its folder, the number is part of the movie's name.
+        assert_eq!(movies.len(), 1);
+        assert!(movies[0].name.ends_with("451"));
+    }
 }
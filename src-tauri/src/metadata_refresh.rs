@@ -0,0 +1,285 @@
+use anyhow::Result;
+use std::time::Duration;
+use tokio::{sync::Mutex, time::sleep};
+
+use crate::db::{DB, unix_time};
+use crate::error::AppError;
+use crate::fetch_imdb::{FreeImdb, is_series_type};
+use crate::settings;
+
+/// Titles sent to freeimdb at once.
+const REFRESH_BATCH: usize = 20;
+
+/// Pause between batches, so refreshing a whole library stays under the rate
+/// limit.
+const BATCH_DELAY: Duration = Duration::from_secs(5);
+
+/// How often the scheduler looks for stale titles.
+pub const REFRESH_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RefreshReport {
+    /// Titles whose details were saved again.
+    pub refreshed: usize,
+    /// Stale titles freeimdb returned nothing for.
+    pub missing: Vec<String>,
+}
+
+/// Fetches the details of stale titles again so ratings, votes, genres and
+/// people stay current, along with the episode lists of series. Only one
+/// refresh runs at a time, whether it was started by the scheduler or by the
+/// user.
+pub struct MetadataRefresher {
+    freeimdb: FreeImdb,
+    batch_delay: Duration,
+    running: Mutex<()>,
+}
+
+impl Default for MetadataRefresher {
+    fn default() -> Self {
+        Self::new(FreeImdb::default(), BATCH_DELAY)
+    }
+}
+
+impl MetadataRefresher {
+    pub fn new(freeimdb: FreeImdb, batch_delay: Duration) -> Self {
+        Self {
+            freeimdb,
+            batch_delay,
+            running: Mutex::new(()),
+        }
+    }
+
+    /// Refreshes every title older than the configured age, oldest first.
+    /// Batches saved before a failure are kept.
+    pub async fn refresh_stale<T: DB + Sync>(&self, db: &T) -> Result<RefreshReport> {
+        let Ok(_running) = self.running.try_lock() else {
+            return Err(AppError::Conflict("A metadata refresh is already running".into()).into());
+        };
+        if settings::get_metadata_cache_settings(db)?.offline {
+            return Err(AppError::NetworkUnavailable(
+                "Offline mode: metadata is not refreshed".into(),
+            )
+            .into());
+        }
+
+        let max_age = settings::get_metadata_refresh_settings(db)?.max_age_days;
+        let cutoff = unix_time() - i64::from(max_age) * SECONDS_PER_DAY;
+        let stale = db.get_stale_imdb_ids(cutoff)?;
+
+        let mut report = RefreshReport::default();
+        for (i, batch) in stale.chunks(REFRESH_BATCH).enumerate() {
+            if i > 0 {
+                sleep(self.batch_delay).await;
+            }
+
+            let mut imdbs = self.freeimdb.process_movies(batch).await?;
+            imdbs.retain(|imdb| batch.contains(&imdb.imdb_id));
+            db.upsert_imdbs(&imdbs, unix_time())?;
+
+            for series in imdbs.iter().filter(|imdb| is_series_type(&imdb.r#type)) {
+                self.refresh_episodes(db, &series.imdb_id).await?;
+            }
+
+            report.refreshed += imdbs.len();
+            report.missing.extend(
+                batch
+                    .iter()
+                    .filter(|id| !imdbs.iter().any(|imdb| &imdb.imdb_id == *id))
+                    .cloned(),
+            );
+        }
+
+        // Episode lists that failed or were never fetched, even when the title
+        // itself is still fresh.
+        for series_id in db.get_stale_episode_list_imdb_ids(cutoff)? {
+            self.refresh_episodes(db, &series_id).await?;
+        }
+
+        Ok(report)
+    }
+
+    async fn refresh_episodes<T: DB>(&self, db: &T, series_id: &str) -> Result<()> {
+        match self.freeimdb.get_episodes(series_id).await {
+            Ok(episodes) => db.set_imdb_episodes(series_id, &episodes)?,
+            Err(err) => eprintln!("Failed to refresh the episodes of {series_id}: {err}"),
+        }
+        db.mark_imdb_episodes_fetched(series_id, unix_time())
+    }
+}
+
+#[cfg(test)]
+mod tests_metadata_refresh {
+    use super::*;
+    use crate::data_model::{Imdb, Media};
+    use crate::db::{Sqlite, temp_db};
+    use crate::fetch_imdb::moke_server::MokeServer;
+    use crate::settings::{MetadataCacheSettings, set_metadata_cache_settings};
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    const BATCH: &str = include_str!("fetch_imdb/fixtures/freeimdb_batch_get.json");
+
+    fn setup(server: &MokeServer) -> (TempDir, Sqlite, MetadataRefresher) {
+        let (temp_dir, db) = temp_db();
+        let refresher =
+            MetadataRefresher::new(FreeImdb::new(server.url(), Duration::ZERO), Duration::ZERO);
+        (temp_dir, db, refresher)
+    }
+
+    fn outdated(imdb_id: &str) -> Imdb {
+        Imdb {
+            imdb_id: imdb_id.into(),
+            r#type: "movie".into(),
+            title: "Old Title".into(),
+            year: 2014,
+            imdb_rating: "5.0".into(),
+            imdb_votes: 10,
+            genres: vec!["Comedy".into()],
+            ..Imdb::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn refreshes_stale_titles_only() {
+        let server = MokeServer::start().await;
+        server.route("/titles:batchGet", &[(200, BATCH)]);
+        let (_temp_dir, db, refresher) = setup(&server);
+
+        db.upsert_imdbs(&[outdated("tt2172934")], 0).unwrap();
+        db.upsert_imdbs(&[outdated("tt2085059")], unix_time())
+            .unwrap();
+
+        let report = refresher.refresh_stale(&db).await.unwrap();
+
+        assert_eq!(report.refreshed, 1);
+        assert!(report.missing.is_empty());
+        assert_eq!(server.hits("/titles:batchGet"), 1);
+
+        let refreshed = db.get_imdb("tt2172934").unwrap().unwrap();
+        assert_eq!(refreshed.title, "3 Days to Kill");
+        assert_eq!(refreshed.imdb_rating, "6.2");
+        assert_eq!(refreshed.imdb_votes, 101234);
+        assert_eq!(refreshed.genres.len(), 3);
+        assert!(!refreshed.genres.contains(&"Comedy".to_string()));
+        assert_eq!(
+            db.get_imdb("tt2085059").unwrap().unwrap().title,
+            "Old Title"
+        );
+
+        // Refreshed titles are fresh until they reach the max age again.
+        let report = refresher.refresh_stale(&db).await.unwrap();
+        assert_eq!(report, RefreshReport::default());
+        assert_eq!(server.hits("/titles:batchGet"), 1);
+    }
+
+    #[tokio::test]
+    async fn refreshes_episode_lists_of_series() {
+        let server = MokeServer::start().await;
+        server.route("/titles:batchGet", &[(200, BATCH)]);
+        server.route(
+            "/titles/tt2085059/episodes?pageSize=50",
+            &[(
+                200,
+                include_str!("fetch_imdb/fixtures/freeimdb_episodes_page_1.json"),
+            )],
+        );
+        server.route(
+            "/titles/tt2085059/episodes?pageSize=50&pageToken=page-2",
+            &[(
+                200,
+                include_str!("fetch_imdb/fixtures/freeimdb_episodes_page_2.json"),
+            )],
+        );
+        let (_temp_dir, db, refresher) = setup(&server);
+
+        db.upsert_imdbs(&[outdated("tt2172934"), outdated("tt2085059")], 0)
+            .unwrap();
+
+        let report = refresher.refresh_stale(&db).await.unwrap();
+
+        assert_eq!(report.refreshed, 2);
+        // Only the series has an episode list to fetch.
+        assert_eq!(server.hits("/titles/tt2085059/episodes"), 2);
+        assert_eq!(server.hits("/titles/tt2172934/episodes"), 0);
+    }
+
+    #[tokio::test]
+    async fn retries_episode_lists_of_fresh_titles() {
+        let server = MokeServer::start().await;
+        server.route(
+            "/titles/tt2085059/episodes?pageSize=50",
+            &[(
+                200,
+                include_str!("fetch_imdb/fixtures/freeimdb_episodes_page_1.json"),
+            )],
+        );
+        server.route(
+            "/titles/tt2085059/episodes?pageSize=50&pageToken=page-2",
+            &[(
+                200,
+                include_str!("fetch_imdb/fixtures/freeimdb_episodes_page_2.json"),
+            )],
+        );
+        let (_temp_dir, db, refresher) = setup(&server);
+
+        let series = Media {
+            imdb: Some(Imdb {
+                imdb_id: "tt2085059".into(),
+                r#type: "tvSeries".into(),
+                ..Imdb::default()
+            }),
+            ..Media::from(PathBuf::from("/s/black.mirror.s02e01.mkv"))
+        };
+        db.insert_media(&series).unwrap();
+        // A fetch that failed long ago.
+        db.mark_imdb_episodes_fetched("tt2085059", 0).unwrap();
+
+        let report = refresher.refresh_stale(&db).await.unwrap();
+        assert_eq!(report, RefreshReport::default());
+        assert_eq!(server.hits("/titles/tt2085059/episodes"), 2);
+
+        refresher.refresh_stale(&db).await.unwrap();
+        assert_eq!(server.hits("/titles/tt2085059/episodes"), 2);
+    }
+
+    #[tokio::test]
+    async fn reports_titles_the_provider_does_not_return() {
+        let server = MokeServer::start().await;
+        server.route("/titles:batchGet", &[(200, BATCH)]);
+        let (_temp_dir, db, refresher) = setup(&server);
+
+        db.upsert_imdbs(&[outdated("tt2172934"), outdated("tt0000001")], 0)
+            .unwrap();
+
+        let report = refresher.refresh_stale(&db).await.unwrap();
+
+        assert_eq!(report.refreshed, 1);
+        assert_eq!(report.missing, vec!["tt0000001".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn does_not_refresh_in_offline_mode() {
+        let server = MokeServer::start().await;
+        server.route("/titles:batchGet", &[(200, BATCH)]);
+        let (_temp_dir, db, refresher) = setup(&server);
+
+        db.upsert_imdbs(&[outdated("tt2172934")], 0).unwrap();
+        set_metadata_cache_settings(
+            &db,
+            MetadataCacheSettings {
+                ttl_hours: 1,
+                offline: true,
+            },
+        )
+        .unwrap();
+
+        let err = refresher.refresh_stale(&db).await.unwrap_err();
+
+        assert_eq!(AppError::from(err).code(), "networkUnavailable");
+        assert_eq!(server.hits("/titles:batchGet"), 0);
+    }
+}
@@ -0,0 +1,407 @@
+use anyhow::Result;
+use ignore::gitignore::GitignoreBuilder;
+use std::{collections::BTreeSet, path::Path};
+
+use crate::{db::DB, error::AppError};
+
+/// Settings table key of the video extension list.
+const VIDEO_EXTENSIONS_KEY: &str = "video_extensions";
+
+/// Settings table key of the scanner ignore rules.
+const IGNORE_RULES_KEY: &str = "ignore_rules";
+
+/// Settings table key of the metadata cache settings.
+const METADATA_CACHE_KEY: &str = "metadata_cache";
+
+/// Settings table key of the metadata refresh settings.
+const METADATA_REFRESH_KEY: &str = "metadata_refresh";
+
+/// Video file extensions scanned until the user picks their own.
+pub const DEFAULT_VIDEO_EXTENSIONS: &[&str] = &[
+    "mp4", "mkv", "avi", "m4v", "webm", "mov", "ts", "wmv", "m2ts",
+];
+
+/// Folders and files that are never part of a movie or episode.
+pub const DEFAULT_EXCLUDE_GLOBS: &[&str] = &[
+    "Extras/",
+    "Featurettes/",
+    "Trailers/",
+    "Behind The Scenes/",
+    "Deleted Scenes/",
+    "Sample/",
+    "Samples/",
+    ".Trash*/",
+    "$RECYCLE.BIN/",
+    "*-trailer.*",
+    "*-sample.*",
+    "*.sample.*",
+];
+
+/// File extensions treated as video, lower-cased and without the leading dot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VideoExtensions(BTreeSet<String>);
+
+impl Default for VideoExtensions {
+    fn default() -> Self {
+        Self::new(DEFAULT_VIDEO_EXTENSIONS)
+    }
+}
+
+impl VideoExtensions {
+    /// Normalises `.MKV`, ` mkv ` and `mkv` to the same entry and drops blanks.
+    pub fn new<I, S>(extensions: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        Self(
+            extensions
+                .into_iter()
+                .map(|ext| ext.as_ref().trim().trim_start_matches('.').to_lowercase())
+                .filter(|ext| !ext.is_empty())
+                .collect(),
+        )
+    }
+
+    /// Whether `path` has one of the extensions, in any case.
+    pub fn is_video(&self, path: &Path) -> bool {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| self.0.contains(&ext.to_lowercase()))
+            .unwrap_or(false)
+    }
+
+    pub fn to_vec(&self) -> Vec<String> {
+        self.0.iter().cloned().collect()
+    }
+}
+
+/// Rules that keep paths out of the library on top of `.movievaultignore` files.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IgnoreRules {
+    /// Gitignore-style patterns applied under every root, matched case-insensitively.
+    pub exclude_globs: Vec<String>,
+    /// Videos smaller than this many bytes are skipped; `0` keeps them all.
+    pub min_file_size: u64,
+}
+
+impl Default for IgnoreRules {
+    fn default() -> Self {
+        Self {
+            exclude_globs: DEFAULT_EXCLUDE_GLOBS
+                .iter()
+                .map(|g| g.to_string())
+                .collect(),
+            min_file_size: 0,
+        }
+    }
+}
+
+/// How provider responses are reused instead of fetched again.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetadataCacheSettings {
+    /// Cached responses younger than this many hours are used without asking
+    /// the providers; `0` always asks and only keeps them for offline use.
+    pub ttl_hours: u32,
+    /// Answer only from the cache and the stored titles, without any request.
+    pub offline: bool,
+}
+
+impl Default for MetadataCacheSettings {
+    fn default() -> Self {
+        Self {
+            ttl_hours: 24 * 30,
+            offline: false,
+        }
+    }
+}
+
+/// When stored title details are fetched again.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetadataRefreshSettings {
+    /// Refresh stale titles in the background.
+    pub enabled: bool,
+    /// Titles fetched longer ago than this many days are stale.
+    pub max_age_days: u32,
+}
+
+impl Default for MetadataRefreshSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_age_days: 14,
+        }
+    }
+}
+
+/// The video extensions stored in the settings, or the defaults when none
+/// were saved yet.
+pub fn get_video_extensions<T: DB>(db: &T) -> Result<VideoExtensions> {
+    match db.get_setting(VIDEO_EXTENSIONS_KEY)? {
+        Some(value) => Ok(VideoExtensions::new(serde_json::from_str::<Vec<String>>(
+            &value,
+        )?)),
+        None => Ok(VideoExtensions::default()),
+    }
+}
+
+/// Normalises and stores the video extensions, returning what was saved.
+pub fn set_video_extensions<T: DB>(db: &T, extensions: &[String]) -> Result<VideoExtensions> {
+    let extensions = VideoExtensions::new(extensions);
+
+    if let Some(invalid) = extensions
+        .0
+        .iter()
+        .find(|ext| !ext.chars().all(|c| c.is_ascii_alphanumeric()))
+    {
+        return Err(AppError::InvalidInput(format!("Invalid video extension: {invalid}")).into());
+    }
+    if extensions.0.is_empty() {
+        return Err(
+            AppError::InvalidInput("At least one video extension is required".into()).into(),
+        );
+    }
+
+    db.set_setting(
+        VIDEO_EXTENSIONS_KEY,
+        &serde_json::to_string(&extensions.to_vec())?,
+    )?;
+
+    Ok(extensions)
+}
+
+/// The ignore rules stored in the settings, or the defaults when none were
+/// saved yet.
+pub fn get_ignore_rules<T: DB>(db: &T) -> Result<IgnoreRules> {
+    match db.get_setting(IGNORE_RULES_KEY)? {
+        Some(value) => Ok(serde_json::from_str(&value)?),
+        None => Ok(IgnoreRules::default()),
+    }
+}
+
+/// Checks and stores the ignore rules, returning what was saved.
+pub fn set_ignore_rules<T: DB>(db: &T, rules: IgnoreRules) -> Result<IgnoreRules> {
+    let rules = IgnoreRules {
+        exclude_globs: rules
+            .exclude_globs
+            .iter()
+            .map(|glob| glob.trim().to_string())
+            .filter(|glob| !glob.is_empty() && !glob.starts_with('#'))
+            .collect(),
+        ..rules
+    };
+
+    let mut builder = GitignoreBuilder::new("");
+    for glob in &rules.exclude_globs {
+        if let Err(err) = builder.add_line(None, glob) {
+            return Err(
+                AppError::InvalidInput(format!("Invalid exclude glob {glob}: {err}")).into(),
+            );
+        }
+    }
+
+    db.set_setting(IGNORE_RULES_KEY, &serde_json::to_string(&rules)?)?;
+
+    Ok(rules)
+}
+
+/// The metadata cache settings stored in the settings, or the defaults when
+/// none were saved yet.
+pub fn get_metadata_cache_settings<T: DB>(db: &T) -> Result<MetadataCacheSettings> {
+    match db.get_setting(METADATA_CACHE_KEY)? {
+        Some(value) => Ok(serde_json::from_str(&value)?),
+        None => Ok(MetadataCacheSettings::default()),
+    }
+}
+
+/// Stores the metadata cache settings, returning what was saved.
+pub fn set_metadata_cache_settings<T: DB>(
+    db: &T,
+    cache: MetadataCacheSettings,
+) -> Result<MetadataCacheSettings> {
+    db.set_setting(METADATA_CACHE_KEY, &serde_json::to_string(&cache)?)?;
+
+    Ok(cache)
+}
+
+/// The metadata refresh settings stored in the settings, or the defaults
+/// when none were saved yet.
+pub fn get_metadata_refresh_settings<T: DB>(db: &T) -> Result<MetadataRefreshSettings> {
+    match db.get_setting(METADATA_REFRESH_KEY)? {
+        Some(value) => Ok(serde_json::from_str(&value)?),
+        None => Ok(MetadataRefreshSettings::default()),
+    }
+}
+
+/// Checks and stores the metadata refresh settings, returning what was saved.
+pub fn set_metadata_refresh_settings<T: DB>(
+    db: &T,
+    refresh: MetadataRefreshSettings,
+) -> Result<MetadataRefreshSettings> {
+    if refresh.max_age_days == 0 {
+        return Err(
+            AppError::InvalidInput("Titles must be kept for at least one day".into()).into(),
+        );
+    }
+
+    db.set_setting(METADATA_REFRESH_KEY, &serde_json::to_string(&refresh)?)?;
+
+    Ok(refresh)
+}
+
+#[cfg(test)]
+mod tests_video_extensions {
+    use super::*;
+    use crate::db::MokeDB;
+
+    #[test]
+    fn defaults_cover_common_containers() {
+        let extensions = get_video_extensions(&MokeDB::default()).unwrap();
+
+        for name in [
+            "a.mp4", "a.mkv", "a.avi", "a.m4v", "a.webm", "a.mov", "a.ts", "a.wmv", "a.m2ts",
+        ] {
+            assert!(extensions.is_video(Path::new(name)), "{name}");
+        }
+        assert!(!extensions.is_video(Path::new("a.srt")));
+        assert!(!extensions.is_video(Path::new("mkv")));
+    }
+
+    #[test]
+    fn matches_mixed_case_extensions() {
+        let extensions = VideoExtensions::default();
+
+        for name in [
+            "Movie.MKV",
+            "movie.Mp4",
+            "clip.WebM",
+            "disc.M2TS",
+            "show.Ts",
+        ] {
+            assert!(extensions.is_video(Path::new(name)), "{name}");
+        }
+    }
+
+    #[test]
+    fn normalises_stored_extensions() {
+        let db = MokeDB::default();
+
+        let saved = set_video_extensions(
+            &db,
+            &[" .MKV ".into(), "mkv".into(), "DivX".into(), "".into()],
+        )
+        .unwrap();
+
+        assert_eq!(saved.to_vec(), vec!["divx", "mkv"]);
+        assert_eq!(get_video_extensions(&db).unwrap(), saved);
+        assert!(saved.is_video(Path::new("Movie.DIVX")));
+        assert!(!saved.is_video(Path::new("movie.mp4")));
+    }
+
+    #[test]
+    fn rejects_empty_and_invalid_lists() {
+        let db = MokeDB::default();
+
+        assert!(set_video_extensions(&db, &[" ".into()]).is_err());
+        assert!(set_video_extensions(&db, &["mk v".into()]).is_err());
+        assert!(set_video_extensions(&db, &["*.mkv".into()]).is_err());
+        assert_eq!(
+            get_video_extensions(&db).unwrap(),
+            VideoExtensions::default()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests_ignore_rules {
+    use super::*;
+    use crate::db::MokeDB;
+
+    #[test]
+    fn defaults_until_saved() {
+        let db = MokeDB::default();
+        assert_eq!(get_ignore_rules(&db).unwrap(), IgnoreRules::default());
+
+        let saved = set_ignore_rules(
+            &db,
+            IgnoreRules {
+                exclude_globs: vec![" *.part ".into(), "".into(), "# comment".into()],
+                min_file_size: 50_000_000,
+            },
+        )
+        .unwrap();
+
+        assert_eq!(saved.exclude_globs, vec!["*.part"]);
+        assert_eq!(get_ignore_rules(&db).unwrap(), saved);
+    }
+
+    #[test]
+    fn rejects_invalid_globs() {
+        let db = MokeDB::default();
+        let rules = IgnoreRules {
+            exclude_globs: vec!["Extras/{a,b".into()],
+            min_file_size: 0,
+        };
+
+        assert!(set_ignore_rules(&db, rules).is_err());
+        assert_eq!(get_ignore_rules(&db).unwrap(), IgnoreRules::default());
+    }
+}
+
+#[cfg(test)]
+mod tests_metadata_cache_settings {
+    use super::*;
+    use crate::db::MokeDB;
+
+    #[test]
+    fn defaults_until_saved() {
+        let db = MokeDB::default();
+        assert_eq!(
+            get_metadata_cache_settings(&db).unwrap(),
+            MetadataCacheSettings::default()
+        );
+
+        let saved = set_metadata_cache_settings(
+            &db,
+            MetadataCacheSettings {
+                ttl_hours: 6,
+                offline: true,
+            },
+        )
+        .unwrap();
+
+        assert_eq!(get_metadata_cache_settings(&db).unwrap(), saved);
+    }
+}
+
+#[cfg(test)]
+mod tests_metadata_refresh_settings {
+    use super::*;
+    use crate::db::MokeDB;
+
+    #[test]
+    fn rejects_zero_max_age() {
+        let db = MokeDB::default();
+        let refresh = MetadataRefreshSettings {
+            enabled: false,
+            max_age_days: 0,
+        };
+
+        assert!(set_metadata_refresh_settings(&db, refresh).is_err());
+        assert_eq!(
+            get_metadata_refresh_settings(&db).unwrap(),
+            MetadataRefreshSettings::default()
+        );
+
+        let refresh = MetadataRefreshSettings {
+            enabled: false,
+            max_age_days: 3,
+        };
+        let saved = set_metadata_refresh_settings(&db, refresh.clone()).unwrap();
+        assert_eq!(saved, refresh);
+        assert_eq!(get_metadata_refresh_settings(&db).unwrap(), refresh);
+    }
+}
@@ -0,0 +1,579 @@
+use anyhow::Result;
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+use tokio::sync::watch;
+
+use crate::{
+    data_model::{IdType, Media, SyncJob, SyncJobStatus},
+    db::DB,
+    error::AppError,
+    fetch_imdb::{LookupFailure, MetadataProviders},
+    metadata_extractor, settings,
+};
+
+/// Medias looked up and inserted per committed chunk.
+const CHUNK_SIZE: usize = 50;
+
+/// Medias whose files are read between two progress reports.
+const READ_BATCH: usize = 64;
+
+/// What a running job should do before its next chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Signal {
+    Run,
+    Pause,
+    Cancel,
+}
+
+/// The steps of a sync, in the order they run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SyncPhase {
+    Walking,
+    Cleanup,
+    Parsing,
+    Lookup,
+    Insert,
+}
+
+/// Progress of a sync, sent after every step of each phase.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncProgress {
+    /// `None` until the job is saved, once its files are parsed.
+    pub job_id: Option<IdType>,
+    pub status: SyncJobStatus,
+    pub phase: SyncPhase,
+    /// Roots walked, files parsed, or medias looked up or inserted so far.
+    pub done: usize,
+    pub total: usize,
+    pub current_path: Option<String>,
+    /// Medias the lookup of this step could not match.
+    pub failures: Vec<LookupFailure>,
+}
+
+impl SyncProgress {
+    pub fn new(phase: SyncPhase, done: usize, total: usize) -> Self {
+        Self {
+            job_id: None,
+            status: SyncJobStatus::Running,
+            phase,
+            done,
+            total,
+            current_path: None,
+            failures: vec![],
+        }
+    }
+
+    pub fn at(self, path: &Path) -> Self {
+        Self {
+            current_path: Some(path.to_string_lossy().into_owned()),
+            ..self
+        }
+    }
+
+    /// Media counts of the job, as reached in `phase`.
+    fn of_job(job: &SyncJob, phase: SyncPhase) -> Self {
+        Self {
+            job_id: Some(job.id),
+            status: job.status,
+            ..Self::new(phase, job.inserted, job.total)
+        }
+    }
+}
+
+/// Registry of the sync jobs running in this process. Jobs check their signal
+/// between chunks, so pausing or cancelling takes effect once the chunk in
+/// flight is committed.
+#[derive(Default)]
+pub struct SyncJobs {
+    signals: Mutex<HashMap<IdType, watch::Sender<Signal>>>,
+}
+
+impl SyncJobs {
+    /// Parses the video files found under `roots` and saves a job for them.
+    /// The job and its medias are handed to [`SyncJobs::drive`], which can
+    /// run in the background.
+    pub async fn prepare<T: DB>(
+        &self,
+        db: &T,
+        roots: &[PathBuf],
+        files: &[PathBuf],
+        on_progress: impl Fn(SyncProgress),
+    ) -> Result<(SyncJob, Vec<Media>)> {
+        // Files still pending in another unfinished job are left to that job.
+        let claimed: HashSet<String> = db
+            .get_sync_jobs()?
+            .into_iter()
+            .filter(|job| !job.status.is_finished())
+            .flat_map(|job| job.pending)
+            .collect();
+        let files: Vec<PathBuf> = files
+            .iter()
+            .filter(|path| !claimed.contains(path.to_string_lossy().as_ref()))
+            .cloned()
+            .collect();
+
+        let medias = parse(db, &files, None, &on_progress).await?;
+        let job = db.insert_sync_job(&to_strings(roots), &to_strings(&files), medias.len())?;
+
+        Ok((job, medias))
+    }
+
+    /// Runs an unfinished job, such as one interrupted by a restart, from its
+    /// last committed chunk.
+    pub async fn run<T: DB>(
+        &self,
+        db: &T,
+        providers: &MetadataProviders,
+        job_id: IdType,
+        on_progress: impl Fn(SyncProgress),
+    ) -> Result<SyncJob> {
+        let mut job = unfinished_job(db, job_id)?;
+
+        // Files that disappeared or were picked up by the library watcher
+        // since the job was saved are no longer pending.
+        let known: HashSet<String> = db.get_all_files()?.into_iter().map(|f| f.path).collect();
+        let files: Vec<PathBuf> = job
+            .pending
+            .iter()
+            .filter(|path| !known.contains(*path))
+            .map(PathBuf::from)
+            .filter(|path| path.is_file())
+            .collect();
+        job.pending = to_strings(&files);
+
+        let medias = parse(db, &files, Some(job_id), &on_progress).await?;
+        self.drive(db, providers, job, medias, on_progress).await
+    }
+
+    /// Looks up and inserts `medias` chunk by chunk until they are all in, the
+    /// job is cancelled or a chunk fails. `on_progress` is called after every
+    /// lookup and insert, whenever the job is paused, resumed or cancelled,
+    /// and once it completes.
+    pub async fn drive<T: DB>(
+        &self,
+        db: &T,
+        providers: &MetadataProviders,
+        job: SyncJob,
+        medias: Vec<Media>,
+        on_progress: impl Fn(SyncProgress),
+    ) -> Result<SyncJob> {
+        let job_id = job.id;
+        let signal = {
+            let mut signals = self.signals.lock().unwrap();
+            if signals.contains_key(&job_id) {
+                return Err(
+                    AppError::Conflict(format!("Sync job {job_id} is already running")).into(),
+                );
+            }
+            let (sender, receiver) = watch::channel(Signal::Run);
+            signals.insert(job_id, sender);
+            receiver
+        };
+
+        let result = run_chunks(db, providers, job, &medias, signal, on_progress).await;
+        self.signals.lock().unwrap().remove(&job_id);
+
+        if let Err(err) = &result {
+            db.update_sync_job_status(job_id, SyncJobStatus::Failed, Some(&err.to_string()))?;
+        }
+        result
+    }
+
+    /// Pauses a job after its current chunk. A job left unfinished by an
+    /// earlier run is only marked paused, so it is not resumed on startup.
+    pub fn pause<T: DB>(&self, db: &T, job_id: IdType) -> Result<()> {
+        if !self.send(job_id, Signal::Pause) {
+            unfinished_job(db, job_id)?;
+            db.update_sync_job_status(job_id, SyncJobStatus::Paused, None)?;
+        }
+        Ok(())
+    }
+
+    /// Cancels a job after its current chunk; what was committed stays.
+    pub fn cancel<T: DB>(&self, db: &T, job_id: IdType) -> Result<()> {
+        if !self.send(job_id, Signal::Cancel) {
+            unfinished_job(db, job_id)?;
+            db.update_sync_job_status(job_id, SyncJobStatus::Cancelled, None)?;
+        }
+        Ok(())
+    }
+
+    /// Wakes a paused job. Returns `false` when the job is not running in
+    /// this process and has to be started again with [`SyncJobs::run`].
+    pub fn resume<T: DB>(&self, db: &T, job_id: IdType) -> Result<bool> {
+        if self.send(job_id, Signal::Run) {
+            return Ok(true);
+        }
+        unfinished_job(db, job_id)?;
+        Ok(false)
+    }
+
+    fn send(&self, job_id: IdType, signal: Signal) -> bool {
+        match self.signals.lock().unwrap().get(&job_id) {
+            Some(sender) => {
+                sender.send_replace(signal);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+fn to_strings(paths: &[PathBuf]) -> Vec<String> {
+    paths
+        .iter()
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect()
+}
+
+/// Parses the files into medias and reads their details, reporting every
+/// batch.
+async fn parse<T: DB>(
+    db: &T,
+    files: &[PathBuf],
+    job_id: Option<IdType>,
+    on_progress: &impl Fn(SyncProgress),
+) -> Result<Vec<Media>> {
+    let videos = settings::get_video_extensions(db)?;
+    let mut pending = metadata_extractor::get_metadata(files).into_iter();
+    let mut medias = Vec::new();
+    let mut done = 0;
+
+    loop {
+        let batch: Vec<Media> = pending.by_ref().take(READ_BATCH).collect();
+        if batch.is_empty() {
+            return Ok(medias);
+        }
+
+        let batch = metadata_extractor::read_details(batch, videos.clone()).await?;
+        let paths: HashSet<&str> = batch.iter().flat_map(Media::paths).collect();
+        done += paths.len();
+        if let Some(last) = batch.iter().flat_map(Media::paths).last() {
+            on_progress(SyncProgress {
+                job_id,
+                ..SyncProgress::new(SyncPhase::Parsing, done, files.len()).at(Path::new(last))
+            });
+        }
+        medias.extend(batch);
+    }
+}
+
+fn unfinished_job<T: DB>(db: &T, job_id: IdType) -> Result<SyncJob> {
+    let job = db
+        .get_sync_job(job_id)?
+        .ok_or_else(|| AppError::NotFound(format!("Sync job {job_id} not found")))?;
+    if job.status.is_finished() {
+        return Err(
+            AppError::Conflict(format!("Sync job {job_id} is already {}", job.status)).into(),
+        );
+    }
+    Ok(job)
+}
+
+/// Waits while the job is paused. Returns `false` once it is cancelled.
+async fn checkpoint<T: DB>(
+    db: &T,
+    job: &mut SyncJob,
+    signal: &mut watch::Receiver<Signal>,
+    on_progress: &impl Fn(SyncProgress),
+) -> Result<bool> {
+    let current = *signal.borrow_and_update();
+
+    if current == Signal::Pause {
+        job.status = SyncJobStatus::Paused;
+        db.update_sync_job_status(job.id, job.status, None)?;
+        on_progress(SyncProgress::of_job(job, SyncPhase::Insert));
+
+        let next = *signal.wait_for(|signal| *signal != Signal::Pause).await?;
+        if next == Signal::Run {
+            job.status = SyncJobStatus::Running;
+            db.update_sync_job_status(job.id, job.status, None)?;
+            on_progress(SyncProgress::of_job(job, SyncPhase::Insert));
+        }
+    }
+
+    if *signal.borrow() == Signal::Cancel {
+        job.status = SyncJobStatus::Cancelled;
+        db.update_sync_job_status(job.id, job.status, None)?;
+        on_progress(SyncProgress::of_job(job, SyncPhase::Insert));
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+async fn run_chunks<T: DB>(
+    db: &T,
+    providers: &MetadataProviders,
+    mut job: SyncJob,
+    medias: &[Media],
+    mut signal: watch::Receiver<Signal>,
+    on_progress: impl Fn(SyncProgress),
+) -> Result<SyncJob> {
+    let job_id = job.id;
+    job.status = SyncJobStatus::Running;
+    db.update_sync_job_status(job_id, job.status, None)?;
+    let rejected = db.get_rejected_matches()?;
+
+    for chunk in medias.chunks(CHUNK_SIZE) {
+        if !checkpoint(db, &mut job, &mut signal, &on_progress).await? {
+            return Ok(job);
+        }
+
+        let mut chunk = chunk.to_vec();
+        let failures = providers.set_imdb_data(&mut chunk, &rejected).await;
+        on_progress(SyncProgress {
+            done: job.inserted + chunk.len(),
+            current_path: chunk
+                .iter()
+                .flat_map(Media::paths)
+                .next()
+                .map(str::to_string),
+            failures,
+            ..SyncProgress::of_job(&job, SyncPhase::Lookup)
+        });
+
+        let done: HashSet<&str> = chunk.iter().flat_map(Media::paths).collect();
+        let pending: Vec<String> = job
+            .pending
+            .iter()
+            .filter(|path| !done.contains(path.as_str()))
+            .cloned()
+            .collect();
+        db.commit_sync_job_chunk(job_id, &chunk, &pending)?;
+        let imdb_ids: Vec<String> = chunk
+            .iter()
+            .filter_map(Media::imdb_id)
+            .map(str::to_string)
+            .collect();
+        providers.store_new_episode_lists(db, &imdb_ids).await?;
+
+        job.pending = pending;
+        job.inserted += chunk.len();
+        on_progress(SyncProgress::of_job(&job, SyncPhase::Insert));
+    }
+
+    job.status = SyncJobStatus::Completed;
+    db.update_sync_job_status(job_id, job.status, None)?;
+    on_progress(SyncProgress::of_job(&job, SyncPhase::Insert));
+    Ok(job)
+}
+
+#[cfg(test)]
+mod tests_sync_jobs {
+    use super::*;
+    use crate::db::{Sqlite, temp_db};
+    use std::{cell::Cell, fs, path::Path};
+
+    /// One video file per movie, so every file is its own media.
+    fn write_movies(dir: &Path, count: usize) -> Vec<PathBuf> {
+        (0..count)
+            .map(|i| {
+                let path = dir.join(format!("Movie {i:03} ({}).mkv", 1950 + i));
+                fs::write(&path, path.to_string_lossy().as_bytes()).expect("Failed to write");
+                path
+            })
+            .collect()
+    }
+
+    /// No providers, so lookups fail fast instead of going online.
+    fn offline() -> MetadataProviders {
+        MetadataProviders::new(vec![])
+    }
+
+    /// Prepares a job for `files` and drives it without any provider.
+    async fn start(
+        jobs: &SyncJobs,
+        db: &Sqlite,
+        roots: &[PathBuf],
+        files: &[PathBuf],
+        on_progress: impl Fn(SyncProgress),
+    ) -> Result<SyncJob> {
+        let (job, medias) = jobs.prepare(db, roots, files, &on_progress).await?;
+        jobs.drive(db, &offline(), job, medias, on_progress).await
+    }
+
+    #[tokio::test]
+    async fn reports_every_phase_with_failures() {
+        let (temp_dir, db) = temp_db();
+        let files = write_movies(temp_dir.path(), CHUNK_SIZE + 10);
+        let jobs = SyncJobs::default();
+        let events = Cell::new(vec![]);
+
+        let job = start(&jobs, &db, &[temp_dir.path().into()], &files, |progress| {
+            let mut seen = events.take();
+            seen.push(progress);
+            events.set(seen);
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(job.status, SyncJobStatus::Completed);
+        assert_eq!(
+            (job.inserted, job.total),
+            (CHUNK_SIZE + 10, CHUNK_SIZE + 10)
+        );
+        assert_eq!(db.get_all_files().unwrap().len(), files.len());
+
+        let events = events.take();
+        let steps: Vec<_> = events
+            .iter()
+            .map(|p| (p.phase, p.status, p.done, p.failures.len()))
+            .collect();
+        let total = CHUNK_SIZE + 10;
+        assert_eq!(
+            steps,
+            vec![
+                (SyncPhase::Parsing, SyncJobStatus::Running, total, 0),
+                (
+                    SyncPhase::Lookup,
+                    SyncJobStatus::Running,
+                    CHUNK_SIZE,
+                    CHUNK_SIZE
+                ),
+                (SyncPhase::Insert, SyncJobStatus::Running, CHUNK_SIZE, 0),
+                (SyncPhase::Lookup, SyncJobStatus::Running, total, 10),
+                (SyncPhase::Insert, SyncJobStatus::Running, total, 0),
+                (SyncPhase::Insert, SyncJobStatus::Completed, total, 0),
+            ]
+        );
+        assert_eq!(events[0].job_id, None);
+        assert_eq!(
+            events[0].current_path.as_deref(),
+            files.last().unwrap().to_str()
+        );
+        assert!(events[1..].iter().all(|p| p.job_id == Some(job.id)));
+        assert!(events.iter().all(|p| p.total == total));
+
+        let failure = &events[1].failures[0];
+        assert_eq!(failure.path.as_deref(), events[1].current_path.as_deref());
+        assert_eq!(failure.reason, "No metadata provider configured");
+
+        let saved = db.get_sync_job(job.id).unwrap().unwrap();
+        assert_eq!(saved.status, SyncJobStatus::Completed);
+        assert!(saved.pending.is_empty());
+    }
+
+    #[tokio::test]
+    async fn cancel_keeps_committed_chunks() {
+        let (temp_dir, db) = temp_db();
+        let files = write_movies(temp_dir.path(), CHUNK_SIZE + 10);
+        let jobs = SyncJobs::default();
+
+        let job = start(&jobs, &db, &[], &files, |progress| {
+            if progress.phase == SyncPhase::Insert && progress.status == SyncJobStatus::Running {
+                jobs.cancel(&db, progress.job_id.unwrap()).unwrap();
+            }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(job.status, SyncJobStatus::Cancelled);
+        assert_eq!(job.inserted, CHUNK_SIZE);
+        assert_eq!(db.get_all_files().unwrap().len(), CHUNK_SIZE);
+
+        let saved = db.get_sync_job(job.id).unwrap().unwrap();
+        assert_eq!(saved.status, SyncJobStatus::Cancelled);
+        assert_eq!(saved.pending.len(), 10);
+        assert!(jobs.resume(&db, job.id).is_err());
+        assert!(jobs.run(&db, &offline(), job.id, |_| {}).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn pause_waits_for_resume() {
+        let (temp_dir, db) = temp_db();
+        let files = write_movies(temp_dir.path(), CHUNK_SIZE + 10);
+        let jobs = SyncJobs::default();
+        let statuses = Cell::new(vec![]);
+
+        let job = start(&jobs, &db, &[], &files, |progress| {
+            if progress.phase != SyncPhase::Insert {
+                return;
+            }
+            let mut seen = statuses.take();
+            seen.push(progress.status);
+            statuses.set(seen);
+
+            let job_id = progress.job_id.unwrap();
+            match progress.status {
+                SyncJobStatus::Running if progress.done == CHUNK_SIZE => {
+                    jobs.pause(&db, job_id).unwrap()
+                }
+                SyncJobStatus::Paused => {
+                    let saved = db.get_sync_job(job_id).unwrap().unwrap();
+                    assert_eq!(saved.status, SyncJobStatus::Paused);
+                    assert!(jobs.resume(&db, job_id).unwrap());
+                }
+                _ => {}
+            }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(job.status, SyncJobStatus::Completed);
+        assert_eq!(job.inserted, CHUNK_SIZE + 10);
+        assert_eq!(
+            statuses.take(),
+            vec![
+                SyncJobStatus::Running,
+                SyncJobStatus::Paused,
+                SyncJobStatus::Running,
+                SyncJobStatus::Running,
+                SyncJobStatus::Completed,
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn interrupted_job_continues_from_last_chunk() {
+        let (temp_dir, db) = temp_db();
+        let files = write_movies(temp_dir.path(), 3);
+        let pending = to_strings(&files);
+
+        // A job whose first file was committed before the app closed.
+        let job = db.insert_sync_job(&[], &pending, 3).unwrap();
+        let first = metadata_extractor::get_metadata(&files[..1]);
+        db.commit_sync_job_chunk(job.id, &first, &pending[1..])
+            .unwrap();
+
+        let jobs = SyncJobs::default();
+        jobs.pause(&db, job.id).unwrap();
+        assert!(!jobs.resume(&db, job.id).unwrap());
+
+        let job = jobs.run(&db, &offline(), job.id, |_| {}).await.unwrap();
+
+        assert_eq!(job.status, SyncJobStatus::Completed);
+        assert_eq!((job.inserted, job.total), (3, 3));
+        let mut paths: Vec<_> = db
+            .get_all_files()
+            .unwrap()
+            .into_iter()
+            .map(|f| f.path)
+            .collect();
+        paths.sort();
+        assert_eq!(paths, pending);
+    }
+
+    #[tokio::test]
+    async fn files_of_unfinished_jobs_are_not_synced_twice() {
+        let (temp_dir, db) = temp_db();
+        let files = write_movies(temp_dir.path(), 3);
+
+        let paused = db
+            .insert_sync_job(&[], &to_strings(&files[..2]), 2)
+            .unwrap();
+        db.update_sync_job_status(paused.id, SyncJobStatus::Paused, None)
+            .unwrap();
+
+        let job = start(&SyncJobs::default(), &db, &[], &files, |_| {})
+            .await
+            .unwrap();
+
+        assert_eq!(job.total, 1);
+        assert_eq!(db.get_all_files().unwrap().len(), 1);
+    }
+}
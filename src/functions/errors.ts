@@ -0,0 +1,16 @@
+import type { AppError, AppErrorCode } from '../type'
+
+// Commands reject with an AppError; plugins and the browser may still throw Error or plain strings.
+export function isAppError(error: unknown): error is AppError {
+  return typeof error === 'object' && error !== null && 'code' in error && 'message' in error
+}
+
+export function hasErrorCode(error: unknown, code: AppErrorCode): boolean {
+  return isAppError(error) && error.code === code
+}
+
+export function errorMessage(error: unknown, fallback = 'Unknown error'): string {
+  if (isAppError(error) || error instanceof Error) return error.message
+  if (typeof error === 'string') return error
+  return fallback
+}
@@ -1,10 +1,109 @@
-import type { FilterValues, NumericalString, Media, Tag } from '../type'
+import type {
+  FilterValues,
+  NumericalString,
+  Media,
+  Tag,
+  LibraryRoot,
+  OrphanCleanup,
+  OrphanReport,
+  IgnoreRules,
+  MetadataCacheSettings,
+  MetadataRefreshSettings,
+  RefreshReport,
+  SyncSummary,
+  SyncJob,
+  MatchCandidate,
+  ContentType,
+  MissingEpisodes,
+} from '../type'
 import { invoke } from '@tauri-apps/api/core'
 
-export async function sync_files(dir: string): Promise<number> {
+export async function sync_files(dir: string): Promise<SyncSummary> {
   return await invoke('sync_files', { root: dir })
 }
 
+export async function sync_all(): Promise<SyncSummary> {
+  return await invoke('sync_all')
+}
+
+export async function get_sync_jobs(): Promise<SyncJob[]> {
+  return await invoke('get_sync_jobs')
+}
+
+export async function pause_sync_job(jobId: number): Promise<void> {
+  return await invoke('pause_sync_job', { jobId })
+}
+
+export async function resume_sync_job(jobId: number): Promise<void> {
+  return await invoke('resume_sync_job', { jobId })
+}
+
+export async function cancel_sync_job(jobId: number): Promise<void> {
+  return await invoke('cancel_sync_job', { jobId })
+}
+
+export async function remove_orphans(cleanup: OrphanCleanup): Promise<OrphanReport> {
+  return await invoke('remove_orphans', { cleanup })
+}
+
+export async function get_library_roots(): Promise<LibraryRoot[]> {
+  return await invoke('get_library_roots')
+}
+
+export async function add_library_root(path: string): Promise<LibraryRoot> {
+  return await invoke('add_library_root', { path })
+}
+
+export async function remove_library_root(rootId: number): Promise<void> {
+  return await invoke('remove_library_root', { rootId })
+}
+
+export async function update_library_root_enabled(rootId: number, enabled: boolean): Promise<void> {
+  return await invoke('update_library_root_enabled', { rootId, enabled })
+}
+
+export async function get_video_extensions(): Promise<string[]> {
+  return await invoke('get_video_extensions')
+}
+
+export async function set_video_extensions(extensions: string[]): Promise<string[]> {
+  return await invoke('set_video_extensions', { extensions })
+}
+
+export async function get_ignore_rules(): Promise<IgnoreRules> {
+  return await invoke('get_ignore_rules')
+}
+
+export async function set_ignore_rules(rules: IgnoreRules): Promise<IgnoreRules> {
+  return await invoke('set_ignore_rules', { rules })
+}
+
+export async function get_metadata_cache_settings(): Promise<MetadataCacheSettings> {
+  return await invoke('get_metadata_cache_settings')
+}
+
+export async function set_metadata_cache_settings(cache: MetadataCacheSettings): Promise<MetadataCacheSettings> {
+  return await invoke('set_metadata_cache_settings', { cache })
+}
+
+export async function clear_metadata_cache(): Promise<number> {
+  return await invoke('clear_metadata_cache')
+}
+
+export async function get_metadata_refresh_settings(): Promise<MetadataRefreshSettings> {
+  return await invoke('get_metadata_refresh_settings')
+}
+
+export async function set_metadata_refresh_settings(
+  refresh: MetadataRefreshSettings,
+): Promise<MetadataRefreshSettings> {
+  return await invoke('set_metadata_refresh_settings', { refresh })
+}
+
+export async function refresh_metadata(): Promise<RefreshReport> {
+  return await invoke('refresh_metadata')
+}
+
 export async function get_people(): Promise<NumericalString[]> {
   return await invoke('get_people')
 }
@@ -17,6 +116,14 @@ export async function get_countries(): Promise<NumericalString[]> {
   return await invoke('get_countries')
 }
 
+export async function get_subtitle_languages(): Promise<string[]> {
+  return await invoke('get_subtitle_languages')
+}
+
+export async function get_certifications(): Promise<string[]> {
+  return await invoke('get_certifications')
+}
+
 export async function filter_medias(filters: FilterValues, page: number): Promise<Media[]> {
   return await invoke('filter_medias', { filters, page })
 }
@@ -29,10 +136,58 @@ export async function update_media_imdb(mediaId: number, imdbId: string): Promis
   return await invoke('update_media_imdb', { mediaId, imdbId })
 }
 
+export async function refresh_episodes(mediaId: number): Promise<Media> {
+  return await invoke('refresh_episodes', { mediaId })
+}
+
+export async function get_missing_episodes(mediaId: number): Promise<MissingEpisodes> {
+  return await invoke('get_missing_episodes', { mediaId })
+}
+
+export async function get_missing_episodes_report(): Promise<MissingEpisodes[]> {
+  return await invoke('get_missing_episodes_report')
+}
+
 export async function create_media_from_imdb(imdbId: string): Promise<number> {
   return await invoke('create_media_from_imdb', { imdbId })
 }
 
+export async function search_titles(
+  query: string,
+  year: number | null,
+  contentType: ContentType,
+): Promise<MatchCandidate[]> {
+  return await invoke('search_titles', { query, year, contentType })
+}
+
+export async function merge_medias(targetId: number, sourceIds: number[]): Promise<void> {
+  return await invoke('merge_medias', { targetId, sourceIds })
+}
+
+export async function split_media(mediaId: number, fileIds: number[]): Promise<number> {
+  return await invoke('split_media', { mediaId, fileIds })
+}
+
+export async function get_review_queue(): Promise<Media[]> {
+  return await invoke('get_review_queue')
+}
+
+export async function get_match_candidates(mediaId: number): Promise<MatchCandidate[]> {
+  return await invoke('get_match_candidates', { mediaId })
+}
+
+export async function accept_match(mediaId: number, imdbId: string): Promise<Media> {
+  return await invoke('accept_match', { mediaId, imdbId })
+}
+
+export async function reject_match(mediaId: number, imdbId: string): Promise<Media> {
+  return await invoke('reject_match', { mediaId, imdbId })
+}
+
+export async function skip_match(mediaId: number): Promise<Media> {
+  return await invoke('skip_match', { mediaId })
+}
+
 export async function update_media_watched(mediaId: number, watched: boolean): Promise<void> {
   return await invoke('update_media_watched', { mediaId, watched })
 }
@@ -45,6 +200,10 @@ export async function update_episode_watched(episodeId: number, watched: boolean
   return await invoke('update_episode_watched', { episodeId, watched })
 }
 
+export async function update_file_watched(fileId: number, watched: boolean): Promise<void> {
+  return await invoke('update_file_watched', { fileId, watched })
+}
+
 export async function update_media_my_ranking(mediaId: number, myRanking: number): Promise<void> {
   return await invoke('update_media_my_ranking', { mediaId, myRanking })
 }
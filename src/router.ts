@@ -9,6 +9,7 @@ const settingChildren: RouteRecordRaw[] = [
     component: () => import('./pages/settings/DirectorySetting.vue'),
     name: 'directories_setting',
   },
+  { path: 'metadata', component: () => import('./pages/settings/MetadataSetting.vue'), name: 'metadata_setting' },
 ]
 
 const routes: RouteRecordRaw[] = [
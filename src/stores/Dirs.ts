@@ -1,83 +1,85 @@
 import { defineStore } from 'pinia'
-import { ref } from 'vue'
+import { computed, ref } from 'vue'
+import type { LibraryRoot } from '../type'
+import {
+  add_library_root,
+  get_library_roots,
+  remove_library_root,
+  update_library_root_enabled,
+} from '../functions/invoker'
+
+/** localStorage key used when the directory list lived in the frontend. */
+const LEGACY_STORAGE_KEY = 'dirs'
 
 /**
- * Pinia store for managing a list of directory paths.
+ * Pinia store mirroring the library roots persisted by the backend.
  */
-export const useDirsStore = defineStore(
-  'dirs',
-  () => {
-    // Reactive array holding directory paths
-    const directoryPaths = ref<string[]>([])
-
-    /**
-     * Checks if a path is a parent of another path.
-     * @param parent - The potential parent path.
-     * @param child - The potential child path.
-     * @returns True if parent is a parent of child.
-     */
-    function isParent(parent: string, child: string): boolean {
-      const parentPath = parent.replace(/\\/g, '/').replace(/\/$/, '')
-      const childPath = child.replace(/\\/g, '/').replace(/\/$/, '')
-      return childPath.startsWith(parentPath + '/')
-    }
+export const useDirsStore = defineStore('dirs', () => {
+  // Library roots as returned by the backend
+  const roots = ref<LibraryRoot[]>([])
 
-    /**
-     * Adds a new directory path with parent-child logic.
-     * If the new directory is a parent of existing directories, removes the children.
-     * If an existing directory is a parent of the new one, doesn't add it.
-     * @param dir - The directory path to add.
-     * @returns True if added, false if not added or already covered.
-     */
-    function addDirectory(dir: string): boolean {
-      const normalizedDir = dir.replace(/\\/g, '/').replace(/\/$/, '')
+  // Paths of the roots that take part in syncing and watching
+  const directoryPaths = computed(() => roots.value.filter((root) => root.enabled).map((root) => root.path))
 
-      // Check if already exists
-      if (directoryPaths.value.includes(normalizedDir)) return false
+  /**
+   * Moves directories saved by older versions from localStorage into the backend.
+   */
+  async function migrateLegacyDirectories() {
+    const legacy = localStorage.getItem(LEGACY_STORAGE_KEY)
+    if (!legacy) return
 
-      // Check if any existing directory is a parent of the new one
-      for (const existing of directoryPaths.value) {
-        if (isParent(existing, normalizedDir)) {
-          return false // Already covered by parent
+    try {
+      const { directoryPaths: paths = [] } = JSON.parse(legacy) as { directoryPaths?: string[] }
+      for (const path of paths) {
+        try {
+          await add_library_root(path)
+        } catch (error) {
+          console.warn(`Skipping legacy directory ${path}:`, error)
         }
       }
+    } finally {
+      localStorage.removeItem(LEGACY_STORAGE_KEY)
+    }
+  }
 
-      // Remove any existing directories that are children of the new one
-      directoryPaths.value = directoryPaths.value.filter((existing) => !isParent(normalizedDir, existing))
+  /**
+   * Reloads the library roots from the backend.
+   */
+  async function reload() {
+    await migrateLegacyDirectories()
+    roots.value = await get_library_roots()
+  }
 
-      // Add the new directory
-      directoryPaths.value.push(normalizedDir)
-      return true
-    }
+  /**
+   * Adds a new library root. The backend rejects directories that are already
+   * covered by another root and replaces roots nested under the new one.
+   * @param dir - The directory path to add.
+   * @returns The stored library root.
+   */
+  async function addDirectory(dir: string): Promise<LibraryRoot> {
+    const root = await add_library_root(dir)
+    await reload()
+    return root
+  }
 
-    /**
-     * Removes a specific directory path from the list.
-     * @param dir - The directory path to remove.
-     * @returns True if removed, false if not found.
-     */
-    function removeDirectory(dir: string): boolean {
-      const index = directoryPaths.value.indexOf(dir)
-      if (index === -1) return false
-      directoryPaths.value.splice(index, 1)
-      return true
-    }
+  /**
+   * Removes a library root.
+   * @param rootId - The id of the root to remove.
+   */
+  async function removeDirectory(rootId: number) {
+    await remove_library_root(rootId)
+    await reload()
+  }
 
-    /**
-     * Removes the last directory path from the list.
-     * @returns True if removed, false if the list was empty.
-     */
-    function removeLastDirectory(): boolean {
-      if (directoryPaths.value.length === 0) return false
-      directoryPaths.value.pop()
-      return true
-    }
+  /**
+   * Enables or disables a library root without forgetting it.
+   * @param rootId - The id of the root to update.
+   * @param enabled - Whether the root should be synced and watched.
+   */
+  async function setDirectoryEnabled(rootId: number, enabled: boolean) {
+    await update_library_root_enabled(rootId, enabled)
+    await reload()
+  }
 
-    return { directoryPaths, addDirectory, removeDirectory, removeLastDirectory }
-  },
-  {
-    persist: {
-      storage: localStorage,
-      pick: ['directoryPaths'],
-    },
-  },
-)
+  return { roots, directoryPaths, reload, addDirectory, removeDirectory, setDirectoryEnabled }
+})
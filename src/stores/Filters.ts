@@ -16,6 +16,12 @@ const defaultFilters: FilterValues = {
   sortDirection: 'asc',
   watchList: null,
   tags: [],
+  hasSubtitles: null,
+  subtitleLanguage: [],
+  minRuntime: null,
+  maxRuntime: null,
+  rated: [],
+  complete: null,
 }
 
 export const useFiltersStore = defineStore('filters', () => {
@@ -1,3 +1,107 @@
+export type AppErrorCode =
+  | 'notFound'
+  | 'conflict'
+  | 'invalidInput'
+  | 'networkUnavailable'
+  | 'rateLimited'
+  | 'fileStillOnDisk'
+  | 'alreadyMatched'
+  | 'database'
+  | 'internal'
+
+export interface AppError {
+  code: AppErrorCode
+  message: string
+  /** The media already matched to the title, for `alreadyMatched`. */
+  mediaId?: number
+}
+
+export interface LibraryRoot {
+  id: number
+  path: string
+  enabled: boolean
+}
+
+export interface IgnoreRules {
+  excludeGlobs: string[]
+  minFileSize: number
+}
+
+export interface MetadataCacheSettings {
+  ttlHours: number
+  offline: boolean
+}
+
+export interface MetadataRefreshSettings {
+  enabled: boolean
+  maxAgeDays: number
+}
+
+export interface RefreshReport {
+  refreshed: number
+  missing: string[]
+}
+
+export type SkipReason =
+  | { reason: 'ignoreFile'; ignoreFile: string; pattern: string }
+  | { reason: 'excludeGlob'; pattern: string }
+  | { reason: 'tooSmall'; size: number }
+
+export type SkippedPath = { path: string } & SkipReason
+
+export type SyncJobStatus = 'running' | 'paused' | 'cancelled' | 'completed' | 'failed'
+
+export interface SyncJob {
+  id: number
+  roots: string[]
+  status: SyncJobStatus
+  total: number
+  inserted: number
+  error: string | null
+}
+
+export type SyncPhase = 'walking' | 'cleanup' | 'parsing' | 'lookup' | 'insert'
+
+export interface LookupFailure {
+  name: string
+  year: number | null
+  path: string | null
+  reason: string
+}
+
+export interface SyncProgress {
+  jobId: number | null
+  status: SyncJobStatus
+  phase: SyncPhase
+  done: number
+  total: number
+  currentPath: string | null
+  failures: LookupFailure[]
+}
+
+export interface SyncSummary {
+  jobId: number
+  status: SyncJobStatus
+  /** Medias the job looks up and inserts in the background, see `sync-finished`. */
+  total: number
+  skipped: SkippedPath[]
+  orphans: OrphanReport
+}
+
+export interface OrphanCleanup {
+  dryRun: boolean
+  includeUserData: boolean
+  mediaIds?: number[]
+}
+
+export interface OrphanReport {
+  medias: NumericalString[]
+  seasons: number[]
+  episodes: number[]
+  imdbs: string[]
+  keptMedias: NumericalString[]
+}
+
 export interface Tag {
   id: number
   name: string
@@ -9,6 +113,36 @@ export interface File {
   path: string
   quality?: string
   language_format: string
+  fingerprint?: string
+  probe?: MediaProbe
+  subtitles: Subtitle[]
+}
+
+export interface Subtitle {
+  id: number
+  path: string
+  language?: string
+}
+
+export interface MediaProbe {
+  duration_ms?: number
+  video_codec?: string
+  width?: number
+  height?: number
+  bitrate?: number
+  audio_languages: string[]
+  subtitle_languages: string[]
+}
+
+export interface ImdbEpisode {
+  imdb_id: string
+  season: number
+  episode: number
+  title: string
+  air_date: string
+  runtime: number
+  plot: string
+  rating: string
 }
 
 export interface Episode {
@@ -16,6 +150,20 @@ export interface Episode {
   number: number
   watched: boolean
   files: File[]
+  imdb?: ImdbEpisode
+}
+
+export interface SeasonGap {
+  season: number
+  aired: number
+  missing: ImdbEpisode[]
+}
+
+export interface MissingEpisodes {
+  mediaId: number
+  name: string
+  checked: boolean
+  seasons: SeasonGap[]
 }
 
 export interface Season {
@@ -43,6 +191,11 @@ export interface Imdb {
   poster: string
   imdb_rating: string
   imdb_votes: number
+  rated: string
+  runtime: number
+  awards: string
+  box_office: string
+  total_seasons: number
   imdb_id: string
   type: string
 }
@@ -58,10 +211,28 @@ export interface Media {
   my_ranking: number
   watch_list: boolean
   tags: Tag[]
+  suggestion?: MatchSuggestion
+}
+
+export interface MatchSuggestion {
+  imdb_id: string
+  title: string
+  year?: number
+  confidence: number
+}
+
+export interface MatchCandidate {
+  imdb_id: string
+  title: string
+  year?: number
+  type?: string
+  poster?: string
+  rating?: string
+  confidence: number
 }
 
-type ContentType = 'all' | 'movie' | 'series'
-type SortByType = 'name' | 'year' | 'imdb'
+export type ContentType = 'all' | 'movie' | 'series'
+type SortByType = 'name' | 'year' | 'imdb' | 'runtime'
 type SortDirectionType = 'asc' | 'desc'
 type NullableBool = boolean | null
 type NullableNumber = number | null
@@ -81,4 +252,10 @@ export interface FilterValues {
   sortDirection: SortDirectionType
   watchList: NullableBool
   tags: number[]
+  hasSubtitles: NullableBool
+  subtitleLanguage: string[]
+  minRuntime: NullableNumber
+  maxRuntime: NullableNumber
+  rated: string[]
+  complete: NullableBool
 }